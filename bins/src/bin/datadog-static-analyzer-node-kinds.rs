@@ -0,0 +1,90 @@
+use kernel::analysis::tree_sitter::{get_language_fields, get_language_node_kinds};
+use kernel::constants::VERSION;
+use kernel::model::common::Language;
+
+use getopts::Options;
+use std::env;
+use std::process::exit;
+
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} [options]", program);
+    print!("{}", opts.usage(&brief));
+}
+
+// List the kinds of nodes and the fields of the grammar of a language to write queries.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+    let mut opts = Options::new();
+    opts.optopt("l", "language", "language of the grammar", "python");
+    opts.optflag("a", "all", "also list the anonymous and hidden nodes");
+    opts.optflag("j", "json", "print the kinds and fields as JSON");
+    opts.optflag("h", "help", "print this help");
+    opts.optflag("v", "version", "shows the version");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("error when parsing arguments: {}", f)
+        }
+    };
+
+    if matches.opt_present("v") {
+        println!("{}", VERSION);
+        exit(1);
+    }
+
+    if matches.opt_present("h") {
+        print_usage(&program, opts);
+        exit(1);
+    }
+
+    let language = match matches.opt_str("l").map(|name| Language::from_name(&name)) {
+        Some(Some(language)) => language,
+        Some(None) => {
+            eprintln!("unknown language");
+            exit(1);
+        }
+        None => {
+            eprintln!("--language not defined");
+            print_usage(&program, opts);
+            exit(1);
+        }
+    };
+
+    let show_all = matches.opt_present("a");
+    let node_kinds: Vec<_> = get_language_node_kinds(&language)
+        .into_iter()
+        .filter(|k| show_all || (k.is_named && k.is_visible))
+        .collect();
+    let fields = get_language_fields(&language);
+
+    if matches.opt_present("j") {
+        let value = serde_json::json!({"nodeKinds": node_kinds, "fields": fields});
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&value).expect("error when writing JSON")
+        );
+        return;
+    }
+
+    println!("node kinds:");
+    for node_kind in node_kinds {
+        let mut flags = vec![];
+        if !node_kind.is_named {
+            flags.push("anonymous");
+        }
+        if !node_kind.is_visible {
+            flags.push("hidden");
+        }
+        if flags.is_empty() {
+            println!("  {}", node_kind.kind);
+        } else {
+            println!("  {} ({})", node_kind.kind, flags.join(", "));
+        }
+    }
+    println!("fields:");
+    for field in fields {
+        println!("  {}", field);
+    }
+}
@@ -1,12 +1,13 @@
 use cli::datadog_utils::get_ruleset;
 use kernel::analysis::analyze::analyze;
-use kernel::model::analysis::AnalysisOptions;
+use kernel::model::analysis::{AnalysisOptions, PositionEncoding};
 use kernel::model::rule::Rule;
 
 use anyhow::{Error, Result};
 use getopts::Options;
 use kernel::model::rule_test::RuleTest;
 use kernel::utils::decode_base64_string;
+use std::collections::HashMap;
 use std::env;
 use std::process::exit;
 
@@ -21,6 +22,13 @@ fn test_rule(rule: &Rule, test: &RuleTest) -> Result<String> {
     let analysis_options = AnalysisOptions {
         log_output: true,
         use_debug: true,
+        max_parsing_error_rate: None,
+        file_timeout_ms: None,
+        position_encoding: PositionEncoding::default(),
+        is_test_file: None,
+        rule_timeout_ms: None,
+        rule_memory_limit_mb: None,
+        language_overrides: HashMap::new(),
     };
     let rules = vec![rule_internal];
     let analyze_result = analyze(
@@ -0,0 +1,105 @@
+use kernel::analysis::tree_sitter::{get_query, tree_to_sexp, tree_to_sexp_with_captures};
+use kernel::constants::VERSION;
+use kernel::model::analysis::SexpOptions;
+use kernel::model::common::Language;
+
+use getopts::Options;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::exit;
+use std::{env, fs};
+
+fn print_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} FILE [options]", program);
+    print!("{}", opts.usage(&brief));
+}
+
+// Print the tree of a file as an S-expression, with the nodes captured by a query, to debug
+// the queries that do not match.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+    let mut opts = Options::new();
+    opts.optopt(
+        "l",
+        "language",
+        "language of the file, found from its extension by default",
+        "python",
+    );
+    opts.optopt(
+        "q",
+        "query",
+        "file with a query to show its captures",
+        "query.scm",
+    );
+    opts.optflag("p", "positions", "print the range of each node");
+    opts.optflag("", "no-fields", "do not print the fields of the nodes");
+    opts.optflag("h", "help", "print this help");
+    opts.optflag("v", "version", "shows the version");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(f) => {
+            panic!("error when parsing arguments: {}", f)
+        }
+    };
+
+    if matches.opt_present("v") {
+        println!("{}", VERSION);
+        exit(1);
+    }
+
+    if matches.opt_present("h") || matches.free.len() != 1 {
+        print_usage(&program, opts);
+        exit(1);
+    }
+
+    let filename = &matches.free[0];
+    let language = match matches.opt_str("l") {
+        Some(name) => Language::from_name(&name),
+        None => Language::from_path(Path::new(filename), &HashMap::new()),
+    };
+    let Some(language) = language else {
+        eprintln!("unknown language");
+        exit(1);
+    };
+
+    let code = match fs::read_to_string(filename) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("cannot read file {}: {}", filename, e);
+            exit(1);
+        }
+    };
+
+    let options = SexpOptions {
+        positions: matches.opt_present("p"),
+        fields: !matches.opt_present("no-fields"),
+    };
+
+    let sexp = match matches.opt_str("q") {
+        Some(query_file) => {
+            let query = fs::read_to_string(&query_file)
+                .map_err(|e| e.to_string())
+                .and_then(|query_code| {
+                    get_query(&query_code, &language).map_err(|e| e.to_string())
+                });
+            match query {
+                Ok(query) => tree_to_sexp_with_captures(&code, &language, &query, &options),
+                Err(e) => {
+                    eprintln!("invalid query {}: {}", query_file, e);
+                    exit(1);
+                }
+            }
+        }
+        None => tree_to_sexp(&code, &language, &options),
+    };
+
+    match sexp {
+        Ok(sexp) => println!("{}", sexp),
+        Err(e) => {
+            eprintln!("cannot parse file {}: {}", filename, e);
+            exit(1);
+        }
+    }
+}
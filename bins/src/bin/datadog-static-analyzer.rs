@@ -1,17 +1,29 @@
 use cli::config_file::read_config_file;
 use cli::datadog_utils::get_rules_from_rulesets;
-use cli::file_utils::{filter_files_for_language, get_files, read_files_from_gitignore};
+use cli::file_utils::{
+    filter_files_for_language, get_files, get_language_overrides, get_test_file_override,
+    parse_language_map, read_files_from_gitignore,
+};
+use cli::fix::fix_files;
 use cli::model::config_file::ConfigFile;
+use cli::performance::{format_slowest_rules, get_slowest_rules};
+use cli::results::sort_rule_results;
 use cli::rule_utils::{get_languages_for_rules, get_rulesets_from_file};
 use itertools::Itertools;
-use kernel::analysis::analyze::analyze;
+use kernel::analysis::analyze::analyze_bytes;
+use kernel::analysis::rule_validation::{get_unknown_captures, get_unknown_query_predicates};
 use kernel::constants::{CARGO_VERSION, VERSION};
-use kernel::model::analysis::{AnalysisOptions, ERROR_RULE_TIMEOUT};
+use kernel::model::analysis::{
+    AnalysisOptions, PositionEncoding, DEFAULT_MAX_PARSING_ERROR_RATE, ERROR_FILE_DECODED_LOSSILY,
+    ERROR_FILE_TIMEOUT, ERROR_INVALID_QUERY, ERROR_NOT_ANALYZED_PARSE_ERRORS, ERROR_PARSING_FAILED,
+    ERROR_PARSING_TIMEOUT, ERROR_RULE_MATCHES_TRUNCATED, ERROR_RULE_OUT_OF_MEMORY,
+    ERROR_RULE_TIMEOUT,
+};
 use kernel::model::common::OutputFormat;
-use kernel::model::rule::{Rule, RuleInternal, RuleResult};
+use kernel::model::rule::{Rule, RuleExecutionError, RuleInternal, RuleResult, RuleSeverity};
 
 use anyhow::{Context, Result};
-use cli::constants::DEFAULT_MAX_FILE_SIZE_KB;
+use cli::constants::{DEFAULT_MAX_FILE_SIZE_KB, SLOWEST_RULES_COUNT};
 use cli::csv;
 use cli::model::cli_configuration::CliConfiguration;
 use cli::sarif::sarif_utils::generate_sarif_report;
@@ -76,11 +88,45 @@ fn print_configuration(configuration: &CliConfiguration) {
     );
     println!("use debug           : {}", configuration.use_debug);
     println!("use staging         : {}", configuration.use_staging);
+    println!("debug rules         : {}", configuration.debug_rules);
     println!("rules languages     : {}", languages_string.join(","));
+    println!(
+        "language overrides  : {}",
+        configuration
+            .language_overrides
+            .iter()
+            .map(|(extension, language)| format!("{extension}={language}"))
+            .sorted()
+            .join(",")
+    );
     println!(
         "max file size       : {} kb",
         configuration.max_file_size_kb
     );
+    println!(
+        "max parse error rate: {}",
+        configuration
+            .max_parsing_error_rate
+            .unwrap_or(DEFAULT_MAX_PARSING_ERROR_RATE)
+    );
+    println!(
+        "file timeout        : {}",
+        configuration
+            .file_timeout_ms
+            .map_or("none".to_string(), |timeout| format!("{timeout} ms"))
+    );
+    println!(
+        "rule timeout        : {}",
+        configuration
+            .rule_timeout_ms
+            .map_or("default".to_string(), |timeout| format!("{timeout} ms"))
+    );
+    println!(
+        "rule memory limit   : {}",
+        configuration
+            .rule_memory_limit_mb
+            .map_or("default".to_string(), |limit| format!("{limit} MB"))
+    );
 }
 
 fn main() -> Result<()> {
@@ -120,6 +166,42 @@ fn main() -> Result<()> {
         "path to ignore - the value is a glob",
         "**/test*.py (multiple values possible)",
     );
+    opts.optopt(
+        "l",
+        "language-map",
+        "languages to use for some extensions, overrides the default extensions",
+        "bzl=python,es6=javascript",
+    );
+    opts.optopt(
+        "",
+        "max-parsing-error-rate",
+        "skip the files when more than this ratio of the code has syntax errors (default 0.2)",
+        "0.5",
+    );
+    opts.optopt(
+        "",
+        "file-timeout",
+        "time budget to parse and query each file, in milliseconds",
+        "10000",
+    );
+    opts.optopt(
+        "",
+        "rule-timeout",
+        "time budget of each rule on each file, in milliseconds",
+        "5000",
+    );
+    opts.optopt(
+        "",
+        "rule-memory-limit",
+        "memory of each rule on each file, in megabytes",
+        "512",
+    );
+    opts.optopt(
+        "",
+        "fail-on-severity",
+        "exit with an error when a violation has this severity or a higher one",
+        "error/warning/notice",
+    );
     opts.optflag("h", "help", "print this help");
     opts.optflag("v", "version", "shows the tool version");
     opts.optflag(
@@ -133,6 +215,21 @@ fn main() -> Result<()> {
         "enable performance statistics",
     );
     opts.optflag("s", "staging", "use staging");
+    opts.optflag(
+        "",
+        "strict",
+        "exit with an error when the code of a rule throws an exception",
+    );
+    opts.optflag(
+        "",
+        "fix",
+        "apply the first fix of each violation to the files",
+    );
+    opts.optflag(
+        "",
+        "debug-rules",
+        "print the output of the rules (console.log and console.error)",
+    );
     opts.optflag(
         "g",
         "add-git-info",
@@ -166,6 +263,9 @@ fn main() -> Result<()> {
     let use_staging = matches.opt_present("s");
     let add_git_info = matches.opt_present("g");
     let enable_performance_statistics = matches.opt_present("x");
+    let debug_rules = matches.opt_present("debug-rules");
+    let strict = matches.opt_present("strict");
+    let fix = matches.opt_present("fix");
 
     let output_format = match matches.opt_str("f") {
         Some(f) => match f.as_str() {
@@ -209,6 +309,9 @@ fn main() -> Result<()> {
     let configuration_file: Option<ConfigFile> =
         read_config_file(directory_to_analyze.as_str()).unwrap();
     let mut rules: Vec<Rule> = Vec::new();
+    let mut language_map: HashMap<String, String> = HashMap::new();
+    let mut test_paths: Vec<String> = vec![];
+    let mut production_paths: Vec<String> = vec![];
 
     // if there is a configuration file, we load the rules from it. But it means
     // we cannot have the rule parameter given.
@@ -223,13 +326,41 @@ fn main() -> Result<()> {
         let rules_from_api = get_rules_from_rulesets(&conf.rulesets, use_staging);
         rules.extend(rules_from_api.context("error when reading rules from API")?);
 
+        // set the arguments of the rules from the configuration file
+        for (rule_name, values) in conf.rule_arguments.unwrap_or_default() {
+            let Some(rule) = rules.iter_mut().find(|r| r.name == rule_name) else {
+                eprintln!("arguments set for rule {rule_name} that is not in the rulesets");
+                continue;
+            };
+            if let Err(e) = rule.set_argument_values(&values) {
+                eprintln!("invalid arguments for rule {rule_name}: {e}");
+                exit(1);
+            }
+        }
+
+        // set the timeouts of the rules from the configuration file
+        for (rule_name, timeout_ms) in conf.rule_timeouts.unwrap_or_default() {
+            let Some(rule) = rules.iter_mut().find(|r| r.name == rule_name) else {
+                eprintln!("timeout set for rule {rule_name} that is not in the rulesets");
+                continue;
+            };
+            rule.timeout_ms = Some(timeout_ms);
+        }
+
         // copy the ignore paths from the configuration file
         if let Some(v) = conf.ignore_paths {
             ignore_paths.extend(v);
         }
 
         // Get the max file size from the configuration or default to the default constant.
-        max_file_size_kb = conf.max_file_size_kb.unwrap_or(DEFAULT_MAX_FILE_SIZE_KB)
+        max_file_size_kb = conf.max_file_size_kb.unwrap_or(DEFAULT_MAX_FILE_SIZE_KB);
+
+        if let Some(m) = conf.language_map {
+            language_map.extend(m);
+        }
+
+        test_paths = conf.test_paths.unwrap_or_default();
+        production_paths = conf.production_paths.unwrap_or_default();
     } else {
         use_configuration_file = false;
         // if there is no config file, we must read the rules from a file.
@@ -249,9 +380,92 @@ fn main() -> Result<()> {
         rules.extend(rules_from_file);
     }
 
+    // the defaults of the arguments must have the type of the arguments
+    for rule in &rules {
+        if let Err(e) = rule.get_arguments() {
+            eprintln!("invalid arguments for rule {}: {e}", rule.name);
+            exit(1);
+        }
+    }
+
+    // the rules must have a known category and valid CWE identifiers
+    for rule in &rules {
+        if let Err(e) = rule.check_metadata() {
+            eprintln!("invalid rule {}: {e}", rule.name);
+            exit(1);
+        }
+    }
+
     // add ignore path from the options
     ignore_paths.extend(ignore_paths_from_options);
 
+    // the language map from the options wins over the one from the configuration file
+    if let Some(language_map_option) = matches.opt_str("l") {
+        language_map.extend(parse_language_map(&language_map_option)?);
+    }
+    let language_overrides = match get_language_overrides(&language_map) {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            eprintln!("invalid language map: {e}");
+            exit(1);
+        }
+    };
+
+    let max_parsing_error_rate = match matches.opt_str("max-parsing-error-rate") {
+        Some(value) => match value.parse::<f64>() {
+            Ok(rate) if (0.0..=1.0).contains(&rate) => Some(rate),
+            _ => {
+                eprintln!("max parsing error rate must be between 0 and 1");
+                exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let file_timeout_ms = match matches.opt_str("file-timeout") {
+        Some(value) => match value.parse::<u64>() {
+            Ok(timeout) => Some(timeout),
+            Err(_) => {
+                eprintln!("file timeout must be a number of milliseconds");
+                exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let rule_timeout_ms = match matches.opt_str("rule-timeout") {
+        Some(value) => match value.parse::<u64>() {
+            Ok(timeout) => Some(timeout),
+            Err(_) => {
+                eprintln!("rule timeout must be a number of milliseconds");
+                exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let rule_memory_limit_mb = match matches.opt_str("rule-memory-limit") {
+        Some(value) => match value.parse::<u64>() {
+            Ok(limit) if limit > 0 => Some(limit),
+            _ => {
+                eprintln!("rule memory limit must be a number of megabytes");
+                exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let fail_on_severity = match matches.opt_str("fail-on-severity") {
+        Some(value) => match value.parse::<RuleSeverity>() {
+            Ok(severity) => Some(severity),
+            Err(_) => {
+                eprintln!("severity must be error, warning or notice");
+                exit(1);
+            }
+        },
+        None => None,
+    };
+
     // ignore all directories that are in gitignore
     if !ignore_gitignore {
         let paths_from_gitignore = read_files_from_gitignore(directory_to_analyze.as_str());
@@ -289,6 +503,14 @@ fn main() -> Result<()> {
         output_file,
         max_file_size_kb,
         use_staging,
+        language_overrides,
+        max_parsing_error_rate,
+        file_timeout_ms,
+        test_paths,
+        production_paths,
+        debug_rules,
+        rule_timeout_ms,
+        rule_memory_limit_mb,
     };
 
     print_configuration(&configuration);
@@ -296,8 +518,15 @@ fn main() -> Result<()> {
     let mut all_rule_results = vec![];
 
     let analysis_options = AnalysisOptions {
-        log_output: true,
+        log_output: configuration.debug_rules,
         use_debug,
+        max_parsing_error_rate,
+        file_timeout_ms,
+        position_encoding: PositionEncoding::default(),
+        is_test_file: None,
+        rule_timeout_ms,
+        rule_memory_limit_mb,
+        language_overrides: configuration.language_overrides.clone(),
     };
 
     // verify rule checksum
@@ -317,6 +546,32 @@ fn main() -> Result<()> {
         println!("Skipping checksum verification");
     }
 
+    // the rules reading captures that are not in their query silently report no violation
+    for rule in configuration
+        .rules
+        .iter()
+        .filter_map(|r| r.to_rule_internal().ok())
+    {
+        if let Ok(unknown_captures) = get_unknown_captures(&rule) {
+            if !unknown_captures.is_empty() {
+                eprintln!(
+                    "Rule {} reads captures not defined in its query: {}",
+                    rule.name,
+                    unknown_captures.join(", ")
+                );
+            }
+        }
+        if let Ok(unknown_predicates) = get_unknown_query_predicates(&rule) {
+            if !unknown_predicates.is_empty() {
+                eprintln!(
+                    "Rule {} uses predicates that are not evaluated: {}",
+                    rule.name,
+                    unknown_predicates.join(", ")
+                );
+            }
+        }
+    }
+
     // we always keep one thread free and some room for the management threads that monitor
     // the rule execution.
     let ideal_threads = ((configuration.num_cpus as f32 - 1.0) * 0.90) as usize;
@@ -333,7 +588,11 @@ fn main() -> Result<()> {
         .as_secs();
 
     for language in &languages {
-        let files_for_language = filter_files_for_language(&files_to_analyze, language);
+        let files_for_language = filter_files_for_language(
+            &files_to_analyze,
+            language,
+            &configuration.language_overrides,
+        );
 
         println!(
             "Analyzing {} {:?} files",
@@ -371,17 +630,29 @@ fn main() -> Result<()> {
         // take the relative path for the analysis
         let rule_results: Vec<RuleResult> = files_for_language
             .into_par_iter()
-            .flat_map(|path| match fs::read_to_string(&path) {
+            .flat_map(|path| match fs::read(&path) {
                 Ok(file_content) => {
-                    let res = analyze(
+                    let relative_path = path
+                        .strip_prefix(directory_path)
+                        .unwrap()
+                        .to_str()
+                        .expect("path contains non-Unicode characters");
+                    // the configuration can force the file to be a test file or production code
+                    let file_analysis_options = AnalysisOptions {
+                        is_test_file: get_test_file_override(
+                            relative_path,
+                            &configuration.test_paths,
+                            &configuration.production_paths,
+                        ),
+                        ..analysis_options.clone()
+                    };
+                    // the files that are not valid UTF-8 are decoded lossily
+                    let res = analyze_bytes(
                         language,
                         rules_for_language.clone(),
-                        path.strip_prefix(directory_path)
-                            .unwrap()
-                            .to_str()
-                            .expect("path contains non-Unicode characters"),
+                        relative_path,
                         &file_content,
-                        &analysis_options,
+                        &file_analysis_options,
                     );
 
                     if let Some(pb) = &progress_bar {
@@ -403,6 +674,10 @@ fn main() -> Result<()> {
         }
     }
 
+    // the languages, the rules and the files are analyzed in any order, the results are sorted
+    // so that two runs on the same files have the same reports and errors
+    sort_rule_results(&mut all_rule_results);
+
     let end_timestamp = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
@@ -421,30 +696,161 @@ fn main() -> Result<()> {
         end_timestamp - start_timestamp
     );
 
-    // If the performance statistics are enabled, we show the total execution time per rule
-    // and the rule that timed-out.
-    if enable_performance_statistics {
-        let mut rules_execution_time_ms: HashMap<String, u128> = HashMap::new();
+    // Report the rules with an invalid query once, they were skipped for all files.
+    for (rule_name, error) in all_rule_results
+        .iter()
+        .filter(|r| r.errors.contains(&ERROR_INVALID_QUERY.to_string()))
+        .map(|r| {
+            (
+                &r.rule_name,
+                r.execution_error.as_deref().unwrap_or("no query"),
+            )
+        })
+        .unique_by(|(rule_name, _)| *rule_name)
+    {
+        eprintln!("Rule {} has an invalid query: {}", rule_name, error);
+    }
 
-        // first, get the rule execution time
-        for rule_result in &all_rule_results {
-            let current_value = rules_execution_time_ms
-                .get(&rule_result.rule_name)
-                .unwrap_or(&0u128);
-            let new_value = current_value + rule_result.execution_time_ms;
-            rules_execution_time_ms.insert(rule_result.rule_name.clone(), new_value);
+    // Report the exceptions thrown by the rules, with their stack trace in the code of the rule.
+    let rule_exceptions: Vec<&RuleExecutionError> = all_rule_results
+        .iter()
+        .filter_map(|r| r.exception.as_ref())
+        .collect();
+    if !rule_exceptions.is_empty() {
+        eprintln!("Errors");
+        eprintln!("------");
+        for exception in &rule_exceptions {
+            eprintln!(
+                "Rule {} failed on file {}: {}",
+                exception.rule_name, exception.filename, exception.message
+            );
+            for frame in &exception.stack {
+                eprintln!("    {}", frame);
+            }
         }
+    }
 
-        println!("Rule execution time");
-        println!("-------------------");
-        // Show execution time, in sorted order
-        for v in rules_execution_time_ms
-            .iter()
-            .sorted_by(|a, b| Ord::cmp(b.1, a.1))
-            .as_slice()
-        {
-            println!("rule {:?} execution time {:?} ms", v.0, v.1);
+    // Report the rules that used too much memory on a file, they were stopped.
+    for rule_result in all_rule_results
+        .iter()
+        .filter(|r| r.errors.contains(&ERROR_RULE_OUT_OF_MEMORY.to_string()))
+    {
+        eprintln!(
+            "rule {} ran out of memory on file {}",
+            rule_result.rule_name, rule_result.filename
+        );
+    }
+
+    // Report the rules that had too many matches on a file, they may have missed violations.
+    for rule_result in all_rule_results
+        .iter()
+        .filter(|r| r.errors.contains(&ERROR_RULE_MATCHES_TRUNCATED.to_string()))
+    {
+        eprintln!(
+            "rule {} truncated on file {}",
+            rule_result.rule_name, rule_result.filename
+        );
+    }
+
+    // Report the files we could not parse once, no rule was applied on them.
+    let files_not_parsed: Vec<(&String, &str)> = all_rule_results
+        .iter()
+        .filter(|r| r.errors.contains(&ERROR_PARSING_FAILED.to_string()))
+        .map(|r| {
+            (
+                &r.filename,
+                r.execution_error.as_deref().unwrap_or("unknown error"),
+            )
+        })
+        .unique_by(|(filename, _)| *filename)
+        .collect();
+    if !files_not_parsed.is_empty() {
+        eprintln!("Failed to parse {} files", files_not_parsed.len());
+        for (filename, error) in files_not_parsed {
+            eprintln!("Error when parsing file {}: {}", filename, error);
         }
+    }
+
+    let nb_files_not_analyzed = all_rule_results
+        .iter()
+        .filter(|r| {
+            r.errors
+                .contains(&ERROR_NOT_ANALYZED_PARSE_ERRORS.to_string())
+        })
+        .map(|r| &r.filename)
+        .unique()
+        .count();
+    if nb_files_not_analyzed > 0 {
+        eprintln!("{} files not analyzed: parse errors", nb_files_not_analyzed);
+    }
+
+    // Report the files that are not valid UTF-8, their invalid bytes were replaced.
+    let files_decoded_lossily: Vec<&String> = all_rule_results
+        .iter()
+        .filter(|r| r.errors.contains(&ERROR_FILE_DECODED_LOSSILY.to_string()))
+        .map(|r| &r.filename)
+        .unique()
+        .collect();
+    for filename in files_decoded_lossily {
+        eprintln!(
+            "Warning: file {} is not valid UTF-8, it was decoded lossily",
+            filename
+        );
+    }
+
+    // Report the files skipped because they could not be parsed within their time budget.
+    let files_parse_timed_out: Vec<&String> = all_rule_results
+        .iter()
+        .filter(|r| r.errors.contains(&ERROR_PARSING_TIMEOUT.to_string()))
+        .map(|r| &r.filename)
+        .unique()
+        .collect();
+    if !files_parse_timed_out.is_empty() {
+        eprintln!(
+            "{} files skipped: parse timeout",
+            files_parse_timed_out.len()
+        );
+        for filename in files_parse_timed_out {
+            eprintln!("File {} skipped: parse timeout", filename);
+        }
+    }
+
+    // Report the files that exceeded their time budget, their results are partial.
+    let files_timed_out: Vec<&String> = all_rule_results
+        .iter()
+        .filter(|r| r.errors.contains(&ERROR_FILE_TIMEOUT.to_string()))
+        .map(|r| &r.filename)
+        .unique()
+        .collect();
+    if !files_timed_out.is_empty() {
+        eprintln!(
+            "{} files timed out, their results are partial",
+            files_timed_out.len()
+        );
+        for filename in files_timed_out {
+            eprintln!("File {} timed out", filename);
+        }
+    }
+
+    // Show what the rules printed, to debug them.
+    if configuration.debug_rules {
+        for rule_result in &all_rule_results {
+            if let Some(output) = &rule_result.output {
+                println!(
+                    "Output of rule {} on file {}:\n{}",
+                    rule_result.rule_name, rule_result.filename, output
+                );
+            }
+        }
+    }
+
+    // If the performance statistics are enabled, we show the slowest rules with the time of
+    // their queries and of their execution and the rule that timed-out.
+    if enable_performance_statistics {
+        println!("Slowest rules");
+        println!("-------------");
+        let slowest_rules = get_slowest_rules(&all_rule_results, SLOWEST_RULES_COUNT);
+        print!("{}", format_slowest_rules(&slowest_rules));
 
         // show the rules that timed out
         println!("Rule timed out");
@@ -487,5 +893,37 @@ fn main() -> Result<()> {
     let mut file = fs::File::create(configuration.output_file).context("cannot create file")?;
     file.write_all(value.as_bytes())
         .context("error when writing results")?;
+
+    // the files are fixed once the report is written, the report has the original positions
+    if fix {
+        let (fixes_applied, files_changed) = fix_files(
+            directory_path,
+            &all_rule_results,
+            analysis_options.position_encoding,
+        )?;
+        println!("Applied {} fixes in {} files", fixes_applied, files_changed);
+    }
+
+    // the report is written even when the rules failed
+    if strict && !rule_exceptions.is_empty() {
+        eprintln!("{} rule executions failed", rule_exceptions.len());
+        exit(1);
+    }
+
+    // the severity of each violation, the rules can override their severity
+    if let Some(fail_on_severity) = fail_on_severity {
+        let failing_violations = all_rule_results
+            .iter()
+            .flat_map(|r| &r.violations)
+            .filter(|v| v.severity.is_at_least(fail_on_severity))
+            .count();
+        if failing_violations > 0 {
+            eprintln!(
+                "{} violations with severity {} or higher",
+                failing_violations, fail_on_severity
+            );
+            exit(1);
+        }
+    }
     Ok(())
 }
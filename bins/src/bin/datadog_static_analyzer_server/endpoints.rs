@@ -1,4 +1,6 @@
+use kernel::analysis::tree_sitter::{get_language_fields, get_language_node_kinds};
 use kernel::constants::{CARGO_VERSION, VERSION};
+use kernel::model::common::Language;
 use rocket::{
     fs::NamedFile,
     futures::FutureExt,
@@ -93,13 +95,20 @@ fn shutdown_post(state: &State<ServerState>, shutdown: Shutdown) -> Status {
 /// Gets a list of supported languages.
 #[rocket::get("/languages", format = "application/json")]
 fn languages() -> Value {
-    let languages: Vec<Value> = kernel::model::common::ALL_LANGUAGES
-        .iter()
-        .map(|x| json!(x))
-        .collect();
+    let languages: Vec<Value> = Language::all().map(|x| json!(x)).collect();
     json!(languages)
 }
 
+/// Gets the kinds of nodes and the fields of the grammar of a language (e.g. `python`).
+#[rocket::get("/languages/<language>/node-kinds", format = "application/json")]
+fn node_kinds(language: &str) -> Option<Value> {
+    let language = Language::from_name(language)?;
+    Some(json!({
+        "nodeKinds": get_language_node_kinds(&language),
+        "fields": get_language_fields(&language),
+    }))
+}
+
 #[rocket::post("/analyze", format = "application/json", data = "<request>")]
 fn analyze(request: Json<AnalysisRequest>) -> Value {
     json!(process_analysis_request(request.into_inner()))
@@ -158,6 +167,7 @@ fn mount_endpoints(rocket: Rocket<Build>) -> Rocket<Build> {
         .mount("/", rocket::routes![get_options])
         .mount("/", rocket::routes![serve_static])
         .mount("/", rocket::routes![languages])
+        .mount("/", rocket::routes![node_kinds])
         .mount("/", rocket::routes![shutdown_get])
         .mount("/", rocket::routes![shutdown_post])
 }
@@ -0,0 +1,3 @@
+const page = `<div>
+  <script>eval(input);</script>
+</div>`;
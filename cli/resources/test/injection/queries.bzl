@@ -0,0 +1,3 @@
+def get_users(db):
+    return db.execute("SELECT * FROM users")
+PAGE = "<script>eval(input);</script>"
@@ -52,6 +52,7 @@ pub fn read_config_file(path: &str) -> Result<Option<model::config_file::ConfigF
 #[cfg(test)]
 mod tests {
     use super::*;
+    use kernel::model::rule::RuleArgumentValue;
 
     // test when we have only rulesets. We should then have the ignore-paths set to None
     #[test]
@@ -84,6 +85,89 @@ ignore-paths:
         assert_eq!("path1", ignore_paths.get(1).unwrap().as_str());
     }
 
+    // the language map associates extensions to languages
+    #[test]
+    fn parse_config_file_with_language_map() {
+        let data = r#"
+rulesets:
+  - python-security
+language-map:
+  bzl: python
+  es6: javascript
+    "#;
+        let res = parse_config_file(data);
+        assert!(res.is_ok());
+        let language_map = res.unwrap().language_map.unwrap();
+        assert_eq!(2, language_map.len());
+        assert_eq!("python", language_map.get("bzl").unwrap());
+        assert_eq!("javascript", language_map.get("es6").unwrap());
+    }
+
+    // the arguments of the rules keep their types
+    #[test]
+    fn parse_config_file_with_rule_arguments() {
+        let data = r#"
+rulesets:
+  - python-security
+rule-arguments:
+  python-security/too-many-args:
+    max: 3
+    strict: true
+    names:
+      - foo
+      - bar
+    "#;
+        let res = parse_config_file(data);
+        assert!(res.is_ok());
+        let rule_arguments = res.unwrap().rule_arguments.unwrap();
+        let values = rule_arguments.get("python-security/too-many-args").unwrap();
+        assert_eq!(Some(&RuleArgumentValue::Integer(3)), values.get("max"));
+        assert_eq!(
+            Some(&RuleArgumentValue::Boolean(true)),
+            values.get("strict")
+        );
+        assert_eq!(
+            Some(&RuleArgumentValue::StringList(vec![
+                "foo".to_string(),
+                "bar".to_string()
+            ])),
+            values.get("names")
+        );
+    }
+
+    // the paths of the test files and of the production code
+    #[test]
+    fn parse_config_file_with_test_paths() {
+        let data = r#"
+rulesets:
+  - python-security
+test-paths:
+  - "**/fixtures/**"
+production-paths:
+  - tests/fixtures/prod
+    "#;
+        let res = parse_config_file(data).unwrap();
+        assert_eq!(Some(vec!["**/fixtures/**".to_string()]), res.test_paths);
+        assert_eq!(
+            Some(vec!["tests/fixtures/prod".to_string()]),
+            res.production_paths
+        );
+    }
+
+    // the timeouts of the rules, in milliseconds
+    #[test]
+    fn parse_config_file_with_rule_timeouts() {
+        let data = r#"
+rulesets:
+  - python-security
+rule-timeouts:
+  python-security/slow-rule: 20000
+    "#;
+        let res = parse_config_file(data).unwrap();
+        let rule_timeouts = res.rule_timeouts.unwrap();
+        assert_eq!(Some(&20000), rule_timeouts.get("python-security/slow-rule"));
+    }
+
     // No ruleset available in the data means that we have no configuration file
     // whatsoever and we should return None
     #[test]
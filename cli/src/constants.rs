@@ -1,3 +1,6 @@
 pub static DATADOG_CONFIG_FILE_WITHOUT_PREFIX: &str = "static-analysis.datadog";
 
 pub static DEFAULT_MAX_FILE_SIZE_KB: u64 = 200;
+
+// number of rules in the table of the slowest rules of the performance statistics
+pub static SLOWEST_RULES_COUNT: usize = 20;
@@ -1,28 +1,28 @@
+use crate::results::get_sorted_violations;
 use kernel::model::rule::RuleResult;
 
-pub fn generate_csv_results(rule_results: &Vec<RuleResult>) -> String {
+// the violations are sorted by file, position and rule
+pub fn generate_csv_results(rule_results: &[RuleResult]) -> String {
     let mut result = String::new();
     result.push_str(
         "filename,rule,category,severity,message,start_line,start_col,end_line,end_col\n",
     );
-    for r in rule_results {
-        for v in &r.violations {
-            result.push_str(
-                format!(
-                    "{},{},{},{},{},{},{},{},{}\n",
-                    r.filename,
-                    r.rule_name,
-                    v.category,
-                    v.severity,
-                    v.message,
-                    v.start.line,
-                    v.start.col,
-                    v.end.line,
-                    v.end.col
-                )
-                .as_str(),
-            );
-        }
+    for (r, v) in get_sorted_violations(rule_results) {
+        result.push_str(
+            format!(
+                "{},{},{},{},{},{},{},{},{}\n",
+                r.filename,
+                r.rule_name,
+                v.category,
+                v.severity,
+                v.message,
+                v.start.line,
+                v.start.col,
+                v.end.line,
+                v.end.col
+            )
+            .as_str(),
+        );
     }
 
     result
@@ -48,17 +48,29 @@ mod tests {
             rule_name: "myrule".to_string(),
             filename: "filename".to_string(),
             violations: vec![Violation {
-                start: Position { line: 10, col: 12 },
-                end: Position { line: 12, col: 10 },
+                start: Position {
+                    line: 10,
+                    col: 12,
+                    cell: None,
+                },
+                end: Position {
+                    line: 12,
+                    col: 10,
+                    cell: None,
+                },
                 message: "message".to_string(),
                 severity: RuleSeverity::Error,
                 category: RuleCategory::Performance,
                 fixes: vec![],
+                related_locations: vec![],
+                cwe: vec![],
             }],
             errors: vec![],
             execution_error: None,
             output: None,
             execution_time_ms: 10,
+            query_time_ms: 0,
+            exception: None,
         }]);
         assert_eq!(res_with_result, "filename,rule,category,severity,message,start_line,start_col,end_line,end_col\nfilename,myrule,performance,error,message,10,12,12,10\n");
     }
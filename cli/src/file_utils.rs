@@ -1,32 +1,36 @@
 use crate::model::cli_configuration::CliConfiguration;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use glob_match::glob_match;
-use kernel::model::common::Language;
+use kernel::analysis::injection::has_injected_code;
+use kernel::model::common::{Language, FILE_EXTENSIONS_PER_LANGUAGE_LIST};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs;
 use std::fs::read_to_string;
+use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-static FILE_EXTENSIONS_PER_LANGUAGE_LIST: &[(Language, &[&str])] = &[
-    (Language::Csharp, &["cs"]),
-    (Language::Dockerfile, &["docker", "dockerfile"]),
-    (Language::Java, &["java"]),
-    (Language::JavaScript, &["js", "jsx"]),
-    (Language::Kotlin, &["kt", "kts"]),
-    (Language::Python, &["py", "py3"]),
-    (Language::Rust, &["rs"]),
-    (Language::Swift, &["swift"]),
-    (Language::Terraform, &["tf"]),
-    (Language::TypeScript, &["ts", "tsx"]),
-    (Language::Yaml, &["yml", "yaml"]),
+static FILE_EXACT_MATCH_PER_LANGUAGE_LIST: &[(Language, &[&str])] = &[
+    (Language::Dockerfile, &["Dockerfile"]),
+    (Language::Groovy, &["Jenkinsfile"]),
+    (Language::Ruby, &["Gemfile", "Rakefile"]),
 ];
 
-static FILE_EXACT_MATCH_PER_LANGUAGE_LIST: &[(Language, &[&str])] =
-    &[(Language::Dockerfile, &["Dockerfile"])];
-
 static FILE_PREFIX_PER_LANGUAGE_LIST: &[(Language, &[&str])] =
     &[(Language::Dockerfile, &["Dockerfile"])];
 
+// Documentation files contain code blocks of any language and are analyzed for all
+// languages.
+static DOCUMENTATION_EXTENSIONS: &[&str] = &["markdown", "md"];
+
+// Header files with these extensions are shared between C and C++. We look at their
+// content to decide which language should analyze them.
+static AMBIGUOUS_HEADER_EXTENSIONS: &[&str] = &["h"];
+
+// Keywords that only exist in C++. If a header contains one of them, it is a C++ header.
+static CPP_ONLY_KEYWORDS: &[&str] = &["class", "template", "namespace"];
+
 // get all extensions for a language.
 fn get_extensions_for_language(language: &Language) -> Option<Vec<String>> {
     for fe in FILE_EXTENSIONS_PER_LANGUAGE_LIST {
@@ -184,11 +188,173 @@ fn match_prefix_filename(path: &Path, prefixes_list: &[String]) -> bool {
     }
 }
 
+thread_local! {
+    // The language of the shebang of the files without extension. The files are filtered once
+    // for each language, the shebang of a file is only read the first time.
+    static SHEBANG_LANGUAGES: RefCell<HashMap<PathBuf, Option<Language>>> =
+        RefCell::new(HashMap::new());
+}
+
+// get the language of the interpreter in the shebang of a file
+fn read_shebang_language(path: &Path) -> Option<Language> {
+    let file = fs::File::open(path).ok()?;
+
+    // only read the beginning of the file, a shebang is always on the first line.
+    let mut first_line = String::new();
+    BufReader::new(file.take(256))
+        .read_line(&mut first_line)
+        .ok()?;
+
+    Language::from_shebang(&first_line)
+}
+
+// filter a file without extension based on the interpreter of its shebang
+fn match_shebang(path: &Path, language: &Language) -> bool {
+    if path.extension().is_some() {
+        return false;
+    }
+
+    let shebang_language = SHEBANG_LANGUAGES.with(|shebang_languages| {
+        *shebang_languages
+            .borrow_mut()
+            .entry(path.to_path_buf())
+            .or_insert_with(|| read_shebang_language(path))
+    });
+    shebang_language == Some(*language)
+}
+
+// check if the content of a file contains keywords that only exist in C++.
+fn content_looks_like_cpp(content: &str) -> bool {
+    content
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|token| CPP_ONLY_KEYWORDS.contains(&token))
+}
+
+// For headers that can be either C or C++ (e.g. `.h`), check that the content of the
+// header matches the language. Files that are not ambiguous headers always match.
+fn match_ambiguous_header(path: &Path, language: &Language) -> bool {
+    let is_ambiguous_header = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| AMBIGUOUS_HEADER_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false);
+
+    if !is_ambiguous_header {
+        return true;
+    }
+
+    let is_cpp = read_to_string(path)
+        .map(|content| content_looks_like_cpp(&content))
+        .unwrap_or(false);
+
+    match language {
+        Language::C => !is_cpp,
+        Language::Cpp => is_cpp,
+        _ => true,
+    }
+}
+
+// check if a path relative to the repository is matched by a glob or is in a directory
+fn path_matches(pattern: &str, relative_path: &str) -> bool {
+    !pattern.is_empty()
+        && (glob_match(pattern, relative_path) || Path::new(relative_path).starts_with(pattern))
+}
+
+// Get the kind of a file set in the configuration: Some(false) for the production paths,
+// Some(true) for the test paths and None when the analyzer guesses it. The production paths
+// win over the test paths.
+pub fn get_test_file_override(
+    relative_path: &str,
+    test_paths: &[String],
+    production_paths: &[String],
+) -> Option<bool> {
+    if production_paths
+        .iter()
+        .any(|p| path_matches(p, relative_path))
+    {
+        Some(false)
+    } else if test_paths.iter().any(|p| path_matches(p, relative_path)) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+// Parse the language overrides given on the command line (e.g. `bzl=python,es6=javascript`).
+pub fn parse_language_map(value: &str) -> Result<HashMap<String, String>> {
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (extension, language) = entry.split_once('=').ok_or_else(|| {
+                anyhow!("invalid language mapping {entry}, expected extension=language")
+            })?;
+            Ok((extension.trim().to_string(), language.trim().to_string()))
+        })
+        .collect()
+}
+
+// Get the language of each extension overridden by the user. Fails if a language is unknown.
+pub fn get_language_overrides(
+    language_map: &HashMap<String, String>,
+) -> Result<HashMap<String, Language>> {
+    language_map
+        .iter()
+        .map(|(extension, language_name)| {
+            let language = Language::from_name(language_name).ok_or_else(|| {
+                anyhow!("unknown language {language_name} for extension {extension}")
+            })?;
+            Ok((extension.trim_start_matches('.').to_lowercase(), language))
+        })
+        .collect()
+}
+
+// filter a file according to its extension. An extension overridden by the user only
+// matches the language it is mapped to.
+fn match_extension_with_overrides(
+    path: &Path,
+    language: &Language,
+    extensions: &[String],
+    language_overrides: &HashMap<String, Language>,
+) -> bool {
+    let overridden_language = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(|e| language_overrides.get(&e.to_lowercase()));
+
+    match overridden_language {
+        Some(l) => l == language,
+        None => match_extension(path, extensions),
+    }
+}
+
+// filter the files of another language whose strings can contain code of the language (e.g. the
+// SQL queries of the Python files)
+fn match_injection(
+    path: &Path,
+    language: &Language,
+    language_overrides: &HashMap<String, Language>,
+) -> bool {
+    Language::from_path(path, language_overrides)
+        .map(|host_language| {
+            host_language != *language && has_injected_code(&host_language, language)
+        })
+        .unwrap_or(false)
+}
+
 // filter files to analyze for a language. It will filter the files based on the prefix or suffix.
-pub fn filter_files_for_language(files: &[PathBuf], language: &Language) -> Vec<PathBuf> {
+pub fn filter_files_for_language(
+    files: &[PathBuf],
+    language: &Language,
+    language_overrides: &HashMap<String, Language>,
+) -> Vec<PathBuf> {
     let extensions = get_extensions_for_language(language).unwrap_or_default();
     let exact_matches = get_exact_filename_for_language(language).unwrap_or_default();
     let prefixes = get_prefix_for_language(language).unwrap_or_default();
+    let documentation_extensions: Vec<String> = DOCUMENTATION_EXTENSIONS
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
 
     if extensions.is_empty() && exact_matches.is_empty() && prefixes.is_empty() {
         return vec![];
@@ -197,11 +363,21 @@ pub fn filter_files_for_language(files: &[PathBuf], language: &Language) -> Vec<
     let result = files
         .iter()
         .filter(|p| {
-            let extension_match = match_extension(p, &extensions);
+            let extension_match =
+                match_extension_with_overrides(p, language, &extensions, language_overrides);
             let filename_match = match_exact_filename(p, &exact_matches);
             let prefix_match = match_prefix_filename(p, &prefixes);
+            let shebang_match = match_shebang(p, language);
+            let documentation_match = match_extension(p, &documentation_extensions);
+            let injection_match = match_injection(p, language, language_overrides);
 
-            extension_match || filename_match || prefix_match
+            (extension_match
+                || filename_match
+                || prefix_match
+                || shebang_match
+                || documentation_match
+                || injection_match)
+                && match_ambiguous_header(p, language)
         })
         .cloned()
         .collect();
@@ -237,8 +413,13 @@ pub fn filter_files_by_size(files: &[PathBuf], configuration: &CliConfiguration)
 #[cfg(test)]
 mod tests {
     use super::*;
+    use kernel::analysis::analyze::analyze_bytes;
+    use kernel::model::analysis::AnalysisOptions;
     use kernel::model::common::OutputFormat::Sarif;
+    use kernel::model::common::{Position, Range};
+    use kernel::model::rule::{RuleCategory, RuleInternal, RuleSeverity};
     use std::collections::HashMap;
+    use std::env;
     use std::path::Path;
 
     #[test]
@@ -284,6 +465,14 @@ mod tests {
             rules: vec![],
             max_file_size_kb: 1,
             use_staging: false,
+            language_overrides: HashMap::new(),
+            max_parsing_error_rate: None,
+            file_timeout_ms: None,
+            test_paths: vec![],
+            production_paths: vec![],
+            debug_rules: false,
+            rule_timeout_ms: None,
+            rule_memory_limit_mb: None,
         };
         assert_eq!(0, filter_files_by_size(&files1, &cli_configuration).len());
 
@@ -424,10 +613,26 @@ mod tests {
     #[test]
     fn get_extensions_for_language_all_languages() {
         let mut extensions_per_languages: HashMap<Language, usize> = HashMap::new();
-        extensions_per_languages.insert(Language::JavaScript, 2);
-        extensions_per_languages.insert(Language::Python, 2);
+        extensions_per_languages.insert(Language::C, 2);
+        extensions_per_languages.insert(Language::Cpp, 6);
+        extensions_per_languages.insert(Language::Dart, 1);
+        extensions_per_languages.insert(Language::Elixir, 2);
+        extensions_per_languages.insert(Language::Go, 1);
+        extensions_per_languages.insert(Language::Groovy, 2);
+        extensions_per_languages.insert(Language::JavaScript, 6);
+        extensions_per_languages.insert(Language::Json, 2);
+        extensions_per_languages.insert(Language::Kotlin, 2);
+        extensions_per_languages.insert(Language::Lua, 1);
+        extensions_per_languages.insert(Language::Php, 1);
+        extensions_per_languages.insert(Language::Python, 3);
+        extensions_per_languages.insert(Language::Ruby, 2);
         extensions_per_languages.insert(Language::Rust, 1);
-        extensions_per_languages.insert(Language::TypeScript, 2);
+        extensions_per_languages.insert(Language::Scala, 2);
+        extensions_per_languages.insert(Language::Shell, 2);
+        extensions_per_languages.insert(Language::Sql, 1);
+        extensions_per_languages.insert(Language::Terraform, 2);
+        extensions_per_languages.insert(Language::Toml, 1);
+        extensions_per_languages.insert(Language::TypeScript, 6);
         extensions_per_languages.insert(Language::Dockerfile, 2);
         extensions_per_languages.insert(Language::Yaml, 2);
 
@@ -455,14 +660,18 @@ mod tests {
         let files = &files.unwrap();
         assert_eq!(
             0,
-            filter_files_for_language(files, &Language::TypeScript).len()
+            filter_files_for_language(files, &Language::TypeScript, &HashMap::new()).len()
+        );
+        assert_ne!(
+            0,
+            filter_files_for_language(files, &Language::Rust, &HashMap::new()).len()
         );
-        assert_ne!(0, filter_files_for_language(files, &Language::Rust).len());
         assert_eq!(
             1,
             filter_files_for_language(
                 &[PathBuf::from("path").join(PathBuf::from("foobar.Dockerfile"))],
-                &Language::Dockerfile
+                &Language::Dockerfile,
+                &HashMap::new()
             )
             .len()
         );
@@ -474,7 +683,8 @@ mod tests {
             1,
             filter_files_for_language(
                 &[PathBuf::from("path").join(PathBuf::from("Dockerfile.foobar"))],
-                &Language::Dockerfile
+                &Language::Dockerfile,
+                &HashMap::new()
             )
             .len()
         );
@@ -482,7 +692,133 @@ mod tests {
             0,
             filter_files_for_language(
                 &[PathBuf::from("path").join(PathBuf::from("Dock3rfile.foobar"))],
-                &Language::Dockerfile
+                &Language::Dockerfile,
+                &HashMap::new()
+            )
+            .len()
+        );
+    }
+
+    #[test]
+    fn test_content_looks_like_cpp() {
+        assert!(content_looks_like_cpp("namespace foo { int bar(); }"));
+        assert!(content_looks_like_cpp(
+            "template <typename T> T max(T a, T b);"
+        ));
+        assert!(content_looks_like_cpp("class Foo {};"));
+        assert!(!content_looks_like_cpp(
+            "struct foo { int classification; };"
+        ));
+        assert!(!content_looks_like_cpp("int max(int a, int b);"));
+    }
+
+    #[test]
+    fn test_filter_files_for_language_c_and_cpp() {
+        let files = [
+            PathBuf::from("src").join(PathBuf::from("main.c")),
+            PathBuf::from("src").join(PathBuf::from("main.cpp")),
+            PathBuf::from("src").join(PathBuf::from("util.hpp")),
+        ];
+        assert_eq!(
+            1,
+            filter_files_for_language(&files, &Language::C, &HashMap::new()).len()
+        );
+        assert_eq!(
+            2,
+            filter_files_for_language(&files, &Language::Cpp, &HashMap::new()).len()
+        );
+
+        // a header we cannot read defaults to C
+        let headers = [PathBuf::from("src").join(PathBuf::from("does-not-exist.h"))];
+        assert_eq!(
+            1,
+            filter_files_for_language(&headers, &Language::C, &HashMap::new()).len()
+        );
+        assert_eq!(
+            0,
+            filter_files_for_language(&headers, &Language::Cpp, &HashMap::new()).len()
+        );
+    }
+
+    // files without extension are detected by their shebang
+    #[test]
+    fn test_filter_files_for_language_shebang() {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/shebang");
+        let files = [
+            d.join("build"),
+            d.join("manage"),
+            d.join("serve"),
+            d.join("README"),
+            PathBuf::from("scripts").join(PathBuf::from("deploy.sh")),
+        ];
+        let shell_files = filter_files_for_language(&files, &Language::Shell, &HashMap::new());
+        assert_eq!(2, shell_files.len());
+        assert!(shell_files.contains(&d.join("build")));
+        assert_eq!(
+            vec![d.join("manage")],
+            filter_files_for_language(&files, &Language::Python, &HashMap::new())
+        );
+        assert_eq!(
+            vec![d.join("serve")],
+            filter_files_for_language(&files, &Language::JavaScript, &HashMap::new())
+        );
+        assert_eq!(
+            0,
+            filter_files_for_language(&files, &Language::Ruby, &HashMap::new()).len()
+        );
+    }
+
+    // the shebang of a file is read once, not again for each language
+    #[test]
+    fn test_filter_files_for_language_shebang_read_once() {
+        let directory = env::temp_dir().join(format!("shebang-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let script = directory.join("run");
+        fs::write(&script, "#!/bin/bash\necho hello\n").unwrap();
+        let files = [script.clone()];
+
+        let shell_files = filter_files_for_language(&files, &Language::Shell, &HashMap::new());
+        // the file changes after it was filtered, its shebang is not read again
+        fs::write(&script, "#!/usr/bin/env python3\nprint('hello')\n").unwrap();
+        let python_files = filter_files_for_language(&files, &Language::Python, &HashMap::new());
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(vec![script], shell_files);
+        assert!(python_files.is_empty());
+    }
+
+    #[test]
+    fn test_filter_files_for_language_ruby() {
+        assert_eq!(
+            3,
+            filter_files_for_language(
+                &[
+                    PathBuf::from("app").join(PathBuf::from("user.rb")),
+                    PathBuf::from("lib").join(PathBuf::from("tasks.rake")),
+                    PathBuf::from("Gemfile"),
+                    PathBuf::from("Gemfile.lock"),
+                ],
+                &Language::Ruby,
+                &HashMap::new()
+            )
+            .len()
+        );
+    }
+
+    #[test]
+    fn test_filter_files_for_language_groovy() {
+        assert_eq!(
+            3,
+            filter_files_for_language(
+                &[
+                    PathBuf::from("Jenkinsfile"),
+                    PathBuf::from("app").join(PathBuf::from("build.gradle")),
+                    PathBuf::from("src").join(PathBuf::from("Utils.groovy")),
+                    PathBuf::from("Jenkinsfile.bak"),
+                ],
+                &Language::Groovy,
+                &HashMap::new()
             )
             .len()
         );
@@ -494,7 +830,8 @@ mod tests {
             1,
             filter_files_for_language(
                 &[PathBuf::from("path").join(PathBuf::from("Dockerfile"))],
-                &Language::Dockerfile
+                &Language::Dockerfile,
+                &HashMap::new()
             )
             .len()
         );
@@ -502,9 +839,196 @@ mod tests {
             0,
             filter_files_for_language(
                 &[PathBuf::from("path").join(PathBuf::from("Dock3rfile"))],
-                &Language::Dockerfile
+                &Language::Dockerfile,
+                &HashMap::new()
             )
             .len()
         );
     }
+
+    #[test]
+    fn test_filter_files_for_language_documentation() {
+        let files = [
+            PathBuf::from("README.md"),
+            PathBuf::from("docs").join(PathBuf::from("guide.markdown")),
+            PathBuf::from("notes.txt"),
+        ];
+        assert_eq!(
+            2,
+            filter_files_for_language(&files, &Language::Python, &HashMap::new()).len()
+        );
+        assert_eq!(
+            2,
+            filter_files_for_language(&files, &Language::Go, &HashMap::new()).len()
+        );
+    }
+
+    #[test]
+    fn test_parse_language_map() {
+        let language_map = parse_language_map("bzl=python, es6=javascript").unwrap();
+        assert_eq!(2, language_map.len());
+        assert_eq!("python", language_map.get("bzl").unwrap());
+        assert_eq!("javascript", language_map.get("es6").unwrap());
+        assert!(parse_language_map("").unwrap().is_empty());
+        assert!(parse_language_map("bzl:python").is_err());
+    }
+
+    #[test]
+    fn test_get_language_overrides() {
+        let language_map = HashMap::from([
+            (".BZL".to_string(), "python".to_string()),
+            ("es6".to_string(), "JavaScript".to_string()),
+        ]);
+        let overrides = get_language_overrides(&language_map).unwrap();
+        assert_eq!(Some(&Language::Python), overrides.get("bzl"));
+        assert_eq!(Some(&Language::JavaScript), overrides.get("es6"));
+
+        let language_map = HashMap::from([("bzl".to_string(), "starlark".to_string())]);
+        let error = get_language_overrides(&language_map).unwrap_err();
+        assert_eq!(
+            "unknown language starlark for extension bzl",
+            error.to_string()
+        );
+    }
+
+    // the overrides of the user win over the default extensions
+    #[test]
+    fn test_filter_files_for_language_with_overrides() {
+        let files = [
+            PathBuf::from("tools").join(PathBuf::from("defs.bzl")),
+            PathBuf::from("src").join(PathBuf::from("app.es6")),
+            PathBuf::from("src").join(PathBuf::from("index.js")),
+            PathBuf::from("src").join(PathBuf::from("main.py")),
+        ];
+        let overrides = HashMap::from([
+            ("bzl".to_string(), Language::Python),
+            ("es6".to_string(), Language::JavaScript),
+            ("js".to_string(), Language::TypeScript),
+        ]);
+        let python_files = filter_files_for_language(&files, &Language::Python, &overrides);
+        assert_eq!(2, python_files.len());
+        assert!(python_files.contains(&files[0]));
+        // the Python and TypeScript files are analyzed for the scripts of their HTML strings
+        assert_eq!(
+            files.to_vec(),
+            filter_files_for_language(&files, &Language::JavaScript, &overrides)
+        );
+        assert_eq!(
+            vec![files[2].clone()],
+            filter_files_for_language(&files, &Language::TypeScript, &overrides)
+        );
+    }
+
+    // the configuration forces the kind of the files, the production paths win
+    #[test]
+    fn test_get_test_file_override() {
+        let test_paths = vec!["**/fixtures/**".to_string(), "qa".to_string()];
+        let production_paths = vec!["tests/fixtures/prod/**".to_string()];
+        assert_eq!(
+            Some(true),
+            get_test_file_override("src/fixtures/data.py", &test_paths, &production_paths)
+        );
+        assert_eq!(
+            Some(true),
+            get_test_file_override("qa/check.py", &test_paths, &production_paths)
+        );
+        assert_eq!(
+            Some(false),
+            get_test_file_override("tests/fixtures/prod/api.py", &test_paths, &production_paths)
+        );
+        assert_eq!(
+            None,
+            get_test_file_override("src/api.py", &test_paths, &production_paths)
+        );
+        assert_eq!(
+            None,
+            get_test_file_override("qa_tools/check.py", &test_paths, &[])
+        );
+    }
+
+    // the SQL queries and the scripts of the HTML strings of the files are analyzed, the language
+    // of the files comes from the overrides
+    #[test]
+    fn test_analyze_injected_code() {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/injection");
+        let files = get_files(d.to_str().unwrap(), None, &[]).unwrap();
+        let overrides = HashMap::from([("bzl".to_string(), Language::Python)]);
+        let analysis_options = AnalysisOptions {
+            log_output: false,
+            use_debug: false,
+            language_overrides: overrides.clone(),
+            ..AnalysisOptions::default()
+        };
+        let rule = |language: Language, query: &str| RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
+            language,
+            code: r#"
+function visit(node, filename, code) {
+    const n = node.captures["node"];
+    addError(buildError(n.start.line, n.start.col, n.end.line, n.end.col, "found"));
+}
+"#
+            .to_string(),
+            tree_sitter_query: Some(query.to_string()),
+            variables: HashMap::new(),
+            include_unnamed_nodes: false,
+            max_capture_nodes: None,
+            include_siblings: false,
+            include_ancestors: false,
+            capture_mode: false,
+            arguments: HashMap::new(),
+            cwe: vec![],
+            timeout_ms: None,
+            query_match_limit: None,
+        };
+        // the positions of the violations of a rule on the files of the language, by file
+        let analyze_files = |language: Language, query: &str| {
+            let mut ranges: Vec<(String, Range)> =
+                filter_files_for_language(&files, &language, &overrides)
+                    .iter()
+                    .flat_map(|path| {
+                        let filename = path.file_name().unwrap().to_str().unwrap();
+                        analyze_bytes(
+                            &language,
+                            vec![rule(language, query)],
+                            filename,
+                            &fs::read(path).unwrap(),
+                            &analysis_options,
+                        )
+                    })
+                    .flat_map(|result| {
+                        result
+                            .violations
+                            .iter()
+                            .map(|v| (result.filename.clone(), v.get_range()))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+            ranges.sort_by(|a, b| (&a.0, &a.1.start).cmp(&(&b.0, &b.1.start)));
+            ranges
+        };
+        let range = |start: (u32, u32), end: (u32, u32)| {
+            Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+        };
+
+        assert_eq!(
+            vec![("queries.bzl".to_string(), range((2, 24), (2, 43)))],
+            analyze_files(Language::Sql, "(select_statement) @node")
+        );
+        assert_eq!(
+            vec![
+                ("page.js".to_string(), range((2, 11), (2, 15))),
+                ("queries.bzl".to_string(), range((3, 17), (3, 21))),
+            ],
+            analyze_files(
+                Language::JavaScript,
+                "(call_expression function: (identifier) @node)"
+            )
+        );
+    }
 }
@@ -0,0 +1,143 @@
+use anyhow::{Context, Result};
+use kernel::analysis::fix::apply_first_fixes;
+use kernel::model::analysis::{PositionEncoding, ERROR_FILE_DECODED_LOSSILY};
+use kernel::model::rule::RuleResult;
+use kernel::model::violation::Violation;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Apply the first fix of each violation to the files of the directory, the other fixes of a
+// violation are alternatives. The files decoded lossily and the cells of the notebooks are not
+// fixed. Returns the number of fixes applied and the number of files changed.
+pub fn fix_files(
+    directory: &Path,
+    rule_results: &[RuleResult],
+    encoding: PositionEncoding,
+) -> Result<(usize, usize)> {
+    let mut violations_by_file: BTreeMap<&str, Vec<&Violation>> = BTreeMap::new();
+    for rule_result in rule_results {
+        // writing the file back would replace its invalid bytes
+        if rule_result
+            .errors
+            .contains(&ERROR_FILE_DECODED_LOSSILY.to_string())
+        {
+            continue;
+        }
+        violations_by_file
+            .entry(rule_result.filename.as_str())
+            .or_default()
+            .extend(
+                rule_result
+                    .violations
+                    .iter()
+                    .filter(|v| !v.fixes.is_empty() && v.start.cell.is_none()),
+            );
+    }
+
+    let mut fixes_applied = 0;
+    let mut files_changed = 0;
+    for (filename, violations) in violations_by_file {
+        if violations.is_empty() {
+            continue;
+        }
+        let path = directory.join(filename);
+        let code = fs::read_to_string(&path)
+            .with_context(|| format!("cannot read file {}", path.display()))?;
+        let (fixed, applied) = apply_first_fixes(&code, &violations, encoding)
+            .with_context(|| format!("cannot fix file {}", path.display()))?;
+        if fixed != code {
+            fs::write(&path, fixed)
+                .with_context(|| format!("cannot write file {}", path.display()))?;
+            fixes_applied += applied;
+            files_changed += 1;
+        }
+    }
+    Ok((fixes_applied, files_changed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kernel::model::common::Position;
+    use kernel::model::rule::{RuleCategory, RuleSeverity};
+    use kernel::model::violation::{Edit, EditType, Fix};
+    use std::env;
+
+    fn position(line: u32, col: u32) -> Position {
+        Position {
+            line,
+            col,
+            cell: None,
+        }
+    }
+
+    fn fix(description: &str, line: u32, content: &str) -> Fix {
+        Fix {
+            description: description.to_string(),
+            edits: vec![Edit {
+                start: position(line, 1),
+                end: Some(position(line, 6)),
+                edit_type: EditType::Update,
+                content: Some(content.to_string()),
+            }],
+        }
+    }
+
+    fn rule_result(filename: &str, fixes: Vec<Fix>, errors: Vec<String>) -> RuleResult {
+        RuleResult {
+            rule_name: "myrule".to_string(),
+            filename: filename.to_string(),
+            violations: vec![Violation {
+                start: position(1, 1),
+                end: position(1, 6),
+                message: "invalid print".to_string(),
+                severity: RuleSeverity::Warning,
+                category: RuleCategory::BestPractices,
+                fixes,
+                related_locations: vec![],
+                cwe: vec![],
+            }],
+            errors,
+            execution_error: None,
+            output: None,
+            execution_time_ms: 0,
+            query_time_ms: 0,
+            exception: None,
+        }
+    }
+
+    // only the first fix of a violation is applied
+    #[test]
+    fn test_fix_files() {
+        let directory = env::temp_dir().join(format!("fix-files-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("a.py"), "print(1)\n").unwrap();
+        fs::write(directory.join("b.py"), "print(2)\n").unwrap();
+        fs::write(directory.join("c.py"), "print(3)\n").unwrap();
+
+        let rule_results = vec![
+            rule_result(
+                "a.py",
+                vec![fix("use log", 1, "log"), fix("use debug", 1, "debug")],
+                vec![],
+            ),
+            // no fix
+            rule_result("b.py", vec![], vec![]),
+            rule_result(
+                "c.py",
+                vec![fix("use log", 1, "log")],
+                vec![ERROR_FILE_DECODED_LOSSILY.to_string()],
+            ),
+        ];
+        let result = fix_files(&directory, &rule_results, PositionEncoding::default());
+        let read = |filename: &str| fs::read_to_string(directory.join(filename)).unwrap();
+        let contents = (read("a.py"), read("b.py"), read("c.py"));
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!((1, 1), result.unwrap());
+        assert_eq!("log(1)\n", contents.0);
+        assert_eq!("print(2)\n", contents.1);
+        assert_eq!("print(3)\n", contents.2);
+    }
+}
@@ -3,6 +3,9 @@ pub mod constants;
 pub mod csv;
 pub mod datadog_utils;
 pub mod file_utils;
+pub mod fix;
 pub mod model;
+pub mod performance;
+pub mod results;
 pub mod rule_utils;
 pub mod sarif;
@@ -1,5 +1,6 @@
-use kernel::model::common::OutputFormat;
+use kernel::model::common::{Language, OutputFormat};
 use kernel::model::rule::Rule;
+use std::collections::HashMap;
 
 /// represents the CLI configuratoin
 #[derive(Clone)]
@@ -17,4 +18,19 @@ pub struct CliConfiguration {
     pub rules: Vec<Rule>,
     pub max_file_size_kb: u64,
     pub use_staging: bool,
+    pub language_overrides: HashMap<String, Language>, // extension -> language
+    // skip files with more syntax errors
+    pub max_parsing_error_rate: Option<f64>,
+    // time budget to parse and query each file
+    pub file_timeout_ms: Option<u64>,
+    // paths forced to be test files
+    pub test_paths: Vec<String>,
+    // paths forced to be production code
+    pub production_paths: Vec<String>,
+    // print the output of the rules (console.log)
+    pub debug_rules: bool,
+    // time budget of each rule on each file
+    pub rule_timeout_ms: Option<u64>,
+    // memory of each rule on each file
+    pub rule_memory_limit_mb: Option<u64>,
 }
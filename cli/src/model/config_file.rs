@@ -1,5 +1,7 @@
+use std::collections::HashMap;
 use std::fmt;
 
+use kernel::model::rule::RuleArgumentValue;
 use serde;
 use serde::{Deserialize, Serialize};
 
@@ -13,6 +15,20 @@ pub struct ConfigFile {
     pub ignore_gitignore: Option<bool>,
     #[serde(rename(serialize = "max-file-size-kb", deserialize = "max-file-size-kb"))]
     pub max_file_size_kb: Option<u64>,
+    #[serde(rename(serialize = "language-map", deserialize = "language-map"))]
+    pub language_map: Option<HashMap<String, String>>,
+    // values of the arguments of the rules, by rule name and argument name
+    #[serde(rename(serialize = "rule-arguments", deserialize = "rule-arguments"))]
+    pub rule_arguments: Option<HashMap<String, HashMap<String, RuleArgumentValue>>>,
+    // paths (or globs) of the test files and of the production code, to override the kind of
+    // file guessed by the analyzer
+    #[serde(rename(serialize = "test-paths", deserialize = "test-paths"))]
+    pub test_paths: Option<Vec<String>>,
+    #[serde(rename(serialize = "production-paths", deserialize = "production-paths"))]
+    pub production_paths: Option<Vec<String>>,
+    // how long each rule can execute on a file, in milliseconds, by rule name
+    #[serde(rename(serialize = "rule-timeouts", deserialize = "rule-timeouts"))]
+    pub rule_timeouts: Option<HashMap<String, u64>>,
 }
 
 impl fmt::Display for ConfigFile {
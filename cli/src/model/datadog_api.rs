@@ -1,5 +1,7 @@
 use kernel::model::common::Language;
-use kernel::model::rule::{EntityChecked, Rule, RuleCategory, RuleSeverity, RuleType};
+use kernel::model::rule::{
+    EntityChecked, Rule, RuleArgument, RuleCategory, RuleSeverity, RuleType,
+};
 use kernel::model::rule_test::RuleTest;
 use kernel::model::ruleset::RuleSet;
 use serde::{Deserialize, Serialize};
@@ -32,6 +34,12 @@ pub struct ApiResponseRule {
     pub severity: RuleSeverity,
     pub category: RuleCategory,
     pub tests: Vec<ApiResponseRuleTest>,
+    pub include_unnamed_nodes: Option<bool>,
+    pub max_capture_nodes: Option<usize>,
+    pub include_siblings: Option<bool>,
+    pub include_ancestors: Option<bool>,
+    pub capture_mode: Option<bool>,
+    pub arguments: Option<Vec<RuleArgument>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -77,6 +85,15 @@ impl ApiResponseRuleset {
                             annotation_count: t.annotation_count,
                         })
                         .collect(),
+                    include_unnamed_nodes: rule_from_api.include_unnamed_nodes.unwrap_or(false),
+                    max_capture_nodes: rule_from_api.max_capture_nodes,
+                    include_siblings: rule_from_api.include_siblings.unwrap_or(false),
+                    include_ancestors: rule_from_api.include_ancestors.unwrap_or(false),
+                    capture_mode: rule_from_api.capture_mode.unwrap_or(false),
+                    arguments: rule_from_api.arguments.unwrap_or_default(),
+                    argument_values: HashMap::new(),
+                    timeout_ms: None,
+                    query_match_limit: None,
                 })
                 .collect(),
             None => Vec::new(),
@@ -0,0 +1,171 @@
+use kernel::model::rule::RuleResult;
+use std::collections::HashMap;
+
+// The time spent by a rule on all the files. The times of each file are wall-clock times of
+// one execution of the rule, they are added for all the files.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RuleTiming {
+    pub rule_name: String,
+    pub files: usize,
+    pub query_time_ms: u128,
+    pub execution_time_ms: u128,
+    // the file on which the rule spent the most time and the time spent on it
+    pub slowest_file: String,
+    pub slowest_file_time_ms: u128,
+}
+
+impl RuleTiming {
+    pub fn total_time_ms(&self) -> u128 {
+        self.query_time_ms + self.execution_time_ms
+    }
+}
+
+// Get the time spent by each rule on all the files, the slowest rules first. Only the first
+// `count` rules are returned.
+pub fn get_slowest_rules(rule_results: &[RuleResult], count: usize) -> Vec<RuleTiming> {
+    let mut timings: HashMap<&str, RuleTiming> = HashMap::new();
+    for rule_result in rule_results {
+        let file_time_ms = rule_result.query_time_ms + rule_result.execution_time_ms;
+        let timing = timings
+            .entry(rule_result.rule_name.as_str())
+            .or_insert_with(|| RuleTiming {
+                rule_name: rule_result.rule_name.clone(),
+                files: 0,
+                query_time_ms: 0,
+                execution_time_ms: 0,
+                slowest_file: rule_result.filename.clone(),
+                slowest_file_time_ms: file_time_ms,
+            });
+        timing.files += 1;
+        timing.query_time_ms += rule_result.query_time_ms;
+        timing.execution_time_ms += rule_result.execution_time_ms;
+        if file_time_ms > timing.slowest_file_time_ms {
+            timing.slowest_file = rule_result.filename.clone();
+            timing.slowest_file_time_ms = file_time_ms;
+        }
+    }
+
+    let mut timings: Vec<RuleTiming> = timings.into_values().collect();
+    // the rules with the same time are sorted by name so that the table is stable
+    timings.sort_by(|a, b| {
+        b.total_time_ms()
+            .cmp(&a.total_time_ms())
+            .then_with(|| a.rule_name.cmp(&b.rule_name))
+    });
+    timings.truncate(count);
+    timings
+}
+
+// Format the time spent by the rules as a table to show in the summary of the run.
+pub fn format_slowest_rules(timings: &[RuleTiming]) -> String {
+    let mut result = format!(
+        "{:<50} {:>6} {:>10} {:>10} {:>10}  {}\n",
+        "rule", "files", "total ms", "query ms", "exec ms", "slowest file (ms)"
+    );
+    for timing in timings {
+        result.push_str(
+            format!(
+                "{:<50} {:>6} {:>10} {:>10} {:>10}  {} ({})\n",
+                timing.rule_name,
+                timing.files,
+                timing.total_time_ms(),
+                timing.query_time_ms,
+                timing.execution_time_ms,
+                timing.slowest_file,
+                timing.slowest_file_time_ms
+            )
+            .as_str(),
+        );
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule_result(rule_name: &str, filename: &str, query: u128, execution: u128) -> RuleResult {
+        RuleResult {
+            rule_name: rule_name.to_string(),
+            filename: filename.to_string(),
+            violations: vec![],
+            errors: vec![],
+            execution_error: None,
+            output: None,
+            execution_time_ms: execution,
+            query_time_ms: query,
+            exception: None,
+        }
+    }
+
+    #[test]
+    fn test_get_slowest_rules() {
+        let rule_results = vec![
+            rule_result("python-security/eval", "a.py", 1, 10),
+            rule_result("python-security/eval", "b.py", 2, 40),
+            rule_result("python-best-practices/no-print", "a.py", 0, 3),
+            rule_result("python-code-style/max-length", "a.py", 30, 50),
+            rule_result("python-code-style/max-length", "b.py", 0, 0),
+            rule_result("python-inclusive/names", "a.py", 1, 2),
+        ];
+
+        let timings = get_slowest_rules(&rule_results, 10);
+        assert_eq!(
+            vec![
+                "python-code-style/max-length",
+                "python-security/eval",
+                "python-best-practices/no-print",
+                "python-inclusive/names",
+            ],
+            timings
+                .iter()
+                .map(|t| t.rule_name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            RuleTiming {
+                rule_name: "python-security/eval".to_string(),
+                files: 2,
+                query_time_ms: 3,
+                execution_time_ms: 50,
+                slowest_file: "b.py".to_string(),
+                slowest_file_time_ms: 42,
+            },
+            timings[1]
+        );
+        // the rules with the same time are sorted by name
+        assert_eq!(3, timings[2].total_time_ms());
+        assert_eq!(3, timings[3].total_time_ms());
+
+        // only the slowest rules
+        let timings = get_slowest_rules(&rule_results, 2);
+        assert_eq!(2, timings.len());
+        assert_eq!("python-code-style/max-length", timings[0].rule_name);
+        assert!(get_slowest_rules(&[], 10).is_empty());
+    }
+
+    #[test]
+    fn test_format_slowest_rules() {
+        let rule_results = vec![
+            rule_result("rule-fast", "a.py", 0, 1),
+            rule_result("rule-slow", "a.py", 5, 100),
+            rule_result("rule-slow", "b.py", 5, 20),
+            rule_result("rule-medium", "b.py", 10, 10),
+        ];
+        let table = format_slowest_rules(&get_slowest_rules(&rule_results, 10));
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(4, lines.len());
+        assert!(lines[0].starts_with("rule "));
+        assert!(lines[1].starts_with("rule-slow "));
+        assert!(lines[1].ends_with("a.py (105)"));
+        assert!(lines[2].starts_with("rule-medium "));
+        assert!(lines[3].starts_with("rule-fast "));
+
+        // the totals of the table are in descending order
+        let totals: Vec<u128> = lines[1..]
+            .iter()
+            .map(|l| l.split_whitespace().nth(2).unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(vec![130, 20, 1], totals);
+    }
+}
@@ -0,0 +1,221 @@
+use kernel::model::rule::RuleResult;
+use kernel::model::violation::Violation;
+use std::cmp::Ordering;
+
+// The order of the violations in the reports: by file, start position and rule. The violations
+// that start at the same position for the same rule are sorted by end and message.
+fn compare_violations(a: (&RuleResult, &Violation), b: (&RuleResult, &Violation)) -> Ordering {
+    let ((result_a, violation_a), (result_b, violation_b)) = (a, b);
+    result_a
+        .filename
+        .cmp(&result_b.filename)
+        .then_with(|| violation_a.start.cmp(&violation_b.start))
+        .then_with(|| result_a.rule_name.cmp(&result_b.rule_name))
+        .then_with(|| violation_a.end.cmp(&violation_b.end))
+        .then_with(|| violation_a.message.cmp(&violation_b.message))
+}
+
+// Sort the results of the rules by file and rule, and the violations of each result by position.
+// The rules run in any order and the files are analyzed in parallel, the results are sorted so
+// that two runs on the same files have the same report. The errors of the rules are in their
+// results and are sorted with them.
+pub fn sort_rule_results(rule_results: &mut [RuleResult]) {
+    rule_results.sort_by(|a, b| {
+        a.filename
+            .cmp(&b.filename)
+            .then_with(|| a.rule_name.cmp(&b.rule_name))
+    });
+    for rule_result in rule_results.iter_mut() {
+        let mut violations = std::mem::take(&mut rule_result.violations);
+        let result: &RuleResult = rule_result;
+        violations.sort_by(|a, b| compare_violations((result, a), (result, b)));
+        rule_result.violations = violations;
+    }
+}
+
+// Get the violations of all the results with their result, sorted by file, start line, start
+// column and rule. The reports that list the violations of all the rules together use this
+// order.
+pub fn get_sorted_violations(rule_results: &[RuleResult]) -> Vec<(&RuleResult, &Violation)> {
+    let mut violations: Vec<(&RuleResult, &Violation)> = rule_results
+        .iter()
+        .flat_map(|r| r.violations.iter().map(move |v| (r, v)))
+        .collect();
+    violations.sort_by(|a, b| compare_violations(*a, *b));
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kernel::analysis::analyze::analyze;
+    use kernel::model::analysis::AnalysisOptions;
+    use kernel::model::common::{Language, Position};
+    use kernel::model::rule::{RuleCategory, RuleInternal, RuleSeverity};
+    use rayon::prelude::*;
+    use std::collections::HashMap;
+
+    fn position(line: u32, col: u32) -> Position {
+        Position {
+            line,
+            col,
+            cell: None,
+        }
+    }
+
+    fn violation(line: u32, col: u32, message: &str) -> Violation {
+        Violation {
+            start: position(line, col),
+            end: position(line, col + 1),
+            message: message.to_string(),
+            severity: RuleSeverity::Warning,
+            category: RuleCategory::BestPractices,
+            fixes: vec![],
+            related_locations: vec![],
+            cwe: vec![],
+        }
+    }
+
+    fn rule_result(rule_name: &str, filename: &str, violations: Vec<Violation>) -> RuleResult {
+        RuleResult {
+            rule_name: rule_name.to_string(),
+            filename: filename.to_string(),
+            violations,
+            errors: vec![],
+            execution_error: None,
+            output: None,
+            execution_time_ms: 0,
+            query_time_ms: 0,
+            exception: None,
+        }
+    }
+
+    #[test]
+    fn test_get_sorted_violations() {
+        let rule_results = vec![
+            rule_result("rule-b", "b.py", vec![violation(1, 1, "b1")]),
+            rule_result(
+                "rule-b",
+                "a.py",
+                vec![violation(3, 1, "b3"), violation(1, 5, "b2")],
+            ),
+            rule_result(
+                "rule-a",
+                "a.py",
+                vec![violation(3, 1, "a3"), violation(1, 5, "a2")],
+            ),
+        ];
+        let messages: Vec<&str> = get_sorted_violations(&rule_results)
+            .iter()
+            .map(|(_, v)| v.message.as_str())
+            .collect();
+        // by file, line, column, then rule
+        assert_eq!(vec!["a2", "b2", "a3", "b3", "b1"], messages);
+
+        let mut rule_results = rule_results;
+        sort_rule_results(&mut rule_results);
+        let names: Vec<(&str, &str)> = rule_results
+            .iter()
+            .map(|r| (r.filename.as_str(), r.rule_name.as_str()))
+            .collect();
+        assert_eq!(
+            vec![("a.py", "rule-a"), ("a.py", "rule-b"), ("b.py", "rule-b")],
+            names
+        );
+        assert_eq!("a2", rule_results[0].violations[0].message);
+        assert_eq!("a3", rule_results[0].violations[1].message);
+    }
+
+    // run the rules on the files in parallel, like the analyzer, and get the JSON report
+    fn get_json_report(
+        rules: &[RuleInternal],
+        files: &[(&str, &str)],
+        num_threads: usize,
+    ) -> String {
+        let analysis_options = AnalysisOptions {
+            log_output: false,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .unwrap();
+        let mut rule_results: Vec<RuleResult> = pool.install(|| {
+            files
+                .par_iter()
+                .flat_map(|(filename, code)| {
+                    analyze(
+                        &Language::Python,
+                        rules.to_vec(),
+                        filename,
+                        code,
+                        &analysis_options,
+                    )
+                })
+                .collect()
+        });
+        sort_rule_results(&mut rule_results);
+        // the times change between two runs, they are the only values that are not compared
+        for rule_result in &mut rule_results {
+            rule_result.execution_time_ms = 0;
+            rule_result.query_time_ms = 0;
+        }
+        serde_json::to_string(&rule_results).unwrap()
+    }
+
+    // two runs on the same files have the same report, whatever the number of threads and the
+    // order of the rules and the files
+    #[test]
+    fn test_sort_rule_results_deterministic() {
+        let rule = |name: &str, query: &str| RuleInternal {
+            name: name.to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::BestPractices,
+            severity: RuleSeverity::Warning,
+            language: Language::Python,
+            code: r#"
+function visit(node, filename, code) {
+    const n = node.captures["node"];
+    addError(buildError(n.start.line, n.start.col, n.end.line, n.end.col, "found"));
+}
+"#
+            .to_string(),
+            tree_sitter_query: Some(query.to_string()),
+            variables: HashMap::new(),
+            include_unnamed_nodes: false,
+            max_capture_nodes: None,
+            include_siblings: false,
+            include_ancestors: false,
+            capture_mode: false,
+            arguments: HashMap::new(),
+            cwe: vec![],
+            timeout_ms: None,
+            query_match_limit: None,
+        };
+        let mut rules = vec![
+            rule(
+                "python/functions",
+                "(function_definition name: (identifier) @node)",
+            ),
+            rule("python/calls", "(call function: (identifier) @node)"),
+            rule("python/strings", "(string) @node"),
+        ];
+        let codes: Vec<String> = (0..20)
+            .map(|i| format!("def f{i}():\n    print(\"{i}\")\n\nf{i}()\n"))
+            .collect();
+        let filenames: Vec<String> = (0..20).map(|i| format!("src/file{i}.py")).collect();
+        let mut files: Vec<(&str, &str)> = filenames
+            .iter()
+            .zip(codes.iter())
+            .map(|(f, c)| (f.as_str(), c.as_str()))
+            .collect();
+
+        let report = get_json_report(&rules, &files, 1);
+        rules.reverse();
+        files.reverse();
+        assert_eq!(report, get_json_report(&rules, &files, 4));
+        assert!(report.contains("python/strings"));
+    }
+}
@@ -4,13 +4,16 @@ use git2::{BlameOptions, Repository};
 use serde_sarif::sarif::{
     self, ArtifactChangeBuilder, ArtifactLocationBuilder, Fix, FixBuilder, LocationBuilder,
     MessageBuilder, PhysicalLocationBuilder, PropertyBagBuilder, RegionBuilder, Replacement,
-    ReportingDescriptor, Result as SarifResult, ResultBuilder, RunBuilder, Sarif, SarifBuilder,
-    Tool, ToolBuilder, ToolComponent, ToolComponentBuilder,
+    ReportingDescriptor, ReportingDescriptorBuilder, ReportingDescriptorReference,
+    ReportingDescriptorReferenceBuilder, Result as SarifResult, ResultBuilder, RunBuilder, Sarif,
+    SarifBuilder, Tool, ToolBuilder, ToolComponent, ToolComponentBuilder,
+    ToolComponentReferenceBuilder,
 };
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::Path;
 use std::rc::Rc;
 
+use crate::results::get_sorted_violations;
 use kernel::model::rule::RuleSeverity;
 use kernel::model::{
     common::PositionBuilder,
@@ -67,11 +70,10 @@ impl IntoSarif for &Rule {
             builder.short_description(text);
         }
 
-        if let Some(cwe) = self.cwe.as_ref() {
-            let props = PropertyBagBuilder::default()
-                .tags(vec![format!("CWE:{}", cwe)])
-                .build()
-                .unwrap();
+        let cwe = self.get_cwe().unwrap_or_default();
+        if !cwe.is_empty() {
+            let tags: Vec<String> = cwe.iter().map(|c| format!("CWE:{}", c)).collect();
+            let props = PropertyBagBuilder::default().tags(tags).build().unwrap();
             builder.properties(props);
         }
 
@@ -181,6 +183,50 @@ fn generate_tool_section(rules: &[Rule]) -> Result<Tool> {
     Ok(ToolBuilder::default().driver(driver).build()?)
 }
 
+// name of the taxonomy of the CWE identifiers
+const CWE_TAXONOMY: &str = "CWE";
+
+// Generate the CWE taxonomy with all the CWE identifiers of the rules being run. The results
+// reference the identifiers of their rule.
+fn generate_cwe_taxonomy(rules: &[Rule]) -> Result<Option<ToolComponent>> {
+    let cwe: BTreeSet<String> = rules
+        .iter()
+        .flat_map(|r| r.get_cwe().unwrap_or_default())
+        .collect();
+    if cwe.is_empty() {
+        return Ok(None);
+    }
+    let taxa = cwe
+        .into_iter()
+        .map(|c| Ok(ReportingDescriptorBuilder::default().id(c).build()?))
+        .collect::<Result<Vec<ReportingDescriptor>>>()?;
+    Ok(Some(
+        ToolComponentBuilder::default()
+            .name(CWE_TAXONOMY)
+            .information_uri("https://cwe.mitre.org")
+            .taxa(taxa)
+            .build()?,
+    ))
+}
+
+// the references to the CWE taxonomy for the CWE identifiers of a rule
+fn get_cwe_references(cwe: &[String]) -> Vec<ReportingDescriptorReference> {
+    cwe.iter()
+        .map(|c| {
+            ReportingDescriptorReferenceBuilder::default()
+                .id(c)
+                .tool_component(
+                    ToolComponentReferenceBuilder::default()
+                        .name(CWE_TAXONOMY)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+        })
+        .collect()
+}
+
 /// Convert our severity enumeration into the corresponding SARIF values.
 /// The main discrepancy here is that Notice maps to note.
 /// See [this document](https://github.com/oasis-tcs/sarif-spec/blob/main/Documents/CommitteeSpecifications/2.1.0/sarif-schema-2.1.0.json#L1566)
@@ -218,114 +264,147 @@ fn get_sha_for_line(
     None
 }
 
-// Generate the tool section that reports all the rules being run
+// Generate the tool section that reports all the rules being run. The results are sorted by
+// file, position and rule.
 fn generate_results(
     rules: &[Rule],
     rules_results: &[RuleResult],
-    options_orig: SarifGenerationOptions,
+    options: SarifGenerationOptions,
 ) -> Result<Vec<SarifResult>> {
-    rules_results
-        .iter()
-        .flat_map(|rule_result| {
-            // if we find the rule for this violation, get the id, level and category
+    get_sorted_violations(rules_results)
+        .into_iter()
+        .map(|(rule_result, violation)| {
+            // if we find the rule for this violation, get the id and the CWE identifiers
             let mut result_builder = ResultBuilder::default();
             let mut tags = vec![];
 
             if let Some(rule_index) = rules.iter().position(|r| r.name == rule_result.rule_name) {
                 let rule = &rules[rule_index];
-                let category = format!("DATADOG_CATEGORY:{}", rule.category).to_uppercase();
 
                 result_builder.rule_index(i64::try_from(rule_index).unwrap());
-                result_builder.level(get_level_from_severity(rule.severity));
-                tags.push(category);
 
-                // If there is a CWE, add it
-                if let Some(cwe) = &rule.cwe {
-                    tags.push(format!("CWE:{}", cwe));
+                // If there are CWE identifiers, add them
+                let cwe = rule.get_cwe().unwrap_or_default();
+                tags.extend(cwe.iter().map(|c| format!("CWE:{}", c)));
+                if !cwe.is_empty() {
+                    result_builder.taxa(get_cwe_references(&cwe));
                 }
             }
 
-            let options = options_orig.clone();
-            rule_result.violations.iter().map(move |violation| {
-                // if we find the rule for this violation, get the id, level and category
-
-                let location = LocationBuilder::default()
-                    .physical_location(
-                        PhysicalLocationBuilder::default()
-                            .artifact_location(
-                                ArtifactLocationBuilder::default()
-                                    .uri(rule_result.filename.clone())
-                                    .build()
-                                    .unwrap(),
-                            )
-                            .region(
-                                RegionBuilder::default()
-                                    .start_line(violation.start.line)
-                                    .start_column(violation.start.col)
-                                    .end_line(violation.end.line)
-                                    .end_column(violation.end.col)
-                                    .build()?,
-                            )
-                            .build()?,
-                    )
-                    .build()?;
-
-                let fixes: Vec<Fix> = violation
-                    .fixes
-                    .iter()
-                    .map(|fix| {
-                        let replacements: Vec<Replacement> =
-                            fix.edits.iter().map(IntoSarif::into_sarif).collect();
-
-                        let changes = ArtifactChangeBuilder::default()
-                            .artifact_location(
-                                ArtifactLocationBuilder::default()
-                                    .uri(rule_result.filename.clone())
-                                    .build()?,
-                            )
-                            .replacements(replacements)
-                            .build()?;
-                        Ok(FixBuilder::default()
-                            .description(
-                                MessageBuilder::default()
-                                    .text(fix.description.clone())
-                                    .build()?,
-                            )
-                            .artifact_changes(vec![changes])
-                            .build()?)
-                    })
-                    .collect::<Result<Vec<_>>>()?;
-
-                let sha_option = get_sha_for_line(
-                    rule_result.filename.as_str(),
-                    violation.start.line as usize,
-                    &options,
-                );
-                let partial_fingerprints: BTreeMap<String, String> = match sha_option {
-                    Some(s) => BTreeMap::from([("SHA".to_string(), s)]),
-                    None => BTreeMap::new(),
-                };
-
-                Ok(result_builder
-                    .clone()
-                    .rule_id(rule_result.rule_name.clone())
-                    .locations([location])
-                    .fixes(fixes)
-                    .message(
-                        MessageBuilder::default()
-                            .text(violation.message.clone())
-                            .build()
-                            .unwrap(),
-                    )
-                    .properties(
-                        PropertyBagBuilder::default()
-                            .tags(tags.clone())
-                            .build()
-                            .unwrap(),
-                    )
-                    .partial_fingerprints(partial_fingerprints)
-                    .build()?)
-            })
+            let location = LocationBuilder::default()
+                .physical_location(
+                    PhysicalLocationBuilder::default()
+                        .artifact_location(
+                            ArtifactLocationBuilder::default()
+                                .uri(rule_result.filename.clone())
+                                .build()
+                                .unwrap(),
+                        )
+                        .region(
+                            RegionBuilder::default()
+                                .start_line(violation.start.line)
+                                .start_column(violation.start.col)
+                                .end_line(violation.end.line)
+                                .end_column(violation.end.col)
+                                .build()?,
+                        )
+                        .build()?,
+                )
+                .build()?;
+
+            // the related locations are in the file of the violation by default
+            let related_locations = violation
+                .related_locations
+                .iter()
+                .map(|related_location| {
+                    let uri = related_location
+                        .filename
+                        .clone()
+                        .unwrap_or_else(|| rule_result.filename.clone());
+                    Ok(LocationBuilder::default()
+                        .physical_location(
+                            PhysicalLocationBuilder::default()
+                                .artifact_location(
+                                    ArtifactLocationBuilder::default().uri(uri).build()?,
+                                )
+                                .region(
+                                    RegionBuilder::default()
+                                        .start_line(related_location.start.line)
+                                        .start_column(related_location.start.col)
+                                        .end_line(related_location.end.line)
+                                        .end_column(related_location.end.col)
+                                        .build()?,
+                                )
+                                .build()?,
+                        )
+                        .message(
+                            MessageBuilder::default()
+                                .text(related_location.label.clone())
+                                .build()?,
+                        )
+                        .build()?)
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let fixes: Vec<Fix> = violation
+                .fixes
+                .iter()
+                .map(|fix| {
+                    let replacements: Vec<Replacement> =
+                        fix.edits.iter().map(IntoSarif::into_sarif).collect();
+
+                    let changes = ArtifactChangeBuilder::default()
+                        .artifact_location(
+                            ArtifactLocationBuilder::default()
+                                .uri(rule_result.filename.clone())
+                                .build()?,
+                        )
+                        .replacements(replacements)
+                        .build()?;
+                    Ok(FixBuilder::default()
+                        .description(
+                            MessageBuilder::default()
+                                .text(fix.description.clone())
+                                .build()?,
+                        )
+                        .artifact_changes(vec![changes])
+                        .build()?)
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let sha_option = get_sha_for_line(
+                rule_result.filename.as_str(),
+                violation.start.line as usize,
+                &options,
+            );
+            let partial_fingerprints: BTreeMap<String, String> = match sha_option {
+                Some(s) => BTreeMap::from([("SHA".to_string(), s)]),
+                None => BTreeMap::new(),
+            };
+
+            // the level and the category of the violation, the rule can override its own
+            tags.insert(
+                0,
+                format!("DATADOG_CATEGORY:{}", violation.category).to_uppercase(),
+            );
+            result_builder.level(get_level_from_severity(violation.severity));
+            if !related_locations.is_empty() {
+                result_builder.related_locations(related_locations);
+            }
+
+            Ok(result_builder
+                .rule_id(rule_result.rule_name.clone())
+                .locations([location])
+                .fixes(fixes)
+                .message(
+                    MessageBuilder::default()
+                        .text(violation.message.clone())
+                        .build()
+                        .unwrap(),
+                )
+                .properties(PropertyBagBuilder::default().tags(tags).build().unwrap())
+                .partial_fingerprints(partial_fingerprints)
+                .build()?)
         })
         .collect()
 }
@@ -359,7 +438,11 @@ pub fn generate_sarif_report(
         debug,
     };
 
-    let run = RunBuilder::default()
+    let mut run_builder = RunBuilder::default();
+    if let Some(cwe_taxonomy) = generate_cwe_taxonomy(rules)? {
+        run_builder.taxonomies(vec![cwe_taxonomy]);
+    }
+    let run = run_builder
         .tool(generate_tool_section(rules)?)
         .results(generate_results(rules, rules_results, options)?)
         .build()?;
@@ -377,7 +460,10 @@ mod tests {
     use kernel::model::{
         common::{Language, PositionBuilder},
         rule::{RuleBuilder, RuleCategory, RuleResultBuilder, RuleSeverity, RuleType},
-        violation::{EditBuilder, EditType, FixBuilder as RosieFixBuilder, ViolationBuilder},
+        violation::{
+            EditBuilder, EditType, FixBuilder as RosieFixBuilder, RelatedLocationBuilder,
+            ViolationBuilder,
+        },
     };
     use serde_json::{from_str, Value};
     use std::collections::HashMap;
@@ -459,7 +545,83 @@ mod tests {
         println!("{}", sarif_report_to_string);
         assert_json_eq!(
             sarif_report_to_string,
-            serde_json::json!({"runs":[{"results":[{"fixes":[{"artifactChanges":[{"artifactLocation":{"uri":"myfile"},"replacements":[{"deletedRegion":{"endColumn":6,"endLine":6,"startColumn":6,"startLine":6},"insertedContent":{"text":"newcontent"}}]}],"description":{"text":"myfix"}}],"level":"error","locations":[{"physicalLocation":{"artifactLocation":{"uri":"myfile"},"region":{"endColumn":4,"endLine":3,"startColumn":2,"startLine":1}}}],"message":{"text":"violation message"},"partialFingerprints":{},"properties":{"tags":["DATADOG_CATEGORY:BEST_PRACTICES","CWE:1234"]},"ruleId":"my-rule","ruleIndex":0}],"tool":{"driver":{"informationUri":"https://www.datadoghq.com","name":"datadog-static-analyzer","rules":[{"fullDescription":{"text":"awesome rule"},"helpUri":"https://docs.datadoghq.com/continuous_integration/static_analysis/rules/my-rule","id":"my-rule","properties":{"tags":["CWE:1234"]},"shortDescription":{"text":"short description"}}]}}}],"version":"2.1.0"})
+            serde_json::json!({"runs":[{"results":[{"fixes":[{"artifactChanges":[{"artifactLocation":{"uri":"myfile"},"replacements":[{"deletedRegion":{"endColumn":6,"endLine":6,"startColumn":6,"startLine":6},"insertedContent":{"text":"newcontent"}}]}],"description":{"text":"myfix"}}],"level":"error","locations":[{"physicalLocation":{"artifactLocation":{"uri":"myfile"},"region":{"endColumn":4,"endLine":3,"startColumn":2,"startLine":1}}}],"message":{"text":"violation message"},"partialFingerprints":{},"properties":{"tags":["DATADOG_CATEGORY:BEST_PRACTICES","CWE:1234"]},"ruleId":"my-rule","ruleIndex":0,"taxa":[{"id":"1234","toolComponent":{"name":"CWE"}}]}],"taxonomies":[{"informationUri":"https://cwe.mitre.org","name":"CWE","taxa":[{"id":"1234"}]}],"tool":{"driver":{"informationUri":"https://www.datadoghq.com","name":"datadog-static-analyzer","rules":[{"fullDescription":{"text":"awesome rule"},"helpUri":"https://docs.datadoghq.com/continuous_integration/static_analysis/rules/my-rule","id":"my-rule","properties":{"tags":["CWE:1234"]},"shortDescription":{"text":"short description"}}]}}}],"version":"2.1.0"})
+        );
+
+        // validate the schema
+        assert!(validate_data(&sarif_report_to_string));
+    }
+
+    // the related locations of the violations are in the results, in the file of the violation
+    // by default
+    #[test]
+    fn test_generate_sarif_report_related_locations() {
+        let rule = RuleBuilder::default()
+            .name("my-rule".to_string())
+            .description_base64(Some("YXdlc29tZSBydWxl".to_string()))
+            .language(Language::Python)
+            .checksum("blabla".to_string())
+            .pattern(None)
+            .tree_sitter_query_base64(Some("ts-query".to_string()))
+            .category(RuleCategory::BestPractices)
+            .code_base64("Zm9vYmFyYmF6".to_string())
+            .short_description_base64(Some("c2hvcnQgZGVzY3JpcHRpb24=".to_string()))
+            .entity_checked(None)
+            .rule_type(RuleType::TreeSitterQuery)
+            .severity(RuleSeverity::Error)
+            .cwe(None)
+            .variables(HashMap::new())
+            .tests(vec![])
+            .build()
+            .unwrap();
+
+        let rule_result = RuleResultBuilder::default()
+            .rule_name("my-rule".to_string())
+            .filename("myfile".to_string())
+            .violations(vec![ViolationBuilder::default()
+                .start(PositionBuilder::default().line(5).col(5).build().unwrap())
+                .end(PositionBuilder::default().line(5).col(8).build().unwrap())
+                .message("x shadows a variable".to_string())
+                .severity(RuleSeverity::Error)
+                .category(RuleCategory::BestPractices)
+                .fixes(vec![])
+                .related_locations(vec![
+                    RelatedLocationBuilder::default()
+                        .start(PositionBuilder::default().line(1).col(1).build().unwrap())
+                        .end(PositionBuilder::default().line(1).col(2).build().unwrap())
+                        .label("x is defined here".to_string())
+                        .build()
+                        .unwrap(),
+                    RelatedLocationBuilder::default()
+                        .filename(Some("otherfile".to_string()))
+                        .start(PositionBuilder::default().line(2).col(1).build().unwrap())
+                        .end(PositionBuilder::default().line(2).col(2).build().unwrap())
+                        .label("x is imported from here".to_string())
+                        .build()
+                        .unwrap(),
+                ])
+                .build()
+                .unwrap()])
+            .output(None)
+            .errors(vec![])
+            .execution_time_ms(42)
+            .execution_error(None)
+            .build()
+            .expect("building violation");
+
+        let sarif_report = generate_sarif_report(
+            &[rule],
+            &vec![rule_result],
+            &"mydir".to_string(),
+            false,
+            false,
+        )
+        .expect("generate sarif report");
+
+        let sarif_report_to_string = serde_json::to_value(sarif_report).unwrap();
+        assert_json_eq!(
+            sarif_report_to_string["runs"][0]["results"][0]["relatedLocations"],
+            serde_json::json!([{"message":{"text":"x is defined here"},"physicalLocation":{"artifactLocation":{"uri":"myfile"},"region":{"endColumn":2,"endLine":1,"startColumn":1,"startLine":1}}},{"message":{"text":"x is imported from here"},"physicalLocation":{"artifactLocation":{"uri":"otherfile"},"region":{"endColumn":2,"endLine":2,"startColumn":1,"startLine":2}}}])
         );
 
         // validate the schema
@@ -542,4 +704,153 @@ mod tests {
         // validate the schema
         assert!(validate_data(&serde_json::to_value(sarif_report).unwrap()));
     }
+
+    // the CWE identifiers of the rules are in the tags and the taxa of the results and in the
+    // CWE taxonomy of the run
+    #[test]
+    fn test_generate_sarif_report_cwe_taxonomy() {
+        let rule = |name: &str, cwe: Option<&str>| {
+            RuleBuilder::default()
+                .name(name.to_string())
+                .description_base64(None)
+                .language(Language::Python)
+                .checksum("blabla".to_string())
+                .pattern(None)
+                .tree_sitter_query_base64(Some("ts-query".to_string()))
+                .category(RuleCategory::Security)
+                .code_base64("Zm9vYmFyYmF6".to_string())
+                .short_description_base64(None)
+                .entity_checked(None)
+                .rule_type(RuleType::TreeSitterQuery)
+                .severity(RuleSeverity::Error)
+                .cwe(cwe.map(|c| c.to_string()))
+                .variables(HashMap::new())
+                .tests(vec![])
+                .build()
+                .unwrap()
+        };
+        let rules = [
+            rule("rule-injection", Some("CWE-89, 79")),
+            rule("rule-eval", Some("95,89")),
+            rule("rule-no-cwe", None),
+        ];
+        let rule_result = |name: &str| {
+            RuleResultBuilder::default()
+                .rule_name(name.to_string())
+                .filename("myfile".to_string())
+                .violations(vec![ViolationBuilder::default()
+                    .start(PositionBuilder::default().line(1).col(2).build().unwrap())
+                    .end(PositionBuilder::default().line(3).col(4).build().unwrap())
+                    .message("violation message".to_string())
+                    .severity(RuleSeverity::Error)
+                    .category(RuleCategory::Security)
+                    .fixes(vec![])
+                    .build()
+                    .unwrap()])
+                .output(None)
+                .errors(vec![])
+                .execution_time_ms(42)
+                .execution_error(None)
+                .build()
+                .unwrap()
+        };
+
+        let sarif_report = generate_sarif_report(
+            &rules,
+            &[rule_result("rule-injection"), rule_result("rule-no-cwe")],
+            &"mydir".to_string(),
+            false,
+            false,
+        )
+        .expect("generate sarif report");
+        let sarif_report_to_string = serde_json::to_value(sarif_report).unwrap();
+
+        let run = &sarif_report_to_string["runs"][0];
+        assert_json_eq!(
+            run["taxonomies"],
+            serde_json::json!([{"informationUri":"https://cwe.mitre.org","name":"CWE","taxa":[{"id":"79"},{"id":"89"},{"id":"95"}]}])
+        );
+        assert_json_eq!(
+            run["tool"]["driver"]["rules"][0]["properties"],
+            serde_json::json!({"tags":["CWE:89","CWE:79"]})
+        );
+        assert_json_eq!(
+            run["results"][0]["properties"],
+            serde_json::json!({"tags":["DATADOG_CATEGORY:SECURITY","CWE:89","CWE:79"]})
+        );
+        assert_json_eq!(
+            run["results"][0]["taxa"],
+            serde_json::json!([{"id":"89","toolComponent":{"name":"CWE"}},{"id":"79","toolComponent":{"name":"CWE"}}])
+        );
+        assert!(run["results"][1].get("taxa").is_none());
+
+        // validate the schema
+        assert!(validate_data(&sarif_report_to_string));
+    }
+
+    // the level and the category of a result are the ones of its violation, the rules can
+    // override them
+    #[test]
+    fn test_generate_sarif_report_violation_severity() {
+        let rule = RuleBuilder::default()
+            .name("rule-sql".to_string())
+            .description_base64(None)
+            .language(Language::Python)
+            .checksum("blabla".to_string())
+            .pattern(None)
+            .tree_sitter_query_base64(Some("ts-query".to_string()))
+            .category(RuleCategory::Security)
+            .code_base64("Zm9vYmFyYmF6".to_string())
+            .short_description_base64(None)
+            .entity_checked(None)
+            .rule_type(RuleType::TreeSitterQuery)
+            .severity(RuleSeverity::Warning)
+            .cwe(None)
+            .variables(HashMap::new())
+            .tests(vec![])
+            .build()
+            .unwrap();
+        let violation = |severity: RuleSeverity, category: RuleCategory| {
+            ViolationBuilder::default()
+                .start(PositionBuilder::default().line(1).col(2).build().unwrap())
+                .end(PositionBuilder::default().line(3).col(4).build().unwrap())
+                .message("violation message".to_string())
+                .severity(severity)
+                .category(category)
+                .fixes(vec![])
+                .build()
+                .unwrap()
+        };
+        let rule_result = RuleResultBuilder::default()
+            .rule_name("rule-sql".to_string())
+            .filename("myfile".to_string())
+            .violations(vec![
+                violation(RuleSeverity::Warning, RuleCategory::Security),
+                violation(RuleSeverity::Error, RuleCategory::ErrorProne),
+            ])
+            .output(None)
+            .errors(vec![])
+            .execution_time_ms(42)
+            .execution_error(None)
+            .build()
+            .unwrap();
+
+        let sarif_report =
+            generate_sarif_report(&[rule], &[rule_result], &"mydir".to_string(), false, false)
+                .expect("generate sarif report");
+        let sarif_report_to_string = serde_json::to_value(sarif_report).unwrap();
+
+        let results = &sarif_report_to_string["runs"][0]["results"];
+        assert_eq!("warning", results[0]["level"]);
+        assert_json_eq!(
+            results[0]["properties"],
+            serde_json::json!({"tags":["DATADOG_CATEGORY:SECURITY"]})
+        );
+        assert_eq!("error", results[1]["level"]);
+        assert_json_eq!(
+            results[1]["properties"],
+            serde_json::json!({"tags":["DATADOG_CATEGORY:ERROR_PRONE"]})
+        );
+        assert!(validate_data(&sarif_report_to_string));
+    }
 }
@@ -41,6 +41,22 @@ fn main() {
     }
 
     let tree_sitter_projects: Vec<TreeSitterProject> = vec![
+        TreeSitterProject {
+            name: "tree-sitter-bash".to_string(),
+            compilation_unit: "tree-sitter-bash".to_string(),
+            repository: "https://github.com/tree-sitter/tree-sitter-bash.git".to_string(),
+            build_dir: ["tree-sitter-bash", "src"].iter().collect(),
+            files: vec!["parser.c".to_string(), "scanner.c".to_string()],
+            cpp: false,
+        },
+        TreeSitterProject {
+            name: "tree-sitter-c".to_string(),
+            compilation_unit: "tree-sitter-c".to_string(),
+            repository: "https://github.com/tree-sitter/tree-sitter-c.git".to_string(),
+            build_dir: ["tree-sitter-c", "src"].iter().collect(),
+            files: vec!["parser.c".to_string()],
+            cpp: false,
+        },
         TreeSitterProject {
             name: "tree-sitter-c-sharp".to_string(),
             compilation_unit: "tree-sitter-c-sharp".to_string(),
@@ -49,6 +65,22 @@ fn main() {
             files: vec!["parser.c".to_string(), "scanner.c".to_string()],
             cpp: false,
         },
+        TreeSitterProject {
+            name: "tree-sitter-cpp".to_string(),
+            compilation_unit: "tree-sitter-cpp".to_string(),
+            repository: "https://github.com/tree-sitter/tree-sitter-cpp.git".to_string(),
+            build_dir: ["tree-sitter-cpp", "src"].iter().collect(),
+            files: vec!["parser.c".to_string(), "scanner.c".to_string()],
+            cpp: false,
+        },
+        TreeSitterProject {
+            name: "tree-sitter-dart".to_string(),
+            compilation_unit: "tree-sitter-dart".to_string(),
+            repository: "https://github.com/UserNobody14/tree-sitter-dart.git".to_string(),
+            build_dir: ["tree-sitter-dart", "src"].iter().collect(),
+            files: vec!["parser.c".to_string(), "scanner.c".to_string()],
+            cpp: false,
+        },
         TreeSitterProject {
             name: "tree-sitter-dockerfile".to_string(),
             compilation_unit: "tree-sitter-dockerfile".to_string(),
@@ -57,6 +89,14 @@ fn main() {
             files: vec!["parser.c".to_string()],
             cpp: false,
         },
+        TreeSitterProject {
+            name: "tree-sitter-elixir".to_string(),
+            compilation_unit: "tree-sitter-elixir".to_string(),
+            repository: "https://github.com/elixir-lang/tree-sitter-elixir.git".to_string(),
+            build_dir: ["tree-sitter-elixir", "src"].iter().collect(),
+            files: vec!["parser.c".to_string(), "scanner.c".to_string()],
+            cpp: false,
+        },
         TreeSitterProject {
             name: "tree-sitter-go".to_string(),
             compilation_unit: "tree-sitter-go".to_string(),
@@ -65,6 +105,14 @@ fn main() {
             files: vec!["parser.c".to_string()],
             cpp: false,
         },
+        TreeSitterProject {
+            name: "tree-sitter-groovy".to_string(),
+            compilation_unit: "tree-sitter-groovy".to_string(),
+            repository: "https://github.com/murtaza64/tree-sitter-groovy.git".to_string(),
+            build_dir: ["tree-sitter-groovy", "src"].iter().collect(),
+            files: vec!["parser.c".to_string()],
+            cpp: false,
+        },
         TreeSitterProject {
             name: "tree-sitter-hcl".to_string(),
             compilation_unit: "tree-sitter-hcl".to_string(),
@@ -105,6 +153,22 @@ fn main() {
             files: vec!["parser.c".to_string()],
             cpp: false,
         },
+        TreeSitterProject {
+            name: "tree-sitter-lua".to_string(),
+            compilation_unit: "tree-sitter-lua".to_string(),
+            repository: "https://github.com/MunifTanjim/tree-sitter-lua.git".to_string(),
+            build_dir: ["tree-sitter-lua", "src"].iter().collect(),
+            files: vec!["parser.c".to_string(), "scanner.c".to_string()],
+            cpp: false,
+        },
+        TreeSitterProject {
+            name: "tree-sitter-php".to_string(),
+            compilation_unit: "tree-sitter-php".to_string(),
+            repository: "https://github.com/tree-sitter/tree-sitter-php.git".to_string(),
+            build_dir: ["tree-sitter-php", "php", "src"].iter().collect(),
+            files: vec!["parser.c".to_string(), "scanner.c".to_string()],
+            cpp: false,
+        },
         TreeSitterProject {
             name: "tree-sitter-python".to_string(),
             compilation_unit: "tree-sitter-python".to_string(),
@@ -113,6 +177,14 @@ fn main() {
             files: vec!["parser.c".to_string(), "scanner.c".to_string()],
             cpp: false,
         },
+        TreeSitterProject {
+            name: "tree-sitter-ruby".to_string(),
+            compilation_unit: "tree-sitter-ruby".to_string(),
+            repository: "https://github.com/tree-sitter/tree-sitter-ruby.git".to_string(),
+            build_dir: ["tree-sitter-ruby", "src"].iter().collect(),
+            files: vec!["parser.c".to_string(), "scanner.c".to_string()],
+            cpp: false,
+        },
         TreeSitterProject {
             name: "tree-sitter-rust".to_string(),
             compilation_unit: "tree-sitter-rust".to_string(),
@@ -121,14 +193,48 @@ fn main() {
             files: vec!["parser.c".to_string(), "scanner.c".to_string()],
             cpp: false,
         },
+        TreeSitterProject {
+            name: "tree-sitter-scala".to_string(),
+            compilation_unit: "tree-sitter-scala".to_string(),
+            repository: "https://github.com/tree-sitter/tree-sitter-scala.git".to_string(),
+            build_dir: ["tree-sitter-scala", "src"].iter().collect(),
+            files: vec!["parser.c".to_string(), "scanner.c".to_string()],
+            cpp: false,
+        },
+        TreeSitterProject {
+            name: "tree-sitter-sql".to_string(),
+            compilation_unit: "tree-sitter-sql".to_string(),
+            repository: "https://github.com/m-novikov/tree-sitter-sql.git".to_string(),
+            build_dir: ["tree-sitter-sql", "src"].iter().collect(),
+            files: vec!["parser.c".to_string()],
+            cpp: false,
+        },
+        TreeSitterProject {
+            name: "tree-sitter-toml".to_string(),
+            compilation_unit: "tree-sitter-toml".to_string(),
+            repository: "https://github.com/ikatyang/tree-sitter-toml.git".to_string(),
+            build_dir: ["tree-sitter-toml", "src"].iter().collect(),
+            files: vec!["parser.c".to_string(), "scanner.c".to_string()],
+            cpp: false,
+        },
         TreeSitterProject {
             name: "tree-sitter-typescript".to_string(),
-            compilation_unit: "tree-sitter-typescript".to_string(),
+            compilation_unit: "tree-sitter-tsx".to_string(),
             repository: "https://github.com/tree-sitter/tree-sitter-typescript.git".to_string(),
             build_dir: ["tree-sitter-typescript", "tsx", "src"].iter().collect(),
             files: vec!["parser.c".to_string(), "scanner.c".to_string()],
             cpp: false,
         },
+        TreeSitterProject {
+            name: "tree-sitter-typescript".to_string(),
+            compilation_unit: "tree-sitter-typescript".to_string(),
+            repository: "https://github.com/tree-sitter/tree-sitter-typescript.git".to_string(),
+            build_dir: ["tree-sitter-typescript", "typescript", "src"]
+                .iter()
+                .collect(),
+            files: vec!["parser.c".to_string(), "scanner.c".to_string()],
+            cpp: false,
+        },
         TreeSitterProject {
             name: "tree-sitter-yaml".to_string(),
             compilation_unit: "tree-sitter-yaml-parser".to_string(),
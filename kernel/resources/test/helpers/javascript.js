@@ -0,0 +1,21 @@
+import fs from "fs";
+import { join } from "path";
+const child = require("child_process");
+
+function handler(event) {
+  return [1, 2].map((x) => eval(event));
+}
+
+class Service {
+  run(command) {
+    child.exec(command);
+  }
+}
+
+const Anonymous = class {
+  start() {
+    eval("start");
+  }
+};
+
+eval("1 + 1");
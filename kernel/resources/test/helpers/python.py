@@ -0,0 +1,20 @@
+import os
+import os.path as osp
+from collections import defaultdict
+from . import utils
+from ..models import User
+
+
+def handler(event):
+    def inner():
+        return eval(event)
+
+    return inner
+
+
+class Service:
+    def run(self, command):
+        os.system(command)
+
+
+eval("1 + 1")
@@ -0,0 +1,18 @@
+import * as fs from "fs";
+import { join } from "path";
+import type { Config } from "./config";
+
+export function handler(event: string): number {
+  return eval(event);
+}
+
+abstract class Base {
+  abstract run(command: string): void;
+}
+
+export class Service extends Base {
+  run(command: string): void {
+    const exec = require("child_process").exec;
+    exec(command);
+  }
+}
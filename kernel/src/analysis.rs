@@ -1,3 +1,13 @@
 pub mod analyze;
+pub mod decoding;
+pub mod embedded;
+pub mod fix;
+pub mod injection;
 pub mod javascript;
+pub mod js_helpers;
+pub mod line_index;
+pub mod notebook;
+pub mod rule_validation;
+pub mod test_file;
+pub mod tree_cache;
 pub mod tree_sitter;
@@ -1,15 +1,41 @@
-use crate::analysis::javascript::execute_rule;
-use crate::analysis::tree_sitter::{get_query, get_query_nodes, get_tree};
-use crate::model::analysis::{AnalysisOptions, ERROR_INVALID_QUERY};
+use crate::analysis::decoding::decode_lossy;
+use crate::analysis::embedded::{get_embedded_code, EmbeddedCode};
+use crate::analysis::fix::{remove_invalid_fixes, strip_bom};
+use crate::analysis::injection::get_injected_code;
+use crate::analysis::javascript::{execute_rule_with_tree, RuleTree};
+use crate::analysis::notebook::{get_notebook_source, is_notebook};
+use crate::analysis::test_file::is_test_file;
+use crate::analysis::tree_sitter::{
+    get_comments_with_encoding, get_error_rate, get_fallback_grammar, get_grammar_for_file,
+    get_query_capture_nodes_with_options, get_query_for_grammar, get_query_nodes_with_options,
+    get_tree_with_timeout, Grammar, QueryNodesBatch,
+};
+use crate::model::analysis::{
+    AnalysisOptions, FileMetadata, MapNodeOptions, ParseError, QueryError, QueryErrorKind,
+    QueryNodesOptions, DEFAULT_MAX_PARSING_ERROR_RATE, ERROR_FILE_DECODED_LOSSILY,
+    ERROR_FILE_TIMEOUT, ERROR_INVALID_FIX, ERROR_INVALID_QUERY, ERROR_NOT_ANALYZED_PARSE_ERRORS,
+    ERROR_PARSING_FAILED, ERROR_PARSING_TIMEOUT, ERROR_RULE_MATCHES_TRUNCATED,
+};
 use crate::model::common::Language;
 use crate::model::rule::{RuleInternal, RuleResult};
+use crate::model::violation::dedup_violations;
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 fn get_lines_to_ignore(code: &str, language: &Language) -> Vec<u32> {
     let mut lines_to_ignore = vec![];
     let mut line_number = 1u32;
     let disabling_patterns = match language {
-        Language::Python | Language::Dockerfile | Language::Terraform | Language::Yaml => {
+        Language::Python
+        | Language::Dockerfile
+        | Language::Elixir
+        | Language::Ruby
+        | Language::Shell
+        | Language::Terraform
+        | Language::Toml
+        | Language::Yaml => {
             vec!["#no-dd-sa", "#datadog-disable"]
         }
         Language::JavaScript | Language::TypeScript => {
@@ -25,9 +51,25 @@ fn get_lines_to_ignore(code: &str, language: &Language) -> Vec<u32> {
         | Language::Csharp
         | Language::Java
         | Language::Kotlin
-        | Language::Swift => {
+        | Language::Swift
+        | Language::C
+        | Language::Cpp
+        | Language::Scala
+        | Language::Dart
+        | Language::Groovy => {
             vec!["//no-dd-sa", "//datadog-disable"]
         }
+        Language::Php => {
+            vec![
+                "//no-dd-sa",
+                "#no-dd-sa",
+                "//datadog-disable",
+                "#datadog-disable",
+            ]
+        }
+        Language::Lua | Language::Sql => {
+            vec!["--no-dd-sa", "--datadog-disable"]
+        }
         Language::Json => {
             vec!["impossiblestringtoreach"]
         }
@@ -46,6 +88,43 @@ fn get_lines_to_ignore(code: &str, language: &Language) -> Vec<u32> {
     lines_to_ignore
 }
 
+// Get the query of a rule and the tree to run it on. A query with JSX nodes, that only exist in
+// the default grammar of the language, runs on the code parsed with the default grammar (e.g.
+// on a `.ts` file), the code is parsed once for all the rules of the file. The other errors of
+// the query are reported: the default grammar misparses the code (e.g. generics as JSX). The
+// grammar of the tree is returned with it.
+fn get_rule_query_and_tree(
+    query_code: &str,
+    grammar: Grammar,
+    tree: &tree_sitter::Tree,
+    fallback_grammar: Option<Grammar>,
+    fallback_tree: &mut Option<Option<tree_sitter::Tree>>,
+    code: &str,
+    deadline: Option<Instant>,
+) -> Result<(Arc<tree_sitter::Query>, tree_sitter::Tree, Grammar), QueryError> {
+    let error = match get_query_for_grammar(query_code, grammar) {
+        Ok(query) => return Ok((query, tree.clone(), grammar)),
+        Err(e) => e,
+    };
+    let is_jsx_node_error =
+        error.kind == QueryErrorKind::NodeType && error.message.starts_with("jsx_");
+    let Some(fallback_grammar) = fallback_grammar.filter(|_| is_jsx_node_error) else {
+        return Err(error);
+    };
+    let Ok(query) = get_query_for_grammar(query_code, fallback_grammar) else {
+        return Err(error);
+    };
+    let fallback_tree = fallback_tree.get_or_insert_with(|| {
+        let timeout = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        let fallback_language = fallback_grammar.get_tree_sitter_language().ok()?;
+        get_tree_with_timeout(code, fallback_language, timeout).ok()
+    });
+    fallback_tree
+        .clone()
+        .map(|t| (query, t, fallback_grammar))
+        .ok_or(error)
+}
+
 // main function
 // 1. Build the context (tree-sitter tree, etc)
 // 2. Run the tree-sitter query and build the object that hold the match
@@ -57,17 +136,345 @@ pub fn analyze(
     filename: &str,
     code: &str,
     analysis_option: &AnalysisOptions,
+) -> Vec<RuleResult> {
+    let file_metadata = FileMetadata::new(filename, code.len());
+    analyze_file(
+        language,
+        rules,
+        filename,
+        code,
+        &file_metadata,
+        analysis_option,
+    )
+}
+
+fn analyze_file(
+    language: &Language,
+    rules: Vec<RuleInternal>,
+    filename: &str,
+    code: &str,
+    file_metadata: &FileMetadata,
+    analysis_option: &AnalysisOptions,
+) -> Vec<RuleResult> {
+    // the positions do not count the byte order mark, like in the editors
+    let code = strip_bom(code);
+    let file_metadata = &FileMetadata {
+        is_test_file: analysis_option
+            .is_test_file
+            .unwrap_or_else(|| is_test_file(language, file_metadata, code)),
+        ..file_metadata.clone()
+    };
+
+    // Jupyter notebooks are analyzed using the Python code of their cells
+    if *language == Language::Python && is_notebook(filename) {
+        return match get_notebook_source(code) {
+            Ok(notebook) => analyze_source(
+                language,
+                rules,
+                filename,
+                &notebook.code,
+                file_metadata,
+                analysis_option,
+            )
+            .into_iter()
+            .map(|rule_result| notebook.map_rule_result(rule_result))
+            .collect(),
+            Err(e) => {
+                if analysis_option.use_debug {
+                    eprintln!("error when reading notebook {filename}: {e}");
+                }
+                get_error_results(
+                    rules,
+                    filename,
+                    ERROR_PARSING_FAILED,
+                    &format!("invalid notebook: {e}"),
+                )
+            }
+        };
+    }
+
+    // HTML pages, Markdown documents and Vue or Svelte components are analyzed using the code they contain
+    if let Some(embedded_code) =
+        get_embedded_code(filename, code, analysis_option.position_encoding)
+    {
+        return analyze_embedded_code(
+            language,
+            rules,
+            filename,
+            &embedded_code,
+            file_metadata,
+            analysis_option,
+        );
+    }
+
+    let host_language =
+        Language::from_path(Path::new(filename), &analysis_option.language_overrides);
+    let injected_code = host_language.and_then(|host_language| {
+        get_injected_code(
+            &host_language,
+            language,
+            code,
+            analysis_option.position_encoding,
+        )
+    });
+    match injected_code {
+        // the code of the language in the strings of another language (e.g. SQL queries in
+        // Python)
+        Some(injected_code) if host_language != Some(*language) => analyze_embedded_code(
+            language,
+            rules,
+            filename,
+            &injected_code,
+            file_metadata,
+            analysis_option,
+        ),
+        // the code of the file and the code in its strings (e.g. the scripts of the HTML in the
+        // template literals of a JavaScript file)
+        Some(injected_code) if !injected_code.is_empty() => {
+            let mut results = analyze_source(
+                language,
+                rules.clone(),
+                filename,
+                code,
+                file_metadata,
+                analysis_option,
+            );
+            let injected_results = analyze_embedded_code(
+                language,
+                rules,
+                filename,
+                &injected_code,
+                file_metadata,
+                analysis_option,
+            );
+            merge_rule_results(&mut results, injected_results);
+            results
+        }
+        _ => analyze_source(
+            language,
+            rules,
+            filename,
+            code,
+            file_metadata,
+            analysis_option,
+        ),
+    }
+}
+
+// Analyze the content of a file that may not be valid UTF-8. The invalid bytes are replaced,
+// the positions are in the original content and the results report that the file was decoded
+// lossily.
+pub fn analyze_bytes(
+    language: &Language,
+    rules: Vec<RuleInternal>,
+    filename: &str,
+    content: &[u8],
+    analysis_option: &AnalysisOptions,
+) -> Vec<RuleResult> {
+    // the size of the file is the size of its content, not of the decoded code
+    let file_metadata = FileMetadata::new(filename, content.len());
+    // the byte order mark is not in the positions, it is removed before mapping them
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    let decoded = decode_lossy(content);
+    if !decoded.is_lossy() {
+        return analyze_file(
+            language,
+            rules,
+            filename,
+            &decoded.code,
+            &file_metadata,
+            analysis_option,
+        );
+    }
+
+    if analysis_option.use_debug {
+        eprintln!("file {filename} is not valid UTF-8, invalid bytes are replaced");
+    }
+    analyze_file(
+        language,
+        rules,
+        filename,
+        &decoded.code,
+        &file_metadata,
+        analysis_option,
+    )
+    .into_iter()
+    .map(|rule_result| {
+        let mut rule_result =
+            decoded.map_rule_result(rule_result, analysis_option.position_encoding);
+        rule_result
+            .errors
+            .push(ERROR_FILE_DECODED_LOSSILY.to_string());
+        rule_result
+    })
+    .collect()
+}
+
+// Analyze all the code blocks of the language and merge the results of each rule. Each block
+// is analyzed on its own: the state of the rules and their finalize function are per block,
+// not per file.
+fn analyze_embedded_code(
+    language: &Language,
+    rules: Vec<RuleInternal>,
+    filename: &str,
+    embedded_code: &[EmbeddedCode],
+    file_metadata: &FileMetadata,
+    analysis_option: &AnalysisOptions,
+) -> Vec<RuleResult> {
+    let mut results: Vec<RuleResult> = vec![];
+
+    for block in embedded_code.iter().filter(|b| b.language == *language) {
+        let block_results: Vec<RuleResult> = analyze_source(
+            language,
+            rules.clone(),
+            filename,
+            &block.code,
+            file_metadata,
+            analysis_option,
+        )
+        .into_iter()
+        .map(|rule_result| block.map_rule_result(rule_result))
+        .collect();
+
+        merge_rule_results(&mut results, block_results);
+    }
+    results
+}
+
+// Merge the results of the rules on another part of the file in the results of each rule.
+fn merge_rule_results(results: &mut Vec<RuleResult>, other_results: Vec<RuleResult>) {
+    if results.is_empty() {
+        *results = other_results;
+        return;
+    }
+
+    for (result, other_result) in results.iter_mut().zip(other_results) {
+        result.violations.extend(other_result.violations);
+        for error in other_result.errors {
+            if !result.errors.contains(&error) {
+                result.errors.push(error);
+            }
+        }
+        result.execution_error = result
+            .execution_error
+            .take()
+            .or(other_result.execution_error);
+        result.exception = result.exception.take().or(other_result.exception);
+        result.output = match (result.output.take(), other_result.output) {
+            (Some(o1), Some(o2)) => Some(format!("{o1}{o2}")),
+            (o1, o2) => o1.or(o2),
+        };
+        result.execution_time_ms += other_result.execution_time_ms;
+        result.query_time_ms += other_result.query_time_ms;
+    }
+}
+
+// Report the error on each rule when the file cannot be analyzed so that it is in the results.
+fn get_error_results(
+    rules: Vec<RuleInternal>,
+    filename: &str,
+    error: &str,
+    execution_error: &str,
+) -> Vec<RuleResult> {
+    rules
+        .into_iter()
+        .map(|rule| RuleResult {
+            rule_name: rule.name,
+            filename: filename.to_string(),
+            violations: vec![],
+            errors: vec![error.to_string()],
+            execution_error: Some(execution_error.to_string()),
+            execution_time_ms: 0,
+            query_time_ms: 0,
+            exception: None,
+            output: None,
+        })
+        .collect()
+}
+
+// The results of the rules on a file that could not be parsed, the file is skipped and not
+// reported as clean.
+fn get_parse_error_results(
+    rules: Vec<RuleInternal>,
+    filename: &str,
+    error: &ParseError,
+    analysis_option: &AnalysisOptions,
+) -> Vec<RuleResult> {
+    if analysis_option.use_debug {
+        eprintln!("error when parsing source file {filename}: {error}");
+    }
+    match error {
+        ParseError::Timeout => get_error_results(
+            rules,
+            filename,
+            ERROR_PARSING_TIMEOUT,
+            "skipped: parse timeout",
+        ),
+        _ => get_error_results(rules, filename, ERROR_PARSING_FAILED, &error.to_string()),
+    }
+}
+
+fn analyze_source(
+    language: &Language,
+    rules: Vec<RuleInternal>,
+    filename: &str,
+    code: &str,
+    file_metadata: &FileMetadata,
+    analysis_option: &AnalysisOptions,
 ) -> Vec<RuleResult> {
     let lines_to_ignore = get_lines_to_ignore(code, language);
+    let grammar = get_grammar_for_file(language, filename, code);
+    // the parsing and the queries of the file share the same time budget
+    let timeout = analysis_option.file_timeout_ms.map(Duration::from_millis);
+    let deadline = timeout.map(|t| Instant::now() + t);
 
-    get_tree(code, language).map_or_else(
-        || {
-            if analysis_option.use_debug {
-                eprintln!("error when parsing source file {filename}");
+    // the grammar of the language may not be supported by the library
+    let tree = grammar
+        .get_tree_sitter_language()
+        .map_err(ParseError::from)
+        .and_then(|tree_sitter_language| {
+            get_tree_with_timeout(code, tree_sitter_language, timeout)
+        });
+
+    match tree {
+        Err(e) => get_parse_error_results(rules, filename, &e, analysis_option),
+        Ok(tree) => {
+            let error_rate = get_error_rate(&tree, code);
+            let max_error_rate = analysis_option
+                .max_parsing_error_rate
+                .unwrap_or(DEFAULT_MAX_PARSING_ERROR_RATE);
+            if error_rate > max_error_rate {
+                if analysis_option.use_debug {
+                    eprintln!(
+                        "file {} has {:.0}% of its code with syntax errors",
+                        filename,
+                        error_rate * 100.0
+                    );
+                }
+                return get_error_results(
+                    rules,
+                    filename,
+                    ERROR_NOT_ANALYZED_PARSE_ERRORS,
+                    "not analyzed: parse errors",
+                );
             }
-            vec![]
-        },
-        |tree| {
+
+            // the tree of the file with the default grammar, for the queries that need it
+            let fallback_grammar = get_fallback_grammar(language, filename);
+            let mut fallback_tree = None;
+
+            // the queries of the rules on the tree of the file share the cursor and the columns
+            let variables = HashMap::new();
+            let mut batch = QueryNodesBatch::new(&tree, filename, code, &variables);
+
+            // computed once for all the rules and shared by their matches
+            let comments = Arc::new(get_comments_with_encoding(
+                &tree,
+                code,
+                language,
+                analysis_option.position_encoding,
+            ));
+
             rules
                 .into_iter()
                 .map(|rule| {
@@ -78,6 +485,8 @@ pub fn analyze(
                         errors: vec![ERROR_INVALID_QUERY.to_string()],
                         execution_error: None,
                         execution_time_ms: 0,
+                        query_time_ms: 0,
+                        exception: None,
                         output: None,
                     };
 
@@ -86,14 +495,77 @@ pub fn analyze(
                     }
 
                     if let Some(tree_sitter_query) = &rule.tree_sitter_query {
-                        let query_try = get_query(tree_sitter_query.as_str(), &rule.language);
+                        let query_try = get_rule_query_and_tree(
+                            tree_sitter_query.as_str(),
+                            grammar,
+                            &tree,
+                            fallback_grammar,
+                            &mut fallback_tree,
+                            code,
+                            deadline,
+                        );
 
                         match query_try {
-                            Ok(query) => {
-                                let nodes =
-                                    get_query_nodes(&tree, &query, filename, code, &HashMap::new());
+                            Ok((query, tree, query_grammar)) => {
+                                let query_nodes_options = QueryNodesOptions {
+                                    map_node_options: MapNodeOptions {
+                                        include_unnamed: rule.include_unnamed_nodes,
+                                        max_nodes: rule.max_capture_nodes,
+                                        position_encoding: analysis_option.position_encoding,
+                                        ..MapNodeOptions::default()
+                                    },
+                                    match_limit: rule.query_match_limit,
+                                    deadline,
+                                    include_siblings: rule.include_siblings,
+                                    include_ancestors: rule.include_ancestors,
+                                    language: Some(*language),
+                                    ..QueryNodesOptions::default()
+                                };
+                                // the query is timed apart from the execution of the rule
+                                let query_start = Instant::now();
+                                // the rules in capture mode visit each captured node alone, the
+                                // queries on the tree of the default grammar run on their own
+                                let query_nodes =
+                                    match (query_grammar == grammar, rule.capture_mode) {
+                                        (true, true) => batch
+                                            .get_query_capture_nodes(&query, &query_nodes_options),
+                                        (true, false) => {
+                                            batch.get_query_nodes(&query, &query_nodes_options)
+                                        }
+                                        (false, true) => get_query_capture_nodes_with_options(
+                                            &tree,
+                                            &query,
+                                            filename,
+                                            code,
+                                            &variables,
+                                            &query_nodes_options,
+                                        ),
+                                        (false, false) => get_query_nodes_with_options(
+                                            &tree,
+                                            &query,
+                                            filename,
+                                            code,
+                                            &variables,
+                                            &query_nodes_options,
+                                        ),
+                                    };
+                                let query_time_ms = query_start.elapsed().as_millis();
+                                if query_nodes.is_truncated && analysis_option.use_debug {
+                                    eprintln!("rule {} truncated on file {}", rule.name, filename);
+                                }
+                                if query_nodes.is_timed_out && analysis_option.use_debug {
+                                    eprintln!("rule {} timed out on file {}", rule.name, filename);
+                                }
+                                let mut nodes = query_nodes.match_nodes;
+                                for node in &mut nodes {
+                                    node.context.comments = Arc::clone(&comments);
+                                    node.context.arguments = rule.arguments.clone();
+                                    node.context.file = file_metadata.clone();
+                                    node.context.rule_name = Some(rule.name.clone());
+                                    node.context.rule_severity = Some(rule.severity);
+                                }
 
-                                if nodes.is_empty() {
+                                let mut rule_result = if nodes.is_empty() {
                                     RuleResult {
                                         rule_name: rule.name.clone(),
                                         filename: filename.to_string(),
@@ -101,42 +573,93 @@ pub fn analyze(
                                         errors: vec![],
                                         execution_error: None,
                                         execution_time_ms: 0,
+                                        query_time_ms: 0,
+                                        exception: None,
                                         output: None,
                                     }
                                 } else {
-                                    let mut rule_result = execute_rule(
+                                    // the rule can run queries in the nodes it captured
+                                    let rule_tree = RuleTree {
+                                        tree: tree.clone(),
+                                        grammar: query_grammar,
+                                        filename: filename.to_string(),
+                                        code: code.to_string(),
+                                        options: query_nodes_options.clone(),
+                                    };
+                                    let mut rule_result = execute_rule_with_tree(
                                         rule,
                                         nodes,
                                         filename.to_string(),
                                         analysis_option.clone(),
+                                        Some(rule_tree),
+                                    );
+
+                                    // filter violations that have been ignored, the violations
+                                    // reported for several matches of the same code are kept once
+                                    rule_result.violations = dedup_violations(
+                                        rule_result
+                                            .violations
+                                            .into_iter()
+                                            .filter(|v| !lines_to_ignore.contains(&v.start.line))
+                                            .collect(),
                                     );
 
-                                    // filter violations that have been ignored
-                                    rule_result.violations = rule_result
-                                        .violations
-                                        .iter()
-                                        .cloned()
-                                        .filter(|v| !lines_to_ignore.contains(&v.start.line))
-                                        .collect();
+                                    // the fixes that cannot be applied are not reported
+                                    let invalid_fixes = remove_invalid_fixes(
+                                        code,
+                                        &mut rule_result.violations,
+                                        analysis_option.position_encoding,
+                                    );
+                                    if !invalid_fixes.is_empty() {
+                                        if analysis_option.use_debug {
+                                            for error in &invalid_fixes {
+                                                eprintln!(
+                                                    "rule {} on file {}: {}",
+                                                    rule_result.rule_name, filename, error
+                                                );
+                                            }
+                                        }
+                                        rule_result.errors.push(ERROR_INVALID_FIX.to_string());
+                                    }
+                                    rule_result
+                                };
+                                rule_result.query_time_ms = query_time_ms;
+                                if query_nodes.is_truncated {
                                     rule_result
+                                        .errors
+                                        .push(ERROR_RULE_MATCHES_TRUNCATED.to_string());
+                                }
+                                if query_nodes.is_timed_out {
+                                    rule_result.errors.push(ERROR_FILE_TIMEOUT.to_string());
+                                }
+                                rule_result
+                            }
+                            Err(e) => {
+                                if analysis_option.use_debug {
+                                    eprintln!("Invalid query for rule {}: {}", rule.name, e);
+                                }
+                                RuleResult {
+                                    execution_error: Some(e.to_string()),
+                                    ..invalid_query_result
                                 }
                             }
-                            Err(_) => invalid_query_result,
                         }
                     } else {
                         invalid_query_result
                     }
                 })
                 .collect()
-        },
-    )
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::common::Language;
-    use crate::model::rule::{RuleCategory, RuleSeverity};
+    use crate::analysis::tree_sitter::get_tree_for_file;
+    use crate::model::analysis::{PositionEncoding, ERROR_RULE_TIMEOUT};
+    use crate::model::common::{Language, Position, Range};
+    use crate::model::rule::{RuleArgumentValue, RuleCategory, RuleSeverity};
     use std::collections::HashMap;
 
     const QUERY_CODE: &str = r#"
@@ -182,11 +705,13 @@ function visit(node, filename, code) {
             code: rule_code.to_string(),
             tree_sitter_query: Some(QUERY_CODE.to_string()),
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
 
         let analysis_options = AnalysisOptions {
             log_output: true,
             use_debug: false,
+            ..AnalysisOptions::default()
         };
         let results = analyze(
             &Language::Python,
@@ -200,6 +725,158 @@ function visit(node, filename, code) {
         assert_eq!(result.violations.len(), 1);
     }
 
+    // the violations reported for several matches of the same code are reported once, the rule
+    // still visits all the matches
+    #[test]
+    fn test_analyze_dedup_violations() {
+        let query_code = r#"
+(call function: (identifier) @name)
+(call function: (identifier) @name arguments: (argument_list (identifier) @arg))
+"#;
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    console.log(name.text);
+    addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                        "call of " + name.text, "WARNING", "BEST_PRACTICES"));
+    if (node.captures["arg"]) {
+        addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                            "call with argument", "WARNING", "BEST_PRACTICES"));
+    }
+}
+"#;
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(query_code.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let range = |start: (u32, u32), end: (u32, u32)| {
+            Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+        };
+        let code = "eval(x)\nprint()\n";
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            code,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        // eval(x) is matched by both patterns
+        assert_eq!(Some("eval\neval\nprint".to_string()), results[0].output);
+        let violations: Vec<(Range, &str)> = results[0]
+            .violations
+            .iter()
+            .map(|v| (v.get_range(), v.message.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (range((1, 1), (1, 5)), "call of eval"),
+                (range((1, 1), (1, 5)), "call with argument"),
+                (range((2, 1), (2, 6)), "call of print"),
+            ],
+            violations
+        );
+    }
+
+    // all the matches of the file have the comments of the file
+    #[test]
+    fn test_analyze_comments() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    console.log(node.context.comments.map(c => `${c.start.line}:${c.text}`).join(","));
+}
+"#;
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some("(call function: (identifier) @name)".to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let code = "# first\neval(x)\nprint()  # second\n";
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            code,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        assert_eq!(
+            Some("1:# first,3:# second\n1:# first,3:# second".to_string()),
+            results[0].output
+        );
+    }
+
+    // the time of the query and the time of the execution of the rule are measured apart
+    #[test]
+    fn test_rule_timing() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const start = Date.now();
+    while (Date.now() - start < 20) {}
+}
+        "#;
+
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(QUERY_CODE.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let analysis_options = AnalysisOptions {
+            log_output: false,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            PYTHON_CODE,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let result = &results[0];
+        assert!(result.execution_time_ms >= 20);
+        assert!(result.query_time_ms < result.execution_time_ms);
+
+        // both times are in the results
+        let value = serde_json::to_value(result).unwrap();
+        assert!(value["execution_time_ms"].as_u64().is_some());
+        assert!(value["query_time_ms"].as_u64().is_some());
+    }
+
     // execute two rules and check that both rules are executed and their respective
     // results reported.
     #[test]
@@ -247,6 +924,7 @@ function visit(node, filename, code) {
             code: rule_code1.to_string(),
             tree_sitter_query: Some(QUERY_CODE.to_string()),
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
         let rule2 = RuleInternal {
             name: "myrule".to_string(),
@@ -258,11 +936,13 @@ function visit(node, filename, code) {
             code: rule_code2.to_string(),
             tree_sitter_query: Some(QUERY_CODE.to_string()),
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
 
         let analysis_options = AnalysisOptions {
             log_output: true,
             use_debug: false,
+            ..AnalysisOptions::default()
         };
         let results = analyze(
             &Language::Python,
@@ -358,11 +1038,13 @@ for(var i = 0; i <= 10; i--){}
             code: rule_code1.to_string(),
             tree_sitter_query: Some(tree_sitter_query.to_string()),
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
 
         let analysis_options = AnalysisOptions {
             log_output: true,
             use_debug: false,
+            ..AnalysisOptions::default()
         };
         let results = analyze(
             &Language::JavaScript,
@@ -412,11 +1094,13 @@ def foo():
             code: rule_code1.to_string(),
             tree_sitter_query: Some(tree_sitter_query.to_string()),
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
 
         let analysis_options = AnalysisOptions {
             log_output: true,
             use_debug: false,
+            ..AnalysisOptions::default()
         };
         let results = analyze(
             &Language::Python,
@@ -466,11 +1150,13 @@ def foo(arg1):
             code: rule_code.to_string(),
             tree_sitter_query: Some(QUERY_CODE.to_string()),
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
 
         let analysis_options = AnalysisOptions {
             log_output: true,
             use_debug: false,
+            ..AnalysisOptions::default()
         };
         let results = analyze(
             &Language::Python,
@@ -497,11 +1183,13 @@ def foo(arg1):
             code: "code".to_string(),
             tree_sitter_query: None, // None means there is no query or we fail to parse it
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
 
         let analysis_options = AnalysisOptions {
             log_output: true,
             use_debug: false,
+            ..AnalysisOptions::default()
         };
         let results = analyze(
             &Language::Python,
@@ -518,4 +1206,1373 @@ def foo(arg1):
             result.errors.get(0).unwrap()
         )
     }
+
+    // the rules with JSX nodes in their query still run on the `.ts` files, parsed with the TSX
+    // grammar
+    #[test]
+    fn test_analyze_typescript_jsx_query() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const n = node.captures["node"];
+    addError(buildError(n.start.line, n.start.col, n.end.line, n.end.col, "found"));
+}
+        "#;
+        let rule = |name: &str, query: &str| RuleInternal {
+            name: name.to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::BestPractices,
+            severity: RuleSeverity::Warning,
+            language: Language::TypeScript,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(query.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let code = "const double = (x: number) => x * 2;\n";
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::TypeScript,
+            vec![
+                rule("jsx", "[(jsx_element) (arrow_function)] @node"),
+                rule("arrow", "(arrow_function) @node"),
+            ],
+            "app.ts",
+            code,
+            &analysis_options,
+        );
+        assert_eq!(2, results.len());
+        assert!(results.iter().all(|r| r.errors.is_empty()));
+        let positions = |result: &RuleResult| -> Vec<Position> {
+            result.violations.iter().map(|v| v.start.clone()).collect()
+        };
+        assert_eq!(vec![Position::new(1, 16)], positions(&results[0]));
+        assert_eq!(vec![Position::new(1, 16)], positions(&results[1]));
+    }
+
+    // only the queries with JSX nodes run on the `.ts` files parsed with the TSX grammar, the
+    // generic arrow functions and the `as const` assertions are never parsed as JSX
+    #[test]
+    fn test_get_rule_query_and_tree_typescript() {
+        let code = "const identity = <T>(x: T) => x;\nconst d = [\"up\", \"down\"] as const;\n";
+        let grammar = get_grammar_for_file(&Language::TypeScript, "app.ts", code);
+        let fallback_grammar = get_fallback_grammar(&Language::TypeScript, "app.ts");
+        let tree = get_tree_for_file(code, &Language::TypeScript, "app.ts").unwrap();
+        let mut fallback_tree = None;
+        let mut get_query_and_tree = |query_code: &str| {
+            get_rule_query_and_tree(
+                query_code,
+                grammar,
+                &tree,
+                fallback_grammar,
+                &mut fallback_tree,
+                code,
+                None,
+            )
+        };
+
+        for query_code in ["(arrow_function) @node", "(as_expression) @node"] {
+            let (_, query_tree, query_grammar) = get_query_and_tree(query_code).unwrap();
+            assert!(!query_grammar.is_tsx);
+            assert!(!query_tree.root_node().has_error());
+        }
+        // the other errors of the queries are reported, the code is not parsed with TSX
+        let error = get_query_and_tree("(arrow_functio) @node").unwrap_err();
+        assert_eq!(QueryErrorKind::NodeType, error.kind);
+        assert!(get_query_and_tree("(arrow_function @node").is_err());
+        assert!(fallback_tree.is_none());
+
+        let (_, _, query_grammar) = get_rule_query_and_tree(
+            "(jsx_element) @node",
+            grammar,
+            &tree,
+            fallback_grammar,
+            &mut fallback_tree,
+            code,
+            None,
+        )
+        .unwrap();
+        assert!(query_grammar.is_tsx);
+        assert!(fallback_tree.is_some());
+    }
+
+    // an invalid query is reported on the rule with its location
+    #[test]
+    fn test_execution_invalid_query_location() {
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: "code".to_string(),
+            tree_sitter_query: Some("(call\n  (not_a_node) @name)".to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            "print(1)",
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let result = results.get(0).unwrap();
+        assert_eq!(vec![ERROR_INVALID_QUERY.to_string()], result.errors);
+        assert_eq!(
+            "invalid node type at line 2, col 4: not_a_node ((not_a_node) @name))",
+            result.execution_error.as_ref().unwrap()
+        );
+    }
+
+    // files where most of the code is made of syntax errors are not analyzed by default
+    #[test]
+    fn test_parsing_error_rate_too_high() {
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: "function visit(node, filename, code) {}".to_string(),
+            tree_sitter_query: Some(QUERY_CODE.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule.clone()],
+            "myfile.py",
+            "}}}} ))) def def def ((( {{{",
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        assert_eq!(
+            vec![ERROR_NOT_ANALYZED_PARSE_ERRORS.to_string()],
+            results[0].errors
+        );
+
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            PYTHON_CODE,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        assert!(results.get(0).unwrap().errors.is_empty());
+    }
+
+    // files where the parser cannot be set up are reported as failed, not as clean
+    #[test]
+    fn test_parse_error_results() {
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: "function visit(node, filename, code) {}".to_string(),
+            tree_sitter_query: Some(QUERY_CODE.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+
+        let results = get_parse_error_results(
+            vec![rule.clone()],
+            "myfile.py",
+            &ParseError::ParserSetup("incompatible language version 9".to_string()),
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let result = results.get(0).unwrap();
+        assert_eq!("myrule", result.rule_name);
+        assert_eq!("myfile.py", result.filename);
+        assert!(result.violations.is_empty());
+        assert_eq!(vec![ERROR_PARSING_FAILED.to_string()], result.errors);
+        assert_eq!(
+            Some("parser setup failed: incompatible language version 9".to_string()),
+            result.execution_error
+        );
+
+        let results = get_parse_error_results(
+            vec![rule],
+            "myfile.py",
+            &ParseError::Timeout,
+            &analysis_options,
+        );
+        assert_eq!(
+            vec![ERROR_PARSING_TIMEOUT.to_string()],
+            results.get(0).unwrap().errors
+        );
+    }
+
+    // files with too many syntax errors are not analyzed when the option is set
+    #[test]
+    fn test_skip_file_with_parse_errors() {
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: "function visit(node, filename, code) {}".to_string(),
+            tree_sitter_query: Some("(identifier) @name".to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let code = "def foo(arg1:\n    print(arg1\n";
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            max_parsing_error_rate: Some(0.0),
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule.clone()],
+            "myfile.py",
+            code,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let result = results.get(0).unwrap();
+        assert_eq!(
+            vec![ERROR_NOT_ANALYZED_PARSE_ERRORS.to_string()],
+            result.errors
+        );
+        assert_eq!(
+            "not analyzed: parse errors",
+            result.execution_error.as_ref().unwrap()
+        );
+
+        // files without syntax errors are analyzed
+        let results = analyze(
+            &Language::Python,
+            vec![rule.clone()],
+            "myfile.py",
+            PYTHON_CODE,
+            &analysis_options,
+        );
+        assert!(results.get(0).unwrap().errors.is_empty());
+
+        // with a rate of 1, all the files are analyzed
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            max_parsing_error_rate: Some(1.0),
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            code,
+            &analysis_options,
+        );
+        assert!(!results
+            .get(0)
+            .unwrap()
+            .errors
+            .contains(&ERROR_NOT_ANALYZED_PARSE_ERRORS.to_string()));
+    }
+
+    // the rules can read the language of the file
+    #[test]
+    fn test_context_language() {
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: "function visit(node, filename, code) { console.log(node.context.language); }"
+                .to_string(),
+            tree_sitter_query: Some(QUERY_CODE.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            PYTHON_CODE,
+            &analysis_options,
+        );
+        assert_eq!(Some("PYTHON".to_string()), results[0].output);
+    }
+
+    // the arguments are sent to the rule with their types, separately from the variables
+    #[test]
+    fn test_context_arguments() {
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: r#"
+function visit(node, filename, code) {
+    const args = node.context.arguments;
+    console.log(`${args.max + 1} ${args.strict === false} ${args.modules.join("|")}`);
+}
+"#
+            .to_string(),
+            tree_sitter_query: Some(QUERY_CODE.to_string()),
+            variables: HashMap::new(),
+            arguments: HashMap::from([
+                ("max".to_string(), RuleArgumentValue::Integer(50)),
+                ("strict".to_string(), RuleArgumentValue::Boolean(false)),
+                (
+                    "modules".to_string(),
+                    RuleArgumentValue::StringList(vec!["pickle".to_string(), "os".to_string()]),
+                ),
+            ]),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            PYTHON_CODE,
+            &analysis_options,
+        );
+        assert_eq!(Some("51 true pickle|os".to_string()), results[0].output);
+    }
+
+    // the rules get the size and the path of the file in the context
+    #[test]
+    fn test_context_file_metadata() {
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: r#"
+function visit(node, filename, code) {
+    const c = node.context;
+    console.log(`${c.fileSizeBytes} ${c.basename} [${c.directory}] ${c.relativePath}`);
+}
+"#
+            .to_string(),
+            tree_sitter_query: Some(QUERY_CODE.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let size = PYTHON_CODE.len();
+        for (filename, expected) in [
+            (
+                "tests\\unit\\myfile.py",
+                format!("{size} myfile.py [tests/unit] tests/unit/myfile.py"),
+            ),
+            ("myfile.py", format!("{size} myfile.py [] myfile.py")),
+        ] {
+            let results = analyze(
+                &Language::Python,
+                vec![rule.clone()],
+                filename,
+                PYTHON_CODE,
+                &analysis_options,
+            );
+            assert_eq!(Some(expected), results[0].output);
+        }
+    }
+
+    // the rules know if the file contains tests, the options can force the kind of file
+    #[test]
+    fn test_context_is_test_file() {
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
+            language: Language::Python,
+            code: r#"
+function visit(node, filename, code) {
+    console.log(`${node.context.isTestFile}`);
+}
+"#
+            .to_string(),
+            tree_sitter_query: Some(QUERY_CODE.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        for (filename, is_test_file, expected) in [
+            ("tests/fixtures/passwords.py", None, "true"),
+            ("src/test_api.py", None, "true"),
+            ("src/api.py", None, "false"),
+            // forced by the configuration
+            ("tests/fixtures/passwords.py", Some(false), "false"),
+            ("src/api.py", Some(true), "true"),
+        ] {
+            let results = analyze(
+                &Language::Python,
+                vec![rule.clone()],
+                filename,
+                PYTHON_CODE,
+                &AnalysisOptions {
+                    is_test_file,
+                    ..analysis_options.clone()
+                },
+            );
+            assert_eq!(Some(expected.to_string()), results[0].output, "{filename}");
+        }
+    }
+
+    // each rule sees its own name and severity in the context, the rules cannot change them
+    #[test]
+    fn test_context_rule() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    node.context.ruleName = "other-rule";
+    console.log(`${node.context.ruleName} ${node.context.ruleSeverity}`);
+}
+"#;
+        let rule = |name: &str, severity: RuleSeverity| RuleInternal {
+            name: name.to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(QUERY_CODE.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![
+                rule("rule1", RuleSeverity::Error),
+                rule("rule2", RuleSeverity::Notice),
+            ],
+            "myfile.py",
+            PYTHON_CODE,
+            &analysis_options,
+        );
+        assert_eq!(2, results.len());
+        assert_eq!(Some("rule1 ERROR".to_string()), results[0].output);
+        assert_eq!(Some("rule2 NOTICE".to_string()), results[1].output);
+    }
+
+    // a rule that does not end times out, the other rules still run on the file
+    #[test]
+    fn test_analyze_rule_timeout() {
+        let rule = |name: &str, rule_code: &str| RuleInternal {
+            name: name.to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(QUERY_CODE.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            rule_timeout_ms: Some(200),
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![
+                rule(
+                    "loop",
+                    "function visit(node, filename, code) { while(true) {} }",
+                ),
+                rule(
+                    "log",
+                    "function visit(node, filename, code) { console.log(\"done\"); }",
+                ),
+            ],
+            "myfile.py",
+            PYTHON_CODE,
+            &analysis_options,
+        );
+        assert_eq!(2, results.len());
+        assert_eq!(vec![ERROR_RULE_TIMEOUT.to_string()], results[0].errors);
+        assert!(results[1].errors.is_empty());
+        assert_eq!(Some("done".to_string()), results[1].output);
+    }
+
+    // the files that are not valid UTF-8 are analyzed and the positions are in the original file
+    #[test]
+    fn test_analyze_bytes_latin1() {
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col, "call"));
+}
+"#
+            .to_string(),
+            tree_sitter_query: Some("(call function: (identifier) @name)".to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        // "été" in Latin-1 before the call
+        let content = b"# caf\xe9\ns = \"\xe9t\xe9\"; foo(s)\n";
+
+        for encoding in [PositionEncoding::Chars, PositionEncoding::Utf8Bytes] {
+            let analysis_options = AnalysisOptions {
+                log_output: true,
+                use_debug: false,
+                position_encoding: encoding,
+                ..AnalysisOptions::default()
+            };
+            let results = analyze_bytes(
+                &Language::Python,
+                vec![rule.clone()],
+                "myfile.py",
+                content,
+                &analysis_options,
+            );
+            assert_eq!(1, results.len());
+            let result = results.get(0).unwrap();
+            assert_eq!(vec![ERROR_FILE_DECODED_LOSSILY.to_string()], result.errors);
+            assert_eq!(1, result.violations.len());
+            let violation = result.violations.get(0).unwrap();
+            assert_eq!(Position::new(2, 12), violation.start);
+            assert_eq!(Position::new(2, 15), violation.end);
+        }
+
+        // null bytes do not stop the analysis
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze_bytes(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            b"foo(s)\n# \0\n",
+            &analysis_options,
+        );
+        let result = results.get(0).unwrap();
+        assert!(result.errors.is_empty());
+        assert!(result.execution_error.is_none());
+        assert_eq!(1, result.violations.len());
+    }
+
+    // files that exceed their time budget when parsing are reported as skipped
+    #[test]
+    fn test_file_timeout() {
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: "function visit(node, filename, code) {}".to_string(),
+            tree_sitter_query: Some("(identifier) @name".to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let code = PYTHON_CODE.repeat(20_000);
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            file_timeout_ms: Some(0),
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule.clone()],
+            "myfile.py",
+            &code,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let result = results.get(0).unwrap();
+        assert!(result.violations.is_empty());
+        assert_eq!(vec![ERROR_PARSING_TIMEOUT.to_string()], result.errors);
+        assert_eq!(
+            Some("skipped: parse timeout".to_string()),
+            result.execution_error
+        );
+
+        // files analyzed within their budget are not reported
+        let analysis_options = AnalysisOptions {
+            file_timeout_ms: Some(3_600_000),
+            ..analysis_options
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            PYTHON_CODE,
+            &analysis_options,
+        );
+        assert!(results.get(0).unwrap().errors.is_empty());
+    }
+
+    // the matches of several patterns on the same nodes are only passed once to the rule
+    #[test]
+    fn test_duplicate_matches() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                        "invalid name", "CRITICAL", "security"));
+}
+        "#;
+        let query = r#"
+(function_definition name: (identifier) @name)
+(function_definition name: (identifier) @name parameters: (parameters (identifier)))
+        "#;
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(query.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            PYTHON_CODE,
+            &analysis_options,
+        );
+        assert_eq!(1, results.get(0).unwrap().violations.len());
+    }
+
+    // violations in notebooks are reported in the cell that contains them
+    #[test]
+    fn test_analyze_notebook() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const functionName = node.captures["name"];
+    if(functionName) {
+        const error = buildError(functionName.start.line, functionName.start.col, functionName.end.line, functionName.end.col,
+                                 "invalid name", "CRITICAL", "security");
+        addError(error);
+    }
+}
+        "#;
+
+        let notebook = r##"
+{
+ "cells": [
+  {"cell_type": "code", "source": ["%matplotlib inline\n", "import os"]},
+  {"cell_type": "code", "source": ["x = 1\n", "def foo(arg1):\n", "    pass"]},
+  {"cell_type": "markdown", "source": ["# Results"]}
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+        "##;
+
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(QUERY_CODE.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule.clone()],
+            "notebook.ipynb",
+            notebook,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let result = results.get(0).unwrap();
+        assert!(result.errors.is_empty());
+        assert_eq!(result.violations.len(), 1);
+        let violation = result.violations.get(0).unwrap();
+        assert_eq!(violation.start.cell, Some(1));
+        assert_eq!(violation.start.line, 2);
+        assert_eq!(violation.start.col, 5);
+        assert_eq!(violation.end.cell, Some(1));
+        assert_eq!(violation.end.line, 2);
+        assert_eq!(violation.end.col, 8);
+
+        // the notebooks that cannot be read are reported, not analyzed as clean
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "notebook.ipynb",
+            "not a notebook",
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        assert_eq!(vec![ERROR_PARSING_FAILED.to_string()], results[0].errors);
+        assert!(results[0].violations.is_empty());
+        assert!(results[0]
+            .execution_error
+            .as_ref()
+            .map(|e| e.starts_with("invalid notebook"))
+            .unwrap_or(false));
+    }
+
+    // the violations of the script of a Vue component are reported in the component
+    #[test]
+    fn test_analyze_vue_component() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    if(name) {
+        const error = buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                                 "do not use eval", "CRITICAL", "security");
+        addError(error);
+    }
+}
+        "#;
+        let tree_sitter_query = r#"
+(call_expression
+    function: (identifier) @name
+    (#eq? @name "eval")
+)
+        "#;
+
+        let options_api_component = r#"<template>
+  <div>{{ message }}</div>
+</template>
+
+<script>
+export default {
+  created() {
+    eval(this.message);
+  }
+}
+</script>
+"#;
+        let setup_component = r#"<script setup lang="ts">
+const message: string = "hello";
+eval(message);
+</script>
+
+<template>
+  <div>{{ message }}</div>
+</template>
+"#;
+
+        let rule = |language: Language| RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
+            language,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(tree_sitter_query.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+
+        let results = analyze(
+            &Language::JavaScript,
+            vec![rule(Language::JavaScript)],
+            "Options.vue",
+            options_api_component,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let violations = &results.get(0).unwrap().violations;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations.get(0).unwrap().start.line, 8);
+        assert_eq!(violations.get(0).unwrap().start.col, 5);
+
+        let results = analyze(
+            &Language::TypeScript,
+            vec![rule(Language::TypeScript)],
+            "Setup.vue",
+            setup_component,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let violations = &results.get(0).unwrap().violations;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations.get(0).unwrap().start.line, 3);
+        assert_eq!(violations.get(0).unwrap().start.col, 1);
+
+        // the component does not have any JavaScript
+        let results = analyze(
+            &Language::JavaScript,
+            vec![rule(Language::JavaScript)],
+            "Setup.vue",
+            setup_component,
+            &analysis_options,
+        );
+        assert!(results.is_empty());
+    }
+
+    // reactive statements of Svelte components do not generate errors and the violations
+    // are reported in the component
+    #[test]
+    fn test_analyze_svelte_component() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    if(name) {
+        const error = buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                                 "do not use innerHTML", "CRITICAL", "security");
+        addError(error);
+    }
+}
+        "#;
+        let tree_sitter_query = r#"
+(assignment_expression
+    left: (member_expression
+        property: (property_identifier) @name
+        (#eq? @name "innerHTML")
+    )
+)
+        "#;
+
+        let component = r#"<script lang="ts">
+  export let content: string;
+  let container: HTMLElement;
+
+  $: doubled = content.length * 2;
+  $: if (container) {
+    container.innerHTML = content;
+  }
+</script>
+
+<div bind:this={container}>{doubled}</div>
+"#;
+
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
+            language: Language::TypeScript,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(tree_sitter_query.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::TypeScript,
+            vec![rule],
+            "Content.svelte",
+            component,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let result = results.get(0).unwrap();
+        assert!(result.errors.is_empty());
+        assert_eq!(result.violations.len(), 1);
+        assert_eq!(result.violations.get(0).unwrap().start.line, 7);
+        assert_eq!(result.violations.get(0).unwrap().start.col, 15);
+    }
+
+    // each script of an HTML page is analyzed and its violations reported in the page
+    #[test]
+    fn test_analyze_html_page() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    if(name) {
+        const error = buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                                 "do not use eval", "CRITICAL", "security");
+        addError(error);
+    }
+}
+        "#;
+        let tree_sitter_query = r#"
+(call_expression
+    function: (identifier) @name
+    (#eq? @name "eval")
+)
+        "#;
+
+        let page = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <script>eval(location.hash);</script>
+  </head>
+  <body>
+    <div id="content"></div>
+    <script>
+      const content = document.getElementById("content");
+      eval(content.dataset.code);
+    </script>
+  </body>
+</html>
+"#;
+
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
+            language: Language::JavaScript,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(tree_sitter_query.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::JavaScript,
+            vec![rule],
+            "index.html",
+            page,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let violations = &results.get(0).unwrap().violations;
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations.get(0).unwrap().start.line, 4);
+        assert_eq!(violations.get(0).unwrap().start.col, 13);
+        assert_eq!(violations.get(1).unwrap().start.line, 10);
+        assert_eq!(violations.get(1).unwrap().start.col, 7);
+    }
+
+    // the state of the rule and finalize are per script of the HTML page, not per page
+    #[test]
+    fn test_analyze_html_page_state_per_script() {
+        let rule_code = r#"
+function visit(node, filename, code, state) {
+    state.calls = (state.calls ?? 0) + 1;
+    state.first = state.first ?? node.captures["name"];
+}
+
+function finalize(context, state) {
+    const name = state.first;
+    addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                        `${state.calls} eval calls`, "CRITICAL", "security"));
+}
+        "#;
+        let tree_sitter_query = r#"
+(call_expression
+    function: (identifier) @name
+    (#eq? @name "eval")
+)
+        "#;
+
+        let page = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <script>eval(location.hash);</script>
+  </head>
+  <body>
+    <script>
+      eval(document.title);
+      eval(document.body.dataset.code);
+    </script>
+  </body>
+</html>
+"#;
+
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
+            language: Language::JavaScript,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(tree_sitter_query.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::JavaScript,
+            vec![rule],
+            "index.html",
+            page,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let violations = &results.get(0).unwrap().violations;
+        assert_eq!(violations.len(), 2);
+        assert_eq!("1 eval calls", violations.get(0).unwrap().message);
+        assert_eq!(Position::new(4, 13), violations.get(0).unwrap().start);
+        assert_eq!("2 eval calls", violations.get(1).unwrap().message);
+        assert_eq!(Position::new(8, 7), violations.get(1).unwrap().start);
+    }
+
+    // the SQL queries in the strings of a Python file are analyzed with the SQL rules and their
+    // violations reported in the Python file
+    #[test]
+    fn test_analyze_injected_sql() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const statement = node.captures["statement"];
+    addError(buildError(statement.start.line, statement.start.col, statement.end.line, statement.end.col,
+                        "select statement", "CRITICAL", "security"));
+}
+        "#;
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
+            language: Language::Sql,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some("(select_statement) @statement".to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let code = r#"
+def get_users(db):
+    db.execute("SELECT * FROM users")
+    return db.execute("""
+        SELECT name
+        FROM users
+    """)
+"#;
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Sql,
+            vec![rule],
+            "myfile.py",
+            code,
+            &analysis_options,
+        );
+        let violations = &results.get(0).unwrap().violations;
+        assert_eq!(violations.len(), 2);
+        assert_eq!(Position::new(3, 17), violations[0].start);
+        assert_eq!(Position::new(3, 36), violations[0].end);
+        assert_eq!(Position::new(5, 9), violations[1].start);
+        assert_eq!(Position::new(6, 19), violations[1].end);
+    }
+
+    // the scripts of the HTML template literals are analyzed with the code of the file
+    #[test]
+    fn test_analyze_injected_html_scripts() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col, "eval"));
+}
+        "#;
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
+            language: Language::JavaScript,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some("(call_expression function: (identifier) @name)".to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let code = "eval(a);\nconst page = `<div>\n  <script>eval(b);</script>\n</div>`;\n";
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::JavaScript,
+            vec![rule.clone()],
+            "page.js",
+            code,
+            &analysis_options,
+        );
+        let positions: Vec<Position> = results[0]
+            .violations
+            .iter()
+            .map(|v| v.start.clone())
+            .collect();
+        assert_eq!(vec![Position::new(1, 1), Position::new(3, 11)], positions);
+
+        // the extensions overridden by the user give the language of the file
+        let analysis_options = AnalysisOptions {
+            language_overrides: HashMap::from([("es6".to_string(), Language::JavaScript)]),
+            ..analysis_options
+        };
+        let results = analyze(
+            &Language::JavaScript,
+            vec![rule],
+            "page.es6",
+            code,
+            &analysis_options,
+        );
+        assert_eq!(2, results[0].violations.len());
+    }
+
+    // the code blocks of a Markdown document are analyzed and their violations reported in
+    // the document
+    #[test]
+    fn test_analyze_markdown_code_blocks() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    if(name) {
+        const error = buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                                 "do not use eval", "CRITICAL", "security");
+        addError(error);
+    }
+}
+        "#;
+        let tree_sitter_query = r#"
+(call
+    function: (identifier) @name
+    (#eq? @name "eval")
+)
+        "#;
+
+        let readme = r#"# My project
+
+Install the dependencies:
+
+```python
+import requests
+response = requests.get("https://example.com")
+```
+
+Then evaluate the expression:
+
+```python
+expression = input()
+result = eval(expression)
+```
+"#;
+
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(tree_sitter_query.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "README.md",
+            readme,
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let violations = &results.get(0).unwrap().violations;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations.get(0).unwrap().start.line, 14);
+        assert_eq!(violations.get(0).unwrap().start.col, 10);
+    }
+
+    // the settings of the pattern that matched are available in the visit function
+    #[test]
+    fn test_settings_available_in_visit() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    const error = buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                             "call to " + node.settings["kind"], "CRITICAL", "security");
+    addError(error);
+}
+        "#;
+        let tree_sitter_query = r#"
+(call
+    function: (identifier) @name
+    (#eq? @name "eval")
+    (#set! "kind" "code-sink")
+)
+        "#;
+
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(tree_sitter_query.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            "eval(user_input)",
+            &analysis_options,
+        );
+        assert_eq!(1, results.len());
+        let violations = &results.get(0).unwrap().violations;
+        assert_eq!(violations.len(), 1);
+        assert_eq!("call to code-sink", violations.get(0).unwrap().message);
+    }
+
+    // rules that read the children of the captures must not limit the nodes of the captures
+    #[test]
+    fn test_max_capture_nodes() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const body = node.captures["body"];
+    if (body.children.length > 0) {
+        const error = buildError(body.start.line, body.start.col, body.end.line, body.end.col,
+                                 "function with a body", "CRITICAL", "security");
+        addError(error);
+    }
+}
+        "#;
+        let rule = |max_capture_nodes: Option<usize>| RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some("(function_definition body: (block) @body)".to_string()),
+            variables: HashMap::new(),
+            max_capture_nodes,
+            ..RuleInternal::default()
+        };
+
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule(None), rule(Some(1))],
+            "myfile.py",
+            PYTHON_CODE,
+            &analysis_options,
+        );
+        assert_eq!(2, results.len());
+        assert_eq!(1, results.get(0).unwrap().violations.len());
+        assert!(results.get(1).unwrap().violations.is_empty());
+    }
 }
@@ -0,0 +1,139 @@
+use crate::analysis::embedded::map_rule_result_positions;
+use crate::analysis::line_index::LineIndex;
+use crate::model::analysis::PositionEncoding;
+use crate::model::common::Position;
+use crate::model::rule::RuleResult;
+
+// Code of a file that may not be valid UTF-8 (e.g. Latin-1 legacy sources). Each invalid byte
+// is replaced by one replacement character so that the lines and the columns in characters
+// are the same as in the original file.
+#[derive(Clone, Debug)]
+pub struct DecodedCode {
+    pub code: String,
+    // byte offsets in the code of the replacement characters of the invalid bytes
+    replacements: Vec<usize>,
+}
+
+impl DecodedCode {
+    // the content of the file was not valid UTF-8 and some bytes were replaced
+    pub fn is_lossy(&self) -> bool {
+        !self.replacements.is_empty()
+    }
+
+    // get the byte offset in the original file of a byte offset in the code
+    pub fn get_original_offset(&self, offset: usize) -> usize {
+        let replacements_before = self.replacements.partition_point(|r| *r < offset);
+        // each replacement character takes more bytes than the byte it replaces
+        offset - replacements_before * (char::REPLACEMENT_CHARACTER.len_utf8() - 1)
+    }
+
+    // Map a position in the code to its position in the original file. Only the columns in
+    // bytes change, an invalid byte is one character like its replacement. The positions in the
+    // cells of notebooks are kept.
+    pub fn map_position(
+        &self,
+        line_index: &LineIndex,
+        position: &Position,
+        encoding: PositionEncoding,
+    ) -> Position {
+        if encoding != PositionEncoding::Utf8Bytes || position.cell.is_some() {
+            return position.clone();
+        }
+        let (Some(offset), Some(line_range)) = (
+            line_index.get_offset(position, encoding),
+            line_index.get_line_range(position.line.saturating_sub(1) as usize),
+        ) else {
+            return position.clone();
+        };
+        let column = self.get_original_offset(offset) - self.get_original_offset(line_range.start);
+        Position {
+            line: position.line,
+            col: u32::try_from(column + 1).unwrap(),
+            cell: position.cell,
+        }
+    }
+
+    pub fn map_rule_result(
+        &self,
+        rule_result: RuleResult,
+        encoding: PositionEncoding,
+    ) -> RuleResult {
+        if !self.is_lossy() {
+            return rule_result;
+        }
+        let line_index = LineIndex::new(&self.code);
+        map_rule_result_positions(rule_result, |p| self.map_position(&line_index, p, encoding))
+    }
+}
+
+// Decode the content of a file, replacing the bytes that are not valid UTF-8. Null bytes are
+// valid UTF-8 and kept.
+pub fn decode_lossy(bytes: &[u8]) -> DecodedCode {
+    let mut code = String::with_capacity(bytes.len());
+    let mut replacements = vec![];
+    let mut rest = bytes;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                code.push_str(valid);
+                break;
+            }
+            Err(e) => {
+                let valid_up_to = e.valid_up_to();
+                // the bytes before the error are valid UTF-8
+                code.push_str(std::str::from_utf8(&rest[..valid_up_to]).unwrap());
+                // replace the invalid bytes one at a time to keep one character per byte
+                replacements.push(code.len());
+                code.push(char::REPLACEMENT_CHARACTER);
+                rest = &rest[valid_up_to + 1..];
+            }
+        }
+    }
+    DecodedCode { code, replacements }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_lossy_valid() {
+        let decoded = decode_lossy("a = \"é\"\0\n".as_bytes());
+        assert!(!decoded.is_lossy());
+        assert_eq!("a = \"é\"\0\n", decoded.code);
+        assert_eq!(5, decoded.get_original_offset(5));
+    }
+
+    #[test]
+    fn test_decode_lossy_latin1() {
+        // "é" and "à" in Latin-1, the second one at the end of the content
+        let decoded = decode_lossy(b"a = \"\xe9\xe9t\xe9\"\nb = 1 \xe0");
+        assert!(decoded.is_lossy());
+        assert_eq!(
+            "a = \"\u{fffd}\u{fffd}t\u{fffd}\"\nb = 1 \u{fffd}",
+            decoded.code
+        );
+        assert_eq!(5, decoded.get_original_offset(5));
+        assert_eq!(6, decoded.get_original_offset(8));
+        assert_eq!(8, decoded.get_original_offset(12));
+        assert_eq!(17, decoded.get_original_offset(decoded.code.len() - 3));
+        assert_eq!(18, decoded.get_original_offset(decoded.code.len()));
+    }
+
+    #[test]
+    fn test_map_position() {
+        let decoded = decode_lossy(b"s = \"\xe9t\xe9\"; foo(x)\n");
+        let line_index = LineIndex::new(&decoded.code);
+        // `foo` is at the column 16 in bytes of the code and the column 12 of the file
+        let position = Position {
+            line: 1,
+            col: 16,
+            cell: None,
+        };
+        assert_eq!("foo", &decoded.code[15..18]);
+        let mapped = decoded.map_position(&line_index, &position, PositionEncoding::Utf8Bytes);
+        assert_eq!(Position::new(1, 12), mapped);
+        let mapped = decoded.map_position(&line_index, &position, PositionEncoding::Chars);
+        assert_eq!(Position::new(1, 16), mapped);
+    }
+}
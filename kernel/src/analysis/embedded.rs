@@ -0,0 +1,472 @@
+use crate::analysis::line_index::LineIndex;
+use crate::model::analysis::PositionEncoding;
+use crate::model::common::{Language, Position};
+use crate::model::rule::RuleResult;
+use std::path::Path;
+
+// Code of one language embedded in another file (e.g. the script of a Vue component).
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmbeddedCode {
+    pub language: Language,
+    pub code: String,
+    // position of the first character of the code in the original file, the column is counted
+    // in the position encoding of the analysis
+    pub start_line: u32,
+    pub start_col: u32,
+}
+
+impl EmbeddedCode {
+    // Map a position in the embedded code to its position in the original file.
+    pub fn map_position(&self, position: &Position) -> Position {
+        let col = if position.line == 1 {
+            position.col + self.start_col - 1
+        } else {
+            position.col
+        };
+        Position {
+            line: position.line + self.start_line - 1,
+            col,
+            cell: position.cell,
+        }
+    }
+
+    pub fn map_rule_result(&self, rule_result: RuleResult) -> RuleResult {
+        map_rule_result_positions(rule_result, |p| self.map_position(p))
+    }
+}
+
+// Apply a mapping function to all the positions of the violations and fixes of a result.
+pub fn map_rule_result_positions<F>(mut rule_result: RuleResult, map_position: F) -> RuleResult
+where
+    F: Fn(&Position) -> Position,
+{
+    for violation in &mut rule_result.violations {
+        violation.start = map_position(&violation.start);
+        violation.end = map_position(&violation.end);
+        for fix in &mut violation.fixes {
+            for edit in &mut fix.edits {
+                edit.start = map_position(&edit.start);
+                edit.end = edit.end.as_ref().map(&map_position);
+            }
+        }
+        // the locations in other files are not in the code
+        for location in &mut violation.related_locations {
+            if location.filename.is_none() {
+                location.start = map_position(&location.start);
+                location.end = map_position(&location.end);
+            }
+        }
+    }
+    rule_result
+}
+
+// Get the code embedded in a file if the file is a container format (HTML page, Markdown
+// document, Vue or Svelte component, etc).
+// Returns None when the file should be analyzed as a regular source file.
+pub fn get_embedded_code(
+    filename: &str,
+    code: &str,
+    encoding: PositionEncoding,
+) -> Option<Vec<EmbeddedCode>> {
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())?;
+
+    match extension.as_str() {
+        "htm" | "html" => Some(get_html_script_blocks(code, encoding)),
+        "markdown" | "md" => Some(get_markdown_code_blocks(code)),
+        "svelte" | "vue" => Some(get_script_blocks(
+            code,
+            encoding,
+            get_component_script_language,
+        )),
+        _ => None,
+    }
+}
+
+// Get the value of an attribute in the content of a tag (e.g. `script setup lang="ts"`).
+fn get_attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+    while !rest.is_empty() {
+        rest = rest.trim_start();
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let attribute_name = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+
+        let mut value = None;
+        if let Some(after_equal) = rest.strip_prefix('=') {
+            let after_equal = after_equal.trim_start();
+            let (v, remaining) = match after_equal.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let content = &after_equal[1..];
+                    let end = content.find(quote).unwrap_or(content.len());
+                    (&content[..end], &content[(end + 1).min(content.len())..])
+                }
+                _ => {
+                    let end = after_equal
+                        .find(char::is_whitespace)
+                        .unwrap_or(after_equal.len());
+                    (&after_equal[..end], &after_equal[end..])
+                }
+            };
+            value = Some(v.to_string());
+            rest = remaining;
+        }
+
+        if attribute_name.eq_ignore_ascii_case(name) {
+            return Some(value.unwrap_or_default());
+        }
+    }
+    None
+}
+
+// Components (Vue, Svelte) use the lang attribute to specify the language of the script.
+fn get_component_script_language(tag: &str) -> Option<Language> {
+    match get_attribute(tag, "lang")
+        .map(|l| l.to_ascii_lowercase())
+        .as_deref()
+    {
+        None | Some("js" | "jsx" | "javascript") => Some(Language::JavaScript),
+        Some("ts" | "tsx" | "typescript") => Some(Language::TypeScript),
+        _ => None,
+    }
+}
+
+// HTML pages use the type attribute. Scripts with another type are data blocks (JSON,
+// templates, etc).
+fn get_html_script_language(tag: &str) -> Option<Language> {
+    match get_attribute(tag, "type")
+        .map(|t| t.trim().to_ascii_lowercase())
+        .as_deref()
+    {
+        None
+        | Some(
+            ""
+            | "module"
+            | "text/javascript"
+            | "application/javascript"
+            | "text/ecmascript"
+            | "application/ecmascript",
+        ) => Some(Language::JavaScript),
+        _ => None,
+    }
+}
+
+// Get the JavaScript of the <script> elements of an HTML page.
+pub fn get_html_script_blocks(code: &str, encoding: PositionEncoding) -> Vec<EmbeddedCode> {
+    get_script_blocks(code, encoding, get_html_script_language)
+}
+
+// Get the content of all the <script> elements of a file. Scripts in a language we
+// do not support (e.g. coffee) are ignored.
+fn get_script_blocks<F>(
+    code: &str,
+    encoding: PositionEncoding,
+    get_language: F,
+) -> Vec<EmbeddedCode>
+where
+    F: Fn(&str) -> Option<Language>,
+{
+    let mut blocks = vec![];
+    let line_index = LineIndex::new(code);
+    let lowercase = code.to_ascii_lowercase();
+    let mut offset = 0;
+
+    while let Some(index) = lowercase[offset..].find("<script") {
+        let tag_start = offset + index + "<script".len();
+        // make sure we are not matching a longer tag name
+        if !lowercase[tag_start..].starts_with(|c: char| c.is_whitespace() || c == '>') {
+            offset = tag_start;
+            continue;
+        }
+        let Some(tag_end) = lowercase[tag_start..].find('>').map(|i| tag_start + i) else {
+            break;
+        };
+        let content_start = tag_end + 1;
+        let content_end = lowercase[content_start..]
+            .find("</script")
+            .map_or(code.len(), |i| content_start + i);
+
+        if let Some(language) = get_language(&code[tag_start..tag_end]) {
+            let start = line_index.get_position(content_start, encoding);
+            blocks.push(EmbeddedCode {
+                language,
+                code: code[content_start..content_end].to_string(),
+                start_line: start.line,
+                start_col: start.col,
+            });
+        }
+        offset = content_end;
+    }
+    blocks
+}
+
+// Language of a fenced code block from the first word of its info string (```python).
+fn get_fence_language(info: &str) -> Option<Language> {
+    Language::from_name(info.split_whitespace().next()?)
+}
+
+// A fence (``` or ~~~) that starts or ends a code block: its character, its length and
+// the text after it.
+fn get_fence(line: &str) -> Option<(char, usize, &str)> {
+    let content = line.trim_end_matches(['\n', '\r']);
+    let trimmed = content.trim_start_matches(' ');
+    // fences can be indented by up to 3 spaces
+    if content.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let character = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let length = trimmed.len() - trimmed.trim_start_matches(character).len();
+    if length < 3 {
+        return None;
+    }
+    Some((character, length, &trimmed[length..]))
+}
+
+// Get the content of the fenced code blocks of a Markdown document. Blocks without
+// language or with a language we do not support are ignored.
+fn get_markdown_code_blocks(code: &str) -> Vec<EmbeddedCode> {
+    let mut blocks = vec![];
+    // character and length of the fence of the current block, with the block being built
+    let mut current: Option<(char, usize, Option<EmbeddedCode>)> = None;
+
+    for (line_index, line) in code.split_inclusive('\n').enumerate() {
+        match current.as_mut() {
+            None => {
+                if let Some((character, length, info)) = get_fence(line) {
+                    if character == '`' && info.contains('`') {
+                        continue;
+                    }
+                    let block = get_fence_language(info).map(|language| EmbeddedCode {
+                        language,
+                        code: String::new(),
+                        start_line: u32::try_from(line_index + 2).unwrap(),
+                        start_col: 1,
+                    });
+                    current = Some((character, length, block));
+                }
+            }
+            Some((fence_character, fence_length, block)) => match get_fence(line) {
+                Some((character, length, info))
+                    if character == *fence_character
+                        && length >= *fence_length
+                        && info.trim().is_empty() =>
+                {
+                    if let Some(block) = block.take() {
+                        blocks.push(block);
+                    }
+                    current = None;
+                }
+                _ => {
+                    if let Some(block) = block.as_mut() {
+                        block.code.push_str(line);
+                    }
+                }
+            },
+        }
+    }
+
+    // a block that is not closed goes until the end of the document
+    if let Some((_, _, Some(block))) = current {
+        blocks.push(block);
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENCODING: PositionEncoding = PositionEncoding::Chars;
+
+    #[test]
+    fn test_get_attribute() {
+        assert_eq!(
+            get_attribute(r#" setup lang="ts""#, "lang"),
+            Some("ts".to_string())
+        );
+        assert_eq!(
+            get_attribute(" lang='tsx' setup", "lang"),
+            Some("tsx".to_string())
+        );
+        assert_eq!(get_attribute(" lang=js", "lang"), Some("js".to_string()));
+        assert_eq!(get_attribute(" setup", "setup"), Some("".to_string()));
+        assert_eq!(get_attribute(r#" xml:lang="ts""#, "lang"), None);
+        assert_eq!(get_attribute("", "lang"), None);
+    }
+
+    #[test]
+    fn test_get_embedded_code_vue() {
+        let code = r#"<template>
+  <div>{{ message }}</div>
+</template>
+
+<script>
+export default {
+  data() { return { message: "hello" } }
+}
+</script>
+
+<script setup lang="ts">
+const count: number = 1;
+</script>
+
+<style>
+div { color: red; }
+</style>
+"#;
+        let blocks = get_embedded_code("component.vue", code, ENCODING).unwrap();
+        assert_eq!(2, blocks.len());
+        assert_eq!(blocks[0].language, Language::JavaScript);
+        assert_eq!(blocks[0].start_line, 5);
+        assert_eq!(blocks[0].start_col, 9);
+        assert!(blocks[0].code.contains("export default"));
+        assert_eq!(blocks[1].language, Language::TypeScript);
+        assert_eq!(blocks[1].start_line, 11);
+        assert_eq!(blocks[1].code, "\nconst count: number = 1;\n");
+    }
+
+    #[test]
+    fn test_get_embedded_code_vue_no_script() {
+        let code = "<template>\n  <div>hello</div>\n</template>\n";
+        assert!(get_embedded_code("component.vue", code, ENCODING)
+            .unwrap()
+            .is_empty());
+        let code = "<script lang=\"coffee\">\nx = 1\n</script>\n";
+        assert!(get_embedded_code("component.vue", code, ENCODING)
+            .unwrap()
+            .is_empty());
+        assert!(get_embedded_code("file.js", code, ENCODING).is_none());
+    }
+
+    #[test]
+    fn test_get_embedded_code_svelte() {
+        let code = r#"<script context="module" lang="ts">
+  export const prerender: boolean = true;
+</script>
+
+<script lang="ts">
+  export let count: number = 0;
+  $: doubled = count * 2;
+</script>
+
+<button on:click={() => count++}>{doubled}</button>
+"#;
+        let blocks = get_embedded_code("Counter.svelte", code, ENCODING).unwrap();
+        assert_eq!(2, blocks.len());
+        assert!(blocks.iter().all(|b| b.language == Language::TypeScript));
+        assert_eq!(blocks[0].start_line, 1);
+        assert_eq!(blocks[0].start_col, 36);
+        assert_eq!(blocks[1].start_line, 5);
+        assert!(blocks[1].code.contains("$: doubled"));
+    }
+
+    #[test]
+    fn test_get_embedded_code_html() {
+        let code = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <script src="app.js"></script>
+    <script type="application/ld+json">{"@type": "Organization"}</script>
+    <script>var user = location.hash;</script>
+  </head>
+  <body>
+    <script type="module">
+      import { render } from "./render.js";
+    </script>
+  </body>
+</html>
+"#;
+        let blocks = get_embedded_code("index.html", code, ENCODING).unwrap();
+        assert_eq!(3, blocks.len());
+        assert!(blocks.iter().all(|b| b.language == Language::JavaScript));
+        assert_eq!(blocks[0].code, "");
+        assert_eq!((blocks[1].start_line, blocks[1].start_col), (6, 13));
+        assert_eq!(blocks[1].code, "var user = location.hash;");
+        assert_eq!(blocks[2].start_line, 9);
+    }
+
+    #[test]
+    fn test_get_embedded_code_markdown() {
+        let code = r#"# Project
+
+```python
+import os
+```
+
+Some text with `inline code`.
+
+~~~js title="example.js"
+eval(input);
+~~~
+
+```
+no language
+```
+
+```brainfuck
++++.
+```
+
+````markdown
+```python
+print("nested")
+```
+````
+"#;
+        let blocks = get_embedded_code("README.md", code, ENCODING).unwrap();
+        assert_eq!(2, blocks.len());
+        assert_eq!(blocks[0].language, Language::Python);
+        assert_eq!(blocks[0].code, "import os\n");
+        assert_eq!((blocks[0].start_line, blocks[0].start_col), (4, 1));
+        assert_eq!(blocks[1].language, Language::JavaScript);
+        assert_eq!(blocks[1].code, "eval(input);\n");
+        assert_eq!(blocks[1].start_line, 10);
+    }
+
+    #[test]
+    fn test_get_embedded_code_markdown_not_closed() {
+        let code = "```go\npackage main\n";
+        let blocks = get_embedded_code("doc.markdown", code, ENCODING).unwrap();
+        assert_eq!(1, blocks.len());
+        assert_eq!(blocks[0].code, "package main\n");
+    }
+
+    // the columns of the scripts are counted in the encoding
+    #[test]
+    fn test_get_embedded_code_encoding() {
+        let code = "<p>😀</p><script>foo();</script>\n";
+        let get_start = |encoding| {
+            let blocks = get_embedded_code("index.html", code, encoding).unwrap();
+            (blocks[0].start_line, blocks[0].start_col)
+        };
+        assert_eq!((1, 17), get_start(PositionEncoding::Chars));
+        assert_eq!((1, 18), get_start(PositionEncoding::Utf16));
+        assert_eq!((1, 20), get_start(PositionEncoding::Utf8Bytes));
+    }
+
+    #[test]
+    fn test_map_position() {
+        let embedded = EmbeddedCode {
+            language: Language::JavaScript,
+            code: "foo();".to_string(),
+            start_line: 3,
+            start_col: 9,
+        };
+        let position = embedded.map_position(&Position {
+            line: 1,
+            col: 2,
+            cell: None,
+        });
+        assert_eq!(Position::new(3, 10), position);
+        let position = embedded.map_position(&Position {
+            line: 2,
+            col: 2,
+            cell: None,
+        });
+        assert_eq!(Position::new(4, 2), position);
+    }
+}
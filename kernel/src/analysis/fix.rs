@@ -0,0 +1,692 @@
+use crate::analysis::line_index::LineIndex;
+use crate::model::analysis::PositionEncoding;
+use crate::model::common::{Position, Range};
+use crate::model::violation::{Edit, EditType, Fix, Violation};
+use anyhow::{anyhow, Context};
+
+const BOM: &str = "\u{feff}";
+
+// Remove the byte order mark at the start of the code. Editors do not show it and the positions
+// of the violations are computed without it.
+pub fn strip_bom(code: &str) -> &str {
+    code.strip_prefix(BOM).unwrap_or(code)
+}
+
+// Get the byte offset of a position (line and column starting at 1) in the code. The column
+// after the last character of the line is the end of the line, the columns after it are errors.
+fn get_offset(
+    line_index: &LineIndex,
+    position: &Position,
+    encoding: PositionEncoding,
+) -> anyhow::Result<usize> {
+    let offset = line_index
+        .get_offset(position, encoding)
+        .ok_or_else(|| anyhow!("invalid line {}", position.line))?;
+    // the offset of a column after the end of the line is the end of the line
+    let end_of_line = line_index.get_position(offset, encoding);
+    if position.col > end_of_line.col {
+        return Err(anyhow!(
+            "invalid column {} on line {}, the line ends at column {}",
+            position.col,
+            position.line,
+            end_of_line.col
+        ));
+    }
+    Ok(offset)
+}
+
+// Get the byte ranges of the edits in the code (without byte order mark) and their new content,
+// in the order of the code. Edits that overlap or that are outside of the code are errors.
+fn get_replacements(
+    content: &str,
+    edits: &[Edit],
+    encoding: PositionEncoding,
+) -> anyhow::Result<Vec<(usize, usize, String, Range)>> {
+    let is_crlf = content.contains("\r\n");
+    let line_index = LineIndex::new(content);
+
+    let mut replacements: Vec<(usize, usize, String, Range)> = vec![];
+    for edit in edits {
+        let range = match (edit.edit_type, &edit.end) {
+            (EditType::Add, _) => Range::new(edit.start.clone(), edit.start.clone()),
+            (_, Some(end)) => Range::new(edit.start.clone(), end.clone()),
+            (_, None) => return Err(anyhow!("{:?} edit without end", edit.edit_type)),
+        };
+        if range.end < range.start {
+            return Err(anyhow!("edit ends before its start"));
+        }
+        let start = get_offset(&line_index, &range.start, encoding)?;
+        let end = get_offset(&line_index, &range.end, encoding)?;
+        if end < start {
+            return Err(anyhow!("edit ends before its start"));
+        }
+        let new_content = match edit.edit_type {
+            EditType::Remove => String::new(),
+            EditType::Add | EditType::Update => edit.content.clone().unwrap_or_default(),
+        };
+        // the content uses the line endings of the code
+        let new_content = new_content.replace("\r\n", "\n");
+        let new_content = if is_crlf {
+            new_content.replace('\n', "\r\n")
+        } else {
+            new_content
+        };
+        replacements.push((start, end, new_content, range));
+    }
+
+    replacements.sort_by_key(|(start, end, _, _)| (*start, *end));
+    if let Some(w) = replacements.windows(2).find(|w| w[0].3.overlaps(&w[1].3)) {
+        return Err(anyhow!(
+            "edits overlap: the edit at {}:{} starts before the end of the edit at {}:{}",
+            w[1].3.start.line,
+            w[1].3.start.col,
+            w[0].3.start.line,
+            w[0].3.start.col
+        ));
+    }
+    Ok(replacements)
+}
+
+// Apply the edits of a fix to the code. The positions of the edits are the positions reported
+// by the analysis: they do not count the byte order mark and the columns use the encoding.
+// The byte order mark and the line endings of the code are kept.
+pub fn apply_edits(
+    code: &str,
+    edits: &[Edit],
+    encoding: PositionEncoding,
+) -> anyhow::Result<String> {
+    let content = strip_bom(code);
+    let bom = &code[..code.len() - content.len()];
+    let replacements = get_replacements(content, edits, encoding)?;
+    // apply the edits from the end of the code so that the offsets of the others do not change
+    let mut result = content.to_string();
+    for (start, end, new_content, _) in replacements.iter().rev() {
+        result.replace_range(*start..*end, new_content);
+    }
+    Ok(format!("{bom}{result}"))
+}
+
+// Check that the edits can be applied to the code, without applying them.
+pub fn validate_edits(
+    code: &str,
+    edits: &[Edit],
+    encoding: PositionEncoding,
+) -> anyhow::Result<()> {
+    get_replacements(strip_bom(code), edits, encoding).map(|_| ())
+}
+
+// Apply all the edits of a fix to the code, or none of them when one of the edits cannot be
+// applied. A violation can have several fixes, only one of them is applied.
+pub fn apply_fix(code: &str, fix: &Fix, encoding: PositionEncoding) -> anyhow::Result<String> {
+    apply_edits(code, &fix.edits, encoding)
+        .with_context(|| format!("cannot apply fix \"{}\"", fix.description))
+}
+
+// Remove the fixes of the violations that cannot be applied to the code (e.g. an edit that ends
+// before its start or that is outside of the file). Returns why each fix was removed.
+pub fn remove_invalid_fixes(
+    code: &str,
+    violations: &mut [Violation],
+    encoding: PositionEncoding,
+) -> Vec<String> {
+    let mut errors = vec![];
+    for violation in violations {
+        violation
+            .fixes
+            .retain(|fix| match validate_edits(code, &fix.edits, encoding) {
+                Ok(()) => true,
+                Err(e) => {
+                    errors.push(format!("invalid fix \"{}\": {}", fix.description, e));
+                    false
+                }
+            });
+    }
+    errors
+}
+
+// Apply the first fix of each violation, the other fixes are alternatives. The violations are
+// fixed in the order of the code and the fixes that conflict with the fixes already applied
+// are skipped. Returns the fixed code and the number of fixes applied.
+pub fn apply_first_fixes(
+    code: &str,
+    violations: &[&Violation],
+    encoding: PositionEncoding,
+) -> anyhow::Result<(String, usize)> {
+    let mut violations = violations.to_vec();
+    violations.sort_by(|a, b| a.start.cmp(&b.start));
+    let mut edits: Vec<Edit> = vec![];
+    let mut applied = 0;
+    for fix in violations.iter().filter_map(|v| v.fixes.first()) {
+        let mut candidate = edits.clone();
+        candidate.extend(fix.edits.iter().cloned());
+        if validate_edits(code, &candidate, encoding).is_ok() {
+            edits = candidate;
+            applied += 1;
+        }
+    }
+    Ok((apply_edits(code, &edits, encoding)?, applied))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyze::analyze;
+    use crate::analysis::tree_sitter::{get_query, get_query_captures, get_tree};
+    use crate::model::analysis::{
+        AnalysisOptions, TreeSitterNode, TreeSitterNodeSummary, ERROR_INVALID_FIX,
+    };
+    use crate::model::common::Language;
+    use crate::model::rule::{RuleCategory, RuleInternal, RuleSeverity};
+    use std::collections::HashMap;
+
+    fn edit(
+        edit_type: EditType,
+        start: (u32, u32),
+        end: Option<(u32, u32)>,
+        content: &str,
+    ) -> Edit {
+        let position = |(line, col)| Position {
+            line,
+            col,
+            cell: None,
+        };
+        Edit {
+            start: position(start),
+            end: end.map(position),
+            edit_type,
+            content: Some(content.to_string()),
+        }
+    }
+
+    // the fix of a CRLF file starting with a byte order mark keeps them
+    #[test]
+    fn test_apply_fix_crlf_bom() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const functionName = node.captures["name"];
+    const error = buildError(functionName.start.line, functionName.start.col, functionName.end.line, functionName.end.col,
+                             "invalid name", "CRITICAL", "security");
+    const update = buildEditUpdate(functionName.start.line, functionName.start.col, functionName.end.line, functionName.end.col, "bar");
+    const add = buildEditAdd(functionName.start.line + 1, 1, "    # renamed\n");
+    addError(error.addFix(buildFix("use bar", [update, add])));
+}
+        "#;
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some("(function_definition name: (identifier) @name)".to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let code = "\u{feff}def foo(arg1):\r\n    pass\r\n";
+        let analysis_options = AnalysisOptions {
+            log_output: false,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            code,
+            &analysis_options,
+        );
+        let violation = &results[0].violations[0];
+        // the byte order mark is not a column
+        assert_eq!(Position::new(1, 5), violation.start);
+        assert_eq!(Position::new(1, 8), violation.end);
+
+        let fixed = apply_edits(code, &violation.fixes[0].edits, PositionEncoding::default());
+        assert_eq!(
+            "\u{feff}def bar(arg1):\r\n    # renamed\r\n    pass\r\n",
+            fixed.unwrap()
+        );
+    }
+
+    // edits that go to the end of a line do not remove the line ending, edits that go after it
+    // are errors
+    #[test]
+    fn test_apply_edits_end_of_line() {
+        let code = "a = 1\r\nb = 2\r\n";
+        let edits = [edit(EditType::Update, (1, 5), Some((1, 6)), "3")];
+        assert_eq!(
+            "a = 3\r\nb = 2\r\n",
+            apply_edits(code, &edits, PositionEncoding::Chars).unwrap()
+        );
+        let edits = [edit(EditType::Update, (1, 5), Some((1, 100)), "3")];
+        assert_eq!(
+            "invalid column 100 on line 1, the line ends at column 6",
+            apply_edits(code, &edits, PositionEncoding::Chars)
+                .unwrap_err()
+                .to_string()
+        );
+
+        let edits = [edit(EditType::Remove, (2, 1), Some((2, 6)), "")];
+        assert_eq!(
+            "a = 1\r\n\r\n",
+            apply_edits(code, &edits, PositionEncoding::Chars).unwrap()
+        );
+
+        // files with LF line endings keep them
+        let edits = [edit(EditType::Add, (2, 1), None, "c = 3\r\n")];
+        assert_eq!(
+            "a = 1\nc = 3\nb = 2\n",
+            apply_edits("a = 1\nb = 2\n", &edits, PositionEncoding::Chars).unwrap()
+        );
+    }
+
+    // the columns of the edits use the position encoding
+    #[test]
+    fn test_apply_edits_encoding() {
+        let code = "s = \"é😀\"; foo(x)\n";
+        let edits = [edit(EditType::Update, (1, 11), Some((1, 14)), "bar")];
+        assert_eq!(
+            "s = \"é😀\"; bar(x)\n",
+            apply_edits(code, &edits, PositionEncoding::Chars).unwrap()
+        );
+        let edits = [edit(EditType::Update, (1, 12), Some((1, 15)), "bar")];
+        assert_eq!(
+            "s = \"é😀\"; bar(x)\n",
+            apply_edits(code, &edits, PositionEncoding::Utf16).unwrap()
+        );
+        let edits = [edit(EditType::Update, (1, 15), Some((1, 18)), "bar")];
+        assert_eq!(
+            "s = \"é😀\"; bar(x)\n",
+            apply_edits(code, &edits, PositionEncoding::Utf8Bytes).unwrap()
+        );
+    }
+
+    // overlapping edits and positions outside of the code are errors
+    #[test]
+    fn test_apply_edits_errors() {
+        let code = "a = 1\nb = 2\n";
+        let edits = [
+            edit(EditType::Update, (1, 1), Some((1, 4)), "c"),
+            edit(EditType::Update, (1, 3), Some((1, 6)), "d"),
+        ];
+        assert_eq!(
+            "edits overlap: the edit at 1:3 starts before the end of the edit at 1:1",
+            apply_edits(code, &edits, PositionEncoding::Chars)
+                .unwrap_err()
+                .to_string()
+        );
+
+        // adding text inside the code of another edit is a conflict, adding it at its ends is not
+        let edits = [
+            edit(EditType::Update, (1, 1), Some((1, 4)), "c"),
+            edit(EditType::Add, (1, 2), None, "d"),
+        ];
+        assert!(apply_edits(code, &edits, PositionEncoding::Chars).is_err());
+        let edits = [
+            edit(EditType::Add, (1, 4), None, "d"),
+            edit(EditType::Update, (1, 1), Some((1, 4)), "c"),
+            edit(EditType::Add, (1, 1), None, "e"),
+        ];
+        assert_eq!(
+            "ecd 1\nb = 2\n",
+            apply_edits(code, &edits, PositionEncoding::Chars).unwrap()
+        );
+
+        let edits = [edit(EditType::Update, (5, 1), Some((5, 2)), "c")];
+        assert!(apply_edits(code, &edits, PositionEncoding::Chars).is_err());
+
+        let edits = [edit(EditType::Remove, (1, 1), None, "")];
+        assert!(apply_edits(code, &edits, PositionEncoding::Chars).is_err());
+    }
+
+    // all the edits of a fix are applied, or none of them
+    #[test]
+    fn test_apply_fix() {
+        let code = "import os\nimport sys\n\nprint(os.getcwd())\n";
+        let fix = Fix {
+            description: "use pathlib".to_string(),
+            edits: vec![
+                edit(
+                    EditType::Update,
+                    (1, 1),
+                    Some((1, 10)),
+                    "from pathlib import Path",
+                ),
+                edit(EditType::Update, (4, 7), Some((4, 18)), "Path.cwd()"),
+            ],
+        };
+        assert_eq!(
+            "from pathlib import Path\nimport sys\n\nprint(Path.cwd())\n",
+            apply_fix(code, &fix, PositionEncoding::Chars).unwrap()
+        );
+
+        // the fix is rejected when its edits overlap
+        let fix = Fix {
+            description: "remove the imports".to_string(),
+            edits: vec![
+                edit(EditType::Remove, (1, 1), Some((2, 1)), ""),
+                edit(EditType::Update, (2, 1), Some((2, 11)), "import pathlib"),
+                edit(EditType::Remove, (1, 8), Some((2, 4)), ""),
+            ],
+        };
+        let error = apply_fix(code, &fix, PositionEncoding::Chars).unwrap_err();
+        assert_eq!("cannot apply fix \"remove the imports\"", error.to_string());
+        assert_eq!(
+            "edits overlap: the edit at 1:8 starts before the end of the edit at 1:1",
+            error.root_cause().to_string()
+        );
+    }
+
+    // a violation can have several fixes with several edits, the rule chooses the alternatives
+    #[test]
+    fn test_apply_fix_alternatives() {
+        let rule_code = r##"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    const call = node.captures["call"];
+    const error = buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                             "do not use eval");
+    const remove = buildEditRemove(call.start.line, call.start.col, call.end.line, call.end.col);
+    const comment = buildEditAdd(call.start.line, 1, "# removed eval\n");
+    const replace = buildEditUpdate(name.start.line, name.start.col, name.end.line, name.end.col,
+                                    "ast.literal_eval");
+    const addImport = buildEditAdd(1, 1, "import ast\n");
+    error.addFix(buildFix("remove the call", [remove, comment]));
+    error.addFix(buildFix("use literal_eval", [replace, addImport]));
+    // a single edit without an array
+    error.addFix(buildFix("remove the call only", remove));
+    addError(error);
+}
+        "##;
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some("(call function: (identifier) @name) @call".to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let code = "x = 1\neval(x)\n";
+        let analysis_options = AnalysisOptions {
+            log_output: false,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::Python,
+            vec![rule],
+            "myfile.py",
+            code,
+            &analysis_options,
+        );
+        let fixes = &results[0].violations[0].fixes;
+        assert_eq!(3, fixes.len());
+        assert_eq!(
+            vec![2, 2, 1],
+            fixes.iter().map(|f| f.edits.len()).collect::<Vec<_>>()
+        );
+
+        let encoding = PositionEncoding::default();
+        assert_eq!(
+            "x = 1\n# removed eval\n\n",
+            apply_fix(code, &fixes[0], encoding).unwrap()
+        );
+        assert_eq!(
+            "import ast\nx = 1\nast.literal_eval(x)\n",
+            apply_fix(code, &fixes[1], encoding).unwrap()
+        );
+    }
+
+    // get the nodes captured by a query, in the order of the code
+    fn get_captures(code: &str, language: &Language, query: &str) -> Vec<TreeSitterNodeSummary> {
+        let tree = get_tree(code, language).unwrap();
+        let query = get_query(query, language).unwrap();
+        get_query_captures(&tree, &query, "node", code)
+            .iter()
+            .map(TreeSitterNode::get_summary)
+            .collect()
+    }
+
+    #[test]
+    fn test_node_edits_python() {
+        let encoding = PositionEncoding::Chars;
+        let code = "x = [\"é\", b, c]\nprint(x)";
+        let elements = get_captures(code, &Language::Python, "(list (_) @node)");
+        let apply = |edit: Edit| apply_edits(code, &[edit], encoding).unwrap();
+
+        assert_eq!(
+            "x = [\"é\", a, b, c]\nprint(x)",
+            apply(Edit::insert_before(&elements[1], "a, "))
+        );
+        assert_eq!(
+            "x = [\"é\", bb, c]\nprint(x)",
+            apply(Edit::replace_node(&elements[1], "bb"))
+        );
+        // the separator after the element, or before the last element
+        assert_eq!(
+            "x = [b, c]\nprint(x)",
+            apply(Edit::delete_node(&elements[0], code, encoding, true))
+        );
+        assert_eq!(
+            "x = [\"é\", b]\nprint(x)",
+            apply(Edit::delete_node(&elements[2], code, encoding, true))
+        );
+        assert_eq!(
+            "x = [, b, c]\nprint(x)",
+            apply(Edit::delete_node(&elements[0], code, encoding, false))
+        );
+
+        // at the end of a line and at the end of the file
+        let statements = get_captures(code, &Language::Python, "(module (_) @node)");
+        assert_eq!(
+            "x = [\"é\", b, c]  # list\nprint(x)",
+            apply(Edit::insert_after(&statements[0], "  # list"))
+        );
+        assert_eq!(
+            "x = [\"é\", b, c]\nprint(x)\nprint(x)",
+            apply(Edit::insert_after(&statements[1], "\nprint(x)"))
+        );
+    }
+
+    #[test]
+    fn test_node_edits_typescript() {
+        let encoding = PositionEncoding::Chars;
+        let code = "const o = { \"😀\": 1, b: 2 };\r\nfoo(o);\r\n";
+        let pairs = get_captures(code, &Language::TypeScript, "(pair) @node");
+        let apply = |edit: Edit| apply_edits(code, &[edit], encoding).unwrap();
+
+        assert_eq!(
+            "const o = { b: 2 };\r\nfoo(o);\r\n",
+            apply(Edit::delete_node(&pairs[0], code, encoding, true))
+        );
+        assert_eq!(
+            "const o = { \"😀\": 1 };\r\nfoo(o);\r\n",
+            apply(Edit::delete_node(&pairs[1], code, encoding, true))
+        );
+        assert_eq!(
+            "const o = { \"😀\": 1, b: 3 };\r\nfoo(o);\r\n",
+            apply(Edit::replace_node(&pairs[1], "b: 3"))
+        );
+
+        let statements = get_captures(code, &Language::TypeScript, "(program (_) @node)");
+        assert_eq!(
+            "// object\r\nconst o = { \"😀\": 1, b: 2 };\r\nfoo(o);\r\n",
+            apply(Edit::insert_before(&statements[0], "// object\n"))
+        );
+        // before the line ending
+        assert_eq!(
+            "const o = { \"😀\": 1, b: 2 };\r\nfoo(o); // call\r\n",
+            apply(Edit::insert_after(&statements[1], " // call"))
+        );
+    }
+
+    // the rules build the edits from the nodes they captured
+    #[test]
+    fn test_node_edits_from_rule() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    const args = node.captures["args"];
+    const error = buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                             "do not use var");
+    const edits = [
+        buildEditReplaceNode(name, "logger.debug"),
+        buildEditDeleteNode(args.children[0], true),
+        buildEditInsertBefore(node.captures["statement"], "// debug\n"),
+    ];
+    addError(error.addFix(buildFix("use the logger", edits)));
+}
+        "#;
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::BestPractices,
+            severity: RuleSeverity::Warning,
+            language: Language::TypeScript,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(
+                r#"
+(expression_statement
+  (call_expression function: (_) @name arguments: (_) @args)) @statement
+"#
+                .to_string(),
+            ),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let code = "console.log(\"é\", x);\n";
+        let analysis_options = AnalysisOptions {
+            log_output: false,
+            use_debug: false,
+            position_encoding: PositionEncoding::Utf8Bytes,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(
+            &Language::TypeScript,
+            vec![rule],
+            "myfile.ts",
+            code,
+            &analysis_options,
+        );
+        assert!(results[0].execution_error.is_none());
+        let fix = &results[0].violations[0].fixes[0];
+        assert_eq!(
+            "// debug\nlogger.debug(x);\n",
+            apply_fix(code, fix, PositionEncoding::Utf8Bytes).unwrap()
+        );
+    }
+
+    // the rules add violations with alternative fixes, only the first one is applied by default
+    #[test]
+    fn test_add_violation_with_fix() {
+        let run = |rule_code: &str, code: &str| {
+            let rule = RuleInternal {
+                name: "myrule".to_string(),
+                short_description: None,
+                description: None,
+                category: RuleCategory::Security,
+                severity: RuleSeverity::Error,
+                language: Language::Python,
+                code: rule_code.to_string(),
+                tree_sitter_query: Some(
+                    "(call function: (identifier) @name (#eq? @name \"eval\")) @call".to_string(),
+                ),
+                variables: HashMap::new(),
+                ..RuleInternal::default()
+            };
+            let analysis_options = AnalysisOptions {
+                log_output: false,
+                use_debug: false,
+                ..AnalysisOptions::default()
+            };
+            analyze(
+                &Language::Python,
+                vec![rule],
+                "myfile.py",
+                code,
+                &analysis_options,
+            )
+            .remove(0)
+        };
+
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    const error = buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                             "do not use eval");
+    addViolationWithFix(error, [
+        {
+            description: "use literal_eval",
+            edits: [
+                buildEditReplaceNode(name, "ast.literal_eval"),
+                buildEditAdd(1, 1, "import ast\n"),
+            ],
+        },
+        {description: "remove the call", edits: [buildEditDeleteNode(node.captures["call"])]},
+        // the end is before the start
+        {
+            description: "invalid range",
+            edits: [
+                buildEditUpdate(name.end.line, name.end.col, name.start.line, name.start.col, ""),
+            ],
+        },
+        // outside of the file
+        {description: "invalid line", edits: [buildEditAdd(100, 1, "x")]},
+        // after the end of the line
+        {description: "invalid column", edits: [buildEditAdd(1, 100, "x")]},
+    ]);
+}
+"#;
+        let code = "x = 1\neval(x)\n";
+        let rule_result = run(rule_code, code);
+        assert!(rule_result.execution_error.is_none());
+        assert_eq!(vec![ERROR_INVALID_FIX.to_string()], rule_result.errors);
+        let violation = &rule_result.violations[0];
+        assert_eq!(
+            vec!["use literal_eval", "remove the call"],
+            violation
+                .fixes
+                .iter()
+                .map(|f| f.description.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        let encoding = PositionEncoding::default();
+        let (fixed, applied) = apply_first_fixes(code, &[violation], encoding).unwrap();
+        assert_eq!(1, applied);
+        assert_eq!("import ast\nx = 1\nast.literal_eval(x)\n", fixed);
+        assert_eq!(
+            "x = 1\n\n",
+            apply_fix(code, &violation.fixes[1], encoding).unwrap()
+        );
+
+        // the fixes of the other violations that conflict with the applied fixes are skipped
+        let mut other = violation.clone();
+        other.fixes.reverse();
+        let (fixed, applied) = apply_first_fixes(code, &[violation, &other], encoding).unwrap();
+        assert_eq!(1, applied);
+        assert_eq!("import ast\nx = 1\nast.literal_eval(x)\n", fixed);
+
+        // the structure of the fixes is checked when the rule adds them
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    const error = buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                             "do not use eval");
+    addViolationWithFix(error, [{description: "no edit type", edits: [{start: name.start}]}]);
+}
+"#;
+        let rule_result = run(rule_code, code);
+        assert!(rule_result.violations.is_empty());
+        assert!(rule_result
+            .exception
+            .unwrap()
+            .message
+            .contains("edit 0 of fix 0 is invalid, use the buildEdit helpers"));
+    }
+}
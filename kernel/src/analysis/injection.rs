@@ -0,0 +1,243 @@
+use crate::analysis::embedded::{get_html_script_blocks, EmbeddedCode};
+use crate::analysis::line_index::LineIndex;
+use crate::analysis::tree_sitter::{get_query, get_query_nodes, get_tree};
+use crate::model::analysis::PositionEncoding;
+use crate::model::common::{Language, Position};
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Name of the capture with the string that contains the code and of the setting with the
+// language of the code, like in the injection queries of tree-sitter.
+const INJECTION_CONTENT_CAPTURE: &str = "injection.content";
+const INJECTION_LANGUAGE_SETTING: &str = "injection.language";
+// We do not have a grammar for HTML, the scripts of the HTML strings are analyzed as JavaScript.
+const INJECTION_LANGUAGE_HTML: &str = "html";
+
+// The languages of the code found in the strings: SQL queries and the scripts of HTML strings.
+static INJECTED_LANGUAGES: &[Language] = &[Language::JavaScript, Language::Sql];
+
+const PYTHON_INJECTION_QUERY: &str = r#"
+((string) @injection.content
+  (#match? @injection.content "^[rRuUbBfF]*(\"\"\"|'''|\"|')\\s*(?i:select|insert|update|delete|with|create|alter|drop)\\b")
+  (#set! injection.language "sql"))
+
+((string) @injection.content
+  (#match? @injection.content "^[rRuUbBfF]*(\"\"\"|'''|\"|')\\s*<[a-zA-Z!]")
+  (#set! injection.language "html"))
+"#;
+
+const JAVASCRIPT_INJECTION_QUERY: &str = r#"
+([(string) (template_string)] @injection.content
+  (#match? @injection.content "^(\"|'|`)\\s*(?i:select|insert|update|delete|with|create|alter|drop)\\b")
+  (#set! injection.language "sql"))
+
+([(string) (template_string)] @injection.content
+  (#match? @injection.content "^(\"|'|`)\\s*<[a-zA-Z!]")
+  (#set! injection.language "html"))
+"#;
+
+// Queries to find the code of other languages in the strings of a language. The captured
+// strings include their quotes and prefixes.
+static INJECTION_QUERIES_PER_LANGUAGE_LIST: &[(Language, &str)] = &[
+    (Language::JavaScript, JAVASCRIPT_INJECTION_QUERY),
+    (Language::Python, PYTHON_INJECTION_QUERY),
+    (Language::TypeScript, JAVASCRIPT_INJECTION_QUERY),
+];
+
+// Get the byte range of the content of a string literal, between its prefix (e.g. `f`, `rb`)
+// and its quotes.
+fn get_string_content(literal: &str) -> Option<Range<usize>> {
+    let content = literal.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    let prefix_len = literal.len() - content.len();
+    let quote = ["\"\"\"", "'''", "\"", "'", "`"]
+        .into_iter()
+        .find(|q| content.starts_with(q))?;
+    let start = prefix_len + quote.len();
+    let end = literal.len().checked_sub(quote.len())?;
+    if end < start || !literal.ends_with(quote) {
+        return None;
+    }
+    Some(start..end)
+}
+
+// Check if the strings of a host language can contain code of a language, so that the files of
+// the host language are analyzed for that language too.
+pub fn has_injected_code(host_language: &Language, language: &Language) -> bool {
+    INJECTED_LANGUAGES.contains(language)
+        && INJECTION_QUERIES_PER_LANGUAGE_LIST
+            .iter()
+            .any(|(l, _)| l == host_language)
+}
+
+// Get the code of a language in the strings of the code of the host language (e.g. the SQL
+// queries of a Python file or the scripts of its HTML strings). The columns of the code are
+// counted in the encoding. Returns None when the host language has no injections.
+pub fn get_injected_code(
+    host_language: &Language,
+    language: &Language,
+    code: &str,
+    encoding: PositionEncoding,
+) -> Option<Vec<EmbeddedCode>> {
+    let (_, query_code) = INJECTION_QUERIES_PER_LANGUAGE_LIST
+        .iter()
+        .find(|(l, _)| l == host_language)?;
+    let (Some(tree), Ok(query)) = (
+        get_tree(code, host_language),
+        get_query(query_code, host_language),
+    ) else {
+        return Some(vec![]);
+    };
+
+    let line_index = LineIndex::new(code);
+    let blocks = get_query_nodes(&tree, &query, "", code, &HashMap::new())
+        .into_iter()
+        .filter_map(|match_node| {
+            let name = match_node.settings.get(INJECTION_LANGUAGE_SETTING)?;
+            let node = match_node.captures.get(INJECTION_CONTENT_CAPTURE)?;
+            let content = get_string_content(&code[node.start_byte..node.end_byte])?;
+            let content_start = node.start_byte + content.start;
+            let start = line_index.get_position(content_start, encoding);
+            let string = EmbeddedCode {
+                language: *host_language,
+                code: code[content_start..node.start_byte + content.end].to_string(),
+                start_line: start.line,
+                start_col: start.col,
+            };
+            if name == INJECTION_LANGUAGE_HTML {
+                return Some(get_html_string_scripts(&string, encoding));
+            }
+            let language = Language::from_name(name)?;
+            Some(vec![EmbeddedCode { language, ..string }])
+        })
+        .flatten()
+        .filter(|block| block.language == *language)
+        .collect();
+    Some(blocks)
+}
+
+// Get the scripts of an HTML string, with their position in the file of the string.
+fn get_html_string_scripts(string: &EmbeddedCode, encoding: PositionEncoding) -> Vec<EmbeddedCode> {
+    get_html_script_blocks(&string.code, encoding)
+        .into_iter()
+        .map(|script| {
+            let start = string.map_position(&Position {
+                line: script.start_line,
+                col: script.start_col,
+                cell: None,
+            });
+            EmbeddedCode {
+                start_line: start.line,
+                start_col: start.col,
+                ..script
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENCODING: PositionEncoding = PositionEncoding::Chars;
+
+    #[test]
+    fn test_get_string_content() {
+        assert_eq!(Some(1..7), get_string_content("\"SELECT\""));
+        assert_eq!(Some(2..8), get_string_content("f'SELECT'"));
+        assert_eq!(Some(5..11), get_string_content("rb\"\"\"SELECT\"\"\""));
+        assert_eq!(Some(1..7), get_string_content("`SELECT`"));
+        assert_eq!(Some(1..1), get_string_content("\"\""));
+        assert_eq!(None, get_string_content("\"SELECT"));
+        assert_eq!(None, get_string_content("SELECT"));
+    }
+
+    #[test]
+    fn test_get_injected_code_python() {
+        let code = r#"
+def get_users(db):
+    db.execute("SELECT * FROM users")
+    print("selected users")
+    return db.execute("""
+        SELECT name
+        FROM users
+    """)
+"#;
+        let blocks = get_injected_code(&Language::Python, &Language::Sql, code, ENCODING).unwrap();
+        assert_eq!(2, blocks.len());
+        assert!(blocks.iter().all(|b| b.language == Language::Sql));
+        assert_eq!("SELECT * FROM users", blocks[0].code);
+        assert_eq!((3, 17), (blocks[0].start_line, blocks[0].start_col));
+        assert_eq!(
+            "\n        SELECT name\n        FROM users\n    ",
+            blocks[1].code
+        );
+        assert_eq!((5, 26), (blocks[1].start_line, blocks[1].start_col));
+
+        // only the code of the language is returned
+        let blocks =
+            get_injected_code(&Language::Python, &Language::JavaScript, code, ENCODING).unwrap();
+        assert!(blocks.is_empty());
+    }
+
+    #[test]
+    fn test_get_injected_code_javascript() {
+        let code = "const q = `select * from users where id = ${id}`;\nconst s = 'selected';\n";
+        let blocks =
+            get_injected_code(&Language::JavaScript, &Language::Sql, code, ENCODING).unwrap();
+        assert_eq!(1, blocks.len());
+        assert_eq!("select * from users where id = ${id}", blocks[0].code);
+        assert_eq!((1, 12), (blocks[0].start_line, blocks[0].start_col));
+
+        // languages without injections
+        assert!(get_injected_code(&Language::Go, &Language::Sql, code, ENCODING).is_none());
+    }
+
+    // the scripts of the HTML strings are JavaScript
+    #[test]
+    fn test_get_injected_code_html() {
+        let code = r#"
+const page = `<div>
+  <script>eval(input);</script>
+</div>`;
+"#;
+        let blocks =
+            get_injected_code(&Language::TypeScript, &Language::JavaScript, code, ENCODING)
+                .unwrap();
+        assert_eq!(1, blocks.len());
+        assert_eq!("eval(input);", blocks[0].code);
+        assert_eq!((3, 11), (blocks[0].start_line, blocks[0].start_col));
+
+        let code = "page = \"<script>eval(input);</script>\"\n";
+        let blocks =
+            get_injected_code(&Language::Python, &Language::JavaScript, code, ENCODING).unwrap();
+        assert_eq!(1, blocks.len());
+        assert_eq!("eval(input);", blocks[0].code);
+        assert_eq!((1, 17), (blocks[0].start_line, blocks[0].start_col));
+    }
+
+    // the columns of the code are counted in the encoding, like the columns of the violations
+    #[test]
+    fn test_get_injected_code_encoding() {
+        let code = "q = ('é', 'SELECT 1')\n";
+        let get_start = |encoding| {
+            let blocks = get_injected_code(&Language::Python, &Language::Sql, code, encoding);
+            let block = &blocks.unwrap()[0];
+            (block.start_line, block.start_col)
+        };
+        assert_eq!((1, 12), get_start(PositionEncoding::Chars));
+        assert_eq!((1, 12), get_start(PositionEncoding::Utf16));
+        assert_eq!((1, 13), get_start(PositionEncoding::Utf8Bytes));
+    }
+
+    #[test]
+    fn test_has_injected_code() {
+        assert!(has_injected_code(&Language::Python, &Language::Sql));
+        assert!(has_injected_code(&Language::Python, &Language::JavaScript));
+        assert!(has_injected_code(
+            &Language::TypeScript,
+            &Language::JavaScript
+        ));
+        assert!(!has_injected_code(&Language::Python, &Language::Go));
+        assert!(!has_injected_code(&Language::Go, &Language::Sql));
+    }
+}
@@ -1,33 +1,310 @@
+use crate::analysis::js_helpers::HELPERS_CODE;
+use crate::analysis::line_index::LineIndex;
+use crate::analysis::tree_sitter::{
+    get_node_ancestors, get_node_in_tree, get_query_for_grammar, get_query_nodes_in_node, Grammar,
+};
 use crate::model::analysis::{
-    AnalysisOptions, MatchNode, ERROR_RULE_CODE_TOO_BIG, ERROR_RULE_EXECUTION, ERROR_RULE_TIMEOUT,
+    AnalysisOptions, CommentInfo, MatchNode, QueryNodesOptions, TreeSitterNodeSummary,
+    ERROR_RULE_CODE_TOO_BIG, ERROR_RULE_EXECUTION, ERROR_RULE_MATCHES_TRUNCATED,
+    ERROR_RULE_OUT_OF_MEMORY, ERROR_RULE_TIMEOUT, NODE_SCHEMA_VERSION,
+};
+use crate::model::common::Position;
+use crate::model::rule::{RuleExecutionError, RuleInternal, RuleResult};
+use crate::model::violation::{Edit, Fix, RelatedLocation, Violation};
+use anyhow::anyhow;
+use deno_core::error::{AnyError, JsError};
+use deno_core::{
+    op2, v8, FastString, JsRuntime, JsRuntimeForSnapshot, OpState, RuntimeOptions, Snapshot,
 };
-use crate::model::rule::{RuleInternal, RuleResult};
-use crate::model::violation::Violation;
-use deno_core::{v8, FastString, JsRuntime, JsRuntimeForSnapshot, RuntimeOptions, Snapshot};
-use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{mpsc, Arc};
 use std::thread;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 
-// how long a rule can execute before it's a timeout.
+// how long a rule can execute before it's a timeout, when neither the rule nor the analysis
+// set a timeout.
 const JAVASCRIPT_EXECUTION_TIMEOUT_MS: u64 = 5000;
 
+// how much memory the JavaScript code of a rule can use on a file, when the analysis does not
+// set a limit
+const JAVASCRIPT_MEMORY_LIMIT_MB: u64 = 512;
+
+// the largest memory limit of a rule, the larger limits are lowered to it so that the heap limit
+// in bytes does not overflow
+const JAVASCRIPT_MAX_MEMORY_LIMIT_MB: u64 = 16 * 1024;
+
+// how many queries the code of a rule can run on a file, to stop the rules that query the
+// matches of their queries without end
+const MAX_RULE_QUERIES: usize = 1000;
+
+// how many rules are compiled in the runtime of a thread before the runtime is created again,
+// for the servers that get new rules all the time
+const MAX_RUNTIME_RULES: usize = 1000;
+
+// the code of a rule starts after this number of lines in the script that compiles it
+const RULE_CODE_LINE_OFFSET: usize = 1;
+
+// The tree of the analyzed file, for the queries run by the code of the rules.
+pub struct RuleTree {
+    pub tree: tree_sitter::Tree,
+    // the grammar of the tree, the queries of the rule are built with it
+    pub grammar: Grammar,
+    pub filename: String,
+    pub code: String,
+    // how the nodes of the queries are mapped, like the nodes of the query of the rule
+    pub options: QueryNodesOptions,
+}
+
+// The queries run by the code of a rule on a file. They share the time of the execution of
+// the rule.
+struct RuleQueries {
+    deadline: Instant,
+    count: usize,
+    // a query of the rule did not return all its matches
+    is_truncated: bool,
+}
+
+// Run a query in a node captured by the rule (e.g. to find the methods of a captured class), or
+// in the whole file when there is no node. The positions of the matches are in the file.
+#[op2]
+#[serde]
+fn op_query_nodes_in_node(
+    state: &mut OpState,
+    #[string] query_code: String,
+    #[serde] node: Option<TreeSitterNodeSummary>,
+) -> Result<Vec<MatchNode>, AnyError> {
+    if let Some(queries) = state.try_borrow_mut::<RuleQueries>() {
+        queries.count += 1;
+        if queries.count > MAX_RULE_QUERIES {
+            return Err(anyhow!(
+                "too many queries, a rule can run {} queries on a file",
+                MAX_RULE_QUERIES
+            ));
+        }
+    }
+    let deadline = state.try_borrow::<RuleQueries>().map(|q| q.deadline);
+    let rule_tree = state
+        .try_borrow::<RuleTree>()
+        .ok_or_else(|| anyhow!("the tree of the file is not available"))?;
+    let query = get_query_for_grammar(&query_code, rule_tree.grammar)?;
+    let tree_node = match &node {
+        Some(node) => get_node_in_tree(&rule_tree.tree, node)
+            .ok_or_else(|| anyhow!("node {} not found in the tree", node.ast_type))?,
+        None => rule_tree.tree.root_node(),
+    };
+    let options = QueryNodesOptions {
+        deadline: rule_tree.options.deadline.into_iter().chain(deadline).min(),
+        ..rule_tree.options.clone()
+    };
+    let query_nodes = get_query_nodes_in_node(
+        tree_node,
+        &query,
+        &rule_tree.filename,
+        &rule_tree.code,
+        &HashMap::new(),
+        &options,
+    );
+    if query_nodes.is_timed_out {
+        return Err(anyhow!("the query timed out"));
+    }
+    if query_nodes.is_truncated {
+        if let Some(queries) = state.try_borrow_mut::<RuleQueries>() {
+            queries.is_truncated = true;
+        }
+    }
+    Ok(query_nodes.match_nodes)
+}
+
+// The byte range of each line of the code of the file, without its line ending. Computed at
+// the first call from the rule and kept for the next calls.
+struct SourceLines(Vec<Range<usize>>);
+
+// Get the text of the lines of the file from `start` to `end` (starting at 1, included), the
+// lines after the end of the file are not returned.
+fn get_source_lines(state: &mut OpState, start: u32, end: u32) -> Result<Vec<String>, AnyError> {
+    if !state.has::<SourceLines>() {
+        let code = &state
+            .try_borrow::<RuleTree>()
+            .ok_or_else(|| anyhow!("the code of the file is not available"))?
+            .code;
+        let line_index = LineIndex::new(code);
+        let lines = (0..line_index.line_count())
+            .filter_map(|row| {
+                let range = line_index.get_line_range(row)?;
+                let line_len = line_index.get_line(row)?.len();
+                Some(range.start..range.start + line_len)
+            })
+            .collect();
+        state.put(SourceLines(lines));
+    }
+    let code = &state.borrow::<RuleTree>().code;
+    let SourceLines(lines) = state.borrow::<SourceLines>();
+    let start = start.max(1) as usize - 1;
+    let end = (end as usize).min(lines.len());
+    Ok(lines
+        .get(start..end)
+        .unwrap_or_default()
+        .iter()
+        .map(|range| code[range.clone()].to_string())
+        .collect())
+}
+
+// Get the text of a line of the file (starting at 1), without its line ending
+#[op2]
+#[string]
+fn op_get_line(state: &mut OpState, line: u32) -> Result<Option<String>, AnyError> {
+    Ok(get_source_lines(state, line, line)?.pop())
+}
+
+// Get the text of the lines of the file from `start` to `end` (starting at 1, included)
+#[op2]
+#[serde]
+fn op_get_lines(state: &mut OpState, start: u32, end: u32) -> Result<Vec<String>, AnyError> {
+    get_source_lines(state, start, end)
+}
+
+// Get the code of a node with its byte offsets, None when the rule runs without the code of the
+// file
+#[op2]
+#[string]
+fn op_get_code_for_node(
+    state: &mut OpState,
+    #[serde] node: TreeSitterNodeSummary,
+) -> Result<Option<String>, AnyError> {
+    let Some(rule_tree) = state.try_borrow::<RuleTree>() else {
+        return Ok(None);
+    };
+    rule_tree
+        .code
+        .get(node.start_byte..node.end_byte)
+        .map(|code| Some(code.to_string()))
+        .ok_or_else(|| anyhow!("node {} is not in the code of the file", node.ast_type))
+}
+
+// Get the ancestors of a node, from its parent to the root of the tree, for the rules that do
+// not include them in the captured nodes. Empty when the rule runs without the tree of the file.
+#[op2]
+#[serde]
+fn op_get_node_ancestors(
+    state: &mut OpState,
+    #[serde] node: TreeSitterNodeSummary,
+) -> Result<Vec<TreeSitterNodeSummary>, AnyError> {
+    let Some(rule_tree) = state.try_borrow::<RuleTree>() else {
+        return Ok(vec![]);
+    };
+    get_node_ancestors(
+        &rule_tree.tree,
+        &node,
+        &rule_tree.code,
+        rule_tree.options.map_node_options.position_encoding,
+    )
+    .ok_or_else(|| anyhow!("node {} not found in the tree", node.ast_type))
+}
+
+// Build an edit from the range of a node (e.g. to replace a captured node)
+#[op2]
+#[serde]
+fn op_node_edit(
+    state: &mut OpState,
+    #[string] edit_kind: String,
+    #[serde] node: TreeSitterNodeSummary,
+    #[string] content: String,
+    consume_separator: bool,
+) -> Result<Edit, AnyError> {
+    match edit_kind.as_str() {
+        "INSERT_BEFORE" => Ok(Edit::insert_before(&node, &content)),
+        "INSERT_AFTER" => Ok(Edit::insert_after(&node, &content)),
+        "REPLACE" => Ok(Edit::replace_node(&node, &content)),
+        "DELETE" => {
+            let rule_tree = state
+                .try_borrow::<RuleTree>()
+                .ok_or_else(|| anyhow!("the code of the file is not available"))?;
+            Ok(Edit::delete_node(
+                &node,
+                &rule_tree.code,
+                rule_tree.options.map_node_options.position_encoding,
+                consume_separator,
+            ))
+        }
+        _ => Err(anyhow!("unknown edit {}", edit_kind)),
+    }
+}
+
+deno_core::extension!(
+    stella,
+    ops = [
+        op_query_nodes_in_node,
+        op_get_line,
+        op_get_lines,
+        op_get_code_for_node,
+        op_get_node_ancestors,
+        op_node_edit
+    ]
+);
+
 lazy_static! {
     static ref STARTUP_DATA: Vec<u8> = {
         let code: FastString = FastString::from_static(include_str!("./js/stella.js"));
-        let mut rt = JsRuntimeForSnapshot::new(RuntimeOptions::default());
+        let mut rt = JsRuntimeForSnapshot::new(RuntimeOptions {
+            extensions: vec![stella::init_ops()],
+            ..Default::default()
+        });
         rt.execute_script("common_js", code).unwrap();
+        // the helpers are in the snapshot, the rules use them without loading anything
+        rt.execute_script("helpers_js", FastString::from_static(HELPERS_CODE))
+            .unwrap();
         rt.snapshot().to_vec()
     };
 }
 
+// A violation reported by the code of a rule. The severity and the category are set when the
+// rule overrides the ones of the rule for this violation.
+#[derive(Deserialize, Debug, Serialize, Clone)]
+struct StellaViolation {
+    start: Position,
+    end: Position,
+    message: String,
+    #[serde(default)]
+    severity: Option<String>,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    fixes: Vec<Fix>,
+    #[serde(rename = "relatedLocations", default)]
+    related_locations: Vec<RelatedLocation>,
+}
+
 // This structure is what is returned by the JavaScript code
 #[derive(Deserialize, Debug, Serialize, Clone)]
 struct StellaExecution {
-    violations: Vec<Violation>, // the list of violations returned by the rule
-    console: Vec<String>,       // the log lines from console.log
+    violations: Vec<StellaViolation>, // the list of violations returned by the rule
+    console: Vec<String>,             // the log lines from console.log
+}
+
+// Get the value of a violation that overrides the value of the rule. An invalid value is not an
+// error: the value of the rule is used and a warning is added to the output of the rule.
+fn get_override<T: FromStr<Err = anyhow::Error>>(
+    value: Option<&str>,
+    default: T,
+    warnings: &mut Vec<String>,
+) -> T {
+    match value.map(str::parse::<T>) {
+        Some(Ok(value)) => value,
+        Some(Err(e)) => {
+            warnings.push(format!("warning: {}, the value of the rule is used", e));
+            default
+        }
+        None => default,
+    }
 }
 
 // execute a rule. It is the exposed function to execute a rule and start the underlying
@@ -38,204 +315,379 @@ pub fn execute_rule(
     filename: String,
     analysis_options: AnalysisOptions,
 ) -> RuleResult {
-    let rule_name_copy = rule.name.clone();
-    let filename_copy = filename.clone();
-    let rule_name_copy_thr = rule.name.clone();
-    let filename_copy_thr = filename.clone();
-    let use_debug = analysis_options.use_debug;
-    let start = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_millis();
-    // These mutexes and condition variables are used to wait on the execution
-    // and have a proper timeout.
-    let condvar_main = Arc::new((Mutex::new(()), Condvar::new()));
-    let condvar_thread = Arc::clone(&condvar_main);
-
-    // to send the handle of the JS runtime to terminate the runtime
-    let (tx_runtime, rx_runtime) = mpsc::channel();
-    // To send the result once the execution is complete.
-    let (tx_result, rx_result) = mpsc::channel();
-    let (lock, cvar) = &*condvar_main;
+    execute_rule_with_tree(rule, match_nodes, filename, analysis_options, None)
+}
 
-    let started = lock.lock().expect("should lock mutex");
+// The runtime of the rules on a thread. It is created from the snapshot once and reused for all
+// the files: only the matches of the file are sent to the runtime on each execution, and
+// stella.js restores the global object between the files. A runtime that timed out or ran out
+// of memory is dropped, the next execution creates a new one.
+struct RuleRuntime {
+    runtime: JsRuntime,
+    memory_limit_mb: u64,
+    out_of_memory: Arc<AtomicBool>,
+    // the index of the code of each rule in `stellaRuleFactories`
+    rule_factories: HashMap<String, usize>,
+}
 
-    thread::spawn(move || {
+impl RuleRuntime {
+    fn new(memory_limit_mb: u64) -> RuleRuntime {
+        let heap_limit = memory_limit_mb
+            .checked_mul(1024 * 1024)
+            .and_then(|bytes| usize::try_from(bytes).ok())
+            .unwrap_or(usize::MAX);
         let mut runtime = JsRuntime::new(RuntimeOptions {
             startup_snapshot: Some(Snapshot::Static(&STARTUP_DATA)),
+            extensions: vec![stella::init_ops()],
+            create_params: Some(v8::CreateParams::default().heap_limits(0, heap_limit)),
             ..Default::default()
         });
 
-        let handle = runtime.v8_isolate().thread_safe_handle();
-
-        assert!(
-            tx_runtime.send(handle).is_ok(),
-            "we should be able to send the handle to the main thread"
-        );
+        // The execution is stopped when the heap is almost full. The limit is raised so that
+        // V8 stops the execution of the rule instead of aborting the process.
+        let out_of_memory = Arc::new(AtomicBool::new(false));
+        let out_of_memory_callback = Arc::clone(&out_of_memory);
+        let memory_handle = runtime.v8_isolate().thread_safe_handle();
+        runtime.add_near_heap_limit_callback(move |current_limit, _initial_limit| {
+            out_of_memory_callback.store(true, Ordering::SeqCst);
+            memory_handle.terminate_execution();
+            current_limit * 2
+        });
+        RuleRuntime {
+            runtime,
+            memory_limit_mb,
+            out_of_memory,
+            rule_factories: HashMap::new(),
+        }
+    }
+}
 
-        let (mutex, cvar) = &*condvar_thread;
+enum WatchdogMessage {
+    // a rule starts on the isolate and is terminated after the duration
+    Start(v8::IsolateHandle, Duration),
+    // the rule stopped
+    Done,
+}
 
-        // execute the rule and return
-        let res = execute_rule_internal(
-            &mut runtime,
-            &rule,
-            &match_nodes,
-            filename,
-            &analysis_options,
-        );
+// The thread that terminates the rules of a worker thread when they time out. There is one
+// watchdog for each thread that runs rules, it waits for the executions of the rules one after
+// the other and stops when the thread stops.
+struct Watchdog {
+    messages: mpsc::Sender<WatchdogMessage>,
+    timeouts: mpsc::Receiver<bool>,
+}
 
-        // send the result back
-        let send_result_result = tx_result.send(Some(res));
-        if use_debug && send_result_result.is_err() {
-            eprintln!(
-                "rule {}:{} - error when sending results",
-                rule_name_copy_thr.clone(),
-                filename_copy_thr.clone()
-            );
-        }
+impl Watchdog {
+    fn new() -> Watchdog {
+        let (messages, rx_messages) = mpsc::channel();
+        let (tx_timeouts, timeouts) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(WatchdogMessage::Start(handle, timeout)) = rx_messages.recv() {
+                let timed_out = match rx_messages.recv_timeout(timeout) {
+                    Err(RecvTimeoutError::Timeout) => {
+                        handle.terminate_execution();
+                        // the result is sent once the rule stopped
+                        let _ = rx_messages.recv();
+                        true
+                    }
+                    _ => false,
+                };
+                if tx_timeouts.send(timed_out).is_err() {
+                    break;
+                }
+            }
+        });
+        Watchdog { messages, timeouts }
+    }
 
-        let _ = mutex.lock();
-        // notify the main thread we are done with the execution
-        cvar.notify_one();
-    });
+    // run the execution of a rule, terminated when it takes longer than the timeout. Returns
+    // the value of the execution and if it timed out.
+    fn run<T>(
+        &self,
+        handle: v8::IsolateHandle,
+        timeout: Duration,
+        execution: impl FnOnce() -> T,
+    ) -> (T, bool) {
+        let _ = self.messages.send(WatchdogMessage::Start(handle, timeout));
+        let value = execution();
+        let _ = self.messages.send(WatchdogMessage::Done);
+        // a watchdog that stopped cannot stop the rules, the runtime is not reused
+        let timed_out = self.timeouts.recv().unwrap_or(true);
+        (value, timed_out)
+    }
+}
 
-    let handle = rx_runtime.recv();
+thread_local! {
+    static RULE_RUNTIME: RefCell<Option<RuleRuntime>> = const { RefCell::new(None) };
+    static WATCHDOG: Watchdog = Watchdog::new();
+}
 
-    // Wait for the rule to execute. If the rule times out, we return a specific RuleResult
-    let cond_result = cvar.wait_timeout(
-        started,
-        Duration::from_millis(JAVASCRIPT_EXECUTION_TIMEOUT_MS),
+// execute a rule that can run queries on the tree of the file
+pub fn execute_rule_with_tree(
+    rule: RuleInternal,
+    mut match_nodes: Vec<MatchNode>,
+    filename: String,
+    analysis_options: AnalysisOptions,
+    rule_tree: Option<RuleTree>,
+) -> RuleResult {
+    let use_debug = analysis_options.use_debug;
+    // the timeout of the rule wins over the timeout of the analysis
+    let timeout = Duration::from_millis(
+        rule.timeout_ms
+            .or(analysis_options.rule_timeout_ms)
+            .unwrap_or(JAVASCRIPT_EXECUTION_TIMEOUT_MS),
     );
+    let memory_limit_mb = analysis_options
+        .rule_memory_limit_mb
+        .unwrap_or(JAVASCRIPT_MEMORY_LIMIT_MB)
+        .clamp(1, JAVASCRIPT_MAX_MEMORY_LIMIT_MB);
+    let start = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+
+    // the rule visits the matches in the order of the code, the sort is stable so the matches
+    // that start at the same position keep the order of the query
+    match_nodes.sort_by(|a, b| a.match_start.cmp(&b.match_start));
+    // the comments of the file are shared by all the matches, they are sent once to the rule
+    let comments = match_nodes
+        .iter_mut()
+        .map(|n| std::mem::take(&mut n.context.comments))
+        .find(|c| !c.is_empty())
+        .unwrap_or_default();
+
+    // the runtime of the thread is reused, unless it has another memory limit or too many rules
+    let mut rule_runtime = RULE_RUNTIME
+        .with(|r| r.borrow_mut().take())
+        .filter(|r| {
+            r.memory_limit_mb == memory_limit_mb && r.rule_factories.len() < MAX_RUNTIME_RULES
+        })
+        .unwrap_or_else(|| RuleRuntime::new(memory_limit_mb));
+    if let Some(rule_tree) = rule_tree {
+        let op_state = rule_runtime.runtime.op_state();
+        let mut op_state = op_state.borrow_mut();
+        op_state.put(rule_tree);
+        // the queries of the rule stop when the rule times out
+        op_state.put(RuleQueries {
+            deadline: Instant::now() + timeout,
+            count: 0,
+            is_truncated: false,
+        });
+    }
+
+    // the rule runs on this thread, the watchdog of the thread terminates it when it times out
+    let handle = rule_runtime.runtime.v8_isolate().thread_safe_handle();
+    let (result, timed_out) = WATCHDOG.with(|watchdog| {
+        watchdog.run(handle, timeout, || {
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                execute_rule_internal(
+                    &mut rule_runtime,
+                    &rule,
+                    &match_nodes,
+                    &comments,
+                    filename.clone(),
+                    &analysis_options,
+                )
+            }))
+        })
+    });
 
-    // terminate javascript execution so that the thread we started is stopping
-    handle
-        .expect("should have a javascript handler")
-        .terminate_execution();
     let end = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_millis();
     let execution_time_ms = end - start;
 
-    // drop the thread. Note that it does not terminate the thread, it just put
-    // it out of scope.
-    match cond_result {
-        Ok(v) => {
-            if v.1.timed_out() {
-                if use_debug {
-                    eprintln!(
-                        "[{}] rule:file {}:{} TIMED OUT, execution time: {} ms",
-                        end,
-                        rule_name_copy.as_str(),
-                        filename_copy.as_str(),
-                        execution_time_ms
-                    );
-                }
-                RuleResult {
-                    rule_name: rule_name_copy,
-                    filename: filename_copy,
-                    violations: vec![],
-                    errors: vec![ERROR_RULE_TIMEOUT.to_string()],
-                    execution_error: None,
-                    output: None,
-                    execution_time_ms,
-                }
-            } else if let Some(res) = rx_result.try_recv().unwrap_or(None) {
-                RuleResult {
-                    rule_name: res.rule_name,
-                    filename: res.filename,
-                    violations: res.violations,
-                    errors: res.errors,
-                    execution_error: res.execution_error,
-                    execution_time_ms,
-                    output: res.output,
-                }
-            } else {
-                RuleResult {
-                    rule_name: rule_name_copy,
-                    filename: filename_copy,
-                    violations: vec![],
-                    errors: vec![ERROR_RULE_EXECUTION.to_string()],
-                    execution_error: None,
-                    output: None,
-                    execution_time_ms,
-                }
+    match result {
+        _ if timed_out => {
+            if use_debug {
+                eprintln!(
+                    "[{}] rule:file {}:{} TIMED OUT, execution time: {} ms",
+                    end,
+                    rule.name.as_str(),
+                    filename.as_str(),
+                    execution_time_ms
+                );
+            }
+            RuleResult {
+                rule_name: rule.name,
+                filename,
+                violations: vec![],
+                errors: vec![ERROR_RULE_TIMEOUT.to_string()],
+                execution_error: None,
+                output: None,
+                execution_time_ms,
+                query_time_ms: 0,
+                exception: None,
+            }
+        }
+        Ok(res) if rule_runtime.out_of_memory.load(Ordering::SeqCst) => RuleResult {
+            violations: vec![],
+            errors: vec![ERROR_RULE_OUT_OF_MEMORY.to_string()],
+            execution_error: Some(format!("the rule used more than {memory_limit_mb} MB")),
+            output: None,
+            execution_time_ms,
+            exception: None,
+            ..res
+        },
+        Ok(mut res) => {
+            // the tree of the file is not kept for the next file
+            let queries = {
+                let op_state = rule_runtime.runtime.op_state();
+                let mut op_state = op_state.borrow_mut();
+                op_state.try_take::<RuleTree>();
+                op_state.try_take::<SourceLines>();
+                op_state.try_take::<RuleQueries>()
+            };
+            RULE_RUNTIME.with(|r| *r.borrow_mut() = Some(rule_runtime));
+            if queries.map(|q| q.is_truncated).unwrap_or(false) {
+                res.errors.push(ERROR_RULE_MATCHES_TRUNCATED.to_string());
+            }
+            RuleResult {
+                execution_time_ms,
+                query_time_ms: 0,
+                ..res
             }
         }
         Err(_) => RuleResult {
-            rule_name: rule_name_copy,
-            filename: filename_copy,
+            rule_name: rule.name,
+            filename,
             violations: vec![],
             errors: vec![ERROR_RULE_EXECUTION.to_string()],
             execution_error: None,
             output: None,
             execution_time_ms,
+            query_time_ms: 0,
+            exception: None,
         },
     }
 }
 
-// execute a rule with deno. It creates the JavaScript runtimes and execute
-// the JavaScript code. In the JavaScript code, the last value is what is evaluated
-// and ultimately being deserialized into a `StellaExecution` struct.
+// The result of a rule whose code is too big for the JavaScript engine.
+//
+// We cannot have strings that are  too long. Otherwise, the underlying
+// JS engine crashes. So for now, we just return an error if the code is too big/large.
+//
+// See https://github.com/denoland/deno/issues/19638 for more details. Once the issue
+// is resolved, we can remove it and errors will be detected in runtime.execute_script()
+fn get_code_too_big_result(rule: &RuleInternal, filename: String) -> RuleResult {
+    RuleResult {
+        rule_name: rule.name.clone(),
+        filename,
+        violations: vec![],
+        errors: vec![ERROR_RULE_CODE_TOO_BIG.to_string()],
+        execution_error: Some(ERROR_RULE_CODE_TOO_BIG.to_string()),
+        output: None,
+        execution_time_ms: 0,
+        query_time_ms: 0,
+        exception: None,
+    }
+}
+
+// The result of a rule whose code threw an exception, or that does not compile.
+fn get_exception_result(
+    rule: &RuleInternal,
+    filename: String,
+    error: AnyError,
+    analysis_options: &AnalysisOptions,
+) -> RuleResult {
+    if analysis_options.use_debug {
+        println!(
+            "error when executing the rule {} on file {}, message: {}",
+            rule.name, filename, error
+        );
+    }
+
+    // the message stops at the frames of the code that runs the rule
+    let err_str = error.to_string();
+    let error_message = ["at rule_code", "at common_js", "at rule_execution"]
+        .iter()
+        .filter_map(|frame| err_str.find(frame))
+        .min()
+        .map_or_else(|| err_str.clone(), |pos| err_str[..pos].to_string());
+    let exception = get_rule_exception(rule, &filename, &error, RULE_CODE_LINE_OFFSET);
+
+    RuleResult {
+        rule_name: rule.name.clone(),
+        filename,
+        violations: vec![],
+        errors: vec![ERROR_RULE_EXECUTION.to_string()],
+        execution_error: Some(error_message),
+        output: None,
+        execution_time_ms: 0,
+        query_time_ms: 0,
+        exception: Some(exception),
+    }
+}
+
+// execute a rule with deno. The code of the rule is compiled once in the runtime, as a function
+// called on each file so that the variables declared by the rule are new on each file. Then
+// `stellaExecute` runs the rule on the matches and its value is deserialized into a
+// `StellaExecution` struct.
 //
 // This is the internal code only, the rule used by the code uses
 // `execute_rule`.
 //
 // # Errors
 // Errors are reported in the `RuleResult` structure, in the executionError.
-pub fn execute_rule_internal(
-    runtime: &mut JsRuntime,
+fn execute_rule_internal(
+    rule_runtime: &mut RuleRuntime,
     rule: &RuleInternal,
     match_nodes: &[MatchNode],
+    comments: &[CommentInfo],
     filename: String,
     analysis_options: &AnalysisOptions,
 ) -> RuleResult {
-    let nodes_json: String = serde_json::to_string(match_nodes).unwrap();
-
-    // format the JavaScript code that will be executed
-    let js_code = format!(
-        r#"
-const filename = "{}";
-
+    let factory_id = match rule_runtime.rule_factories.get(&rule.code) {
+        Some(factory_id) => *factory_id,
+        None => {
+            let factory_id = rule_runtime.rule_factories.len();
+            // the code of the rule starts after the first line, see RULE_CODE_LINE_OFFSET
+            let factory_code = format!(
+                r#"stellaRuleFactories[{}] = function (filename) {{
 {}
-
-{}.forEach(n => visit(n, filename, n.context.code));
-
-const res = {{
-    violations: stellaAllErrors,
-    console: console.lines,
-}}
-
-res
+return {{
+    visit: typeof visit === "function" ? visit : null,
+    finalize: typeof finalize === "function" ? finalize : null,
+}};
+}};
 "#,
-        filename, rule.code, nodes_json
-    );
+                factory_id, rule.code
+            );
+            if factory_code.len() >= v8::String::max_length() {
+                return get_code_too_big_result(rule, filename);
+            }
+            let code: FastString = factory_code.into();
+            if let Err(e) = rule_runtime.runtime.execute_script("rule_code", code) {
+                return get_exception_result(rule, filename, e, analysis_options);
+            }
+            rule_runtime
+                .rule_factories
+                .insert(rule.code.clone(), factory_id);
+            factory_id
+        }
+    };
 
-    // We cannot have strings that are  too long. Otherwise, the underlying
-    // JS engine crashes. So for now, we just return an error if the code is too big/large.
-    //
-    // See https://github.com/denoland/deno/issues/19638 for more details. Once the issue
-    // is resolved, we can remove it and errors will be detected in runtime.execute_script()
+    let nodes_json: String = serde_json::to_string(match_nodes).unwrap();
+    let js_code = format!(
+        "stellaExecute({}, {}, {}, {}, {})",
+        factory_id,
+        serde_json::to_string(&filename).unwrap(),
+        NODE_SCHEMA_VERSION,
+        serde_json::to_string(comments).unwrap(),
+        nodes_json
+    );
     if js_code.len() >= v8::String::max_length() {
-        return RuleResult {
-            rule_name: rule.name.clone(),
-            filename,
-            violations: vec![],
-            errors: vec![ERROR_RULE_CODE_TOO_BIG.to_string()],
-            execution_error: Some(ERROR_RULE_CODE_TOO_BIG.to_string()),
-            output: None,
-            execution_time_ms: 0,
-        };
+        return get_code_too_big_result(rule, filename);
     }
 
     let code: FastString = js_code.into();
 
-    let execution_result = runtime.execute_script("rule_code", code);
+    let execution_result = rule_runtime.runtime.execute_script("rule_execution", code);
 
     match execution_result {
         Ok(res) => {
-            let scope = &mut runtime.handle_scope();
-
+            let scope = &mut rule_runtime.runtime.handle_scope();
             let local = v8::Local::new(scope, res);
             // Deserialize a `v8` object into a Rust type using `serde_v8`,
             // in this case deserialize to a JSON `Value`.
@@ -245,14 +697,9 @@ res
                 Ok(value) => {
                     match serde_json::from_value::<StellaExecution>(value) {
                         Ok(stella_execution) => {
-                            let console_lines = if stella_execution.console.is_empty()
-                                || !analysis_options.log_output
-                            {
-                                None
-                            } else {
-                                Some(stella_execution.console.join("\n"))
-                            };
-                            // update the violation with the category and severity of the rule
+                            let mut warnings = vec![];
+                            // the violations have the category and severity of the rule, unless
+                            // the rule overrides them
                             let updated_violations: Vec<Violation> = stella_execution
                                 .violations
                                 .into_iter()
@@ -260,11 +707,37 @@ res
                                     start: v.start,
                                     end: v.end,
                                     message: v.message,
-                                    category: rule.category,
-                                    severity: rule.severity,
+                                    category: get_override(
+                                        v.category.as_deref(),
+                                        rule.category,
+                                        &mut warnings,
+                                    ),
+                                    severity: get_override(
+                                        v.severity.as_deref(),
+                                        rule.severity,
+                                        &mut warnings,
+                                    ),
                                     fixes: v.fixes,
+                                    related_locations: v.related_locations,
+                                    cwe: rule.cwe.clone(),
                                 })
                                 .collect();
+                            if analysis_options.use_debug {
+                                for warning in &warnings {
+                                    eprintln!(
+                                        "rule {} on file {}: {}",
+                                        rule.name, filename, warning
+                                    );
+                                }
+                            }
+                            let mut console = stella_execution.console;
+                            console.extend(warnings);
+                            let console_lines =
+                                if console.is_empty() || !analysis_options.log_output {
+                                    None
+                                } else {
+                                    Some(console.join("\n"))
+                                };
                             RuleResult {
                                 rule_name: rule.name.clone(),
                                 filename,
@@ -273,6 +746,8 @@ res
                                 execution_error: None,
                                 output: console_lines,
                                 execution_time_ms: 0,
+                                query_time_ms: 0,
+                                exception: None,
                             }
                         }
                         Err(e) => RuleResult {
@@ -283,6 +758,8 @@ res
                             execution_error: Some(format!("error when getting violations: ${e}")),
                             output: None,
                             execution_time_ms: 0,
+                            query_time_ms: 0,
+                            exception: None,
                         },
                     }
                 }
@@ -294,32 +771,56 @@ res
                     execution_error: Some(format!("error: {err}")),
                     output: None,
                     execution_time_ms: 0,
+                    query_time_ms: 0,
+                    exception: None,
                 },
             }
         }
-        Err(e) => {
-            if analysis_options.use_debug {
-                println!(
-                    "error when executing the rule {} on file {}, message: {}",
-                    rule.name, filename, e
-                );
-            }
-
-            let err_str = e.to_string();
-            let error_message = err_str
-                .find("at rule_code")
-                .map_or_else(|| err_str.clone(), |pos| err_str[..pos].to_string());
+        Err(e) => get_exception_result(rule, filename, e, analysis_options),
+    }
+}
 
-            RuleResult {
-                rule_name: rule.name.clone(),
-                filename,
-                violations: vec![],
-                errors: vec![ERROR_RULE_EXECUTION.to_string()],
-                execution_error: Some(error_message),
-                output: None,
-                execution_time_ms: 0,
+// Get the exception thrown by the code of a rule. The lines of the stack trace are the lines of
+// the code of the rule, which starts after `line_offset` lines of the executed code. The frames
+// of the code of the analyzer are not kept.
+fn get_rule_exception(
+    rule: &RuleInternal,
+    filename: &str,
+    error: &AnyError,
+    line_offset: usize,
+) -> RuleExecutionError {
+    let Some(js_error) = error.downcast_ref::<JsError>() else {
+        return RuleExecutionError {
+            rule_name: rule.name.clone(),
+            filename: filename.to_string(),
+            message: error.to_string(),
+            stack: vec![],
+        };
+    };
+    let rule_lines = rule.code.lines().count() as i64;
+    let stack = js_error
+        .frames
+        .iter()
+        .filter(|frame| frame.file_name.as_deref() == Some("rule_code"))
+        .filter_map(|frame| {
+            let line = frame.line_number? - line_offset as i64;
+            if line < 1 || line > rule_lines {
+                return None;
             }
-        }
+            Some(format!(
+                "at {} ({}:{}:{})",
+                frame.function_name.as_deref().unwrap_or("<anonymous>"),
+                rule.name,
+                line,
+                frame.column_number.unwrap_or(0)
+            ))
+        })
+        .collect();
+    RuleExecutionError {
+        rule_name: rule.name.clone(),
+        filename: filename.to_string(),
+        message: js_error.exception_message.clone(),
+        stack,
     }
 }
 
@@ -364,6 +865,7 @@ mod tests {
             code: rule_code.to_string(),
             tree_sitter_query: Some(q.to_string()),
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
 
         let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
@@ -375,6 +877,7 @@ mod tests {
             AnalysisOptions {
                 use_debug: true,
                 log_output: true,
+                ..AnalysisOptions::default()
             },
         );
         assert_eq!("myrule", rule_execution.rule_name);
@@ -418,6 +921,7 @@ def foo(arg1):
             code: rule_code.to_string(),
             tree_sitter_query: Some(q.to_string()),
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
 
         let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
@@ -429,6 +933,7 @@ def foo(arg1):
             AnalysisOptions {
                 use_debug: true,
                 log_output: true,
+                ..AnalysisOptions::default()
             },
         );
         assert_eq!("myrule", rule_execution.rule_name);
@@ -441,6 +946,63 @@ def foo(arg1):
         );
     }
 
+    // the timeout of the rules can be set for the analysis and for each rule
+    #[test]
+    fn test_rule_timeout() {
+        let q = "(function_definition name: (identifier) @name)";
+        let c = "def foo(arg1):\n    pass\n";
+        let run = |rule_code: &str, timeout_ms: Option<u64>, rule_timeout_ms: Option<u64>| {
+            let tree = get_tree(c, &Language::Python).unwrap();
+            let query = get_query(q, &Language::Python).unwrap();
+            let rule = RuleInternal {
+                name: "myrule".to_string(),
+                short_description: None,
+                description: None,
+                category: RuleCategory::CodeStyle,
+                severity: RuleSeverity::Notice,
+                language: Language::Python,
+                code: rule_code.to_string(),
+                tree_sitter_query: Some(q.to_string()),
+                variables: HashMap::new(),
+                timeout_ms,
+                ..RuleInternal::default()
+            };
+            let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+            execute_rule(
+                rule,
+                nodes,
+                "myfile.py".to_string(),
+                AnalysisOptions {
+                    use_debug: false,
+                    log_output: true,
+                    rule_timeout_ms,
+                    ..AnalysisOptions::default()
+                },
+            )
+        };
+
+        let infinite_loop = "function visit(node, filename, code) { while(true) {} }";
+        for (timeout_ms, rule_timeout_ms) in [(Some(100), None), (None, Some(100))] {
+            let rule_execution = run(infinite_loop, timeout_ms, rule_timeout_ms);
+            assert_eq!(vec![ERROR_RULE_TIMEOUT.to_string()], rule_execution.errors);
+            assert!(rule_execution.execution_time_ms < JAVASCRIPT_EXECUTION_TIMEOUT_MS as u128);
+        }
+
+        // the rule runs longer than the timeout of the analysis, within its own timeout
+        let slow = r#"
+function visit(node, filename, code) {
+    const start = Date.now();
+    while (Date.now() - start < 300) {}
+    console.log("done");
+}
+"#;
+        let rule_execution = run(slow, Some(3000), Some(100));
+        assert!(rule_execution.errors.is_empty());
+        assert_eq!(Some("done".to_string()), rule_execution.output);
+        let rule_execution = run(slow, None, Some(100));
+        assert_eq!(vec![ERROR_RULE_TIMEOUT.to_string()], rule_execution.errors);
+    }
+
     #[test]
     fn test_execute_with_error_reported() {
         let q = r#"
@@ -480,6 +1042,7 @@ def foo(arg1):
             code: rule_code.to_string(),
             tree_sitter_query: Some(q.to_string()),
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
         let query = get_query(q, &Language::Python).unwrap();
         let nodes = get_query_nodes(&tree, &query, "plop", c, &HashMap::new());
@@ -491,6 +1054,7 @@ def foo(arg1):
             AnalysisOptions {
                 use_debug: true,
                 log_output: true,
+                ..AnalysisOptions::default()
             },
         );
         assert_eq!("myrule", rule_execution.rule_name);
@@ -510,121 +1074,150 @@ def foo(arg1):
         );
     }
 
+    // the violations can have locations in the file or in other files that explain them
     #[test]
-    fn test_execute_with_console() {
+    fn test_execute_with_related_locations() {
         let q = r#"
 (function_definition
-    name: (identifier) @name
-  parameters: (parameters) @params
-)
+  name: (identifier) @name
+  parameters: (parameters (identifier) @param))
         "#;
 
         let rule_code = r#"
 function visit(node, filename, code) {
-    console.log("bla");
+    const name = node.captures["name"];
+    const param = node.captures["param"];
+    const error = buildError(param.start.line, param.start.col, param.end.line, param.end.col,
+                             "parameter shadows the function");
+    error.addRelatedLocation(name.start.line, name.start.col, name.end.line, name.end.col,
+                             "function defined here");
+    addError(error);
 }
         "#;
 
         let c = r#"
-def foo(arg1):
+def foo(foo):
     pass
         "#;
         let tree = get_tree(c, &Language::Python).unwrap();
-        let query = get_query(q, &Language::Python).unwrap();
         let rule = RuleInternal {
             name: "myrule".to_string(),
-            short_description: Some("short desc".to_string()),
-            description: Some("description".to_string()),
+            short_description: None,
+            description: None,
             category: RuleCategory::CodeStyle,
             severity: RuleSeverity::Notice,
             language: Language::Python,
             code: rule_code.to_string(),
             tree_sitter_query: Some(q.to_string()),
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
-
+        let query = get_query(q, &Language::Python).unwrap();
         let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
 
         let rule_execution = execute_rule(
             rule,
             nodes,
-            "foo.py".to_string(),
+            "myfile.py".to_string(),
             AnalysisOptions {
                 use_debug: true,
                 log_output: true,
+                ..AnalysisOptions::default()
             },
         );
-        assert_eq!("myrule", rule_execution.rule_name);
         assert!(rule_execution.execution_error.is_none());
-        assert_eq!("bla", rule_execution.output.unwrap())
+        assert_eq!(1, rule_execution.violations.len());
+        let violation = &rule_execution.violations[0];
+        assert_eq!(Position::new(2, 9), violation.start);
+        assert_eq!(1, violation.related_locations.len());
+        let related_location = &violation.related_locations[0];
+        assert!(related_location.filename.is_none());
+        assert_eq!("function defined here", related_location.label);
+        assert_eq!(Position::new(2, 5), related_location.start);
+        assert_eq!(Position::new(2, 8), related_location.end);
+
+        // in the JSON output
+        let value = serde_json::to_value(violation).unwrap();
+        assert_eq!(
+            serde_json::json!([{
+                "start": {"line": 2, "col": 5},
+                "end": {"line": 2, "col": 8},
+                "label": "function defined here"
+            }]),
+            value["relatedLocations"]
+        );
+        // not in the output of the violations without related locations
+        let value = serde_json::to_value(Violation {
+            related_locations: vec![],
+            ..violation.clone()
+        })
+        .unwrap();
+        assert!(value.get("relatedLocations").is_none());
     }
 
-    // change the type of the edit, which should trigger a serialization issue
     #[test]
-    fn test_execute_with_serialization_issue() {
+    fn test_execute_with_cwe() {
         let q = r#"
-(function_definition
-    name: (identifier) @name
-  parameters: (parameters) @params
-)
+(call
+  function: (identifier) @function
+  (#eq? @function "eval"))
         "#;
 
         let rule_code = r#"
 function visit(node, filename, code) {
-
-    const functionName = node.captures["name"];
-    if(functionName) {
-        const error = buildError(functionName.start.line, functionName.start.col, functionName.end.line, functionName.end.col,
-                                 "invalid name", "CRITICAL", "security");
-
-        const edit = buildEdit(functionName.start.line, functionName.start.col, functionName.end.line, functionName.end.col, "23232", "bar");
-        const fix = buildFix("use bar", [edit]);
-        addError(error.addFix(fix));
-    }
+    const f = node.captures["function"];
+    addError(buildError(f.start.line, f.start.col, f.end.line, f.end.col, "do not use eval"));
 }
         "#;
 
-        let c = r#"
-def foo(arg1):
-    pass
-        "#;
+        let c = "eval(input())";
         let tree = get_tree(c, &Language::Python).unwrap();
-        let query = get_query(q, &Language::Python).unwrap();
         let rule = RuleInternal {
             name: "myrule".to_string(),
-            short_description: Some("short desc".to_string()),
-            description: Some("description".to_string()),
-            category: RuleCategory::CodeStyle,
-            severity: RuleSeverity::Notice,
+            short_description: None,
+            description: None,
+            category: RuleCategory::Security,
+            severity: RuleSeverity::Error,
             language: Language::Python,
             code: rule_code.to_string(),
             tree_sitter_query: Some(q.to_string()),
             variables: HashMap::new(),
+            cwe: vec!["95".to_string()],
+            ..RuleInternal::default()
         };
-
+        let query = get_query(q, &Language::Python).unwrap();
         let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
 
         let rule_execution = execute_rule(
             rule,
             nodes,
-            "foo.py".to_string(),
+            "myfile.py".to_string(),
             AnalysisOptions {
                 use_debug: true,
                 log_output: true,
+                ..AnalysisOptions::default()
             },
         );
-        assert_eq!("myrule", rule_execution.rule_name);
-        println!("error: {:?}", rule_execution);
-        assert!(rule_execution.execution_error.is_some());
-        assert!(rule_execution
-            .execution_error
-            .unwrap()
-            .contains("expected one of `ADD`, `REMOVE`, `UPDATE`"));
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(1, rule_execution.violations.len());
+        let violation = &rule_execution.violations[0];
+        assert_eq!(RuleCategory::Security, violation.category);
+        assert_eq!(vec!["95"], violation.cwe);
+
+        // in the JSON output, only when the rule has CWE identifiers
+        let value = serde_json::to_value(violation).unwrap();
+        assert_eq!(serde_json::json!(["95"]), value["cwe"]);
+        assert_eq!(serde_json::json!("SECURITY"), value["category"]);
+        let value = serde_json::to_value(Violation {
+            cwe: vec![],
+            ..violation.clone()
+        })
+        .unwrap();
+        assert!(value.get("cwe").is_none());
     }
 
-    // invalid javascript code
     #[test]
-    fn test_invalid_javascript() {
+    fn test_execute_with_console() {
         let q = r#"
 (function_definition
     name: (identifier) @name
@@ -633,7 +1226,8 @@ def foo(arg1):
         "#;
 
         let rule_code = r#"
-function visit(node, filena
+function visit(node, filename, code) {
+    console.log("bla");
 }
         "#;
 
@@ -653,6 +1247,7 @@ def foo(arg1):
             code: rule_code.to_string(),
             tree_sitter_query: Some(q.to_string()),
             variables: HashMap::new(),
+            ..RuleInternal::default()
         };
 
         let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
@@ -664,22 +1259,1206 @@ def foo(arg1):
             AnalysisOptions {
                 use_debug: true,
                 log_output: true,
+                ..AnalysisOptions::default()
             },
         );
         assert_eq!("myrule", rule_execution.rule_name);
-        assert!(rule_execution.execution_error.is_some());
-        println!(
-            "message: {}",
-            rule_execution.execution_error.clone().unwrap()
-        );
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!("bla", rule_execution.output.unwrap())
+    }
+
+    // the output of console.log and console.error is truncated and only returned when asked
+    #[test]
+    fn test_execute_with_console_output() {
+        let q = "(function_definition name: (identifier) @name)";
+        let c = "def foo(arg1):\n    pass\n";
+        let run = |rule_code: &str, log_output: bool| {
+            let tree = get_tree(c, &Language::Python).unwrap();
+            let query = get_query(q, &Language::Python).unwrap();
+            let rule = RuleInternal {
+                name: "myrule".to_string(),
+                short_description: None,
+                description: None,
+                category: RuleCategory::CodeStyle,
+                severity: RuleSeverity::Notice,
+                language: Language::Python,
+                code: rule_code.to_string(),
+                tree_sitter_query: Some(q.to_string()),
+                variables: HashMap::new(),
+                ..RuleInternal::default()
+            };
+            let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+            execute_rule(
+                rule,
+                nodes,
+                "myfile.py".to_string(),
+                AnalysisOptions {
+                    use_debug: false,
+                    log_output,
+                    ..AnalysisOptions::default()
+                },
+            )
+        };
+
+        let rule_code = r#"
+function visit(node, filename, code) {
+    console.log("name", node.captures["name"].text, 42, {a: 1});
+    console.error("error", undefined);
+}
+"#;
+        let rule_execution = run(rule_code, true);
+        assert!(rule_execution.execution_error.is_none());
         assert_eq!(
-            "Uncaught SyntaxError: Unexpected token '}'\n    ",
-            rule_execution.execution_error.unwrap()
+            Some("name foo 42 {\"a\":1}\nerror undefined".to_string()),
+            rule_execution.output
         );
-        assert_eq!(1, rule_execution.errors.len());
-        assert_eq!(
-            crate::model::analysis::ERROR_RULE_EXECUTION,
-            rule_execution.errors.get(0).unwrap()
+        // the output is not returned by default
+        assert_eq!(None, run(rule_code, false).output);
+
+        let rule_code = r#"
+function visit(node, filename, code) {
+    for (let i = 0; i < 100; i++) {
+        console.log("x".repeat(1023));
+    }
+}
+"#;
+        let output = run(rule_code, true).output.unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(65, lines.len());
+        assert_eq!("[output truncated, the limit is 64 KB]", lines[64]);
+    }
+
+    // the rules get the children of a node with their field
+    #[test]
+    fn test_execute_with_child_by_field() {
+        let q = r#"(function_definition) @function"#;
+
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const func = node.captures["function"];
+    const name = getChildByField(func, "name");
+    const missing = getChildByField(func, "return_type");
+    console.log(getCodeForNode(name, code) + "," + getChildrenByField(func, "body").length + "," + missing);
+}
+        "#;
+
+        let c = r#"
+def foo(arg1):
+    pass
+        "#;
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(q.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+
+        let rule_execution = execute_rule(
+            rule,
+            nodes,
+            "foo.py".to_string(),
+            AnalysisOptions {
+                use_debug: true,
+                log_output: true,
+                ..AnalysisOptions::default()
+            },
+        );
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!("foo,1,undefined", rule_execution.output.unwrap())
+    }
+
+    // the fields that are not sent to the rules have their default value in the nodes
+    // the nodes are read only when the JavaScript code supports their version
+    #[test]
+    fn test_node_schema_version() {
+        assert!(include_str!("js/stella.js").contains(&format!(
+            "const STELLA_MAX_NODE_SCHEMA_VERSION = {NODE_SCHEMA_VERSION};"
+        )));
+
+        let q = r#"(function_definition) @function"#;
+        let rule_code = r#"
+function visit(node, filename, code) {
+    console.log(`${node.context.schemaVersion} ${STELLA_NODE_SCHEMA_VERSION}`);
+}
+        "#;
+        let c = "def foo(arg1):\n    pass\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(q.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            use_debug: false,
+            log_output: true,
+            ..AnalysisOptions::default()
+        };
+
+        let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert_eq!(NODE_SCHEMA_VERSION, nodes[0].context.schema_version);
+        let rule_execution = execute_rule(
+            rule.clone(),
+            nodes.clone(),
+            "myfile.py".to_string(),
+            analysis_options.clone(),
+        );
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(
+            Some(format!("{NODE_SCHEMA_VERSION} {NODE_SCHEMA_VERSION}")),
+            rule_execution.output
+        );
+
+        // the nodes of another version are not read
+        let mut other_nodes = nodes;
+        other_nodes[0].context.schema_version = NODE_SCHEMA_VERSION + 1;
+        let rule_execution =
+            execute_rule(rule, other_nodes, "myfile.py".to_string(), analysis_options);
+        assert!(rule_execution
+            .execution_error
+            .unwrap()
+            .contains("does not match version"));
+    }
+
+    #[test]
+    fn test_execute_with_compact_nodes() {
+        let q = r#"(function_definition) @function"#;
+
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const func = node.captures["function"];
+    const name = getChildByField(func, "name");
+    const leaves = func.children.filter(c => c.children.length === 0);
+    console.log([
+        name.children.length,
+        func.fieldName,
+        name.isError,
+        name.isMissing,
+        name.text,
+        name.previousSibling,
+        node.capturesList["function"][0].children.length,
+        leaves.every(c => c.isTruncated === false),
+    ].join(","));
+}
+        "#;
+
+        let c = r#"
+def foo(arg1):
+    pass
+        "#;
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(q.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        let children_count = nodes[0].captures["function"].children.len();
+
+        let rule_execution = execute_rule(
+            rule,
+            nodes,
+            "foo.py".to_string(),
+            AnalysisOptions {
+                use_debug: true,
+                log_output: true,
+                ..AnalysisOptions::default()
+            },
+        );
+        assert!(rule_execution.execution_error.is_none());
+        // the values that are null are empty when joined
+        assert_eq!(
+            format!("0,,false,false,,,{children_count},true"),
+            rule_execution.output.unwrap()
+        );
+    }
+
+    // the lines do not have their line ending, also in files with CRLF line endings
+    #[test]
+    fn test_execute_get_lines() {
+        let q = "(module) @module";
+
+        let rule_code = r#"
+function visit(node, filename, code) {
+    console.log(JSON.stringify([getLine(1), getLine(2), getLine(4), getLine(5), getLines(2, 10)]));
+}
+"#;
+
+        for (c, expected) in [
+            (
+                "a = 1\r\nb = \"é😀\"\r\n\r\nc = 3",
+                r#"["a = 1","b = \"é😀\"","c = 3",null,["b = \"é😀\"","","c = 3"]]"#,
+            ),
+            (
+                "a = 1\nb = 2\n",
+                r#"["a = 1","b = 2",null,null,["b = 2",""]]"#,
+            ),
+        ] {
+            let tree = get_tree(c, &Language::Python).unwrap();
+            let query = get_query(q, &Language::Python).unwrap();
+            let rule = RuleInternal {
+                name: "myrule".to_string(),
+                short_description: None,
+                description: None,
+                category: RuleCategory::CodeStyle,
+                severity: RuleSeverity::Notice,
+                language: Language::Python,
+                code: rule_code.to_string(),
+                tree_sitter_query: Some(q.to_string()),
+                variables: HashMap::new(),
+                ..RuleInternal::default()
+            };
+
+            let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+            let rule_tree = RuleTree {
+                tree,
+                grammar: Grammar::new(Language::Python),
+                filename: "myfile.py".to_string(),
+                code: c.to_string(),
+                options: QueryNodesOptions::default(),
+            };
+
+            let rule_execution = execute_rule_with_tree(
+                rule,
+                nodes,
+                "myfile.py".to_string(),
+                AnalysisOptions {
+                    use_debug: true,
+                    log_output: true,
+                    ..AnalysisOptions::default()
+                },
+                Some(rule_tree),
+            );
+            assert!(rule_execution.execution_error.is_none());
+            assert_eq!(Some(expected.to_string()), rule_execution.output);
+        }
+    }
+
+    // the rules can run queries in the nodes they captured
+    #[test]
+    fn test_execute_with_query_in_node() {
+        let q = "(class_definition) @cls";
+
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const cls = node.captures["cls"];
+    const methods = queryInNode(cls, "(function_definition name: (identifier) @name)");
+    methods.forEach(m => {
+        const name = m.captures["name"];
+        addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                            "method", "CRITICAL", "security"));
+    });
+}
+"#;
+
+        let c = r#"
+def helper():
+    pass
+
+class Foo:
+    def bar(self):
+        pass
+"#;
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(q.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        let rule_tree = RuleTree {
+            tree,
+            grammar: Grammar::new(Language::Python),
+            filename: "myfile.py".to_string(),
+            code: c.to_string(),
+            options: QueryNodesOptions::default(),
+        };
+
+        let rule_execution = execute_rule_with_tree(
+            rule,
+            nodes,
+            "myfile.py".to_string(),
+            AnalysisOptions {
+                use_debug: true,
+                log_output: true,
+                ..AnalysisOptions::default()
+            },
+            Some(rule_tree),
+        );
+        assert!(rule_execution.execution_error.is_none());
+        // only the method of the class, at its position in the file
+        assert_eq!(1, rule_execution.violations.len());
+        let violation = &rule_execution.violations[0];
+        assert_eq!(Position::new(6, 9), violation.start);
+        assert_eq!(Position::new(6, 12), violation.end);
+    }
+
+    // change the type of the edit, which should trigger a serialization issue
+    #[test]
+    fn test_execute_with_serialization_issue() {
+        let q = r#"
+(function_definition
+    name: (identifier) @name
+  parameters: (parameters) @params
+)
+        "#;
+
+        let rule_code = r#"
+function visit(node, filename, code) {
+
+    const functionName = node.captures["name"];
+    if(functionName) {
+        const error = buildError(functionName.start.line, functionName.start.col, functionName.end.line, functionName.end.col,
+                                 "invalid name", "CRITICAL", "security");
+
+        const edit = buildEdit(functionName.start.line, functionName.start.col, functionName.end.line, functionName.end.col, "23232", "bar");
+        const fix = buildFix("use bar", [edit]);
+        addError(error.addFix(fix));
+    }
+}
+        "#;
+
+        let c = r#"
+def foo(arg1):
+    pass
+        "#;
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(q.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+
+        let rule_execution = execute_rule(
+            rule,
+            nodes,
+            "foo.py".to_string(),
+            AnalysisOptions {
+                use_debug: true,
+                log_output: true,
+                ..AnalysisOptions::default()
+            },
+        );
+        assert_eq!("myrule", rule_execution.rule_name);
+        println!("error: {:?}", rule_execution);
+        assert!(rule_execution.execution_error.is_some());
+        assert!(rule_execution
+            .execution_error
+            .unwrap()
+            .contains("expected one of `ADD`, `REMOVE`, `UPDATE`"));
+    }
+
+    // invalid javascript code
+    #[test]
+    fn test_invalid_javascript() {
+        let q = r#"
+(function_definition
+    name: (identifier) @name
+  parameters: (parameters) @params
+)
+        "#;
+
+        let rule_code = r#"
+function visit(node, filena
+}
+        "#;
+
+        let c = r#"
+def foo(arg1):
+    pass
+        "#;
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: Some("short desc".to_string()),
+            description: Some("description".to_string()),
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(q.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+
+        let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+
+        let rule_execution = execute_rule(
+            rule,
+            nodes,
+            "foo.py".to_string(),
+            AnalysisOptions {
+                use_debug: true,
+                log_output: true,
+                ..AnalysisOptions::default()
+            },
+        );
+        assert_eq!("myrule", rule_execution.rule_name);
+        assert!(rule_execution.execution_error.is_some());
+        println!(
+            "message: {}",
+            rule_execution.execution_error.clone().unwrap()
+        );
+        assert_eq!(
+            "Uncaught SyntaxError: Unexpected token '}'\n    ",
+            rule_execution.execution_error.unwrap()
+        );
+        assert_eq!(1, rule_execution.errors.len());
+        assert_eq!(
+            crate::model::analysis::ERROR_RULE_EXECUTION,
+            rule_execution.errors.get(0).unwrap()
+        )
+    }
+
+    // the code of the nodes is read with their offsets, for all the characters and lines
+    #[test]
+    fn test_execute_get_code_for_node() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    console.log(getCodeForNode(node.captures["node"], code));
+}
+"#;
+
+        for (language, q, c, expected) in [
+            (
+                Language::Python,
+                "(string) @node",
+                "a = \"é😀\"\nb = 'x'\n",
+                "\"é😀\"\n'x'",
+            ),
+            // multi-line node at the end of the file
+            (
+                Language::Python,
+                "(function_definition) @node",
+                "s = \"😀\"\ndef foo():\n    return \"é\"",
+                "def foo():\n    return \"é\"",
+            ),
+            (
+                Language::TypeScript,
+                "(template_string) @node",
+                "const a = `é${b}😀`;\r\nconst c = 1;\r\n",
+                "`é${b}😀`",
+            ),
+            (
+                Language::TypeScript,
+                "(class_declaration) @node",
+                "const s = \"😀\";\nclass Foo {\n  bar = \"é\";\n}",
+                "class Foo {\n  bar = \"é\";\n}",
+            ),
+        ] {
+            let tree = get_tree(c, &language).unwrap();
+            let query = get_query(q, &language).unwrap();
+            let rule = RuleInternal {
+                name: "myrule".to_string(),
+                short_description: None,
+                description: None,
+                category: RuleCategory::CodeStyle,
+                severity: RuleSeverity::Notice,
+                language,
+                code: rule_code.to_string(),
+                tree_sitter_query: Some(q.to_string()),
+                variables: HashMap::new(),
+                ..RuleInternal::default()
+            };
+
+            let nodes = get_query_nodes(&tree, &query, "myfile", c, &HashMap::new());
+            let rule_tree = RuleTree {
+                tree,
+                grammar: Grammar::new(language),
+                filename: "myfile".to_string(),
+                code: c.to_string(),
+                options: QueryNodesOptions::default(),
+            };
+
+            let rule_execution = execute_rule_with_tree(
+                rule,
+                nodes,
+                "myfile".to_string(),
+                AnalysisOptions {
+                    use_debug: true,
+                    log_output: true,
+                    ..AnalysisOptions::default()
+                },
+                Some(rule_tree),
+            );
+            assert!(rule_execution.execution_error.is_none());
+            assert_eq!(Some(expected.to_string()), rule_execution.output);
+        }
+    }
+
+    // the verdict of the rules can depend on the queries they run in the file and in the nodes
+    #[test]
+    fn test_execute_with_secondary_queries() {
+        let run_with_options = |q: &str, rule_code: &str, c: &str, options: QueryNodesOptions| {
+            let tree = get_tree(c, &Language::Python).unwrap();
+            let query = get_query(q, &Language::Python).unwrap();
+            let rule = RuleInternal {
+                name: "myrule".to_string(),
+                short_description: None,
+                description: None,
+                category: RuleCategory::CodeStyle,
+                severity: RuleSeverity::Notice,
+                language: Language::Python,
+                code: rule_code.to_string(),
+                tree_sitter_query: Some(q.to_string()),
+                variables: HashMap::new(),
+                ..RuleInternal::default()
+            };
+            let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+            let rule_tree = RuleTree {
+                tree,
+                grammar: Grammar::new(Language::Python),
+                filename: "myfile.py".to_string(),
+                code: c.to_string(),
+                options,
+            };
+            execute_rule_with_tree(
+                rule,
+                nodes,
+                "myfile.py".to_string(),
+                AnalysisOptions {
+                    use_debug: true,
+                    log_output: true,
+                    ..AnalysisOptions::default()
+                },
+                Some(rule_tree),
+            )
+        };
+        let run = |q: &str, rule_code: &str, c: &str| {
+            run_with_options(q, rule_code, c, QueryNodesOptions::default())
+        };
+
+        let c = r#"
+import os
+
+def read_ok(path):
+    f = open(path)
+    data = f.read()
+    f.close()
+    return data
+
+def read_leak(path):
+    f = open(path)
+    return f.read()
+"#;
+
+        // the calls to open() in a function that does not call close()
+        let q = r#"(call function: (identifier) @name (#eq? @name "open"))"#;
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    const func = getAncestors(name).find(a => a.astType === "function_definition");
+    const closes = queryInNode(func, "(call function: (attribute attribute: (identifier) @m) (#eq? @m \"close\"))");
+    if (closes.length === 0) {
+        addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                            "file not closed", "WARNING", "BEST_PRACTICES"));
+    }
+}
+"#;
+        let rule_execution = run(q, rule_code, c);
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(1, rule_execution.violations.len());
+        assert_eq!(11, rule_execution.violations[0].start.line);
+
+        // the queries in the whole file
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const imports = queryInFile("(import_statement) @import");
+    const functions = queryInFile("(function_definition name: (identifier) @name)");
+    console.log(`${imports.length} ${functions.map(f => f.captures["name"].text).join(",")}`);
+}
+"#;
+        let rule_execution = run("(module) @module", rule_code, c);
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(
+            Some("1 read_ok,read_leak".to_string()),
+            rule_execution.output
+        );
+
+        // the number of queries of a rule is limited
+        let rule_code = r#"
+function visit(node, filename, code) {
+    for (let i = 0; i <= 1000; i++) {
+        queryInFile("(import_statement) @import");
+    }
+}
+"#;
+        let rule_execution = run("(module) @module", rule_code, c);
+        assert!(rule_execution
+            .execution_error
+            .unwrap()
+            .contains("too many queries, a rule can run 1000 queries on a file"));
+
+        // the invalid queries are errors of the rule
+        let rule_code = r#"
+function visit(node, filename, code) {
+    queryInFile("(import_statement");
+}
+"#;
+        let rule_execution = run("(module) @module", rule_code, c);
+        assert!(rule_execution.execution_error.is_some());
+
+        // the queries of the rule that drop matches report the result as truncated
+        let rule_code = r#"
+function visit(node, filename, code) {
+    queryInFile("(module (function_definition) @first (function_definition) @second)");
+}
+"#;
+        let rule_execution = run("(module) @module", rule_code, c);
+        assert!(rule_execution.errors.is_empty());
+        let options = QueryNodesOptions {
+            match_limit: Some(1),
+            ..QueryNodesOptions::default()
+        };
+        let rule_execution = run_with_options("(module) @module", rule_code, c, options);
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(
+            vec![ERROR_RULE_MATCHES_TRUNCATED.to_string()],
+            rule_execution.errors
+        );
+    }
+
+    // the exceptions of the rules have a stack trace in the code of the rule
+    #[test]
+    fn test_execute_with_exception() {
+        let q = "(function_definition name: (identifier) @name)";
+        let c = "def foo():\n    pass\n\ndef bar():\n    pass\n";
+        let run = |rule_code: &str| {
+            let tree = get_tree(c, &Language::Python).unwrap();
+            let query = get_query(q, &Language::Python).unwrap();
+            let rule = RuleInternal {
+                name: "myrule".to_string(),
+                short_description: None,
+                description: None,
+                category: RuleCategory::CodeStyle,
+                severity: RuleSeverity::Notice,
+                language: Language::Python,
+                code: rule_code.to_string(),
+                tree_sitter_query: Some(q.to_string()),
+                variables: HashMap::new(),
+                ..RuleInternal::default()
+            };
+            let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+            execute_rule(
+                rule,
+                nodes,
+                "myfile.py".to_string(),
+                AnalysisOptions {
+                    use_debug: false,
+                    log_output: false,
+                    ..AnalysisOptions::default()
+                },
+            )
+        };
+
+        // the rule throws on the second function only
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    checkName(name);
+}
+
+function checkName(name) {
+    if (name.text === "bar") {
+        throw new Error(`invalid name ${name.text}`);
+    }
+}
+"#;
+        let rule_execution = run(rule_code);
+        assert_eq!(
+            vec![ERROR_RULE_EXECUTION.to_string()],
+            rule_execution.errors
+        );
+        let exception = rule_execution.exception.unwrap();
+        assert_eq!("myrule", exception.rule_name);
+        assert_eq!("myfile.py", exception.filename);
+        assert_eq!("Uncaught Error: invalid name bar", exception.message);
+        assert_eq!(2, exception.stack.len());
+        assert!(exception.stack[0].starts_with("at checkName (myrule:9:"));
+        assert!(exception.stack[1].starts_with("at visit (myrule:4:"));
+
+        // a capture that is not in the query
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["function"];
+    console.log(name.start.line);
+}
+"#;
+        let exception = run(rule_code).exception.unwrap();
+        assert!(exception.message.starts_with("Uncaught TypeError"));
+        assert_eq!(1, exception.stack.len());
+        assert!(exception.stack[0].starts_with("at visit (myrule:4:"));
+
+        // no exception when the rule succeeds
+        let rule_code = "function visit(node, filename, code) {}";
+        let rule_execution = run(rule_code);
+        assert!(rule_execution.exception.is_none());
+        let value = serde_json::to_value(&rule_execution).unwrap();
+        assert!(value.get("exception").is_none());
+    }
+
+    // the rules can aggregate the matches of a file and report violations in finalize
+    #[test]
+    fn test_execute_with_state_and_finalize() {
+        // the calls first in the query, the rule still visits the matches in the order of the
+        // code
+        let q = r#"
+(call function: (attribute object: (identifier) @object) (#eq? @object "logger")) @call
+(import_statement name: (dotted_name) @module (#eq? @module "logging"))
+"#;
+        let rule_code = r#"
+function visit(node, filename, code, state) {
+    state.visited = (state.visited ?? 0) + 1;
+    state.order = (state.order ?? "") + (node.captures["module"] ? "i" : "c");
+    if (node.captures["module"]) {
+        state.import = node.captures["module"];
+    } else {
+        state.calls = (state.calls ?? 0) + 1;
+    }
+}
+
+function finalize(context, state) {
+    console.log(`${context.filename} ${state.visited} ${state.order}`);
+    if (state.import && !state.calls) {
+        const m = state.import;
+        addError(buildError(m.start.line, m.start.col, m.end.line, m.end.col,
+                            "logging is imported but logger is never called", "WARNING",
+                            "BEST_PRACTICES"));
+    }
+}
+"#;
+        let run = |filename: &str, c: &str| {
+            let tree = get_tree(c, &Language::Python).unwrap();
+            let query = get_query(q, &Language::Python).unwrap();
+            let rule = RuleInternal {
+                name: "myrule".to_string(),
+                short_description: None,
+                description: None,
+                category: RuleCategory::BestPractices,
+                severity: RuleSeverity::Warning,
+                language: Language::Python,
+                code: rule_code.to_string(),
+                tree_sitter_query: Some(q.to_string()),
+                variables: HashMap::new(),
+                ..RuleInternal::default()
+            };
+            let nodes = get_query_nodes(&tree, &query, filename, c, &HashMap::new());
+            execute_rule(
+                rule,
+                nodes,
+                filename.to_string(),
+                AnalysisOptions {
+                    use_debug: false,
+                    log_output: true,
+                    ..AnalysisOptions::default()
+                },
+            )
+        };
+
+        // the file imports logging and never calls logger, the violation is on the import
+        let rule_execution = run("unused.py", "import logging\n\nprint(1)\n");
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(Some("unused.py 1 i".to_string()), rule_execution.output);
+        assert_eq!(1, rule_execution.violations.len());
+        assert_eq!(1, rule_execution.violations[0].start.line);
+        assert_eq!(
+            "logging is imported but logger is never called",
+            rule_execution.violations[0].message
+        );
+
+        // the state of the previous file is not visible
+        let c = "logger.info(1)\nimport logging\nlogger.debug(2)\n";
+        let rule_execution = run("used.py", c);
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(Some("used.py 3 cic".to_string()), rule_execution.output);
+        assert!(rule_execution.violations.is_empty());
+
+        // finalize is not called when there is no match
+        let rule_execution = run("empty.py", "print(1)\n");
+        assert!(rule_execution.execution_error.is_none());
+        assert!(rule_execution.output.is_none());
+        assert!(rule_execution.violations.is_empty());
+    }
+
+    // the rules can override their severity and category for a violation
+    #[test]
+    fn test_execute_with_severity_override() {
+        let q = "(call function: (identifier) @name (#eq? @name \"execute\") arguments: (_) @args)";
+        let c = r#"
+query = "SELECT * FROM users WHERE id = " + user_id
+execute(query)
+execute("SELECT 1")
+execute(other)
+"#;
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const args = node.captures["args"];
+    const error = buildError(args.start.line, args.start.col, args.end.line, args.end.col,
+                             "SQL built by string concatenation");
+    const arg = getCodeForNode(args);
+    if (arg.includes("query")) {
+        addViolation(error, {severity: "ERROR", category: "SECURITY"});
+    } else if (arg.includes("other")) {
+        addViolation(error, {severity: "CRITICAL", category: "SAFETY"});
+    } else {
+        addViolation(error);
+    }
+}
+"#;
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::BestPractices,
+            severity: RuleSeverity::Warning,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(q.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        let rule_execution = execute_rule(
+            rule,
+            nodes,
+            "myfile.py".to_string(),
+            AnalysisOptions {
+                use_debug: false,
+                log_output: true,
+                ..AnalysisOptions::default()
+            },
+        );
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(
+            vec![
+                (RuleSeverity::Error, RuleCategory::Security),
+                (RuleSeverity::Warning, RuleCategory::BestPractices),
+                // the invalid values fall back to the values of the rule
+                (RuleSeverity::Warning, RuleCategory::BestPractices),
+            ],
+            rule_execution
+                .violations
+                .iter()
+                .map(|v| (v.severity, v.category))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(
+                "warning: unknown category SAFETY, the value of the rule is used\n\
+                 warning: unknown severity CRITICAL, the value of the rule is used"
+                    .to_string()
+            ),
+            rule_execution.output
+        );
+    }
+
+    // a rule that uses too much memory is stopped, the next executions are not affected
+    #[test]
+    fn test_rule_out_of_memory() {
+        let q = "(function_definition name: (identifier) @name)";
+        let c = "def foo():\n    pass\n";
+        let run = |rule_code: &str| {
+            let tree = get_tree(c, &Language::Python).unwrap();
+            let query = get_query(q, &Language::Python).unwrap();
+            let rule = RuleInternal {
+                name: "myrule".to_string(),
+                short_description: None,
+                description: None,
+                category: RuleCategory::CodeStyle,
+                severity: RuleSeverity::Notice,
+                language: Language::Python,
+                code: rule_code.to_string(),
+                tree_sitter_query: Some(q.to_string()),
+                variables: HashMap::new(),
+                ..RuleInternal::default()
+            };
+            let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+            execute_rule(
+                rule,
+                nodes,
+                "myfile.py".to_string(),
+                AnalysisOptions {
+                    use_debug: false,
+                    log_output: false,
+                    rule_timeout_ms: Some(30000),
+                    rule_memory_limit_mb: Some(64),
+                    ..AnalysisOptions::default()
+                },
+            )
+        };
+
+        let rule_code = r#"
+const nodes = [];
+function visit(node, filename, code) {
+    while (true) {
+        nodes.push(new Array(100000).fill(node));
+    }
+}
+"#;
+        let rule_execution = run(rule_code);
+        assert_eq!(
+            vec![ERROR_RULE_OUT_OF_MEMORY.to_string()],
+            rule_execution.errors
+        );
+        assert_eq!(
+            Some("the rule used more than 64 MB".to_string()),
+            rule_execution.execution_error
+        );
+        assert!(rule_execution.violations.is_empty());
+
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col, "name"));
+}
+"#;
+        let rule_execution = run(rule_code);
+        assert!(rule_execution.errors.is_empty());
+        assert_eq!(1, rule_execution.violations.len());
+    }
+
+    // a memory limit too large to be counted in bytes is lowered to the largest limit
+    #[test]
+    fn test_rule_memory_limit_too_large() {
+        let q = "(function_definition name: (identifier) @name)";
+        let c = "def foo():\n    pass\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col, "name"));
+}
+"#
+            .to_string(),
+            tree_sitter_query: Some(q.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        let rule_execution = execute_rule(
+            rule,
+            nodes,
+            "myfile.py".to_string(),
+            AnalysisOptions {
+                use_debug: false,
+                log_output: false,
+                rule_memory_limit_mb: Some(u64::MAX),
+                ..AnalysisOptions::default()
+            },
+        );
+        assert!(rule_execution.errors.is_empty());
+        assert_eq!(1, rule_execution.violations.len());
+    }
+
+    // run a rule on a file with one function, on the runtime of the thread
+    fn run_on_file(rule_code: &str, filename: &str) -> RuleResult {
+        let q = "(function_definition name: (identifier) @name)";
+        let c = "def foo():\n    pass\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let rule = RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(q.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let nodes = get_query_nodes(&tree, &query, filename, c, &HashMap::new());
+        execute_rule(
+            rule,
+            nodes,
+            filename.to_string(),
+            AnalysisOptions {
+                use_debug: false,
+                log_output: true,
+                ..AnalysisOptions::default()
+            },
         )
     }
+
+    // the runtime is reused for the files, the globals changed by a rule on a file are reset
+    // before the next file
+    #[test]
+    fn test_execute_with_globals_reset() {
+        let rule_code = r#"
+let visits = 0;
+function visit(node, filename, code) {
+    visits += 1;
+    seen = typeof seen === "undefined" ? 1 : seen + 1;
+    globalThis.files = (globalThis.files ?? []).concat([filename]);
+    console.log(`${visits} ${seen} ${globalThis.files.join(",")} ${typeof helpers}`);
+    helpers = null;
+    if (filename === "a.py") {
+        addError(buildError(1, 1, 1, 2, "first file"));
+    }
+}
+"#;
+        let rule_execution = run_on_file(rule_code, "a.py");
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(Some("1 1 a.py object".to_string()), rule_execution.output);
+        assert_eq!(1, rule_execution.violations.len());
+
+        let rule_execution = run_on_file(rule_code, "b.py");
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(Some("1 1 b.py object".to_string()), rule_execution.output);
+        assert!(rule_execution.violations.is_empty());
+
+        // the code of the rule is compiled once in the runtime of the thread
+        let rules = RULE_RUNTIME.with(|r| r.borrow().as_ref().unwrap().rule_factories.len());
+        assert_eq!(1, rules);
+
+        // another rule does not see the globals of the first rule
+        let rule_code = r#"
+function visit(node, filename, code) {
+    console.log(`${typeof seen} ${typeof files} ${typeof helpers}`);
+}
+"#;
+        let rule_execution = run_on_file(rule_code, "c.py");
+        assert_eq!(
+            Some("undefined undefined object".to_string()),
+            rule_execution.output
+        );
+
+        // a rule without visit
+        let rule_execution = run_on_file("function finalize() {}", "d.py");
+        assert_eq!(
+            vec![ERROR_RULE_EXECUTION.to_string()],
+            rule_execution.errors
+        );
+        assert_eq!(
+            "Uncaught ReferenceError: visit is not defined",
+            rule_execution.exception.unwrap().message
+        );
+    }
+
+    // the properties set by a rule on the builtin objects and their prototypes are removed
+    // before the next file
+    #[test]
+    fn test_execute_with_builtins_reset() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    console.log(`${typeof [].polluted} ${typeof {}.polluted} ${typeof Math.polluted}`);
+    Array.prototype.polluted = () => 1;
+    Object.prototype.polluted = 1;
+    Math.polluted = 1;
+    const push = Array.prototype.push;
+    Array.prototype.push = function () { return push.apply(this, [0]); };
+    JSON.stringify = () => "polluted";
+}
+"#;
+        let rule_execution = run_on_file(rule_code, "a.py");
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(
+            Some("undefined undefined undefined".to_string()),
+            rule_execution.output
+        );
+
+        let rule_code = r#"
+function visit(node, filename, code) {
+    console.log(`${typeof [].polluted} ${typeof {}.polluted} ${typeof Math.polluted}`);
+    const values = [];
+    values.push(1);
+    console.log(`${values[0]} ${JSON.stringify(values)}`);
+}
+"#;
+        let rule_execution = run_on_file(rule_code, "b.py");
+        assert!(rule_execution.execution_error.is_none());
+        assert_eq!(
+            Some("undefined undefined undefined\n1 [1]".to_string()),
+            rule_execution.output
+        );
+    }
+
+    // Compare the time to run a rule on 1,000 small files with the runtime of the thread and
+    // with a new runtime for each file.
+    // Run with `cargo test --release -- --ignored --nocapture bench_execute_rule_files`.
+    #[test]
+    #[ignore]
+    fn bench_execute_rule_files() {
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col, "name"));
+}
+"#;
+        let start = Instant::now();
+        for i in 0..1_000 {
+            let rule_execution = run_on_file(rule_code, &format!("file{i}.py"));
+            assert_eq!(1, rule_execution.violations.len());
+        }
+        let reused = start.elapsed();
+
+        let start = Instant::now();
+        for i in 0..1_000 {
+            RULE_RUNTIME.with(|r| r.borrow_mut().take());
+            let rule_execution = run_on_file(rule_code, &format!("file{i}.py"));
+            assert_eq!(1, rule_execution.violations.len());
+        }
+        let new_runtimes = start.elapsed();
+
+        println!("reused runtime: {reused:?}, new runtime for each file: {new_runtimes:?}");
+        // creating a runtime from the snapshot for each file is several times slower
+        assert!(reused * 2 < new_runtimes);
+    }
 }
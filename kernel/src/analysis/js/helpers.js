@@ -0,0 +1,131 @@
+// Helpers of the rules for each language, loaded after stella.js. They are available as
+// `helpers.<language>.<helper>` (e.g. `helpers.python.getImportedModules()`). They are set on
+// the global object so that the rules can still declare their own `helpers`.
+//
+// The version is increased when the behavior of a helper changes, see HELPERS_VERSION in the
+// kernel.
+
+// the closest ancestor of a captured node with one of the kinds, or null
+function stellaGetAncestor(node, kinds) {
+  return getAncestors(node).find(a => kinds.includes(a.astType)) ?? null;
+}
+
+// the code of the node captured as `name` in the match of a query on the node itself
+function stellaGetNodeName(node, query) {
+  if (node === null) {
+    return null;
+  }
+  const match = queryInNode(node, query)
+    .find(m => m.captures["node"] && m.captures["node"].startByte === node.startByte);
+  return match ? getCodeForNode(match.captures["name"]) : null;
+}
+
+// the code of the captures of the queries in the file, without duplicates and in the order of
+// the queries
+function stellaGetCapturesCode(queries, captureName, transform) {
+  const values = queries
+    .flatMap(query => queryInFile(query))
+    .map(m => transform(getCodeForNode(m.captures[captureName])));
+  return [...new Set(values)];
+}
+
+const STELLA_PYTHON_FUNCTION_KINDS = ["function_definition"];
+const STELLA_PYTHON_CLASS_KINDS = ["class_definition"];
+
+const stellaPythonHelpers = {
+  // the function that contains the node, or null
+  getEnclosingFunction(node) {
+    return stellaGetAncestor(node, STELLA_PYTHON_FUNCTION_KINDS);
+  },
+
+  // the name of the function that contains the node, or null
+  getEnclosingFunctionName(node) {
+    return stellaGetNodeName(
+      stellaGetAncestor(node, STELLA_PYTHON_FUNCTION_KINDS),
+      "(function_definition name: (identifier) @name) @node",
+    );
+  },
+
+  // the class that contains the node, or null
+  getEnclosingClass(node) {
+    return stellaGetAncestor(node, STELLA_PYTHON_CLASS_KINDS);
+  },
+
+  // the name of the class that contains the node, or null
+  getEnclosingClassName(node) {
+    return stellaGetNodeName(
+      stellaGetAncestor(node, STELLA_PYTHON_CLASS_KINDS),
+      "(class_definition name: (identifier) @name) @node",
+    );
+  },
+
+  // the modules imported by the file (e.g. `os.path` for `import os.path` and `from os.path
+  // import join`), relative imports keep their dots
+  getImportedModules() {
+    return stellaGetCapturesCode([
+      "(import_statement name: (dotted_name) @module)",
+      "(import_statement name: (aliased_import name: (dotted_name) @module))",
+      "(import_from_statement module_name: (_) @module)",
+    ], "module", code => code);
+  },
+};
+
+const STELLA_JS_FUNCTION_KINDS = [
+  "function_declaration",
+  "function_expression",
+  "function",
+  "generator_function_declaration",
+  "generator_function",
+  "arrow_function",
+  "method_definition",
+];
+const STELLA_JS_CLASS_KINDS = ["class_declaration", "abstract_class_declaration", "class"];
+
+// JavaScript and TypeScript have the same kinds of nodes for these helpers
+const stellaJsHelpers = {
+  // the function, arrow function or method that contains the node, or null
+  getEnclosingFunction(node) {
+    return stellaGetAncestor(node, STELLA_JS_FUNCTION_KINDS);
+  },
+
+  // the name of the function or method that contains the node, null for the anonymous functions
+  getEnclosingFunctionName(node) {
+    const kinds = ["function_declaration", "generator_function_declaration", "method_definition"];
+    const fn = stellaGetAncestor(node, STELLA_JS_FUNCTION_KINDS);
+    if (fn === null || !kinds.includes(fn.astType)) {
+      return null;
+    }
+    return stellaGetNodeName(fn, `(${fn.astType} name: (_) @name) @node`);
+  },
+
+  // the class that contains the node, or null
+  getEnclosingClass(node) {
+    return stellaGetAncestor(node, STELLA_JS_CLASS_KINDS);
+  },
+
+  // the name of the class that contains the node, null for the anonymous classes
+  getEnclosingClassName(node) {
+    const cls = stellaGetAncestor(node, STELLA_JS_CLASS_KINDS);
+    if (cls === null) {
+      return null;
+    }
+    return stellaGetNodeName(cls, `(${cls.astType} name: (_) @name) @node`);
+  },
+
+  // the modules imported by the file, with `import` or `require()`
+  getImportedModules() {
+    return stellaGetCapturesCode([
+      "(import_statement source: (string) @module)",
+      "(call_expression function: (identifier) @f arguments: (arguments . (string) @module) " +
+        "(#eq? @f \"require\"))",
+    ], "module", code => code.slice(1, -1));
+  },
+};
+
+globalThis.helpers = Object.freeze({
+  version: 1,
+  python: stellaPythonHelpers,
+  js: stellaJsHelpers,
+  javascript: stellaJsHelpers,
+  typescript: stellaJsHelpers,
+});
@@ -10,20 +10,60 @@ function StellaError(startLine, startCol, endLine, endCol, message, severity, ca
       col: endCol,
     },
     this.message = message;
-  this.severity = "NONE";
-  this.category = "SAFETY";
+  // the severity and the category of the rule, unless addViolation overrides them
+  this.severity = null;
+  this.category = null;
   this.fixes = [];
+  this.relatedLocations = [];
   this.addFix = function (fix) {
     this.fixes.push(fix);
     return this;
   }
+  // add a location that explains the violation, in the file of the violation by default
+  this.addRelatedLocation = function (startLine, startCol, endLine, endCol, label, filename) {
+    this.relatedLocations.push(
+      new StellaRelatedLocation(startLine, startCol, endLine, endCol, label, filename),
+    );
+    return this;
+  }
 }
 
-function StellaConsole(startLine, startCol, endLine, endCol, message, severity, category) {
+function StellaRelatedLocation(startLine, startCol, endLine, endCol, label, filename) {
+  this.filename = filename ?? null;
+  this.start = {
+    line: startLine,
+    col: startCol,
+  };
+  this.end = {
+    line: endLine,
+    col: endCol,
+  };
+  this.label = label ?? "";
+}
+
+// the length of the output of a rule on a file, the next lines are dropped
+const STELLA_MAX_CONSOLE_LENGTH = 64 * 1024;
+
+function StellaConsole() {
   this.lines = [];
-  this.log = function (message) {
-    this.lines.push(message);
+  this.length = 0;
+  this.truncated = false;
+  this.log = function (...messages) {
+    if (this.truncated) {
+      return;
+    }
+    const line = messages
+      .map(m => typeof m === "string" ? m : JSON.stringify(m) ?? String(m))
+      .join(" ");
+    if (this.length + line.length > STELLA_MAX_CONSOLE_LENGTH) {
+      this.truncated = true;
+      this.lines.push(`[output truncated, the limit is ${STELLA_MAX_CONSOLE_LENGTH / 1024} KB]`);
+      return;
+    }
+    this.length += line.length + 1;
+    this.lines.push(line);
   }
+  this.error = this.log;
 }
 
 console = new StellaConsole();
@@ -44,8 +84,9 @@ function buildError(startLine, startCol, endLine, endCol, message, severity, cat
   return new StellaError(startLine, startCol, endLine, endCol, message, severity, category);
 }
 
+// the edits of a fix are applied together, a single edit can be given without an array
 function buildFix(message, list) {
-  return new StellaFix(message, list);
+  return new StellaFix(message, Array.isArray(list) ? list : [list]);
 }
 
 function buildEditUpdate(startLine, startCol, endLine, endCol, content) {
@@ -62,6 +103,25 @@ function buildEditAdd(startLine, startCol, content) {
 }
 
 
+// helper functions to build the edits from the range of a node, the columns are computed from
+// the node
+function buildEditInsertBefore(node, content) {
+  return Deno.core.ops.op_node_edit("INSERT_BEFORE", node, content, false);
+}
+
+function buildEditInsertAfter(node, content) {
+  return Deno.core.ops.op_node_edit("INSERT_AFTER", node, content, false);
+}
+
+function buildEditReplaceNode(node, content) {
+  return Deno.core.ops.op_node_edit("REPLACE", node, content, false);
+}
+
+// with consumeSeparator, the separator of the list element (e.g. `,`) is removed too
+function buildEditDeleteNode(node, consumeSeparator) {
+  return Deno.core.ops.op_node_edit("DELETE", node, "", consumeSeparator ?? false);
+}
+
 function buildEdit(startLine, startCol, endLine, endCol, editType, content) {
   const start = {
     line: startLine,
@@ -83,6 +143,52 @@ function addError(error) {
   stellaAllErrors.push(error);
 }
 
+const STELLA_EDIT_TYPES = ["ADD", "REMOVE", "UPDATE"];
+
+function stellaIsPosition(position) {
+  return typeof position === "object" && position !== null &&
+    Number.isInteger(position.line) && Number.isInteger(position.col);
+}
+
+// check the structure of a fix of a rule, the positions of its edits are checked with the code
+// of the file once the rule is executed
+function stellaReadFix(fix, index) {
+  if (typeof fix !== "object" || fix === null || typeof fix.description !== "string") {
+    throw new TypeError(`fix ${index} must be an object with a description and edits`);
+  }
+  const edits = Array.isArray(fix.edits) ? fix.edits : [fix.edits];
+  return new StellaFix(fix.description, edits.map((edit, i) => {
+    const isEdit = typeof edit === "object" && edit !== null &&
+      STELLA_EDIT_TYPES.includes(edit.editType) && stellaIsPosition(edit.start) &&
+      (edit.end === null || edit.end === undefined || stellaIsPosition(edit.end));
+    if (!isEdit) {
+      throw new TypeError(`edit ${i} of fix ${index} is invalid, use the buildEdit helpers`);
+    }
+    return new StellaEdit(edit.start, edit.end ?? null, edit.editType, edit.content ?? null);
+  }));
+}
+
+// Add a violation with its fixes: `[{description, edits: [...]}, ...]` where the edits are built
+// with the buildEdit helpers. The first fix is the one applied by default, the others are
+// alternatives. The fixes with edits that cannot be applied to the file are removed. The
+// overrides are the ones of addViolation.
+function addViolationWithFix(violation, fixes, overrides) {
+  const list = Array.isArray(fixes) ? fixes : [fixes];
+  list.forEach((fix, index) => violation.addFix(stellaReadFix(fix, index)));
+  addViolation(violation, overrides);
+}
+
+// Add a violation. Its severity and category can override the ones of the rule with
+// `{severity, category}` (e.g. `{severity: "ERROR"}`). The invalid values are reported in the
+// output of the rule and the values of the rule are used instead.
+function addViolation(violation, overrides) {
+  const severity = overrides?.severity;
+  const category = overrides?.category;
+  violation.severity = severity === undefined || severity === null ? null : String(severity);
+  violation.category = category === undefined || category === null ? null : String(category);
+  addError(violation);
+}
+
 // helper function getCode
 function getCode(start, end, code) {
   const lines = code.split("\n");
@@ -106,7 +212,214 @@ function getCode(start, end, code) {
   return code.substring(startChar, endChar);
 };
 
-// helper function getCodeForNode
+// helper function getCodeForNode, the code of a node. The code is read with the byte offsets of
+// the node, the code of the file is only used when the rule runs without it.
 function getCodeForNode(node, code) {
-  return getCode(node.start, node.end, code);
+  return Deno.core.ops.op_get_code_for_node(node) ?? getCode(node.start, node.end, code);
+}
+
+// helper function getChildByField, the first child in the field or undefined
+function getChildByField(node, fieldName) {
+  return node.children.find(child => child.fieldName === fieldName);
+}
+
+// helper function getChildrenByField, all the children in the field
+function getChildrenByField(node, fieldName) {
+  return node.children.filter(child => child.fieldName === fieldName);
+}
+
+// helper function getAncestors, the ancestors of a captured node from its parent to the root of
+// the tree. They are in the node when the rule includes them, computed from the tree otherwise.
+function getAncestors(node) {
+  if (node.ancestors && node.ancestors.length > 0) {
+    return node.ancestors;
+  }
+  return Deno.core.ops.op_get_node_ancestors(node);
+}
+
+// helper function queryInNode, the matches of a query in a captured node. The positions of the
+// matches are in the file.
+function queryInNode(node, query) {
+  const matchNodes = Deno.core.ops.op_query_nodes_in_node(query, node);
+  return stellaReadMatchNodes(STELLA_NODE_SCHEMA_VERSION, matchNodes);
+}
+
+// helper function queryInFile, the matches of a query in the whole file
+function queryInFile(query) {
+  const matchNodes = Deno.core.ops.op_query_nodes_in_node(query, null);
+  return stellaReadMatchNodes(STELLA_NODE_SCHEMA_VERSION, matchNodes);
+}
+
+// Add back the fields that are not sent when they have their default value (version 2 of the
+// nodes), so that the rules can read all the fields of the nodes.
+function stellaReadNode(node) {
+  node.text = node.text ?? null;
+  node.textTruncated = node.textTruncated ?? false;
+  node.fieldName = node.fieldName ?? null;
+  node.children = node.children ?? [];
+  node.isError = node.isError ?? false;
+  node.isMissing = node.isMissing ?? false;
+  node.isTruncated = node.isTruncated ?? false;
+  node.ancestors = node.ancestors ?? [];
+  node.parentKind = node.parentKind ?? null;
+  node.parentFieldName = node.parentFieldName ?? null;
+  node.previousSibling = node.previousSibling ?? null;
+  node.nextSibling = node.nextSibling ?? null;
+  node.children.forEach(stellaReadNode);
+  return node;
+}
+
+// The versions of the nodes this code can read, see NODE_SCHEMA_CHANGES in the kernel
+const STELLA_MIN_NODE_SCHEMA_VERSION = 1;
+const STELLA_MAX_NODE_SCHEMA_VERSION = 4;
+
+// the version of the nodes of the file being analyzed, set by stellaExecute
+let STELLA_NODE_SCHEMA_VERSION = null;
+
+// Check that the nodes sent by the kernel can be read, before running the rule
+function stellaCheckNodeSchemaVersion(version) {
+  if (!Number.isInteger(version) || version < STELLA_MIN_NODE_SCHEMA_VERSION ||
+      version > STELLA_MAX_NODE_SCHEMA_VERSION) {
+    throw new Error(`unsupported node schema version ${version}, the supported versions are ` +
+                    `${STELLA_MIN_NODE_SCHEMA_VERSION} to ${STELLA_MAX_NODE_SCHEMA_VERSION}`);
+  }
+}
+
+// The rule running on the nodes cannot be changed by the rules
+function stellaFreezeRule(context) {
+  ["ruleName", "ruleSeverity"].forEach(field => {
+    Object.defineProperty(context, field, {
+      value: context[field] ?? null,
+      writable: false,
+      enumerable: true,
+    });
+  });
+}
+
+// Read the matches sent to the rule in the version of the nodes
+function stellaReadMatchNodes(version, matchNodes) {
+  matchNodes.forEach(matchNode => {
+    if (matchNode.context) {
+      // the matches are all sent with the version of the script
+      const schemaVersion = matchNode.context.schemaVersion;
+      if (schemaVersion !== undefined && schemaVersion !== version) {
+        throw new Error(`node schema version ${schemaVersion} does not match version ${version}`);
+      }
+      stellaFreezeRule(matchNode.context);
+    }
+  });
+  if (version < 2) {
+    return matchNodes;
+  }
+  matchNodes.forEach(matchNode => {
+    Object.values(matchNode.captures).forEach(stellaReadNode);
+    Object.values(matchNode.capturesList).forEach(nodes => nodes.forEach(stellaReadNode));
+  });
+  return matchNodes;
+}
+
+// helper function getLine, the text of a line of the file (starting at 1) without its line
+// ending, or undefined after the end of the file
+function getLine(line) {
+  return Deno.core.ops.op_get_line(line) ?? undefined;
+}
+
+// helper function getLines, the text of the lines of the file from start to end (included)
+function getLines(start, end) {
+  return Deno.core.ops.op_get_lines(start, end);
+}
+
+// The code of the rules compiled in the runtime. Each rule is a function that declares the code
+// of the rule and returns its visit and finalize functions, it is called again for each file.
+const stellaRuleFactories = [];
+
+// The properties of the global object and of the builtin objects (e.g. `Array` and
+// `Array.prototype`) before the first rule ran. The runtime is reused for all the files, these
+// objects are restored before each execution so that the global variables and the properties of
+// the builtins set by a rule on a file are not seen on the next file.
+let stellaSavedObjects = null;
+// the functions that restore the objects, a rule that replaces them does not stop the restore.
+// The loops index the arrays for the same reason.
+const stellaOwnKeys = Reflect.ownKeys;
+const stellaDeleteProperty = Reflect.deleteProperty;
+const stellaHasOwn = Object.hasOwn;
+const stellaGetOwnPropertyDescriptor = Object.getOwnPropertyDescriptor;
+const stellaDefineProperty = Object.defineProperty;
+
+// restore the properties of an object: the new properties are removed and the changed properties
+// get their saved descriptor back
+function stellaRestoreProperties(object, saved) {
+  const names = stellaOwnKeys(object);
+  for (let i = 0; i < names.length; i++) {
+    if (!stellaHasOwn(saved, names[i])) {
+      stellaDeleteProperty(object, names[i]);
+    }
+  }
+  const savedNames = stellaOwnKeys(saved);
+  for (let i = 0; i < savedNames.length; i++) {
+    const name = savedNames[i];
+    const descriptor = saved[name];
+    const current = stellaGetOwnPropertyDescriptor(object, name);
+    if (current === undefined || current.value !== descriptor.value ||
+        current.get !== descriptor.get || current.set !== descriptor.set) {
+      try {
+        stellaDefineProperty(object, name, descriptor);
+      } catch (e) {
+        // the properties that cannot be configured cannot be changed by the rules either
+      }
+    }
+  }
+}
+
+function stellaResetGlobals() {
+  if (stellaSavedObjects === null) {
+    // the global object, the builtins it holds and their prototypes
+    const objects = new Set([globalThis]);
+    for (const descriptor of Object.values(Object.getOwnPropertyDescriptors(globalThis))) {
+      const value = descriptor.value;
+      if ((typeof value === "object" && value !== null) || typeof value === "function") {
+        objects.add(value);
+        if (typeof value.prototype === "object" && value.prototype !== null) {
+          objects.add(value.prototype);
+        }
+      }
+    }
+    stellaSavedObjects = [...objects].map(o => [o, Object.getOwnPropertyDescriptors(o)]);
+    return;
+  }
+  for (let i = 0; i < stellaSavedObjects.length; i++) {
+    stellaRestoreProperties(stellaSavedObjects[i][0], stellaSavedObjects[i][1]);
+  }
+}
+
+// Execute a rule on the matches of a file. The rule visits the matches with a state for the
+// file, then finalize is called once all the matches are visited. The code blocks of a file
+// (e.g. the scripts of an HTML page) are executed one by one, each with its own state.
+function stellaExecute(factoryId, filename, version, comments, matchNodes) {
+  stellaResetGlobals();
+  stellaAllErrors.length = 0;
+  console = new StellaConsole();
+  stellaCheckNodeSchemaVersion(version);
+  STELLA_NODE_SCHEMA_VERSION = version;
+
+  const rule = stellaRuleFactories[factoryId](filename);
+  const state = {};
+  const nodes = stellaReadMatchNodes(version, matchNodes);
+  // the comments of the file are sent once, all the matches share them
+  nodes.forEach(n => {
+    if (n.context) {
+      n.context.comments = comments;
+    }
+  });
+  if (nodes.length > 0 && typeof rule.visit !== "function") {
+    throw new ReferenceError("visit is not defined");
+  }
+  nodes.forEach(n => rule.visit(n, filename, n.context.code, state));
+  if (typeof rule.finalize === "function" && nodes.length > 0) {
+    rule.finalize(nodes[0].context, state);
+  }
+  return {
+    violations: stellaAllErrors,
+    console: console.lines,
+  };
 }
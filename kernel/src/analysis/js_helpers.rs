@@ -0,0 +1,181 @@
+// The helpers of the rules for each language (e.g. `helpers.python.getImportedModules()`). They
+// are in the snapshot of the runtime of the rules, with stella.js.
+pub const HELPERS_CODE: &str = include_str!("js/helpers.js");
+
+// The version of the helpers (`helpers.version`), increased when the behavior of a helper
+// changes.
+pub const HELPERS_VERSION: u32 = 1;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyze::analyze;
+    use crate::model::analysis::AnalysisOptions;
+    use crate::model::common::Language;
+    use crate::model::rule::{RuleCategory, RuleInternal, RuleSeverity};
+    use std::collections::HashMap;
+
+    const PYTHON_CODE: &str = include_str!("../../resources/test/helpers/python.py");
+    const JAVASCRIPT_CODE: &str = include_str!("../../resources/test/helpers/javascript.js");
+    const TYPESCRIPT_CODE: &str = include_str!("../../resources/test/helpers/typescript.ts");
+
+    // run a rule on the code and get what it printed
+    fn run(language: Language, filename: &str, code: &str, query: &str, rule_code: &str) -> String {
+        let rule = RuleInternal {
+            name: "helpers".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language,
+            code: rule_code.to_string(),
+            tree_sitter_query: Some(query.to_string()),
+            variables: HashMap::new(),
+            ..RuleInternal::default()
+        };
+        let analysis_options = AnalysisOptions {
+            log_output: true,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let results = analyze(&language, vec![rule], filename, code, &analysis_options);
+        assert_eq!(1, results.len());
+        assert!(
+            results[0].execution_error.is_none(),
+            "{:?}",
+            results[0].execution_error
+        );
+        results[0].output.clone().unwrap_or_default()
+    }
+
+    const ENCLOSING_RULE_CODE: &str = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    const h = helpers[node.context.language.toLowerCase()];
+    const fn = h.getEnclosingFunction(name);
+    console.log([
+        fn ? fn.astType : null,
+        h.getEnclosingFunctionName(name),
+        h.getEnclosingClassName(name),
+    ].join("|"));
+}
+"#;
+
+    const IMPORTS_RULE_CODE: &str = r#"
+function visit(node, filename, code) {
+    const h = helpers[node.context.language.toLowerCase()];
+    console.log(JSON.stringify(h.getImportedModules()));
+}
+"#;
+
+    #[test]
+    fn test_helpers_version() {
+        let output = run(
+            Language::Python,
+            "myfile.py",
+            PYTHON_CODE,
+            "(module) @module",
+            "function visit(node, filename, code) { console.log(helpers.version); }",
+        );
+        assert_eq!(HELPERS_VERSION.to_string(), output);
+    }
+
+    #[test]
+    fn test_python_helpers() {
+        let query = r#"
+(call function: (identifier) @name (#eq? @name "eval"))
+(call function: (attribute attribute: (identifier) @name) (#eq? @name "system"))
+"#;
+        let output = run(
+            Language::Python,
+            "myfile.py",
+            PYTHON_CODE,
+            query,
+            ENCLOSING_RULE_CODE,
+        );
+        assert_eq!(
+            vec![
+                // the closest function, not the function that contains it
+                "function_definition|inner|",
+                "function_definition|run|Service",
+                "||",
+            ],
+            output.lines().collect::<Vec<_>>()
+        );
+
+        let output = run(
+            Language::Python,
+            "myfile.py",
+            PYTHON_CODE,
+            "(module) @module",
+            IMPORTS_RULE_CODE,
+        );
+        assert_eq!(r#"["os","os.path","collections",".","..models"]"#, output);
+    }
+
+    #[test]
+    fn test_javascript_helpers() {
+        let query = r#"
+(call_expression function: (identifier) @name (#eq? @name "eval"))
+(call_expression
+  function: (member_expression property: (property_identifier) @name)
+  (#eq? @name "exec"))
+"#;
+        let output = run(
+            Language::JavaScript,
+            "myfile.js",
+            JAVASCRIPT_CODE,
+            query,
+            ENCLOSING_RULE_CODE,
+        );
+        assert_eq!(
+            vec![
+                // the arrow functions and the class expressions have no name
+                "arrow_function||",
+                "method_definition|run|Service",
+                "method_definition|start|",
+                "||",
+            ],
+            output.lines().collect::<Vec<_>>()
+        );
+
+        let output = run(
+            Language::JavaScript,
+            "myfile.js",
+            JAVASCRIPT_CODE,
+            "(program) @program",
+            IMPORTS_RULE_CODE,
+        );
+        assert_eq!(r#"["fs","path","child_process"]"#, output);
+    }
+
+    #[test]
+    fn test_typescript_helpers() {
+        let query = r#"
+(call_expression function: (identifier) @name (#match? @name "^(eval|exec)$"))
+"#;
+        let output = run(
+            Language::TypeScript,
+            "myfile.ts",
+            TYPESCRIPT_CODE,
+            query,
+            ENCLOSING_RULE_CODE,
+        );
+        assert_eq!(
+            vec![
+                "function_declaration|handler|",
+                "method_definition|run|Service",
+            ],
+            output.lines().collect::<Vec<_>>()
+        );
+
+        let output = run(
+            Language::TypeScript,
+            "myfile.ts",
+            TYPESCRIPT_CODE,
+            "(program) @program",
+            IMPORTS_RULE_CODE,
+        );
+        assert_eq!(r#"["fs","path","./config","child_process"]"#, output);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::model::analysis::PositionEncoding;
+use crate::model::common::Position;
+use std::ops::Range;
+use tree_sitter::Point;
+
+// Start of each line of the code, computed once to convert between byte offsets, points and
+// positions without scanning the code for each conversion. The lines end with `\n`, the `\r`
+// of CRLF line endings is not in the text of the lines.
+#[derive(Clone, Debug)]
+pub struct LineIndex<'a> {
+    code: &'a str,
+    // byte offset of the start of each line, the first line starts at 0
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(code: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(code.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineIndex { code, line_starts }
+    }
+
+    pub fn code(&self) -> &'a str {
+        self.code
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    // get the byte range of a line (row starting at 0), with the `\r` of its line ending but
+    // without the `\n`
+    pub fn get_line_range(&self, row: usize) -> Option<Range<usize>> {
+        let start = *self.line_starts.get(row)?;
+        let end = self
+            .line_starts
+            .get(row + 1)
+            .map_or(self.code.len(), |next_start| next_start - 1);
+        Some(start..end)
+    }
+
+    // get the text of a line (row starting at 0), without its line ending
+    pub fn get_line(&self, row: usize) -> Option<&'a str> {
+        let line = &self.code[self.get_line_range(row)?];
+        Some(line.strip_suffix('\r').unwrap_or(line))
+    }
+
+    // get the row and the column in bytes (starting at 0) of a byte offset
+    pub fn get_point(&self, offset: usize) -> Point {
+        let offset = offset.min(self.code.len());
+        let row = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        Point {
+            row,
+            column: offset - self.line_starts[row],
+        }
+    }
+
+    // get the column (starting at 0) of a point, counted in the encoding
+    pub fn get_column(&self, point: Point, encoding: PositionEncoding) -> usize {
+        let Some(line_start) = self.line_starts.get(point.row) else {
+            return point.column;
+        };
+        match (
+            encoding,
+            self.code.get(*line_start..line_start + point.column),
+        ) {
+            (PositionEncoding::Utf8Bytes, _) | (_, None) => point.column,
+            (_, Some(before)) => before.chars().map(|c| encoding.get_char_width(c)).sum(),
+        }
+    }
+
+    // get the position (line and column starting at 1) of a byte offset
+    pub fn get_position(&self, offset: usize, encoding: PositionEncoding) -> Position {
+        let point = self.get_point(offset);
+        Position {
+            line: u32::try_from(point.row + 1).unwrap(),
+            col: u32::try_from(self.get_column(point, encoding) + 1).unwrap(),
+            cell: None,
+        }
+    }
+
+    // get the byte offset of a position (line and column starting at 1). The columns after the
+    // end of the line are at the end of the line, before its line ending.
+    pub fn get_offset(&self, position: &Position, encoding: PositionEncoding) -> Option<usize> {
+        let row = usize::try_from(position.line).ok()?.checked_sub(1)?;
+        let line = self.get_line(row)?;
+        let line_start = self.line_starts[row];
+        let target_column = usize::try_from(position.col).ok()?.saturating_sub(1);
+        let mut column = 0;
+        for (offset, c) in line.char_indices() {
+            if column >= target_column {
+                return Some(line_start + offset);
+            }
+            column += encoding.get_char_width(c);
+        }
+        Some(line_start + line.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODES: &[&str] = &[
+        "",
+        "a = 1",
+        "a = 1\n",
+        "a = 1\r\nb = \"é😀\"\r\n\r\nc = 3",
+        "\n\n  x\n",
+        "s = \"日本語\"; foo(x)\r\n  é\n😀😀 = 1\n",
+    ];
+
+    // the conversions used before the index: scan the code before the offset
+    fn get_line_col_by_scan(code: &str, offset: usize) -> (usize, usize) {
+        let before = &code[..offset];
+        let line = before.matches('\n').count();
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, offset - line_start)
+    }
+
+    #[test]
+    fn test_get_point() {
+        for code in CODES {
+            let line_index = LineIndex::new(code);
+            for (offset, _) in code.char_indices().chain([(code.len(), ' ')]) {
+                let point = line_index.get_point(offset);
+                assert_eq!(
+                    get_line_col_by_scan(code, offset),
+                    (point.row, point.column),
+                    "offset {offset} of {code:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_line() {
+        let line_index = LineIndex::new(CODES[3]);
+        assert_eq!(4, line_index.line_count());
+        assert_eq!(Some("a = 1"), line_index.get_line(0));
+        assert_eq!(Some("b = \"é😀\""), line_index.get_line(1));
+        assert_eq!(Some(""), line_index.get_line(2));
+        assert_eq!(Some("c = 3"), line_index.get_line(3));
+        assert_eq!(None, line_index.get_line(4));
+        assert_eq!(Some(7..20), line_index.get_line_range(1));
+        assert_eq!(Some(""), LineIndex::new("").get_line(0));
+    }
+
+    // the positions are the same as the positions of the nodes and they go back to the same
+    // offsets
+    #[test]
+    fn test_get_position_and_offset() {
+        for encoding in [
+            PositionEncoding::Utf8Bytes,
+            PositionEncoding::Utf16,
+            PositionEncoding::Chars,
+        ] {
+            for code in CODES {
+                let line_index = LineIndex::new(code);
+                for (offset, _) in code.char_indices().chain([(code.len(), ' ')]) {
+                    let position = line_index.get_position(offset, encoding);
+                    let (row, byte_column) = get_line_col_by_scan(code, offset);
+                    let line_start = offset - byte_column;
+                    let expected_column: usize = code[line_start..offset]
+                        .chars()
+                        .map(|c| encoding.get_char_width(c))
+                        .sum();
+                    assert_eq!(
+                        (row + 1, expected_column + 1),
+                        (position.line as usize, position.col as usize)
+                    );
+                    // the `\n` of a CRLF line ending is after the end of the line, its
+                    // position goes back to the `\r`
+                    let expected_offset = if code[..offset].ends_with('\r') {
+                        offset - 1
+                    } else {
+                        offset
+                    };
+                    assert_eq!(
+                        Some(expected_offset),
+                        line_index.get_offset(&position, encoding)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_offset_out_of_range() {
+        let line_index = LineIndex::new("a = 1\r\nb = 2\r\n");
+        let position = |line, col| Position {
+            line,
+            col,
+            cell: None,
+        };
+        let encoding = PositionEncoding::Chars;
+        assert_eq!(Some(5), line_index.get_offset(&position(1, 100), encoding));
+        assert_eq!(Some(14), line_index.get_offset(&position(3, 1), encoding));
+        assert_eq!(None, line_index.get_offset(&position(4, 1), encoding));
+        assert_eq!(None, line_index.get_offset(&position(0, 1), encoding));
+    }
+}
@@ -0,0 +1,244 @@
+use crate::analysis::embedded::map_rule_result_positions;
+use crate::model::common::Position;
+use crate::model::rule::RuleResult;
+use anyhow::anyhow;
+use serde::Deserialize;
+use std::path::Path;
+
+const NOTEBOOK_EXTENSION: &str = "ipynb";
+
+#[derive(Deserialize)]
+struct NotebookLanguageInfo {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NotebookMetadata {
+    language_info: Option<NotebookLanguageInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NotebookCellSource {
+    Lines(Vec<String>),
+    Text(String),
+}
+
+#[derive(Deserialize)]
+struct NotebookCell {
+    cell_type: String,
+    source: Option<NotebookCellSource>,
+}
+
+#[derive(Deserialize)]
+struct Notebook {
+    cells: Vec<NotebookCell>,
+    metadata: Option<NotebookMetadata>,
+}
+
+// Location of a line of the generated source in the original notebook.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CellLine {
+    cell: u32,
+    line: u32,
+}
+
+// Python source generated from the code cells of a notebook, with the mapping
+// of each line back to its cell.
+#[derive(Debug)]
+pub struct NotebookSource {
+    pub code: String,
+    lines: Vec<CellLine>,
+}
+
+pub fn is_notebook(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .map(|e| e.eq_ignore_ascii_case(NOTEBOOK_EXTENSION))
+        .unwrap_or(false)
+}
+
+// IPython magics (%matplotlib inline, !pip install ...) and help requests (obj?) are not valid
+// Python and would generate ERROR nodes.
+fn is_ipython_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with('%') || trimmed.starts_with('!') || is_help_request(trimmed)
+}
+
+// check if the line is a help request: a name or a dotted name followed by `?` or `??` (e.g.
+// `os.path.join?`). Other lines ending with `?` (e.g. in a comment) are Python.
+fn is_help_request(line: &str) -> bool {
+    line.strip_suffix("??")
+        .or_else(|| line.strip_suffix('?'))
+        .map(|name| name.split('.').all(is_identifier))
+        .unwrap_or(false)
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|c| c.is_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// Build the Python source from all the code cells of the notebook. Markdown and raw cells
+// are skipped and magics are commented out so that the lines keep the same numbering.
+pub fn get_notebook_source(content: &str) -> anyhow::Result<NotebookSource> {
+    let notebook: Notebook = serde_json::from_str(content)?;
+
+    if let Some(name) = notebook
+        .metadata
+        .and_then(|m| m.language_info)
+        .and_then(|l| l.name)
+    {
+        if !name.eq_ignore_ascii_case("python") {
+            return Err(anyhow!("notebook language {} is not supported", name));
+        }
+    }
+
+    let mut code = String::new();
+    let mut lines = vec![];
+
+    for (cell_index, cell) in notebook.cells.iter().enumerate() {
+        if cell.cell_type != "code" {
+            continue;
+        }
+        let source = match &cell.source {
+            Some(NotebookCellSource::Lines(l)) => l.concat(),
+            Some(NotebookCellSource::Text(t)) => t.clone(),
+            None => continue,
+        };
+        // cell magics (%%bash, %%timeit, ...) apply to the whole cell
+        let is_cell_magic = source.trim_start().starts_with("%%");
+
+        for (line_index, line) in source.lines().enumerate() {
+            if is_cell_magic || is_ipython_line(line) {
+                code.push('#');
+            }
+            code.push_str(line);
+            code.push('\n');
+            lines.push(CellLine {
+                cell: u32::try_from(cell_index).unwrap(),
+                line: u32::try_from(line_index + 1).unwrap(),
+            });
+        }
+    }
+
+    Ok(NotebookSource { code, lines })
+}
+
+impl NotebookSource {
+    // Map a position in the generated source to its cell and line in the cell.
+    pub fn map_position(&self, position: &Position) -> Position {
+        let index = usize::try_from(position.line).unwrap().saturating_sub(1);
+        let cell_line = match self.lines.get(index) {
+            Some(cell_line) => *cell_line,
+            // the end of the last node may be past the last line
+            None => match self.lines.last() {
+                Some(last) => CellLine {
+                    cell: last.cell,
+                    line: last.line + u32::try_from(index + 1 - self.lines.len()).unwrap(),
+                },
+                None => return position.clone(),
+            },
+        };
+
+        Position {
+            line: cell_line.line,
+            col: position.col,
+            cell: Some(cell_line.cell),
+        }
+    }
+
+    pub fn map_rule_result(&self, rule_result: RuleResult) -> RuleResult {
+        map_rule_result_positions(rule_result, |p| self.map_position(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"
+{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": ["# My notebook\n", "Some explanations"]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "metadata": {},
+   "outputs": [],
+   "source": ["%matplotlib inline\n", "!pip install pandas\n", "import pandas as pd"]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 2,
+   "metadata": {},
+   "outputs": [],
+   "source": "def foo(arg1):\n    pass\n"
+  }
+ ],
+ "metadata": {
+  "language_info": {"name": "python"}
+ },
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"##;
+
+    #[test]
+    fn test_is_notebook() {
+        assert!(is_notebook("notebooks/analysis.ipynb"));
+        assert!(is_notebook("Analysis.IPYNB"));
+        assert!(!is_notebook("analysis.py"));
+    }
+
+    #[test]
+    fn test_get_notebook_source() {
+        let notebook = get_notebook_source(NOTEBOOK).unwrap();
+        assert_eq!(
+            notebook.code,
+            "#%matplotlib inline\n#!pip install pandas\nimport pandas as pd\ndef foo(arg1):\n    pass\n"
+        );
+        let position = notebook.map_position(&Position {
+            line: 4,
+            col: 5,
+            cell: None,
+        });
+        assert_eq!(position.cell, Some(2));
+        assert_eq!(position.line, 1);
+        assert_eq!(position.col, 5);
+    }
+
+    #[test]
+    fn test_get_notebook_source_cell_magic() {
+        let content = r#"{"cells": [{"cell_type": "code", "source": ["%%bash\n", "ls -l"]}]}"#;
+        let notebook = get_notebook_source(content).unwrap();
+        assert_eq!(notebook.code, "#%%bash\n#ls -l\n");
+    }
+
+    #[test]
+    fn test_is_ipython_line() {
+        assert!(is_ipython_line("%matplotlib inline"));
+        assert!(is_ipython_line("  !pip install pandas"));
+        assert!(is_ipython_line("pd?"));
+        assert!(is_ipython_line("pd.DataFrame.merge??"));
+        assert!(!is_ipython_line("x = 1  # why?"));
+        assert!(!is_ipython_line("print('done?')"));
+        assert!(!is_ipython_line("?"));
+        assert!(!is_ipython_line("pd.?"));
+    }
+
+    #[test]
+    fn test_get_notebook_source_invalid() {
+        assert!(get_notebook_source("not a notebook").is_err());
+        let content = r#"{"cells": [], "metadata": {"language_info": {"name": "R"}}}"#;
+        assert!(get_notebook_source(content).is_err());
+    }
+}
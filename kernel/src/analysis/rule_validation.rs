@@ -0,0 +1,158 @@
+use crate::analysis::tree_sitter::{get_query, get_unknown_predicates, PRIVATE_CAPTURE_PREFIX};
+use crate::model::analysis::QueryError;
+use crate::model::rule::RuleInternal;
+
+// Get the names of the captures read with a string in the code of a rule (e.g.
+// `node.captures["name"]` or `node.capturesList['name']`). The accesses with a variable
+// (e.g. `node.captures[name]`) cannot be checked and are ignored.
+fn get_capture_accesses(code: &str) -> Vec<String> {
+    let mut names = vec![];
+    for property in ["captures", "capturesList"] {
+        let pattern = format!("{property}[");
+        let mut offset = 0;
+        while let Some(index) = code[offset..].find(&pattern) {
+            let start = offset + index;
+            offset = start + pattern.len();
+            // the end of another identifier (e.g. `allcaptures[`)
+            if code[..start].ends_with(|c: char| c.is_alphanumeric() || c == '_' || c == '$') {
+                continue;
+            }
+            let rest = code[offset..].trim_start();
+            let Some(quote) = rest
+                .chars()
+                .next()
+                .filter(|c| matches!(c, '"' | '\'' | '`'))
+            else {
+                continue;
+            };
+            let Some(end) = rest[1..].find(quote) else {
+                continue;
+            };
+            let name = &rest[1..end + 1];
+            // the string must be the whole index, without escapes or substitutions
+            let is_literal = !name.contains('\\') && !name.contains("${");
+            if is_literal && rest[end + 2..].trim_start().starts_with(']') {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+// Get the captures read by the code of a rule that are not defined in its query. The code
+// gets undefined for these captures and the rule silently reports no violation.
+pub fn get_unknown_captures(rule: &RuleInternal) -> Result<Vec<String>, QueryError> {
+    let Some(query_code) = &rule.tree_sitter_query else {
+        return Ok(vec![]);
+    };
+    let query = get_query(query_code, &rule.language)?;
+    let mut unknown_captures: Vec<String> = get_capture_accesses(&rule.code)
+        .into_iter()
+        .filter(|name| {
+            // the private captures are not passed to the rules
+            name.starts_with(PRIVATE_CAPTURE_PREFIX) || !query.capture_names().contains(name)
+        })
+        .collect();
+    unknown_captures.sort();
+    unknown_captures.dedup();
+    Ok(unknown_captures)
+}
+
+// Get the predicates of the query of a rule that the analyzer does not evaluate. They are
+// ignored and the query may match more nodes than the rule expects.
+pub fn get_unknown_query_predicates(rule: &RuleInternal) -> Result<Vec<String>, QueryError> {
+    let Some(query_code) = &rule.tree_sitter_query else {
+        return Ok(vec![]);
+    };
+    let query = get_query(query_code, &rule.language)?;
+    Ok(get_unknown_predicates(&query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::common::Language;
+    use crate::model::rule::{RuleCategory, RuleSeverity};
+    use std::collections::HashMap;
+
+    fn rule(code: &str) -> RuleInternal {
+        RuleInternal {
+            name: "myrule".to_string(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::CodeStyle,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: code.to_string(),
+            tree_sitter_query: Some(
+                "(function_definition name: (identifier) @funcname body: (_) @_body)".to_string(),
+            ),
+            variables: HashMap::new(),
+            include_unnamed_nodes: false,
+            max_capture_nodes: None,
+            include_siblings: false,
+            include_ancestors: false,
+            capture_mode: false,
+            arguments: HashMap::new(),
+            cwe: vec![],
+            timeout_ms: None,
+            query_match_limit: None,
+        }
+    }
+
+    #[test]
+    fn test_get_unknown_captures_matching() {
+        let code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["funcname"];
+    const names = node.capturesList[ 'funcname' ];
+}
+"#;
+        assert!(get_unknown_captures(&rule(code)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_unknown_captures_mismatched() {
+        let code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["functionName"];
+    const other = node.captures[`functionName`];
+    const names = node.capturesList["names"];
+    const body = node.captures["_body"];
+}
+"#;
+        assert_eq!(
+            vec!["_body", "functionName", "names"],
+            get_unknown_captures(&rule(code)).unwrap()
+        );
+    }
+
+    // the names computed at runtime cannot be verified
+    #[test]
+    fn test_get_unknown_captures_dynamic() {
+        let code = r#"
+function visit(node, filename, code) {
+    const captureName = "function" + "Name";
+    const name = node.captures[captureName];
+    const other = node.captures[`${captureName}`];
+    const prefixed = node.captures["function" + suffix];
+    const allcaptures = {};
+    const value = allcaptures["unknown"];
+}
+"#;
+        assert!(get_unknown_captures(&rule(code)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_unknown_query_predicates() {
+        let mut r = rule("function visit(node, filename, code) {}");
+        assert!(get_unknown_query_predicates(&r).unwrap().is_empty());
+
+        r.tree_sitter_query =
+            Some(r#"((identifier) @name (#any-of? @name "eval") (#is-not? local))"#.to_string());
+        assert_eq!(vec!["#is-not?"], get_unknown_query_predicates(&r).unwrap());
+
+        r.tree_sitter_query = Some("(identifier".to_string());
+        assert!(get_unknown_query_predicates(&r).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::model::analysis::FileMetadata;
+use crate::model::common::Language;
+
+// directories that only contain tests, in all the languages
+const TEST_DIRECTORIES: [&str; 4] = ["test", "tests", "__tests__", "spec"];
+
+// extensions of the JavaScript and TypeScript files, tests are named `*.test.<ext>` or
+// `*.spec.<ext>`
+const JS_EXTENSIONS: [&str; 8] = ["js", "jsx", "mjs", "cjs", "ts", "tsx", "mts", "cts"];
+
+// Check if a file contains tests, using the conventions of each language for the paths and the
+// names of the test files (e.g. `tests/`, `test_api.py`, `api.spec.ts`). The Rust files with a
+// `#[cfg(test)]` module are test files too.
+pub fn is_test_file(language: &Language, file_metadata: &FileMetadata, code: &str) -> bool {
+    let in_test_directory = file_metadata
+        .directory
+        .split('/')
+        .any(|d| TEST_DIRECTORIES.contains(&d.to_lowercase().as_str()));
+    if in_test_directory {
+        return true;
+    }
+
+    let basename = file_metadata.basename.as_str();
+    let (stem, extension) = basename.rsplit_once('.').unwrap_or((basename, ""));
+    match language {
+        Language::Python => {
+            stem.starts_with("test_") || stem.ends_with("_test") || basename == "conftest.py"
+        }
+        Language::JavaScript | Language::TypeScript => {
+            JS_EXTENSIONS.contains(&extension)
+                && (stem.ends_with(".test") || stem.ends_with(".spec"))
+        }
+        Language::Go | Language::Dart | Language::Elixir => stem.ends_with("_test"),
+        Language::Ruby => stem.ends_with("_spec") || stem.ends_with("_test"),
+        Language::Java
+        | Language::Kotlin
+        | Language::Csharp
+        | Language::Scala
+        | Language::Groovy
+        | Language::Php
+        | Language::Swift => {
+            stem.ends_with("Test") || stem.ends_with("Tests") || stem.ends_with("Spec")
+        }
+        Language::Rust => code.contains("#[cfg(test)]"),
+        Language::C | Language::Cpp => stem.ends_with("_test") || stem.ends_with("_unittest"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_test(language: Language, filename: &str) -> bool {
+        is_test_file(&language, &FileMetadata::new(filename, 0), "")
+    }
+
+    #[test]
+    fn test_is_test_file_directories() {
+        assert!(is_test(Language::Python, "tests/fixtures/passwords.py"));
+        assert!(is_test(
+            Language::Java,
+            "src/test/java/com/example/Api.java"
+        ));
+        assert!(is_test(Language::JavaScript, "src/__tests__/api.js"));
+        assert!(is_test(Language::Ruby, "spec/models/user.rb"));
+        assert!(is_test(Language::Yaml, "Tests/config.yaml"));
+        assert!(!is_test(Language::Python, "src/testing/api.py"));
+        assert!(!is_test(Language::Python, "tests.py"));
+    }
+
+    #[test]
+    fn test_is_test_file_names() {
+        assert!(is_test(Language::Python, "src/test_api.py"));
+        assert!(is_test(Language::Python, "src/api_test.py"));
+        assert!(is_test(Language::Python, "conftest.py"));
+        assert!(!is_test(Language::Python, "src/api.py"));
+        assert!(!is_test(Language::Python, "src/latest_api.py"));
+
+        assert!(is_test(Language::TypeScript, "src/api.spec.ts"));
+        assert!(is_test(Language::TypeScript, "src/api.test.tsx"));
+        assert!(is_test(Language::JavaScript, "src/api.test.js"));
+        assert!(!is_test(Language::JavaScript, "src/api.js"));
+        assert!(!is_test(Language::JavaScript, "src/spec.js"));
+
+        assert!(is_test(Language::Go, "pkg/api_test.go"));
+        assert!(!is_test(Language::Go, "pkg/api.go"));
+
+        assert!(is_test(Language::Ruby, "lib/user_spec.rb"));
+        assert!(is_test(Language::Ruby, "lib/user_test.rb"));
+
+        assert!(is_test(Language::Java, "src/main/java/ApiTest.java"));
+        assert!(is_test(Language::Kotlin, "src/ApiTests.kt"));
+        assert!(is_test(Language::Csharp, "Api.Tests/ApiTests.cs"));
+        assert!(is_test(Language::Groovy, "src/ApiSpec.groovy"));
+        assert!(!is_test(Language::Java, "src/main/java/Api.java"));
+        assert!(!is_test(Language::Java, "src/main/java/Contest.java"));
+
+        assert!(is_test(Language::Dart, "lib/api_test.dart"));
+        assert!(is_test(Language::Elixir, "lib/api_test.exs"));
+        assert!(is_test(Language::Cpp, "src/api_unittest.cc"));
+
+        // no convention for the names of the files
+        assert!(!is_test(Language::Dockerfile, "test.Dockerfile"));
+    }
+
+    #[test]
+    fn test_is_test_file_rust() {
+        let file_metadata = FileMetadata::new("src/api.rs", 0);
+        let code = "fn foo() {}\n\n#[cfg(test)]\nmod tests {\n}\n";
+        assert!(is_test_file(&Language::Rust, &file_metadata, code));
+        assert!(!is_test_file(
+            &Language::Rust,
+            &file_metadata,
+            "fn foo() {}\n"
+        ));
+        assert!(is_test_file(
+            &Language::Rust,
+            &FileMetadata::new("tests/api.rs", 0),
+            "fn foo() {}\n"
+        ));
+    }
+}
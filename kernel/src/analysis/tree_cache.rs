@@ -0,0 +1,189 @@
+use crate::analysis::tree_sitter::{
+    get_grammar_for_file, get_input_edit, get_tree_incremental_with_language,
+    get_tree_with_language, Grammar,
+};
+use crate::model::analysis::ParseError;
+use crate::model::common::Language;
+use std::collections::HashMap;
+
+pub const DEFAULT_TREE_CACHE_CAPACITY: usize = 100;
+
+struct TreeCacheEntry {
+    // the grammar of a file can change with its code (e.g. the @flow pragma of JavaScript files)
+    grammar: Grammar,
+    code: String,
+    tree: tree_sitter::Tree,
+    last_used: u64,
+}
+
+// Keep the last tree of each file so that the next version of the file is parsed incrementally.
+// When the cache is full, the least recently used file is evicted.
+pub struct TreeCache {
+    capacity: usize,
+    entries: HashMap<String, TreeCacheEntry>,
+    tick: u64,
+}
+
+impl TreeCache {
+    pub fn new(capacity: usize) -> Self {
+        TreeCache {
+            capacity,
+            entries: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    // Get the tree of the file, reusing the previous tree of the file if there is one.
+    pub fn get_tree(
+        &mut self,
+        filename: &str,
+        code: &str,
+        language: &Language,
+    ) -> Result<tree_sitter::Tree, ParseError> {
+        self.tick += 1;
+        let grammar = get_grammar_for_file(language, filename, code);
+        let tree_sitter_language = grammar.get_tree_sitter_language()?;
+
+        let previous = self.entries.get(filename).filter(|e| e.grammar == grammar);
+        let tree = match previous {
+            Some(entry) => match get_input_edit(&entry.code, code) {
+                Some(edit) => get_tree_incremental_with_language(
+                    &entry.tree,
+                    &[edit],
+                    code,
+                    tree_sitter_language,
+                )?,
+                None => entry.tree.clone(),
+            },
+            None => get_tree_with_language(code, tree_sitter_language)?,
+        };
+
+        if !self.entries.contains_key(filename) && self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        if self.capacity > 0 {
+            self.entries.insert(
+                filename.to_string(),
+                TreeCacheEntry {
+                    grammar,
+                    code: code.to_string(),
+                    tree: tree.clone(),
+                    last_used: self.tick,
+                },
+            );
+        }
+        Ok(tree)
+    }
+
+    // remove the least recently used file
+    fn evict(&mut self) {
+        if let Some(filename) = self
+            .entries
+            .iter()
+            .min_by_key(|(_, e)| e.last_used)
+            .map(|(filename, _)| filename.clone())
+        {
+            self.entries.remove(&filename);
+        }
+    }
+
+    pub fn contains(&self, filename: &str) -> bool {
+        self.entries.contains_key(filename)
+    }
+
+    pub fn remove(&mut self, filename: &str) {
+        self.entries.remove(filename);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for TreeCache {
+    fn default() -> Self {
+        TreeCache::new(DEFAULT_TREE_CACHE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::tree_sitter::{get_tree, get_tree_for_file, map_node};
+
+    // each version of the file gives the same tree as a parse from scratch
+    #[test]
+    fn test_tree_cache_get_tree() {
+        let mut cache = TreeCache::default();
+        let versions = [
+            "def foo(arg1):\n    pass\n",
+            "def foo(arg1, arg2):\n    pass\n",
+            "def foo(arg1, arg2):\n    print(arg2)\n",
+            "def foo(arg1, arg2):\n    print(arg2)\n",
+            "",
+        ];
+        for code in versions {
+            let tree = cache
+                .get_tree("myfile.py", code, &Language::Python)
+                .unwrap();
+            let cold_tree = get_tree(code, &Language::Python).unwrap();
+            assert_eq!(
+                serde_json::to_string(&map_node(cold_tree.root_node())).unwrap(),
+                serde_json::to_string(&map_node(tree.root_node())).unwrap()
+            );
+        }
+        assert_eq!(1, cache.len());
+    }
+
+    // the previous tree is not reused when the grammar of the file changes
+    #[test]
+    fn test_tree_cache_grammar_change() {
+        let mut cache = TreeCache::default();
+        let versions = [
+            "const a = b;\n",
+            "// @flow\nconst a: number = b;\n",
+            "const a = b;\n",
+        ];
+        for code in versions {
+            let tree = cache
+                .get_tree("myfile.js", code, &Language::JavaScript)
+                .unwrap();
+            let cold_tree = get_tree_for_file(code, &Language::JavaScript, "myfile.js").unwrap();
+            assert_eq!(
+                serde_json::to_string(&map_node(cold_tree.root_node())).unwrap(),
+                serde_json::to_string(&map_node(tree.root_node())).unwrap()
+            );
+        }
+    }
+
+    // the least recently used file is evicted when the cache is full
+    #[test]
+    fn test_tree_cache_eviction() {
+        let mut cache = TreeCache::new(2);
+        cache.get_tree("a.py", "a = 1", &Language::Python).unwrap();
+        cache.get_tree("b.py", "b = 1", &Language::Python).unwrap();
+        cache.get_tree("a.py", "a = 2", &Language::Python).unwrap();
+        cache.get_tree("c.py", "c = 1", &Language::Python).unwrap();
+        assert_eq!(2, cache.len());
+        assert!(cache.contains("a.py"));
+        assert!(!cache.contains("b.py"));
+        assert!(cache.contains("c.py"));
+
+        cache.remove("a.py");
+        assert!(!cache.contains("a.py"));
+        cache.clear();
+        assert!(cache.is_empty());
+
+        let mut cache = TreeCache::new(0);
+        assert!(cache.get_tree("a.py", "a = 1", &Language::Python).is_ok());
+        assert!(cache.is_empty());
+    }
+}
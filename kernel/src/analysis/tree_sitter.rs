@@ -1,24 +1,119 @@
-use crate::model::analysis::{MatchNode, MatchNodeContext, TreeSitterNode};
+use crate::analysis::line_index::LineIndex;
+use crate::model::analysis::{
+    CaptureQuantifier, CommentInfo, CommentKind, FileMetadata, LanguageError, MapNodeOptions,
+    MatchNode, MatchNodeContext, NodeKindInfo, ParseError, PositionEncoding, QueryError,
+    QueryErrorKind, QueryNodes, QueryNodesOptions, SexpOptions, TreeSitterNode,
+    TreeSitterNodeSummary, MAX_MAP_NODE_DEPTH, NODE_SCHEMA_VERSION,
+};
 use crate::model::common::{Language, Position};
-use anyhow::Result;
-use std::collections::HashMap;
-use tree_sitter::QueryCursor;
+use lazy_static::lazy_static;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tree_sitter::{QueryCursor, QueryPredicateArg};
 
 // Swift is implemented differently. While most languages are integrated from sources,
 // the sources from the swift tree-sitter syntax do not compile and the rust package works.
 use tree_sitter_swift::language as swift_language;
 
-fn get_tree_sitter_language(language: &Language) -> tree_sitter::Language {
+// Predicates that tree-sitter does not evaluate and that we evaluate ourselves.
+const SUPPORTED_GENERAL_PREDICATES: &[&str] = &["any-of?", "not-any-of?"];
+
+// Captures with a name starting with this prefix are only used to match (e.g. to anchor the
+// pattern or in a predicate) and are not mapped nor passed to the rules.
+pub const PRIVATE_CAPTURE_PREFIX: &str = "_";
+
+// Maximum number of compiled queries kept in the cache.
+const MAX_QUERY_CACHE_SIZE: usize = 10_000;
+
+lazy_static! {
+    static ref QUERY_CACHE: RwLock<QueryCache> = RwLock::new(QueryCache::new(MAX_QUERY_CACHE_SIZE));
+}
+
+// A grammar used to parse the code of a language. Some languages have more than one grammar
+// (e.g. TypeScript with and without JSX), see `get_grammar_for_file`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Grammar {
+    pub language: Language,
+    // the TSX grammar of TypeScript, the default grammar of the language
+    pub is_tsx: bool,
+}
+
+impl Grammar {
+    // the default grammar of the language
+    pub fn new(language: Language) -> Self {
+        Grammar {
+            language,
+            is_tsx: language == Language::TypeScript,
+        }
+    }
+
+    // get the tree-sitter grammar, failing if the library cannot use it
+    pub fn get_tree_sitter_language(&self) -> Result<tree_sitter::Language, LanguageError> {
+        extern "C" {
+            fn tree_sitter_typescript() -> tree_sitter::Language;
+        }
+
+        let tree_sitter_language = match self.language {
+            Language::TypeScript if !self.is_tsx => unsafe { tree_sitter_typescript() },
+            _ => get_grammar(&self.language),
+        };
+        check_grammar_version(&self.language, tree_sitter_language.version())?;
+        Ok(tree_sitter_language)
+    }
+}
+
+// check that the grammar was generated for a version of tree-sitter that the library supports
+fn check_grammar_version(language: &Language, version: usize) -> Result<(), LanguageError> {
+    if (tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION..=tree_sitter::LANGUAGE_VERSION)
+        .contains(&version)
+    {
+        Ok(())
+    } else {
+        Err(LanguageError::new(*language, version))
+    }
+}
+
+// get the default grammar of the language, failing if the library cannot use it
+pub fn get_tree_sitter_language(
+    language: &Language,
+) -> Result<tree_sitter::Language, LanguageError> {
+    let tree_sitter_language = get_grammar(language);
+    check_grammar_version(language, tree_sitter_language.version())?;
+    Ok(tree_sitter_language)
+}
+
+fn get_grammar(language: &Language) -> tree_sitter::Language {
     extern "C" {
+        fn tree_sitter_bash() -> tree_sitter::Language;
+        fn tree_sitter_c() -> tree_sitter::Language;
         fn tree_sitter_c_sharp() -> tree_sitter::Language;
+        fn tree_sitter_cpp() -> tree_sitter::Language;
+        fn tree_sitter_dart() -> tree_sitter::Language;
         fn tree_sitter_dockerfile() -> tree_sitter::Language;
+        fn tree_sitter_elixir() -> tree_sitter::Language;
         fn tree_sitter_go() -> tree_sitter::Language;
+        fn tree_sitter_groovy() -> tree_sitter::Language;
         fn tree_sitter_java() -> tree_sitter::Language;
         fn tree_sitter_javascript() -> tree_sitter::Language;
         fn tree_sitter_json() -> tree_sitter::Language;
         fn tree_sitter_kotlin() -> tree_sitter::Language;
+        fn tree_sitter_lua() -> tree_sitter::Language;
+        fn tree_sitter_php() -> tree_sitter::Language;
         fn tree_sitter_python() -> tree_sitter::Language;
+        fn tree_sitter_ruby() -> tree_sitter::Language;
         fn tree_sitter_rust() -> tree_sitter::Language;
+        fn tree_sitter_scala() -> tree_sitter::Language;
+        fn tree_sitter_sql() -> tree_sitter::Language;
+        fn tree_sitter_toml() -> tree_sitter::Language;
         fn tree_sitter_tsx() -> tree_sitter::Language;
         fn tree_sitter_hcl() -> tree_sitter::Language;
         fn tree_sitter_yaml() -> tree_sitter::Language;
@@ -26,366 +121,5194 @@ fn get_tree_sitter_language(language: &Language) -> tree_sitter::Language {
     }
 
     match language {
+        Language::C => unsafe { tree_sitter_c() },
+        Language::Cpp => unsafe { tree_sitter_cpp() },
         Language::Csharp => unsafe { tree_sitter_c_sharp() },
+        Language::Dart => unsafe { tree_sitter_dart() },
         Language::Dockerfile => unsafe { tree_sitter_dockerfile() },
+        Language::Elixir => unsafe { tree_sitter_elixir() },
         Language::Go => unsafe { tree_sitter_go() },
+        Language::Groovy => unsafe { tree_sitter_groovy() },
         Language::Java => unsafe { tree_sitter_java() },
         Language::JavaScript => unsafe { tree_sitter_javascript() },
         Language::Kotlin => unsafe { tree_sitter_kotlin() },
         Language::Json => unsafe { tree_sitter_json() },
+        Language::Lua => unsafe { tree_sitter_lua() },
+        Language::Php => unsafe { tree_sitter_php() },
         Language::Python => unsafe { tree_sitter_python() },
+        Language::Ruby => unsafe { tree_sitter_ruby() },
         Language::Rust => unsafe { tree_sitter_rust() },
+        Language::Scala => unsafe { tree_sitter_scala() },
+        Language::Shell => unsafe { tree_sitter_bash() },
+        Language::Sql => unsafe { tree_sitter_sql() },
         Language::Swift => swift_language(),
         Language::Terraform => unsafe { tree_sitter_hcl() },
+        Language::Toml => unsafe { tree_sitter_toml() },
         Language::TypeScript => unsafe { tree_sitter_tsx() },
         Language::Yaml => unsafe { tree_sitter_yaml() },
     }
 }
 
-// get the tree-sitter tree
-pub fn get_tree(code: &str, language: &Language) -> Option<tree_sitter::Tree> {
-    let mut tree_sitter_parser = tree_sitter::Parser::new();
-    let tree_sitter_language = get_tree_sitter_language(language);
-    tree_sitter_parser.set_language(tree_sitter_language).ok()?;
-    tree_sitter_parser.parse(code, None)
+// get all the kinds of nodes of the grammar of the language
+pub fn get_language_node_kinds(language: &Language) -> Vec<NodeKindInfo> {
+    let grammar = get_grammar(language);
+    (0..grammar.node_kind_count())
+        .filter_map(|id| u16::try_from(id).ok())
+        .filter_map(|id| {
+            grammar.node_kind_for_id(id).map(|kind| NodeKindInfo {
+                id,
+                kind: kind.to_string(),
+                is_named: grammar.node_kind_is_named(id),
+                is_visible: grammar.node_kind_is_visible(id),
+            })
+        })
+        .collect()
 }
 
-// build the query from tree-sitter
-pub fn get_query(query_code: &str, language: &Language) -> Result<tree_sitter::Query> {
-    let tree_sitter_language = get_tree_sitter_language(language);
-    Ok(tree_sitter::Query::new(tree_sitter_language, query_code)?)
+// get the names of all the fields of the grammar of the language
+pub fn get_language_fields(language: &Language) -> Vec<String> {
+    let grammar = get_grammar(language);
+    // field ids start at 1
+    (1..=grammar.field_count())
+        .filter_map(|id| u16::try_from(id).ok())
+        .filter_map(|id| grammar.field_name_for_id(id))
+        .map(ToString::to_string)
+        .collect()
 }
 
-// Get all the match nodes based on a query. For each match, we build a `MatchNode`
-// object. This object is deserialized and this is what is passed to the visit function.
-// This is the first argument of the visit function.
-// This `MatchNode` must have the captures and captures_list attributes that contains
-// the values of the captures for the match.
+// Extensions of the TypeScript files without JSX, parsed with the TypeScript grammar.
+const TYPESCRIPT_WITHOUT_JSX_EXTENSIONS: &[&str] = &["cts", "mts", "ts"];
+
+// check if the file is a TypeScript file that cannot contain JSX (e.g. `.ts`)
+fn is_typescript_without_jsx(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| TYPESCRIPT_WITHOUT_JSX_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+// Some languages have more than one grammar and the grammar to use depends on the file.
+//  - TypeScript uses the TSX grammar by default (e.g. `.tsx` files, the code of components
+//    and the code without filename). The `.ts`, `.mts` and `.cts` files use the TypeScript
+//    grammar: the TSX grammar parses generics such as `<T>(x: T) => x` as JSX elements.
+//  - JavaScript files (including `.jsx` files) use the JavaScript grammar that supports JSX.
+//    Files with a `@flow` pragma have type annotations and use the TSX grammar instead,
+//    which is a superset of the JavaScript grammar with types and JSX.
 //
-// Note that we also add the context to the node that consists of the code and variables.
-pub fn get_query_nodes(
-    tree: &tree_sitter::Tree,
-    query: &tree_sitter::Query,
+// The tree and the query must always be built with the same grammar.
+pub fn get_grammar_for_file(language: &Language, filename: &str, code: &str) -> Grammar {
+    match language {
+        Language::TypeScript if is_typescript_without_jsx(filename) => Grammar {
+            language: Language::TypeScript,
+            is_tsx: false,
+        },
+        Language::JavaScript if has_flow_pragma(code) => Grammar::new(Language::TypeScript),
+        _ => Grammar::new(*language),
+    }
+}
+
+// get the tree-sitter grammar to parse the file, see `get_grammar_for_file`
+pub fn get_tree_sitter_language_for_file(
+    language: &Language,
     filename: &str,
     code: &str,
-    variables: &HashMap<String, String>,
-) -> Vec<MatchNode> {
-    let mut query_cursor = QueryCursor::new();
-    let mut match_nodes: Vec<MatchNode> = vec![];
-
-    let query_result = query_cursor.matches(query, tree.root_node(), code.as_bytes());
-
-    for query_match in query_result {
-        let mut captures: HashMap<String, TreeSitterNode> = HashMap::new();
-        let mut captures_list: HashMap<String, Vec<TreeSitterNode>> = HashMap::new();
-        for capture in query_match.captures.iter() {
-            let capture_name_opt = query
-                .capture_names()
-                .get(usize::try_from(capture.index).unwrap());
-            let node_opt = map_node(capture.node);
-
-            if let (Some(capture_name), Some(node)) = (capture_name_opt, node_opt) {
-                captures.insert(capture_name.to_string(), node.clone());
-                if !captures_list.contains_key(capture_name) {
-                    captures_list.insert(capture_name.to_string(), vec![]);
-                }
-                captures_list
-                    .get_mut(capture_name)
-                    .unwrap()
-                    .push(node.clone());
-            }
-        }
+) -> Result<tree_sitter::Language, LanguageError> {
+    get_grammar_for_file(language, filename, code).get_tree_sitter_language()
+}
 
-        if !captures.is_empty() {
-            match_nodes.push(MatchNode {
-                captures: captures.clone(),
-                captures_list: captures_list.clone(),
-                context: MatchNodeContext {
-                    code: Some(code.to_string()),
-                    filename: filename.to_string(),
-                    variables: variables.clone(),
-                },
-            });
+// The default grammar of the language when the file uses another grammar of the same language
+// (e.g. the TSX grammar for a `.ts` file), None when the file uses the default grammar. The
+// queries with nodes that only exist in the default grammar (e.g. JSX nodes) run on the file
+// parsed with it.
+pub fn get_fallback_grammar(language: &Language, filename: &str) -> Option<Grammar> {
+    match language {
+        Language::TypeScript if is_typescript_without_jsx(filename) => {
+            Some(Grammar::new(*language))
         }
+        _ => None,
     }
-    match_nodes
 }
 
-// map a node from the tree-sitter representation into our own internal representation
-// this is the representation that is passed to the JavaScript layer and how we represent
-// or expose the node to the end-user.
-pub fn map_node(node: tree_sitter::Node) -> Option<TreeSitterNode> {
-    fn map_node_internal(
-        cursor: &mut tree_sitter::TreeCursor,
-        only_named_node: bool,
-    ) -> Option<TreeSitterNode> {
-        // we do not map space, parenthesis and other non-named nodes if there
-        // when `only_named_node` is true (which is `true` for children only).
-        if only_named_node && !cursor.node().is_named() {
-            return None;
+// check if the leading comments of a JavaScript file contain the `@flow` pragma
+fn has_flow_pragma(code: &str) -> bool {
+    for line in code.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
         }
-
-        // map all the children as we should
-        let mut children: Vec<TreeSitterNode> = vec![];
-        if cursor.goto_first_child() {
-            loop {
-                // For the child, we only want to capture named nodes to avoid polluting the AST.
-                let maybe_child = map_node_internal(cursor, true);
-                if let Some(child) = maybe_child {
-                    children.push(child);
-                }
-                if !cursor.goto_next_sibling() {
-                    break;
-                }
+        if line.starts_with("//") || line.starts_with("/*") || line.starts_with('*') {
+            if line.contains("@flow") {
+                return true;
             }
-            cursor.goto_parent();
+        } else {
+            return false;
         }
+    }
+    false
+}
+
+thread_local! {
+    // Creating a parser allocates all its buffers and setting its grammar resets them, each
+    // thread keeps one parser per grammar.
+    static PARSERS: RefCell<HashMap<tree_sitter::Language, tree_sitter::Parser>> =
+        RefCell::new(HashMap::new());
+}
 
-        // finally, build the return value.
-        let ts_node = TreeSitterNode {
-            ast_type: cursor.node().kind().to_string(),
-            start: Position {
-                line: u32::try_from(cursor.node().range().start_point.row + 1).unwrap(),
-                col: u32::try_from(cursor.node().range().start_point.column + 1).unwrap(),
-            },
-            end: Position {
-                line: u32::try_from(cursor.node().range().end_point.row + 1).unwrap(),
-                col: u32::try_from(cursor.node().range().end_point.column + 1).unwrap(),
-            },
-            field_name: cursor.field_name().map(ToString::to_string),
-            children,
+// run the function with the parser of the grammar for the current thread, the parser is
+// created and set up the first time the grammar is used on the thread
+fn with_parser<T>(
+    tree_sitter_language: tree_sitter::Language,
+    f: impl FnOnce(&mut tree_sitter::Parser) -> T,
+) -> Result<T, ParseError> {
+    PARSERS.with(|parsers| {
+        let mut parsers = parsers.borrow_mut();
+        let parser = match parsers.entry(tree_sitter_language) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut parser = tree_sitter::Parser::new();
+                parser
+                    .set_language(tree_sitter_language)
+                    .map_err(|e| ParseError::ParserSetup(e.to_string()))?;
+                entry.insert(parser)
+            }
         };
+        Ok(f(parser))
+    })
+}
 
-        Some(ts_node)
-    }
+// parse the code, reusing the parts of the old tree that did not change when there is one
+fn parse(
+    code: &str,
+    tree_sitter_language: tree_sitter::Language,
+    old_tree: Option<&tree_sitter::Tree>,
+    timeout: Option<Duration>,
+) -> Result<tree_sitter::Tree, ParseError> {
+    with_parser(tree_sitter_language, |tree_sitter_parser| {
+        // a timeout of 0 disables the timeout, an exhausted budget still gets one microsecond
+        let timeout_micros = timeout.map_or(0, |t| {
+            u64::try_from(t.as_micros()).unwrap_or(u64::MAX).max(1)
+        });
+        tree_sitter_parser.set_timeout_micros(timeout_micros);
+        let tree = tree_sitter_parser.parse(code, old_tree);
+        // a parsing that did not complete must not be resumed with the next file
+        tree_sitter_parser.reset();
+        match tree {
+            Some(tree) => Ok(tree),
+            None if timeout.is_some() => Err(ParseError::Timeout),
+            None => Err(ParseError::ParseFailed),
+        }
+    })?
+}
 
-    let mut ts_cursor = node.walk();
+// get the tree-sitter tree for a given tree-sitter grammar
+pub fn get_tree_with_language(
+    code: &str,
+    tree_sitter_language: tree_sitter::Language,
+) -> Result<tree_sitter::Tree, ParseError> {
+    parse(code, tree_sitter_language, None, None)
+}
 
-    // Initially, we capture both un/named nodes to allow capturing unnamed node from
-    // the tree-sitter query.
-    map_node_internal(&mut ts_cursor, false)
+// get the tree-sitter tree for a given tree-sitter grammar, failing with ParseError::Timeout
+// if the parsing takes longer than the timeout
+pub fn get_tree_with_timeout(
+    code: &str,
+    tree_sitter_language: tree_sitter::Language,
+    timeout: Option<Duration>,
+) -> Result<tree_sitter::Tree, ParseError> {
+    parse(code, tree_sitter_language, None, timeout)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// get the tree-sitter tree of the new code from the tree of the previous version of the code
+// and the edits between both versions. The old tree must have been built with the same grammar.
+pub fn get_tree_incremental_with_language(
+    old_tree: &tree_sitter::Tree,
+    edits: &[tree_sitter::InputEdit],
+    new_code: &str,
+    tree_sitter_language: tree_sitter::Language,
+) -> Result<tree_sitter::Tree, ParseError> {
+    let mut edited_tree = old_tree.clone();
+    for edit in edits {
+        edited_tree.edit(edit);
+    }
+    parse(new_code, tree_sitter_language, Some(&edited_tree), None)
+}
 
-    #[test]
-    fn test_python_get_tree() {
-        let source_code = r#"
-arr = ["foo", "bar"];
+// get the tree-sitter tree of the new code from the tree of the previous version of the code
+pub fn get_tree_incremental(
+    old_tree: &tree_sitter::Tree,
+    edits: &[tree_sitter::InputEdit],
+    new_code: &str,
+    language: &Language,
+) -> Result<tree_sitter::Tree, ParseError> {
+    get_tree_incremental_with_language(
+        old_tree,
+        edits,
+        new_code,
+        get_tree_sitter_language(language)?,
+    )
+}
 
-def func():
-   pass;"#;
-        let t = get_tree(source_code, &Language::Python);
-        assert!(t.is_some());
-        assert_eq!("module", t.unwrap().root_node().kind());
+// get the point (row and column in bytes, starting at 0) of a byte offset in the code
+fn get_point(code: &str, byte: usize) -> tree_sitter::Point {
+    let before = &code[..byte];
+    tree_sitter::Point {
+        row: before.matches('\n').count(),
+        column: before.rfind('\n').map_or(byte, |i| byte - i - 1),
     }
+}
 
-    #[test]
-    fn test_map_node_simple() {
-        let source_code = r#"
-arr = ["foo", "bar"];
+// Get the edit that changes the old code into the new code: everything between the common
+// prefix and the common suffix is replaced. Return None if the code did not change.
+pub fn get_input_edit(old_code: &str, new_code: &str) -> Option<tree_sitter::InputEdit> {
+    if old_code == new_code {
+        return None;
+    }
 
-def func():
-   pass;"#;
-        let t = get_tree(source_code, &Language::Python);
-        assert!(t.is_some());
-        let tree_node = map_node(t.unwrap().root_node());
-        assert!(tree_node.is_some());
-        let root = tree_node.unwrap();
-        assert_eq!(2, root.children.len());
-        assert_eq!(
-            "expression_statement",
-            root.children.get(0).unwrap().ast_type
-        );
-        assert_eq!(
-            "function_definition",
-            root.children.get(1).unwrap().ast_type
-        );
-        assert!(root.children.get(1).unwrap().field_name.is_none());
-        let function_definition = root.children.get(1).unwrap();
-        assert_eq!(
-            "name",
-            function_definition
-                .children
-                .get(0)
-                .unwrap()
-                .field_name
-                .clone()
-                .unwrap()
-        );
+    let mut start_byte = old_code
+        .bytes()
+        .zip(new_code.bytes())
+        .take_while(|(o, n)| o == n)
+        .count();
+    while !old_code.is_char_boundary(start_byte) || !new_code.is_char_boundary(start_byte) {
+        start_byte -= 1;
     }
 
-    #[test]
-    fn test_csharp_get_tree() {
-        let source_code = r#"
-namespace HelloWorld
-{
-    class Hello {
-        static void Main(string[] args)
-        {
-            System.Console.WriteLine("Hello World!");
-        }
+    // the suffix cannot overlap the prefix
+    let max_suffix_len = old_code.len().min(new_code.len()) - start_byte;
+    let mut suffix_len = old_code
+        .bytes()
+        .rev()
+        .zip(new_code.bytes().rev())
+        .take(max_suffix_len)
+        .take_while(|(o, n)| o == n)
+        .count();
+    while !old_code.is_char_boundary(old_code.len() - suffix_len)
+        || !new_code.is_char_boundary(new_code.len() - suffix_len)
+    {
+        suffix_len -= 1;
     }
+
+    let old_end_byte = old_code.len() - suffix_len;
+    let new_end_byte = new_code.len() - suffix_len;
+    Some(tree_sitter::InputEdit {
+        start_byte,
+        old_end_byte,
+        new_end_byte,
+        start_position: get_point(old_code, start_byte),
+        old_end_position: get_point(old_code, old_end_byte),
+        new_end_position: get_point(new_code, new_end_byte),
+    })
 }
-"#;
-        let t = get_tree(source_code, &Language::Csharp);
-        assert!(t.is_some());
-        assert_eq!("compilation_unit", t.unwrap().root_node().kind());
-    }
 
-    #[test]
-    fn test_dockerfile_get_tree() {
-        let source_code = r#"
-RUN /blabla
-"#;
-        let t = get_tree(source_code, &Language::Dockerfile);
-        assert!(t.is_some());
-        assert_eq!("source_file", t.unwrap().root_node().kind());
-    }
+// get the tree-sitter tree
+pub fn try_get_tree(code: &str, language: &Language) -> Result<tree_sitter::Tree, ParseError> {
+    get_tree_with_language(code, get_tree_sitter_language(language)?)
+}
 
-    #[test]
-    fn test_go_test_tree() {
-        let source_code = r#"
-package main
-import "fmt"
-func main() {
-    fmt.Println("hello world")
+// get the tree-sitter tree, None if the code cannot be parsed
+pub fn get_tree(code: &str, language: &Language) -> Option<tree_sitter::Tree> {
+    try_get_tree(code, language).ok()
 }
-"#;
-        let t = get_tree(source_code, &Language::Go);
-        assert!(t.is_some());
-        assert_eq!("source_file", t.unwrap().root_node().kind());
-    }
 
-    #[test]
-    fn test_java_get_tree() {
-        let source_code = r#"
-class Foo {
+// get the tree-sitter tree, using the grammar that matches the file
+pub fn get_tree_for_file(
+    code: &str,
+    language: &Language,
+    filename: &str,
+) -> Result<tree_sitter::Tree, ParseError> {
+    get_tree_with_language(
+        code,
+        get_tree_sitter_language_for_file(language, filename, code)?,
+    )
 }
-"#;
-        let t = get_tree(source_code, &Language::Java);
-        assert!(t.is_some());
-        assert_eq!("program", t.unwrap().root_node().kind());
+
+// build the query from tree-sitter
+pub fn get_query(
+    query_code: &str,
+    language: &Language,
+) -> Result<Arc<tree_sitter::Query>, QueryError> {
+    get_query_for_grammar(query_code, Grammar::new(*language))
+}
+
+fn get_query_hash(query_code: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    query_code.hash(&mut hasher);
+    hasher.finish()
+}
+
+struct QueryCacheEntry {
+    query: Arc<tree_sitter::Query>,
+    // updated when the query is read, under the read lock of the cache
+    last_used: AtomicU64,
+}
+
+// The compiled queries, by grammar and hash of the query code. The rules sent to the server can
+// change at any time: when the cache is full, the least recently used query is evicted.
+struct QueryCache {
+    capacity: usize,
+    entries: HashMap<(Grammar, u64), QueryCacheEntry>,
+    tick: AtomicU64,
+}
+
+impl QueryCache {
+    fn new(capacity: usize) -> Self {
+        QueryCache {
+            capacity,
+            entries: HashMap::new(),
+            tick: AtomicU64::new(0),
+        }
     }
 
-    #[test]
-    fn test_javascript_get_tree() {
-        let source_code = r#"
-function foo() {console.log("bar");}"#;
-        let t = get_tree(source_code, &Language::JavaScript);
-        assert!(t.is_some());
-        assert_eq!("program", t.unwrap().root_node().kind());
+    fn get(&self, key: &(Grammar, u64)) -> Option<Arc<tree_sitter::Query>> {
+        let entry = self.entries.get(key)?;
+        let tick = self.tick.fetch_add(1, Ordering::Relaxed) + 1;
+        entry.last_used.store(tick, Ordering::Relaxed);
+        Some(entry.query.clone())
     }
 
-    #[test]
-    fn test_json_get_tree() {
-        let source_code = r#"
-{}"#;
-        let t = get_tree(source_code, &Language::Json);
-        assert!(t.is_some());
-        assert_eq!("document", t.unwrap().root_node().kind());
+    fn insert(
+        &mut self,
+        key: (Grammar, u64),
+        query: Arc<tree_sitter::Query>,
+    ) -> Arc<tree_sitter::Query> {
+        // another thread may have compiled the same query in the meantime
+        if let Some(query) = self.get(&key) {
+            return query;
+        }
+        if self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        if self.capacity > 0 {
+            let tick = self.tick.fetch_add(1, Ordering::Relaxed) + 1;
+            self.entries.insert(
+                key,
+                QueryCacheEntry {
+                    query: query.clone(),
+                    last_used: AtomicU64::new(tick),
+                },
+            );
+        }
+        query
     }
 
-    #[test]
-    fn test_rust_get_tree() {
-        let source_code = r#"
-fn foo(bar: String) -> String {
-   return "foobar".to_string();
+    // remove the least recently used query
+    fn evict(&mut self) {
+        if let Some(key) = self
+            .entries
+            .iter()
+            .min_by_key(|(_, e)| e.last_used.load(Ordering::Relaxed))
+            .map(|(key, _)| *key)
+        {
+            self.entries.remove(&key);
+        }
+    }
 }
-"#;
-        let t = get_tree(source_code, &Language::Rust);
-        assert!(t.is_some());
-        assert_eq!("source_file", t.unwrap().root_node().kind());
+
+// Get the query for a given grammar. Queries are compiled once and shared between all the
+// files and threads.
+pub fn get_query_for_grammar(
+    query_code: &str,
+    grammar: Grammar,
+) -> Result<Arc<tree_sitter::Query>, QueryError> {
+    let key = (grammar, get_query_hash(query_code));
+    if let Some(query) = QUERY_CACHE.read().unwrap().get(&key) {
+        return Ok(query);
     }
 
-    #[test]
-    fn test_kotlin_get_tree() {
-        let source_code = r#"
-fun main() {
-    println("What's your name?")
-    val name = readln()
-    println("Hello, $name!")
+    let tree_sitter_language = grammar
+        .get_tree_sitter_language()
+        .map_err(|e| QueryError::new(QueryErrorKind::Language, &e.to_string(), query_code, 0))?;
+    let query = Arc::new(build_query(query_code, tree_sitter_language)?);
+    Ok(QUERY_CACHE.write().unwrap().insert(key, query))
 }
-"#;
-        let t = get_tree(source_code, &Language::Kotlin);
-        assert!(t.is_some());
-        assert_eq!("source_file", t.unwrap().root_node().kind());
+
+// build the query from tree-sitter for a given tree-sitter grammar
+fn build_query(
+    query_code: &str,
+    tree_sitter_language: tree_sitter::Language,
+) -> Result<tree_sitter::Query, QueryError> {
+    let query = tree_sitter::Query::new(tree_sitter_language, query_code)
+        .map_err(|e| QueryError::from_tree_sitter(e, query_code))?;
+
+    // tree-sitter keeps the predicates it does not know, make sure we can evaluate them
+    // instead of silently ignoring them.
+    for pattern_index in 0..query.pattern_count() {
+        for predicate in query.general_predicates(pattern_index) {
+            let predicate_error = |message: &str| {
+                // tree-sitter does not keep the location of the predicates, use the first
+                // occurrence of the predicate in the pattern.
+                let pattern_offset = query.start_byte_for_pattern(pattern_index);
+                let offset = query_code[pattern_offset..]
+                    .find(&format!("#{}", predicate.operator))
+                    .map_or(pattern_offset, |i| pattern_offset + i);
+                QueryError::new(QueryErrorKind::Predicate, message, query_code, offset)
+            };
+
+            if !SUPPORTED_GENERAL_PREDICATES.contains(&predicate.operator.as_ref()) {
+                return Err(predicate_error(&format!(
+                    "unknown predicate #{}",
+                    predicate.operator
+                )));
+            }
+            match predicate.args.split_first() {
+                Some((QueryPredicateArg::Capture(_), values))
+                    if values
+                        .iter()
+                        .all(|v| matches!(v, QueryPredicateArg::String(_))) => {}
+                _ => {
+                    return Err(predicate_error(&format!(
+                        "predicate #{} expects a capture followed by strings",
+                        predicate.operator
+                    )))
+                }
+            }
+        }
     }
 
-    #[test]
-    fn test_swift_get_tree() {
-        let source_code = r#"
-print("Hello, world!")
+    Ok(query)
 }
-"#;
-        let t = get_tree(source_code, &Language::Swift);
-        assert!(t.is_some());
-        assert_eq!("source_file", t.unwrap().root_node().kind());
+
+// Get the predicates of a query that tree-sitter parses but does not evaluate when matching
+// (`#is?` and `#is-not?`), the query matches as if they were not there.
+pub fn get_unknown_predicates(query: &tree_sitter::Query) -> Vec<String> {
+    let mut predicates: Vec<String> = (0..query.pattern_count())
+        .flat_map(|pattern_index| query.property_predicates(pattern_index))
+        .map(|(_, is_positive)| if *is_positive { "#is?" } else { "#is-not?" }.to_string())
+        .collect();
+    predicates.sort();
+    predicates.dedup();
+    predicates
+}
+
+// Check that a match satisfies the predicates that are not evaluated by tree-sitter
+// (#any-of? and #not-any-of?). All the nodes of a capture must satisfy the predicate.
+fn satisfies_general_predicates(
+    query: &tree_sitter::Query,
+    query_match: &tree_sitter::QueryMatch,
+    code: &str,
+) -> bool {
+    query
+        .general_predicates(query_match.pattern_index)
+        .iter()
+        .all(|predicate| {
+            let (capture_index, values) = match predicate.args.split_first() {
+                Some((QueryPredicateArg::Capture(capture_index), values)) => {
+                    (*capture_index, values)
+                }
+                _ => return true,
+            };
+            let is_positive = predicate.operator.as_ref() == "any-of?";
+
+            query_match
+                .captures
+                .iter()
+                .filter(|c| c.index == capture_index)
+                .all(|c| {
+                    let text = c.node.utf8_text(code.as_bytes()).unwrap_or_default();
+                    let is_any_of = values.iter().any(|v| match v {
+                        QueryPredicateArg::String(value) => value.as_ref() == text,
+                        QueryPredicateArg::Capture(_) => false,
+                    });
+                    is_any_of == is_positive
+                })
+        })
+}
+
+// Get the ratio (between 0 and 1) of the code covered by syntax errors. A high ratio
+// means the grammar did not understand most of the file and queries will miss matches.
+pub fn get_error_rate(tree: &tree_sitter::Tree, code: &str) -> f64 {
+    if code.is_empty() || !tree.root_node().has_error() {
+        return 0.0;
     }
 
-    #[test]
+    let mut error_bytes: usize = 0;
+    let mut cursor = tree.walk();
+    loop {
+        let node = cursor.node();
+        if node.is_error() {
+            // do not visit the children, they are already covered by the error node
+            error_bytes += node.end_byte() - node.start_byte();
+        } else if node.has_error() && cursor.goto_first_child() {
+            continue;
+        }
+
+        // go to the next node that has not been visited yet
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return error_bytes as f64 / code.len() as f64;
+            }
+        }
+    }
+}
+
+// Get the values set by the #set! directives of a pattern (e.g. `(#set! "kind" "sql-sink")`).
+// A directive without value is set to an empty string.
+fn get_pattern_settings(
+    query: &tree_sitter::Query,
+    pattern_index: usize,
+) -> HashMap<String, String> {
+    query
+        .property_settings(pattern_index)
+        .iter()
+        .map(|property| {
+            (
+                property.key.to_string(),
+                property
+                    .value
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+            )
+        })
+        .collect()
+}
+
+// Get all the match nodes based on a query. For each match, we build a `MatchNode`
+// object. This object is deserialized and this is what is passed to the visit function.
+// This is the first argument of the visit function.
+// This `MatchNode` must have the captures and captures_list attributes that contains
+// the values of the captures for the match.
+//
+// Note that we also add the context to the node that consists of the code and variables.
+pub fn get_query_nodes(
+    tree: &tree_sitter::Tree,
+    query: &tree_sitter::Query,
+    filename: &str,
+    code: &str,
+    variables: &HashMap<String, String>,
+) -> Vec<MatchNode> {
+    get_query_nodes_with_options(
+        tree,
+        query,
+        filename,
+        code,
+        variables,
+        &QueryNodesOptions::default(),
+    )
+    .match_nodes
+}
+
+// get the nodes matching the query, the number of matches and the captured nodes are limited
+// by the options
+pub fn get_query_nodes_with_options(
+    tree: &tree_sitter::Tree,
+    query: &tree_sitter::Query,
+    filename: &str,
+    code: &str,
+    variables: &HashMap<String, String>,
+    options: &QueryNodesOptions,
+) -> QueryNodes {
+    QueryRunner::new(tree, filename, code, variables).run(query, options)
+}
+
+// get the nodes matching each query, the results are the same as with get_query_nodes but the
+// cursor and the columns of the lines are computed once for all the queries
+pub fn get_query_nodes_batch(
+    tree: &tree_sitter::Tree,
+    queries: &[&tree_sitter::Query],
+    filename: &str,
+    code: &str,
+    variables: &HashMap<String, String>,
+) -> Vec<Vec<MatchNode>> {
+    let mut batch = QueryNodesBatch::new(tree, filename, code, variables);
+    let options = QueryNodesOptions::default();
+    queries
+        .iter()
+        .map(|query| batch.get_query_nodes(query, &options).match_nodes)
+        .collect()
+}
+
+// Run several queries with their own options on the same tree (e.g. the queries of all the
+// rules on a file). The cursor and the columns of the lines are computed once for all the
+// queries, the results are the same as with get_query_nodes_with_options and
+// get_query_capture_nodes_with_options.
+pub struct QueryNodesBatch<'a> {
+    runner: QueryRunner<'a>,
+}
+
+impl<'a> QueryNodesBatch<'a> {
+    pub fn new(
+        tree: &'a tree_sitter::Tree,
+        filename: &'a str,
+        code: &'a str,
+        variables: &'a HashMap<String, String>,
+    ) -> Self {
+        QueryNodesBatch {
+            runner: QueryRunner::new(tree, filename, code, variables),
+        }
+    }
+
+    pub fn get_query_nodes(
+        &mut self,
+        query: &tree_sitter::Query,
+        options: &QueryNodesOptions,
+    ) -> QueryNodes {
+        self.runner.run(query, options)
+    }
+
+    pub fn get_query_capture_nodes(
+        &mut self,
+        query: &tree_sitter::Query,
+        options: &QueryNodesOptions,
+    ) -> QueryNodes {
+        self.runner.run_capture_nodes(query, options)
+    }
+}
+
+// Get the nodes matching the query in the subtree of a node (e.g. the methods of a captured
+// class). The matches outside of the node are not returned and the positions are in the
+// whole code, not in the node.
+pub fn get_query_nodes_in_node(
+    node: tree_sitter::Node,
+    query: &tree_sitter::Query,
+    filename: &str,
+    code: &str,
+    variables: &HashMap<String, String>,
+    options: &QueryNodesOptions,
+) -> QueryNodes {
+    let mut root = node;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
+    QueryRunner::new_with_root(root, filename, code, variables).run_in_node(node, query, options)
+}
+
+// Get the node of a tree described by a summary (e.g. a node captured by a rule). The nodes
+// with the same range are told apart with their id, then with their type.
+pub fn get_node_in_tree<'a>(
+    tree: &'a tree_sitter::Tree,
+    summary: &TreeSitterNodeSummary,
+) -> Option<tree_sitter::Node<'a>> {
+    let range = summary.start_byte..summary.end_byte;
+    let mut candidates = vec![];
+    let mut node = tree
+        .root_node()
+        .descendant_for_byte_range(range.start, range.end);
+    while let Some(candidate) = node.filter(|n| n.byte_range() == range) {
+        candidates.push(candidate);
+        node = candidate.parent();
+    }
+    candidates
+        .iter()
+        .find(|n| n.id() == summary.id)
+        .or_else(|| candidates.iter().find(|n| n.kind() == summary.ast_type))
+        .copied()
+}
+
+// Get the nodes captured with a name by a query, in the order of the code and without
+// duplicates. Lighter than get_query_nodes when only the nodes of one capture are needed: the
+// matches are not built.
+pub fn get_query_captures(
+    tree: &tree_sitter::Tree,
+    query: &tree_sitter::Query,
+    capture_name: &str,
+    code: &str,
+) -> Vec<TreeSitterNode> {
+    let variables = HashMap::new();
+    let options = QueryNodesOptions {
+        max_matches: None,
+        ..QueryNodesOptions::default()
+    };
+    let (nodes, _, _) = QueryRunner::new(tree, "", code, &variables).run_captures(
+        query,
+        Some(capture_name),
+        &options,
+    );
+    nodes.into_iter().map(|(_, _, node)| node).collect()
+}
+
+// Get one match for each captured node, for the rules that visit the captured nodes one by
+// one. The matches only have the captured node and the quantifiers of the captures are not
+// set. The number of matches is the number of captured nodes.
+pub fn get_query_capture_nodes_with_options(
+    tree: &tree_sitter::Tree,
+    query: &tree_sitter::Query,
+    filename: &str,
+    code: &str,
+    variables: &HashMap<String, String>,
+    options: &QueryNodesOptions,
+) -> QueryNodes {
+    QueryRunner::new(tree, filename, code, variables).run_capture_nodes(query, options)
+}
+
+// Run queries on a tree, the state that does not depend on the query is shared between them.
+struct QueryRunner<'a> {
+    // the root of the tree
+    root: tree_sitter::Node<'a>,
+    filename: &'a str,
+    code: &'a str,
+    // the code in the context of the matches, copied once for all the matches
+    shared_code: Arc<str>,
+    variables: &'a HashMap<String, String>,
+    query_cursor: QueryCursor,
+    columns: ColumnConverter<'a>,
+}
+
+impl<'a> QueryRunner<'a> {
+    fn new(
+        tree: &'a tree_sitter::Tree,
+        filename: &'a str,
+        code: &'a str,
+        variables: &'a HashMap<String, String>,
+    ) -> Self {
+        QueryRunner::new_with_root(tree.root_node(), filename, code, variables)
+    }
+
+    fn new_with_root(
+        root: tree_sitter::Node<'a>,
+        filename: &'a str,
+        code: &'a str,
+        variables: &'a HashMap<String, String>,
+    ) -> Self {
+        QueryRunner {
+            root,
+            filename,
+            code,
+            shared_code: Arc::from(code),
+            variables,
+            query_cursor: QueryCursor::new(),
+            columns: ColumnConverter::new(Some(code), PositionEncoding::default()),
+        }
+    }
+
+    // set the options of the cursor and the columns for a query
+    fn prepare(&mut self, options: &QueryNodesOptions) {
+        let encoding = options.map_node_options.position_encoding;
+        if self.columns.encoding != encoding {
+            self.columns = ColumnConverter::new(Some(self.code), encoding);
+        }
+        // the cursor is reused, the range and the limit of the previous query must not be kept
+        self.query_cursor
+            .set_byte_range(options.byte_range.clone().unwrap_or(0..usize::MAX));
+        self.query_cursor
+            .set_match_limit(options.match_limit.unwrap_or(u32::MAX));
+    }
+
+    fn run(&mut self, query: &tree_sitter::Query, options: &QueryNodesOptions) -> QueryNodes {
+        self.run_in_node(self.root, query, options)
+    }
+
+    // run the query in the subtree of a node, the positions are still in the whole code
+    fn run_in_node(
+        &mut self,
+        node: tree_sitter::Node<'a>,
+        query: &tree_sitter::Query,
+        options: &QueryNodesOptions,
+    ) -> QueryNodes {
+        let code = self.code;
+        let has_errors = self.root.has_error();
+        self.prepare(options);
+        let mut match_nodes: Vec<MatchNode> = vec![];
+        let mut is_truncated = false;
+        let mut is_timed_out = false;
+        // the quantifiers of the captures, by pattern
+        let mut captures_meta: HashMap<usize, HashMap<String, CaptureQuantifier>> = HashMap::new();
+
+        // The cursor evaluates the text predicates (#eq?, #match? and their negations) against
+        // the code and only returns the matches that satisfy them. The regular expressions are
+        // compiled once, when the query is built.
+        let query_result = self.query_cursor.matches(query, node, code.as_bytes());
+
+        for query_match in query_result {
+            // checked between matches, the matches found so far are kept
+            if options
+                .deadline
+                .map(|deadline| Instant::now() >= deadline)
+                .unwrap_or(false)
+            {
+                is_timed_out = true;
+                break;
+            }
+            if !satisfies_general_predicates(query, &query_match, code) {
+                continue;
+            }
+            // stop before building more nodes, generated files can have a huge number of matches
+            if options
+                .max_matches
+                .map(|max_matches| match_nodes.len() >= max_matches)
+                .unwrap_or(false)
+            {
+                is_truncated = true;
+                break;
+            }
+
+            let mut captures: HashMap<String, TreeSitterNode> = HashMap::new();
+            let mut captures_list: HashMap<String, Vec<TreeSitterNode>> = HashMap::new();
+            // the captures are added in the order of the code, an enclosing node before the
+            // nodes it contains
+            let mut match_captures = query_match.captures.to_vec();
+            match_captures.sort_by_key(|c| (c.node.start_byte(), Reverse(c.node.end_byte())));
+            for capture in match_captures.iter() {
+                let capture_name_opt = query
+                    .capture_names()
+                    .get(usize::try_from(capture.index).unwrap());
+                if capture_name_opt
+                    .map(|name| name.starts_with(PRIVATE_CAPTURE_PREFIX))
+                    .unwrap_or(false)
+                {
+                    continue;
+                }
+                let node_opt = map_captured_node(code, &mut self.columns, capture.node, options);
+
+                if let (Some(capture_name), Some(node)) = (capture_name_opt, node_opt) {
+                    // the first node of the capture, the others are in captures_list
+                    if !captures.contains_key(capture_name) {
+                        captures.insert(capture_name.to_string(), node.clone());
+                    }
+                    if !captures_list.contains_key(capture_name) {
+                        captures_list.insert(capture_name.to_string(), vec![]);
+                    }
+                    captures_list
+                        .get_mut(capture_name)
+                        .unwrap()
+                        .push(node.clone());
+                }
+            }
+
+            if !captures.is_empty() {
+                // the captures are sorted by start, the last one may not end last
+                let match_start = match_captures[0].node.start_position();
+                let match_end = match_captures
+                    .iter()
+                    .map(|c| c.node.end_position())
+                    .max()
+                    .unwrap_or(match_start);
+                match_nodes.push(MatchNode {
+                    match_start: self.columns.get_position(match_start),
+                    match_end: self.columns.get_position(match_end),
+                    captures: captures.clone(),
+                    captures_list: captures_list.clone(),
+                    settings: get_pattern_settings(query, query_match.pattern_index),
+                    captures_meta: captures_meta
+                        .entry(query_match.pattern_index)
+                        .or_insert_with(|| get_captures_meta(query, query_match.pattern_index))
+                        .clone(),
+                    pattern_index: query_match.pattern_index,
+                    pattern_count: query.pattern_count(),
+                    context: MatchNodeContext {
+                        code: Some(Arc::clone(&self.shared_code)),
+                        filename: self.filename.to_string(),
+                        variables: self.variables.clone(),
+                        has_errors,
+                        comments: Arc::default(),
+                        language: options.language,
+                        arguments: HashMap::new(),
+                        file: FileMetadata::default(),
+                        rule_name: None,
+                        rule_severity: None,
+                        schema_version: NODE_SCHEMA_VERSION,
+                    },
+                });
+            }
+        }
+        // in-progress matches were dropped when there were more than the limit
+        if self.query_cursor.did_exceed_match_limit() {
+            is_truncated = true;
+        }
+        QueryNodes {
+            match_nodes,
+            is_truncated,
+            is_timed_out,
+        }
+    }
+
+    // one match for each captured node, see get_query_capture_nodes_with_options
+    fn run_capture_nodes(
+        &mut self,
+        query: &tree_sitter::Query,
+        options: &QueryNodesOptions,
+    ) -> QueryNodes {
+        let has_errors = self.root.has_error();
+        let (nodes, is_truncated, is_timed_out) = self.run_captures(query, None, options);
+        let match_nodes = nodes
+            .into_iter()
+            .map(|(pattern_index, name, node)| MatchNode {
+                match_start: node.start.clone(),
+                match_end: node.end.clone(),
+                captures: HashMap::from([(name.clone(), node.clone())]),
+                captures_list: HashMap::from([(name, vec![node])]),
+                settings: get_pattern_settings(query, pattern_index),
+                captures_meta: HashMap::new(),
+                pattern_index,
+                pattern_count: query.pattern_count(),
+                context: MatchNodeContext {
+                    code: Some(Arc::clone(&self.shared_code)),
+                    filename: self.filename.to_string(),
+                    variables: self.variables.clone(),
+                    has_errors,
+                    comments: Arc::default(),
+                    language: options.language,
+                    arguments: HashMap::new(),
+                    file: FileMetadata::default(),
+                    rule_name: None,
+                    rule_severity: None,
+                    schema_version: NODE_SCHEMA_VERSION,
+                },
+            })
+            .collect();
+        QueryNodes {
+            match_nodes,
+            is_truncated,
+            is_timed_out,
+        }
+    }
+
+    // Get the nodes captured by the query, with the name of their capture and the pattern that
+    // captured them, in the order of the code and without duplicates. The matches are not
+    // grouped. Only the captures with the name are returned when it is set.
+    fn run_captures(
+        &mut self,
+        query: &tree_sitter::Query,
+        capture_name: Option<&str>,
+        options: &QueryNodesOptions,
+    ) -> (Vec<(usize, String, TreeSitterNode)>, bool, bool) {
+        let code = self.code;
+        self.prepare(options);
+        let mut is_truncated = false;
+        let mut is_timed_out = false;
+        let mut seen: HashSet<(u32, usize)> = HashSet::new();
+        let mut captured: Vec<(usize, u32, tree_sitter::Node)> = vec![];
+
+        let query_result = self
+            .query_cursor
+            .captures(query, self.root, code.as_bytes());
+        for (query_match, capture_index) in query_result {
+            if options
+                .deadline
+                .map(|deadline| Instant::now() >= deadline)
+                .unwrap_or(false)
+            {
+                is_timed_out = true;
+                break;
+            }
+            let capture = query_match.captures[capture_index];
+            let name = &query.capture_names()[usize::try_from(capture.index).unwrap()];
+            if name.starts_with(PRIVATE_CAPTURE_PREFIX)
+                || capture_name
+                    .map(|capture_name| name != capture_name)
+                    .unwrap_or(false)
+                || !satisfies_general_predicates(query, &query_match, code)
+            {
+                continue;
+            }
+            if !seen.insert((capture.index, capture.node.id())) {
+                continue;
+            }
+            if options
+                .max_matches
+                .map(|max_matches| captured.len() >= max_matches)
+                .unwrap_or(false)
+            {
+                is_truncated = true;
+                break;
+            }
+            captured.push((query_match.pattern_index, capture.index, capture.node));
+        }
+        if self.query_cursor.did_exceed_match_limit() {
+            is_truncated = true;
+        }
+
+        captured.sort_by_key(|(_, _, node)| (node.start_byte(), Reverse(node.end_byte())));
+        let nodes = captured
+            .into_iter()
+            .filter_map(|(pattern_index, capture_index, node)| {
+                let name = &query.capture_names()[usize::try_from(capture_index).unwrap()];
+                map_captured_node(code, &mut self.columns, node, options)
+                    .map(|node| (pattern_index, name.to_string(), node))
+            })
+            .collect();
+        (nodes, is_truncated, is_timed_out)
+    }
+}
+
+// Get the nodes matching the query in a byte range of the code. The matches that intersect
+// the range are returned with their whole nodes, even when the nodes start or end outside.
+pub fn get_query_nodes_in_range(
+    tree: &tree_sitter::Tree,
+    query: &tree_sitter::Query,
+    range: Range<usize>,
+    filename: &str,
+    code: &str,
+    variables: &HashMap<String, String>,
+) -> Vec<MatchNode> {
+    let options = QueryNodesOptions {
+        byte_range: Some(range),
+        ..QueryNodesOptions::default()
+    };
+    get_query_nodes_with_options(tree, query, filename, code, variables, &options).match_nodes
+}
+
+// Get the code of a node, using its byte offsets. Empty if the node is not in the code.
+pub fn get_code_for_node<'a>(code: &'a str, node: &TreeSitterNode) -> &'a str {
+    code.get(node.start_byte..node.end_byte).unwrap_or("")
+}
+
+// get the start of a text up to a number of bytes, without splitting a character, and whether
+// the text was truncated
+fn truncate_text(text: &str, max_length: usize) -> (&str, bool) {
+    if text.len() <= max_length {
+        return (text, false);
+    }
+    let mut end = max_length;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    (&text[..end], true)
+}
+
+// map a captured node with its code and, when requested, its ancestors and siblings
+fn map_captured_node(
+    code: &str,
+    columns: &mut ColumnConverter,
+    node: tree_sitter::Node,
+    options: &QueryNodesOptions,
+) -> Option<TreeSitterNode> {
+    let mapped_node = map_node_with_converter(node, &options.map_node_options, columns)?;
+    let (previous_sibling, next_sibling) = if options.include_siblings {
+        (
+            node.prev_named_sibling()
+                .map(|sibling| get_node_summary(sibling, columns)),
+            node.next_named_sibling()
+                .map(|sibling| get_node_summary(sibling, columns)),
+        )
+    } else {
+        (None, None)
+    };
+    let (text, text_truncated) = match options.max_text_length {
+        Some(max_text_length) => {
+            let (text, truncated) =
+                truncate_text(get_code_for_node(code, &mapped_node), max_text_length);
+            (Some(text.to_string()), truncated)
+        }
+        None => (None, false),
+    };
+    Some(TreeSitterNode {
+        text,
+        text_truncated,
+        ancestors: if options.include_ancestors {
+            get_ancestors(node, columns)
+        } else {
+            vec![]
+        },
+        parent_kind: node.parent().map(|parent| parent.kind().to_string()),
+        parent_field_name: get_field_name_in_parent(node).map(ToString::to_string),
+        previous_sibling,
+        next_sibling,
+        ..mapped_node
+    })
+}
+
+// get the field of a node in its parent (e.g. `function` for the function of a call)
+fn get_field_name_in_parent(node: tree_sitter::Node) -> Option<&'static str> {
+    let parent = node.parent()?;
+    let mut cursor = parent.walk();
+    cursor.goto_first_child();
+    loop {
+        if cursor.node() == node {
+            return cursor.field_name();
+        }
+        if !cursor.goto_next_sibling() {
+            return None;
+        }
+    }
+}
+
+// Get the ancestors of a node of the tree, from its parent to the root of the tree. None when
+// the node is not in the tree.
+pub fn get_node_ancestors(
+    tree: &tree_sitter::Tree,
+    summary: &TreeSitterNodeSummary,
+    code: &str,
+    position_encoding: PositionEncoding,
+) -> Option<Vec<TreeSitterNodeSummary>> {
+    let node = get_node_in_tree(tree, summary)?;
+    let mut columns = ColumnConverter::new(Some(code), position_encoding);
+    Some(get_ancestors(node, &mut columns))
+}
+
+// get the ancestors of a node, from its parent to the root of the tree
+fn get_ancestors(
+    node: tree_sitter::Node,
+    columns: &mut ColumnConverter,
+) -> Vec<TreeSitterNodeSummary> {
+    let mut ancestors = vec![];
+    let mut parent = node.parent();
+    while let Some(ancestor) = parent {
+        ancestors.push(get_node_summary(ancestor, columns));
+        parent = ancestor.parent();
+    }
+    ancestors
+}
+
+// get the kind and the range of a node, without its children
+fn get_node_summary(
+    node: tree_sitter::Node,
+    columns: &mut ColumnConverter,
+) -> TreeSitterNodeSummary {
+    TreeSitterNodeSummary {
+        id: node.id(),
+        ast_type: node.kind().to_string(),
+        start: columns.get_position(node.start_position()),
+        end: columns.get_position(node.end_position()),
+        start_byte: node.start_byte(),
+        end_byte: node.end_byte(),
+    }
+}
+
+// get the quantifier of each capture of the pattern, except the private captures
+fn get_captures_meta(
+    query: &tree_sitter::Query,
+    pattern_index: usize,
+) -> HashMap<String, CaptureQuantifier> {
+    query
+        .capture_names()
+        .iter()
+        .zip(query.capture_quantifiers(pattern_index))
+        .filter(|(name, _)| !name.starts_with(PRIVATE_CAPTURE_PREFIX))
+        .filter_map(|(name, quantifier)| {
+            let quantifier = match quantifier {
+                tree_sitter::CaptureQuantifier::Zero => return None,
+                tree_sitter::CaptureQuantifier::One => CaptureQuantifier::One,
+                tree_sitter::CaptureQuantifier::ZeroOrOne => CaptureQuantifier::ZeroOrOne,
+                tree_sitter::CaptureQuantifier::ZeroOrMore => CaptureQuantifier::ZeroOrMore,
+                tree_sitter::CaptureQuantifier::OneOrMore => CaptureQuantifier::OneOrMore,
+            };
+            Some((name.to_string(), quantifier))
+        })
+        .collect()
+}
+
+// the kinds of the comment nodes in the grammar of each language
+fn get_comment_kinds(language: &Language) -> &'static [&'static str] {
+    match language {
+        Language::Java | Language::Rust => &["line_comment", "block_comment"],
+        Language::Kotlin => &["line_comment", "multiline_comment"],
+        Language::Scala => &["comment", "block_comment"],
+        Language::Swift => &["comment", "multiline_comment"],
+        Language::Dart => &["comment", "documentation_comment"],
+        Language::Sql => &["comment", "marginalia"],
+        _ => &["comment"],
+    }
+}
+
+// block comments start with a delimiter that is closed at their end, the others go until the
+// end of the line
+fn get_comment_kind(text: &str) -> CommentKind {
+    if ["/*", "--[[", "=begin", "<!--"]
+        .iter()
+        .any(|delimiter| text.starts_with(delimiter))
+    {
+        CommentKind::Block
+    } else {
+        CommentKind::Line
+    }
+}
+
+// get all the comments of the code, the columns are counted in characters
+pub fn get_comments(tree: &tree_sitter::Tree, code: &str, language: &Language) -> Vec<CommentInfo> {
+    get_comments_with_encoding(tree, code, language, PositionEncoding::default())
+}
+
+// get all the comments of the code, the columns are counted in the position encoding
+pub fn get_comments_with_encoding(
+    tree: &tree_sitter::Tree,
+    code: &str,
+    language: &Language,
+    encoding: PositionEncoding,
+) -> Vec<CommentInfo> {
+    let comment_kinds = get_comment_kinds(language);
+    let mut columns = ColumnConverter::new(Some(code), encoding);
+    let mut comments = vec![];
+    let mut cursor = tree.walk();
+    // comments are extras, they can be anywhere in the tree
+    loop {
+        let node = cursor.node();
+        if comment_kinds.contains(&node.kind()) {
+            let text = node.utf8_text(code.as_bytes()).unwrap_or_default();
+            comments.push(CommentInfo {
+                text: text.to_string(),
+                kind: get_comment_kind(text),
+                start: columns.get_position(node.start_position()),
+                end: columns.get_position(node.end_position()),
+                start_byte: node.start_byte(),
+                end_byte: node.end_byte(),
+            });
+        } else if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return comments;
+            }
+        }
+    }
+}
+
+// Write a node and its named descendants as an indented S-expression, to see the shape of the
+// tree when writing a query. The names of the captures of each node are written after it.
+fn node_to_sexp(
+    node: tree_sitter::Node,
+    options: &SexpOptions,
+    captures: &HashMap<usize, Vec<String>>,
+) -> String {
+    let mut sexp = String::new();
+    let mut cursor = node.walk();
+    // for each node from the first node to the node of the cursor, if the node is written
+    let mut written: Vec<bool> = vec![];
+
+    loop {
+        let node = cursor.node();
+        let is_written = node.is_named() || node.is_missing();
+        if is_written {
+            if !sexp.is_empty() {
+                sexp.push('\n');
+            }
+            let depth = written.iter().filter(|w| **w).count();
+            sexp.push_str(&"  ".repeat(depth));
+            if let Some(field_name) = cursor.field_name().filter(|_| options.fields) {
+                write!(sexp, "{field_name}: ").unwrap();
+            }
+            if node.is_missing() {
+                write!(sexp, "(MISSING {}", node.kind()).unwrap();
+            } else {
+                write!(sexp, "({}", node.kind()).unwrap();
+            }
+            if options.positions {
+                let (start, end) = (node.start_position(), node.end_position());
+                write!(
+                    sexp,
+                    " [{}, {}] - [{}, {}]",
+                    start.row, start.column, end.row, end.column
+                )
+                .unwrap();
+            }
+        }
+        written.push(is_written);
+        if cursor.goto_first_child() {
+            continue;
+        }
+
+        // close the nodes that have no more children
+        loop {
+            if written.pop().unwrap_or_default() {
+                sexp.push(')');
+                for capture_name in captures.get(&cursor.node().id()).into_iter().flatten() {
+                    write!(sexp, " @{capture_name}").unwrap();
+                }
+            }
+            if cursor.goto_next_sibling() {
+                break;
+            }
+            if !cursor.goto_parent() {
+                return sexp;
+            }
+        }
+    }
+}
+
+// Parse the code and write its tree as an S-expression
+pub fn tree_to_sexp(
+    code: &str,
+    language: &Language,
+    options: &SexpOptions,
+) -> Result<String, ParseError> {
+    let tree = try_get_tree(code, language)?;
+    Ok(node_to_sexp(tree.root_node(), options, &HashMap::new()))
+}
+
+// Parse the code and write its tree as an S-expression with the nodes captured by the query,
+// including the private captures
+pub fn tree_to_sexp_with_captures(
+    code: &str,
+    language: &Language,
+    query: &tree_sitter::Query,
+    options: &SexpOptions,
+) -> Result<String, ParseError> {
+    let tree = try_get_tree(code, language)?;
+    let mut captures: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut query_cursor = QueryCursor::new();
+    for query_match in query_cursor.matches(query, tree.root_node(), code.as_bytes()) {
+        for capture in query_match.captures {
+            let capture_name = &query.capture_names()[usize::try_from(capture.index).unwrap()];
+            let node_captures = captures.entry(capture.node.id()).or_default();
+            if !node_captures.contains(capture_name) {
+                node_captures.push(capture_name.clone());
+            }
+        }
+    }
+    // the order of the matches depends on where they end, the names are sorted to be stable
+    for node_captures in captures.values_mut() {
+        node_captures.sort();
+    }
+    Ok(node_to_sexp(tree.root_node(), options, &captures))
+}
+
+// map a node from the tree-sitter representation into our own internal representation
+// this is the representation that is passed to the JavaScript layer and how we represent
+// or expose the node to the end-user. The code is not available, the columns are counted in
+// UTF-8 bytes.
+pub fn map_node(node: tree_sitter::Node) -> Option<TreeSitterNode> {
+    let options = MapNodeOptions {
+        position_encoding: PositionEncoding::Utf8Bytes,
+        ..MapNodeOptions::default()
+    };
+    let mut columns = ColumnConverter::new(None, options.position_encoding);
+    map_node_with_converter(node, &options, &mut columns)
+}
+
+// Convert the columns of tree-sitter (in bytes) into columns in the position encoding. The
+// columns of a line are computed once, the first time a node starts or ends on the line.
+struct ColumnConverter<'a> {
+    line_index: Option<LineIndex<'a>>,
+    encoding: PositionEncoding,
+    // for each line, the column of each byte offset of the line
+    lines: HashMap<usize, Vec<usize>>,
+}
+
+impl<'a> ColumnConverter<'a> {
+    fn new(code: Option<&'a str>, encoding: PositionEncoding) -> Self {
+        ColumnConverter {
+            line_index: code.map(LineIndex::new),
+            encoding,
+            lines: HashMap::new(),
+        }
+    }
+
+    // get the column (starting at 0) of a point in the code
+    fn get_column(&mut self, point: tree_sitter::Point) -> usize {
+        let line = match (self.encoding, &self.line_index) {
+            (PositionEncoding::Utf8Bytes, _) | (_, None) => return point.column,
+            (_, Some(line_index)) => line_index
+                .get_line_range(point.row)
+                .and_then(|range| line_index.code().get(range))
+                .unwrap_or_default(),
+        };
+        let encoding = self.encoding;
+        let columns = self
+            .lines
+            .entry(point.row)
+            .or_insert_with(|| get_line_columns(line, encoding));
+        columns.get(point.column).copied().unwrap_or(point.column)
+    }
+
+    // get the position (line and column starting at 1) of a point in the code
+    fn get_position(&mut self, point: tree_sitter::Point) -> Position {
+        Position {
+            line: u32::try_from(point.row + 1).unwrap(),
+            col: u32::try_from(self.get_column(point) + 1).unwrap(),
+            cell: None,
+        }
+    }
+}
+
+// get the column of each byte offset of the line starting the code, including the offset
+// after the last character of the line
+fn get_line_columns(code: &str, encoding: PositionEncoding) -> Vec<usize> {
+    let line = code.split('\n').next().unwrap_or_default();
+    let mut columns = Vec::with_capacity(line.len() + 1);
+    let mut column = 0;
+    for c in line.chars() {
+        columns.resize(columns.len() + c.len_utf8(), column);
+        column += encoding.get_char_width(c);
+    }
+    columns.push(column);
+    columns
+}
+
+// map a node, the columns are counted in the position encoding of the options using the code
+// of the node
+pub fn map_node_in_code(
+    node: tree_sitter::Node,
+    code: &str,
+    options: &MapNodeOptions,
+) -> Option<TreeSitterNode> {
+    let mut columns = ColumnConverter::new(Some(code), options.position_encoding);
+    map_node_with_converter(node, options, &mut columns)
+}
+
+fn map_node_with_converter(
+    node: tree_sitter::Node,
+    options: &MapNodeOptions,
+    columns: &mut ColumnConverter,
+) -> Option<TreeSitterNode> {
+    // a node being mapped, its children are added as they are mapped
+    struct PendingNode<'a> {
+        node: tree_sitter::Node<'a>,
+        field_name: Option<&'static str>,
+        children: Vec<TreeSitterNode>,
+        is_truncated: bool,
+    }
+
+    impl<'a> PendingNode<'a> {
+        fn new(node: tree_sitter::Node<'a>, field_name: Option<&'static str>) -> Self {
+            PendingNode {
+                node,
+                field_name,
+                children: vec![],
+                is_truncated: false,
+            }
+        }
+    }
+
+    // we do not map space, parenthesis and other non-named nodes of the children to avoid
+    // polluting the AST, unless the rule asked for all the nodes. MISSING nodes are always
+    // mapped so that rules can see where the code is incomplete.
+    let is_mapped = |child: tree_sitter::Node| {
+        options.include_unnamed || child.is_named() || child.is_missing()
+    };
+    let has_mapped_children = |node: tree_sitter::Node| {
+        node.named_child_count() > 0 || (options.include_unnamed && node.child_count() > 0)
+    };
+    let is_full = |nodes_count: usize| {
+        options
+            .max_nodes
+            .map(|max_nodes| nodes_count >= max_nodes)
+            .unwrap_or(false)
+    };
+
+    let mut build_node = |pending: PendingNode| TreeSitterNode {
+        id: pending.node.id(),
+        ast_type: pending.node.kind().to_string(),
+        start: columns.get_position(pending.node.start_position()),
+        end: columns.get_position(pending.node.end_position()),
+        start_byte: pending.node.range().start_byte,
+        end_byte: pending.node.range().end_byte,
+        text: None,
+        text_truncated: false,
+        field_name: pending.field_name.map(ToString::to_string),
+        children: pending.children,
+        is_error: pending.node.is_error(),
+        is_missing: pending.node.is_missing(),
+        is_truncated: pending.is_truncated,
+        ancestors: vec![],
+        parent_kind: None,
+        parent_field_name: None,
+        previous_sibling: None,
+        next_sibling: None,
+    };
+
+    // The tree is mapped with a loop and the nodes being mapped are kept on the heap: files
+    // with deeply nested code would overflow the stack with a recursive function.
+    // Initially, we capture both un/named nodes to allow capturing unnamed node from
+    // the tree-sitter query.
+    let mut cursor = node.walk();
+    let mut stack: Vec<PendingNode> = vec![PendingNode::new(node, None)];
+    let mut nodes_count: usize = 1;
+    let max_depth = options.max_depth.map_or(MAX_MAP_NODE_DEPTH, |max_depth| {
+        max_depth.min(MAX_MAP_NODE_DEPTH)
+    });
+
+    loop {
+        // go down to the first child to map
+        let depth = stack.len() - 1;
+        let can_map_children = depth < max_depth && !is_full(nodes_count);
+        if !can_map_children {
+            stack.last_mut().unwrap().is_truncated = has_mapped_children(cursor.node());
+        } else if cursor.goto_first_child() {
+            let mut has_child = false;
+            loop {
+                if is_mapped(cursor.node()) {
+                    stack.push(PendingNode::new(cursor.node(), cursor.field_name()));
+                    nodes_count += 1;
+                    has_child = true;
+                    break;
+                }
+                if !cursor.goto_next_sibling() {
+                    cursor.goto_parent();
+                    break;
+                }
+            }
+            if has_child {
+                continue;
+            }
+        }
+
+        // the node is complete, add it to its parent and go to the next sibling to map
+        loop {
+            let ts_node = build_node(stack.pop().unwrap());
+            let parent = match stack.last_mut() {
+                Some(parent) => parent,
+                None => return Some(ts_node),
+            };
+            parent.children.push(ts_node);
+
+            let mut has_sibling = false;
+            while cursor.goto_next_sibling() {
+                if is_mapped(cursor.node()) {
+                    if is_full(nodes_count) {
+                        stack.last_mut().unwrap().is_truncated = true;
+                        break;
+                    }
+                    stack.push(PendingNode::new(cursor.node(), cursor.field_name()));
+                    nodes_count += 1;
+                    has_sibling = true;
+                    break;
+                }
+            }
+            if has_sibling {
+                break;
+            }
+            cursor.goto_parent();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::analysis::DEFAULT_MAP_NODE_MAX_DEPTH;
+    use crate::model::common::ALL_LANGUAGES;
+
+    // files with an extension mapped by the user are parsed with the grammar of the language
+    #[test]
+    fn test_get_tree_for_file_with_language_override() {
+        let source_code = r#"
+def _impl(ctx):
+    return [DefaultInfo(files = depset(ctx.files.srcs))]
+
+my_rule = rule(implementation = _impl, attrs = {"srcs": attr.label_list()})
+"#;
+        let overrides = HashMap::from([("bzl".to_string(), Language::Python)]);
+        let language = Language::from_path(Path::new("tools/defs.bzl"), &overrides).unwrap();
+        assert_eq!(language, Language::Python);
+
+        let tree = get_tree_for_file(source_code, &language, "tools/defs.bzl").unwrap();
+        assert_eq!(tree.root_node().kind(), "module");
+        assert!(!tree.root_node().has_error());
+        assert_eq!(
+            tree.root_node().child(0).unwrap().kind(),
+            "function_definition"
+        );
+    }
+
+    #[test]
+    fn test_python_get_tree() {
+        let source_code = r#"
+arr = ["foo", "bar"];
+
+def func():
+   pass;"#;
+        let t = get_tree(source_code, &Language::Python);
+        assert!(t.is_some());
+        assert_eq!("module", t.unwrap().root_node().kind());
+    }
+
+    #[test]
+    fn test_map_node_simple() {
+        let source_code = r#"
+arr = ["foo", "bar"];
+
+def func():
+   pass;"#;
+        let t = get_tree(source_code, &Language::Python);
+        assert!(t.is_some());
+        let tree_node = map_node(t.unwrap().root_node());
+        assert!(tree_node.is_some());
+        let root = tree_node.unwrap();
+        assert_eq!(2, root.children.len());
+        assert_eq!(
+            "expression_statement",
+            root.children.get(0).unwrap().ast_type
+        );
+        assert_eq!(
+            "function_definition",
+            root.children.get(1).unwrap().ast_type
+        );
+        assert!(root.children.get(1).unwrap().field_name.is_none());
+        let function_definition = root.children.get(1).unwrap();
+        assert_eq!(
+            "name",
+            function_definition
+                .children
+                .get(0)
+                .unwrap()
+                .field_name
+                .clone()
+                .unwrap()
+        );
+    }
+
+    // preprocessor directives are exposed as named nodes
+    #[test]
+    fn test_c_map_node_preprocessor() {
+        let source_code = r#"
+#include <stdio.h>
+#define MAX 10
+
+int main() {
+    return 0;
+}
+"#;
+        let t = get_tree(source_code, &Language::C);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("translation_unit", tree.root_node().kind());
+        let root = map_node(tree.root_node()).unwrap();
+        assert_eq!(3, root.children.len());
+        let include = root.children.get(0).unwrap();
+        assert_eq!("preproc_include", include.ast_type);
+        assert_eq!(2, include.start.line);
+        assert_eq!(
+            Some("path".to_string()),
+            include.children.get(0).unwrap().field_name
+        );
+        let define = root.children.get(1).unwrap();
+        assert_eq!("preproc_def", define.ast_type);
+        assert_eq!(3, define.start.line);
+        assert_eq!(
+            "function_definition",
+            root.children.get(2).unwrap().ast_type
+        );
+    }
+
+    #[test]
+    fn test_cpp_get_tree() {
+        let source_code = r#"
+#include <iostream>
+
+int main() {
+    std::cout << "Hello World!" << std::endl;
+    return 0;
+}
+"#;
+        let t = get_tree(source_code, &Language::Cpp);
+        assert!(t.is_some());
+        assert_eq!("translation_unit", t.unwrap().root_node().kind());
+    }
+
+    #[test]
+    fn test_csharp_get_tree() {
+        let source_code = r#"
+namespace HelloWorld
+{
+    class Hello {
+        static void Main(string[] args)
+        {
+            System.Console.WriteLine("Hello World!");
+        }
+    }
+}
+"#;
+        let t = get_tree(source_code, &Language::Csharp);
+        assert!(t.is_some());
+        assert_eq!("compilation_unit", t.unwrap().root_node().kind());
+    }
+
+    // class members, named constructors and annotations keep their field names
+    #[test]
+    fn test_dart_map_node() {
+        let source_code = r#"
+class Point extends Base {
+  Point.origin() : super(0);
+
+  @override
+  String toString() => "point";
+}
+"#;
+        let t = get_tree(source_code, &Language::Dart);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("program", tree.root_node().kind());
+        let root = map_node(tree.root_node()).unwrap();
+        let class = root.children.get(0).unwrap();
+        assert_eq!("class_definition", class.ast_type);
+        let field_names: Vec<String> = class
+            .children
+            .iter()
+            .filter_map(|c| c.field_name.clone())
+            .collect();
+        assert_eq!(vec!["name", "superclass", "body"], field_names);
+        let body = class.children.get(2).unwrap();
+        assert_eq!(2, body.start.line);
+        assert_eq!(7, body.end.line);
+        assert!(body.children.iter().any(|c| c.ast_type == "annotation"));
+    }
+
+    #[test]
+    fn test_dockerfile_get_tree() {
+        let source_code = r#"
+RUN /blabla
+"#;
+        let t = get_tree(source_code, &Language::Dockerfile);
+        assert!(t.is_some());
+        assert_eq!("source_file", t.unwrap().root_node().kind());
+    }
+
+    // instructions are exposed with their arguments as children
+    #[test]
+    fn test_dockerfile_map_node() {
+        let source_code = r#"
+FROM ubuntu:latest
+USER root
+"#;
+        let t = get_tree(source_code, &Language::Dockerfile);
+        assert!(t.is_some());
+        let root = map_node(t.unwrap().root_node()).unwrap();
+        assert_eq!(2, root.children.len());
+        let from = root.children.get(0).unwrap();
+        assert_eq!("from_instruction", from.ast_type);
+        let image_spec = from.children.get(0).unwrap();
+        assert_eq!("image_spec", image_spec.ast_type);
+        assert_eq!(2, image_spec.children.len());
+        assert_eq!(
+            Some("name".to_string()),
+            image_spec.children.get(0).unwrap().field_name
+        );
+        assert_eq!(
+            Some("tag".to_string()),
+            image_spec.children.get(1).unwrap().field_name
+        );
+        assert_eq!("user_instruction", root.children.get(1).unwrap().ast_type);
+    }
+
+    // defmodule/def macros are calls whose children expose the module and function names
+    #[test]
+    fn test_elixir_map_node() {
+        let source_code = r#"
+defmodule Greeter do
+  def hello(name) do
+    "Hello " <> name
+  end
+end
+"#;
+        let t = get_tree(source_code, &Language::Elixir);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("source", tree.root_node().kind());
+        let root = map_node(tree.root_node()).unwrap();
+        let module = root.children.get(0).unwrap();
+        assert_eq!("call", module.ast_type);
+        let target = module.children.get(0).unwrap();
+        assert_eq!(Some("target".to_string()), target.field_name);
+        assert_eq!("identifier", target.ast_type);
+        let arguments = module.children.get(1).unwrap();
+        assert_eq!("arguments", arguments.ast_type);
+        assert_eq!("alias", arguments.children.get(0).unwrap().ast_type);
+        let do_block = module.children.get(2).unwrap();
+        assert_eq!("do_block", do_block.ast_type);
+        let function = do_block.children.get(0).unwrap();
+        assert_eq!("call", function.ast_type);
+        assert_eq!(3, function.start.line);
+        assert_eq!(5, function.end.line);
+    }
+
+    #[test]
+    fn test_go_test_tree() {
+        let source_code = r#"
+package main
+import "fmt"
+func main() {
+    fmt.Println("hello world")
+}
+"#;
+        let t = get_tree(source_code, &Language::Go);
+        assert!(t.is_some());
+        assert_eq!("source_file", t.unwrap().root_node().kind());
+    }
+
+    #[test]
+    fn test_groovy_get_tree() {
+        let source_code = r#"
+pipeline {
+    agent any
+    stages {
+        stage('Build') {
+            steps {
+                sh "make ${TARGET}"
+                sh('make test')
+            }
+        }
+    }
+}
+"#;
+        let t = get_tree(source_code, &Language::Groovy);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("source_file", tree.root_node().kind());
+        assert!(!tree.root_node().has_error());
+    }
+
+    // closures and method calls without parentheses are mapped as regular nodes
+    #[test]
+    fn test_groovy_map_node() {
+        let source_code = r#"
+dependencies {
+    implementation 'org.slf4j:slf4j-api:2.0.9'
+}
+tasks.register('hello') {
+    doLast { println 'hello' }
+}
+"#;
+        let t = get_tree(source_code, &Language::Groovy);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert!(!tree.root_node().has_error());
+        let root = map_node(tree.root_node()).unwrap();
+        assert_eq!(2, root.children.len());
+        fn find_node<'a>(node: &'a TreeSitterNode, ast_type: &str) -> Option<&'a TreeSitterNode> {
+            if node.ast_type == ast_type {
+                return Some(node);
+            }
+            node.children.iter().find_map(|c| find_node(c, ast_type))
+        }
+        let dependencies = root.children.get(0).unwrap();
+        let closure = find_node(dependencies, "closure").unwrap();
+        assert_eq!(2, closure.start.line);
+        assert_eq!(4, closure.end.line);
+        let implementation = find_node(closure, "juxt_function_call").unwrap();
+        assert_eq!(3, implementation.start.line);
+        assert_eq!(5, implementation.start.col);
+        let register = root.children.get(1).unwrap();
+        assert_eq!(5, register.start.line);
+        assert_eq!(7, register.end.line);
+        assert!(find_node(register, "ERROR").is_none());
+    }
+
+    #[test]
+    fn test_java_get_tree() {
+        let source_code = r#"
+class Foo {
+}
+"#;
+        let t = get_tree(source_code, &Language::Java);
+        assert!(t.is_some());
+        assert_eq!("program", t.unwrap().root_node().kind());
+    }
+
+    #[test]
+    fn test_javascript_get_tree() {
+        let source_code = r#"
+function foo() {console.log("bar");}"#;
+        let t = get_tree(source_code, &Language::JavaScript);
+        assert!(t.is_some());
+        assert_eq!("program", t.unwrap().root_node().kind());
+    }
+
+    // React components in .jsx files are parsed by the JavaScript grammar
+    #[test]
+    fn test_jsx_get_tree() {
+        let source_code = r#"
+const Greeting = ({ name, age }) => {
+  return <div className="greeting">Hello {name}, {age}</div>;
+};
+"#;
+        let t = get_tree_for_file(source_code, &Language::JavaScript, "Greeting.jsx");
+        assert!(t.is_ok());
+        let tree = t.unwrap();
+        assert!(!tree.root_node().has_error());
+        assert_eq!(0.0, get_error_rate(&tree, source_code));
+    }
+
+    // files with a @flow pragma have type annotations and use a grammar that supports them
+    #[test]
+    fn test_flow_get_tree() {
+        let source_code = r#"
+// @flow
+function add(a: number, b: number): number {
+  return a + b;
+}
+"#;
+        let javascript_tree = get_tree(source_code, &Language::JavaScript).unwrap();
+        assert!(javascript_tree.root_node().has_error());
+        assert!(get_error_rate(&javascript_tree, source_code) > 0.0);
+
+        let flow_tree = get_tree_for_file(source_code, &Language::JavaScript, "add.js").unwrap();
+        assert!(!flow_tree.root_node().has_error());
+        assert_eq!(0.0, get_error_rate(&flow_tree, source_code));
+    }
+
+    #[test]
+    fn test_has_flow_pragma() {
+        assert!(has_flow_pragma("// @flow\nconst a = 1;"));
+        assert!(has_flow_pragma("/**\n * @flow strict\n */\nconst a = 1;"));
+        assert!(!has_flow_pragma("const a = 1;\n// @flow"));
+        assert!(!has_flow_pragma("const a = 1;"));
+    }
+
+    #[test]
+    fn test_json_get_tree() {
+        let source_code = r#"
+{}"#;
+        let t = get_tree(source_code, &Language::Json);
+        assert!(t.is_some());
+        assert_eq!("document", t.unwrap().root_node().kind());
+    }
+
+    // HTML outside of the PHP tags is kept as named `text` nodes
+    // functions, calls and table constructors are mapped as named nodes
+    #[test]
+    fn test_lua_map_node() {
+        let source_code = r#"
+local config = { debug = true, level = 3 }
+function greet(name)
+  print("hello " .. name)
+end
+"#;
+        let t = get_tree(source_code, &Language::Lua);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("chunk", tree.root_node().kind());
+        let root = map_node(tree.root_node()).unwrap();
+        assert_eq!(2, root.children.len());
+        let declaration = root.children.get(0).unwrap();
+        assert_eq!("variable_declaration", declaration.ast_type);
+        fn find_node<'a>(node: &'a TreeSitterNode, ast_type: &str) -> Option<&'a TreeSitterNode> {
+            if node.ast_type == ast_type {
+                return Some(node);
+            }
+            node.children.iter().find_map(|c| find_node(c, ast_type))
+        }
+        let table = find_node(declaration, "table_constructor").unwrap();
+        assert_eq!(2, table.start.line);
+        assert_eq!(16, table.start.col);
+        assert_eq!(2, table.children.len());
+        let function = root.children.get(1).unwrap();
+        assert_eq!("function_declaration", function.ast_type);
+        assert_eq!(3, function.start.line);
+        assert_eq!(5, function.end.line);
+        assert_eq!(
+            Some("name".to_string()),
+            function.children.get(0).unwrap().field_name
+        );
+    }
+
+    #[test]
+    fn test_php_map_node_with_html() {
+        let source_code = r#"<html>
+<?php echo "foo"; ?>
+</html>
+"#;
+        let t = get_tree(source_code, &Language::Php);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("program", tree.root_node().kind());
+        let root = map_node(tree.root_node()).unwrap();
+        let text_nodes: Vec<&TreeSitterNode> = root
+            .children
+            .iter()
+            .filter(|c| c.ast_type == "text")
+            .collect();
+        assert_eq!(2, text_nodes.len());
+        assert_eq!(1, text_nodes.get(0).unwrap().start.line);
+        assert_eq!(1, text_nodes.get(0).unwrap().start.col);
+        assert_eq!(3, text_nodes.get(1).unwrap().start.line);
+    }
+
+    #[test]
+    fn test_ruby_get_tree() {
+        let source_code = r#"
+class Foo
+  def bar
+    puts "bar"
+  end
+end
+"#;
+        let t = get_tree(source_code, &Language::Ruby);
+        assert!(t.is_some());
+        assert_eq!("program", t.unwrap().root_node().kind());
+    }
+
+    // interpolated strings and multi-line blocks must keep their start/end lines
+    #[test]
+    fn test_ruby_map_node_positions() {
+        let source_code = r#"
+name = "world"
+greeting = "hello #{name}"
+[1, 2].each do |i|
+  puts i
+end
+"#;
+        let t = get_tree(source_code, &Language::Ruby);
+        assert!(t.is_some());
+        let root = map_node(t.unwrap().root_node()).unwrap();
+        assert_eq!(3, root.children.len());
+
+        let string = root
+            .children
+            .get(1)
+            .unwrap()
+            .children
+            .iter()
+            .find(|c| c.ast_type == "string")
+            .unwrap();
+        assert_eq!(3, string.start.line);
+        assert_eq!(12, string.start.col);
+        assert_eq!(3, string.end.line);
+        assert_eq!(27, string.end.col);
+        assert!(string
+            .children
+            .iter()
+            .any(|c| c.ast_type == "interpolation"));
+
+        let call = root.children.get(2).unwrap();
+        assert_eq!("call", call.ast_type);
+        assert_eq!(4, call.start.line);
+        assert_eq!(1, call.start.col);
+        assert_eq!(6, call.end.line);
+        assert_eq!(4, call.end.col);
+        let block = call
+            .children
+            .iter()
+            .find(|c| c.field_name == Some("block".to_string()))
+            .unwrap();
+        assert_eq!("do_block", block.ast_type);
+        assert_eq!(4, block.start.line);
+        assert_eq!(6, block.end.line);
+    }
+
+    // JSON with comments (e.g. tsconfig.json, VS Code settings) is handled by the grammar
+    #[test]
+    fn test_json_get_tree_with_comments() {
+        let source_code = r#"
+{
+  // enable strict mode
+  "strict": true /* always */
+}"#;
+        let t = get_tree(source_code, &Language::Json);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert!(!tree.root_node().has_error());
+        let root = map_node(tree.root_node()).unwrap();
+        let object = root.children.get(0).unwrap();
+        assert_eq!("object", object.ast_type);
+        let pair = object
+            .children
+            .iter()
+            .find(|c| c.ast_type == "pair")
+            .unwrap();
+        assert_eq!(4, pair.start.line);
+        assert_eq!(3, pair.start.col);
+    }
+
+    #[test]
+    fn test_rust_get_tree() {
+        let source_code = r#"
+fn foo(bar: String) -> String {
+   return "foobar".to_string();
+}
+"#;
+        let t = get_tree(source_code, &Language::Rust);
+        assert!(t.is_some());
+        assert_eq!("source_file", t.unwrap().root_node().kind());
+    }
+
+    #[test]
+    fn test_kotlin_get_tree() {
+        let source_code = r#"
+fun main() {
+    println("What's your name?")
+    val name = readln()
+    println("Hello, $name!")
+}
+"#;
+        let t = get_tree(source_code, &Language::Kotlin);
+        assert!(t.is_some());
+        assert_eq!("source_file", t.unwrap().root_node().kind());
+    }
+
+    // Kotlin scripts (.kts) have top-level statements and must still produce a usable tree
+    #[test]
+    fn test_kotlin_script_get_tree() {
+        let source_code = r#"
+plugins {
+    kotlin("jvm") version "1.9.0"
+}
+println("configured")
+"#;
+        let t = get_tree(source_code, &Language::Kotlin);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("source_file", tree.root_node().kind());
+        assert!(!tree.root_node().has_error());
+        let root = map_node(tree.root_node()).unwrap();
+        assert_eq!(2, root.children.len());
+        assert_eq!("call_expression", root.children.get(0).unwrap().ast_type);
+        assert_eq!("call_expression", root.children.get(1).unwrap().ast_type);
+        assert_eq!(5, root.children.get(1).unwrap().start.line);
+    }
+
+    // objects, classes and values are exposed with their field names
+    #[test]
+    fn test_scala_map_node() {
+        let source_code = r#"
+object Main {
+  val greeting = "hello"
+}
+class Person(name: String)
+"#;
+        let t = get_tree(source_code, &Language::Scala);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("compilation_unit", tree.root_node().kind());
+        let root = map_node(tree.root_node()).unwrap();
+        assert_eq!(2, root.children.len());
+
+        let object = root.children.get(0).unwrap();
+        assert_eq!("object_definition", object.ast_type);
+        let object_name = object.children.get(0).unwrap();
+        assert_eq!(Some("name".to_string()), object_name.field_name);
+        assert_eq!(2, object_name.start.line);
+        assert_eq!(8, object_name.start.col);
+        let body = object.children.get(1).unwrap();
+        assert_eq!(Some("body".to_string()), body.field_name);
+        let value = body.children.get(0).unwrap();
+        assert_eq!("val_definition", value.ast_type);
+        assert_eq!(
+            Some("pattern".to_string()),
+            value.children.get(0).unwrap().field_name
+        );
+        assert_eq!(
+            Some("value".to_string()),
+            value.children.get(1).unwrap().field_name
+        );
+
+        let class = root.children.get(1).unwrap();
+        assert_eq!("class_definition", class.ast_type);
+        assert_eq!(
+            Some("name".to_string()),
+            class.children.get(0).unwrap().field_name
+        );
+    }
+
+    // the commands of a pipeline are mapped in order
+    #[test]
+    fn test_shell_map_node_pipeline() {
+        let source_code = r#"
+curl -s https://example.com/install.sh | sh
+"#;
+        let t = get_tree(source_code, &Language::Shell);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("program", tree.root_node().kind());
+        let root = map_node(tree.root_node()).unwrap();
+        let pipeline = root.children.get(0).unwrap();
+        assert_eq!("pipeline", pipeline.ast_type);
+        assert_eq!(2, pipeline.children.len());
+        let curl = pipeline.children.get(0).unwrap();
+        assert_eq!("command", curl.ast_type);
+        assert_eq!(1, curl.start.col);
+        let sh = pipeline.children.get(1).unwrap();
+        assert_eq!("command", sh.ast_type);
+        assert_eq!(2, sh.start.line);
+        assert_eq!(42, sh.start.col);
+        assert_eq!(44, sh.end.col);
+    }
+
+    // each statement of a migration file is a distinct named child
+    #[test]
+    fn test_sql_map_node_statements() {
+        let source_code = r#"
+CREATE INDEX CONCURRENTLY idx_users_email ON users (email);
+ALTER TABLE users ADD COLUMN age INTEGER;
+"#;
+        let t = get_tree(source_code, &Language::Sql);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("source_file", tree.root_node().kind());
+        assert!(!tree.root_node().has_error());
+        let root = map_node(tree.root_node()).unwrap();
+        assert_eq!(2, root.children.len());
+        let create_index = root.children.get(0).unwrap();
+        assert_eq!("create_index_statement", create_index.ast_type);
+        assert_eq!(2, create_index.start.line);
+        let alter_table = root.children.get(1).unwrap();
+        assert_eq!("alter_table_statement", alter_table.ast_type);
+        assert_eq!(3, alter_table.start.line);
+    }
+
+    #[test]
+    fn test_swift_get_tree() {
+        let source_code = r#"
+print("Hello, world!")
+}
+"#;
+        let t = get_tree(source_code, &Language::Swift);
+        assert!(t.is_some());
+        assert_eq!("source_file", t.unwrap().root_node().kind());
+    }
+
+    // trailing closures and multi-line strings keep their children ordering and positions
+    #[test]
+    fn test_swift_map_node_closures_and_strings() {
+        let source_code = r#"
+let message = """
+Hello
+World
+"""
+numbers.map { n in
+    n * 2
+}
+"#;
+        let t = get_tree(source_code, &Language::Swift);
+        assert!(t.is_some());
+        let root = map_node(t.unwrap().root_node()).unwrap();
+        assert_eq!(2, root.children.len());
+
+        let property = root.children.get(0).unwrap();
+        assert_eq!("property_declaration", property.ast_type);
+        assert_eq!(2, property.start.line);
+        assert_eq!(5, property.end.line);
+        let string = property
+            .children
+            .iter()
+            .find(|c| c.ast_type == "multi_line_string_literal")
+            .unwrap();
+        assert_eq!(2, string.start.line);
+        assert_eq!(15, string.start.col);
+        assert_eq!(5, string.end.line);
+        assert_eq!(4, string.end.col);
+
+        let call = root.children.get(1).unwrap();
+        assert_eq!("call_expression", call.ast_type);
+        assert_eq!(6, call.start.line);
+        assert_eq!(8, call.end.line);
+        assert_eq!(2, call.children.len());
+        assert_eq!(
+            "navigation_expression",
+            call.children.get(0).unwrap().ast_type
+        );
+        assert_eq!("call_suffix", call.children.get(1).unwrap().ast_type);
+    }
+
+    // nested blocks and attributes are mapped so that rules can navigate resources
+    #[test]
+    fn test_terraform_map_node_nested_blocks() {
+        let source_code = r#"
+resource "aws_s3_bucket" "logs" {
+  bucket = "my-logs"
+  versioning {
+    enabled = true
+  }
+}
+"#;
+        let t = get_tree(source_code, &Language::Terraform);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("config_file", tree.root_node().kind());
+        let root = map_node(tree.root_node()).unwrap();
+        let body = root.children.get(0).unwrap();
+        assert_eq!("body", body.ast_type);
+        let resource = body.children.get(0).unwrap();
+        assert_eq!("block", resource.ast_type);
+        assert_eq!(2, resource.start.line);
+        assert_eq!(7, resource.end.line);
+        let resource_body = resource
+            .children
+            .iter()
+            .find(|c| c.ast_type == "body")
+            .unwrap();
+        assert_eq!(2, resource_body.children.len());
+        let attribute = resource_body.children.get(0).unwrap();
+        assert_eq!("attribute", attribute.ast_type);
+        assert_eq!(3, attribute.start.line);
+        assert_eq!(3, attribute.start.col);
+        let versioning = resource_body.children.get(1).unwrap();
+        assert_eq!("block", versioning.ast_type);
+        assert_eq!(4, versioning.start.line);
+        assert_eq!(6, versioning.end.line);
+    }
+
+    // table headers and dotted keys are mapped as named nodes
+    #[test]
+    fn test_toml_map_node() {
+        let source_code = r#"
+[package]
+name = "kernel"
+metadata.docs.rs = true
+"#;
+        let t = get_tree(source_code, &Language::Toml);
+        assert!(t.is_some());
+        let tree = t.unwrap();
+        assert_eq!("document", tree.root_node().kind());
+        let root = map_node(tree.root_node()).unwrap();
+        let table = root.children.get(0).unwrap();
+        assert_eq!("table", table.ast_type);
+        assert_eq!("bare_key", table.children.get(0).unwrap().ast_type);
+        assert_eq!(2, table.children.get(0).unwrap().start.line);
+        let dotted_pair = table.children.get(2).unwrap();
+        assert_eq!("pair", dotted_pair.ast_type);
+        let dotted_key = dotted_pair.children.get(0).unwrap();
+        assert_eq!("dotted_key", dotted_key.ast_type);
+        assert_eq!(4, dotted_key.start.line);
+        assert_eq!(1, dotted_key.start.col);
+        assert_eq!(17, dotted_key.end.col);
+    }
+
+    #[test]
     fn test_typescript_get_tree() {
         let source_code = r#"
 let myAdd = function (x: number, y: number): number {
   return x + y;
 };
 "#;
-        let t = get_tree(source_code, &Language::TypeScript);
-        assert!(t.is_some());
-        assert_eq!("program", t.unwrap().root_node().kind());
+        let t = get_tree(source_code, &Language::TypeScript);
+        assert!(t.is_some());
+        assert_eq!("program", t.unwrap().root_node().kind());
+    }
+
+    // generic arrow functions are misparsed as JSX by the TSX grammar, the default grammar
+    #[test]
+    fn test_typescript_generic_arrow_function() {
+        let source_code = r#"
+const identity = <T>(x: T) => x;
+const directions = ["up", "down"] as const;
+"#;
+        let t = get_tree(source_code, &Language::TypeScript);
+        assert!(t.unwrap().root_node().has_error());
+
+        let ts = get_tree_for_file(source_code, &Language::TypeScript, "src/identity.ts");
+        assert!(!ts.unwrap().root_node().has_error());
+
+        let tsx = get_tree_for_file(source_code, &Language::TypeScript, "src/identity.tsx");
+        assert!(tsx.unwrap().root_node().has_error());
+    }
+
+    // when the query cache is full, the least recently used query is evicted
+    #[test]
+    fn test_query_cache_evicts_least_recently_used() {
+        let grammar = Grammar::new(Language::Python);
+        let query = get_query("(identifier) @id", &Language::Python).unwrap();
+        let mut cache = QueryCache::new(2);
+        cache.insert((grammar, 1), query.clone());
+        cache.insert((grammar, 2), query.clone());
+        assert!(cache.get(&(grammar, 1)).is_some());
+        cache.insert((grammar, 3), query);
+        assert_eq!(2, cache.entries.len());
+        assert!(cache.get(&(grammar, 1)).is_some());
+        assert!(cache.get(&(grammar, 2)).is_none());
+        assert!(cache.get(&(grammar, 3)).is_some());
+
+        // the grammars of the same language are different keys
+        let ts_grammar = get_grammar_for_file(&Language::TypeScript, "app.ts", "");
+        let tsx_grammar = get_grammar_for_file(&Language::TypeScript, "app.tsx", "");
+        assert_ne!(ts_grammar, tsx_grammar);
+    }
+
+    // .ts files use the TypeScript grammar and fall back to the TSX grammar, other TypeScript
+    // files use the TSX grammar
+    #[test]
+    fn test_typescript_query_for_file() {
+        let q = r#"(jsx_element) @element"#;
+        let c = r#"
+const App = () => <div>hello</div>;
+"#;
+
+        let ts_grammar = get_grammar_for_file(&Language::TypeScript, "app.ts", c);
+        assert!(!ts_grammar.is_tsx);
+        assert!(get_query_for_grammar(q, ts_grammar).is_err());
+        let fallback_grammar = get_fallback_grammar(&Language::TypeScript, "app.ts").unwrap();
+        assert!(fallback_grammar.is_tsx);
+        assert!(get_query_for_grammar(q, fallback_grammar).is_ok());
+        assert!(get_fallback_grammar(&Language::TypeScript, "app.tsx").is_none());
+        assert!(get_fallback_grammar(&Language::JavaScript, "app.js").is_none());
+        // the code without filename uses the TSX grammar
+        assert!(get_query(q, &Language::TypeScript).is_ok());
+
+        let tsx_grammar = get_grammar_for_file(&Language::TypeScript, "app.tsx", c);
+        let tree =
+            get_tree_with_language(c, tsx_grammar.get_tree_sitter_language().unwrap()).unwrap();
+        let query = get_query_for_grammar(q, tsx_grammar).unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "app.tsx", c, &HashMap::new());
+        assert_eq!(1, query_nodes.len());
+        let element = query_nodes.get(0).unwrap().captures.get("element").unwrap();
+        assert_eq!(2, element.start.line);
+        assert_eq!(19, element.start.col);
+    }
+
+    #[test]
+    fn test_yaml_get_tree() {
+        let source_code = r#"
+rulesets:
+  - my-ruleset
+"#;
+        let t = get_tree(source_code, &Language::Yaml);
+        assert!(t.is_some());
+        assert_eq!("stream", t.unwrap().root_node().kind());
+    }
+
+    // key/value pairs keep their positions across documents, anchors and aliases
+    #[test]
+    fn test_yaml_map_node_multi_documents() {
+        let source_code = r#"
+defaults: &defaults
+  image: ubuntu
+---
+job:
+  <<: *defaults
+  privileged: true
+"#;
+        let t = get_tree(source_code, &Language::Yaml);
+        assert!(t.is_some());
+        let root = map_node(t.unwrap().root_node()).unwrap();
+        assert_eq!("stream", root.ast_type);
+        assert_eq!(2, root.children.len());
+        assert_eq!("document", root.children.get(0).unwrap().ast_type);
+        assert_eq!("document", root.children.get(1).unwrap().ast_type);
+
+        let second_document = root.children.get(1).unwrap();
+        assert_eq!(4, second_document.start.line);
+        let job_pair = second_document
+            .children
+            .get(0)
+            .unwrap()
+            .children
+            .get(0)
+            .unwrap()
+            .children
+            .get(0)
+            .unwrap();
+        assert_eq!("block_mapping_pair", job_pair.ast_type);
+        assert_eq!(5, job_pair.start.line);
+        let key = job_pair.children.get(0).unwrap();
+        assert_eq!(Some("key".to_string()), key.field_name);
+        assert_eq!(5, key.start.line);
+        assert_eq!(1, key.start.col);
+        let value = job_pair.children.get(1).unwrap();
+        assert_eq!(Some("value".to_string()), value.field_name);
+        assert_eq!(6, value.start.line);
+        assert_eq!(7, value.end.line);
+    }
+
+    // test the number of node we should retrieve when executing a rule
+    #[test]
+    fn test_get_query_nodes() {
+        let q = r#"
+(class_definition
+  name: (identifier) @classname
+  superclasses: (argument_list
+    (identifier)+ @superclasses
+  )
+)
+        "#;
+
+        let c = r#"
+ class myClass(Parent):
+    def __init__(self):
+        pass
+        "#;
+
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        assert_eq!(2, query_node.captures_list.len());
+        assert_eq!(1, query_node.captures_list.get("classname").unwrap().len());
+        assert_eq!(
+            1,
+            query_node.captures_list.get("superclasses").unwrap().len()
+        );
+        assert_eq!(2, query_node.captures.len());
+        assert!(query_node.captures.contains_key("superclasses"));
+        let superclasses = query_node.captures.get("superclasses").unwrap();
+        assert_eq!(2, superclasses.start.line);
+        assert_eq!(16, superclasses.start.col);
+        assert_eq!(2, superclasses.end.line);
+        assert_eq!(22, superclasses.end.col);
+        assert_eq!("identifier", superclasses.ast_type);
+        assert_eq!(None, superclasses.field_name);
+        assert!(query_node.captures.contains_key("classname"));
+    }
+
+    // run a Go query end to end and check the positions of the captures
+    #[test]
+    fn test_get_query_nodes_go() {
+        let q = r#"
+(function_declaration
+  name: (identifier) @name
+  parameters: (parameter_list) @params
+)
+        "#;
+
+        let c = r#"
+package main
+import "fmt"
+func main() {
+    fmt.Println("hello world")
+}
+"#;
+
+        let tree = get_tree(c, &Language::Go).unwrap();
+        let query = get_query(q, &Language::Go).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "main.go", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        assert_eq!(2, query_node.captures.len());
+        let name = query_node.captures.get("name").unwrap();
+        assert_eq!("identifier", name.ast_type);
+        assert_eq!(4, name.start.line);
+        assert_eq!(6, name.start.col);
+        assert_eq!(4, name.end.line);
+        assert_eq!(10, name.end.col);
+        let params = query_node.captures.get("params").unwrap();
+        assert_eq!("parameter_list", params.ast_type);
+        assert_eq!(4, params.start.line);
+        assert_eq!(10, params.start.col);
+    }
+
+    // capture import specs and struct fields in Go
+    #[test]
+    fn test_get_query_nodes_go_imports_and_struct_fields() {
+        let q = r#"
+[
+  (import_spec path: (interpreted_string_literal) @import)
+  (field_declaration name: (field_identifier) @field)
+]
+        "#;
+
+        let c = r#"
+package main
+
+import (
+    "fmt"
+    "os"
+)
+
+type Foo struct {
+    Bar string
+    Baz int
+}
+"#;
+
+        let tree = get_tree(c, &Language::Go).unwrap();
+        let query = get_query(q, &Language::Go).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "main.go", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 4);
+        let imports: Vec<&TreeSitterNode> = query_nodes
+            .iter()
+            .filter_map(|n| n.captures.get("import"))
+            .collect();
+        assert_eq!(2, imports.len());
+        assert_eq!(5, imports.get(0).unwrap().start.line);
+        assert_eq!(5, imports.get(0).unwrap().start.col);
+        let fields: Vec<&TreeSitterNode> = query_nodes
+            .iter()
+            .filter_map(|n| n.captures.get("field"))
+            .collect();
+        assert_eq!(2, fields.len());
+        assert_eq!(10, fields.get(0).unwrap().start.line);
+        assert_eq!(11, fields.get(1).unwrap().start.line);
+    }
+
+    // capture annotated methods in Java and check the field names of the signature
+    #[test]
+    fn test_get_query_nodes_java() {
+        let q = r#"
+(method_declaration
+  (modifiers (marker_annotation name: (identifier) @annotation))
+  name: (identifier) @name
+) @method
+        "#;
+
+        let c = r#"
+class Foo {
+    @Override
+    public String toString() {
+        return "foo";
+    }
+
+    public void bar(int baz) {
+    }
+}
+"#;
+
+        let tree = get_tree(c, &Language::Java).unwrap();
+        let query = get_query(q, &Language::Java).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "Foo.java", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        assert_eq!(3, query_node.captures.len());
+        let annotation = query_node.captures.get("annotation").unwrap();
+        assert_eq!(3, annotation.start.line);
+        assert_eq!(6, annotation.start.col);
+        let name = query_node.captures.get("name").unwrap();
+        assert_eq!(4, name.start.line);
+        assert_eq!(19, name.start.col);
+        assert_eq!(4, name.end.line);
+        assert_eq!(27, name.end.col);
+
+        // the children of the method expose the field names of the signature
+        let method = query_node.captures.get("method").unwrap();
+        assert_eq!("method_declaration", method.ast_type);
+        let name_child = method
+            .children
+            .iter()
+            .find(|c| c.field_name == Some("name".to_string()))
+            .unwrap();
+        assert_eq!("identifier", name_child.ast_type);
+        let parameters_child = method
+            .children
+            .iter()
+            .find(|c| c.field_name == Some("parameters".to_string()))
+            .unwrap();
+        assert_eq!("formal_parameters", parameters_child.ast_type);
+    }
+
+    // capture class names, using directives and string literals in C#
+    #[test]
+    fn test_get_query_nodes_csharp() {
+        let q = r#"
+[
+  (class_declaration name: (identifier) @classname)
+  (using_directive) @using
+  (string_literal) @string
+]
+        "#;
+
+        let c = r#"
+using System;
+
+namespace HelloWorld
+{
+    class Hello {
+        static void Main(string[] args)
+        {
+            Console.WriteLine("Hello World!");
+        }
+    }
+}
+"#;
+
+        let tree = get_tree(c, &Language::Csharp).unwrap();
+        let query = get_query(q, &Language::Csharp).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "Hello.cs", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 3);
+
+        let using = query_nodes
+            .iter()
+            .find_map(|n| n.captures.get("using"))
+            .unwrap();
+        assert_eq!(2, using.start.line);
+        assert_eq!(1, using.start.col);
+        assert_eq!(2, using.end.line);
+        assert_eq!(14, using.end.col);
+
+        let classname = query_nodes
+            .iter()
+            .find_map(|n| n.captures.get("classname"))
+            .unwrap();
+        assert_eq!("identifier", classname.ast_type);
+        assert_eq!(6, classname.start.line);
+        assert_eq!(11, classname.start.col);
+        assert_eq!(6, classname.end.line);
+        assert_eq!(16, classname.end.col);
+
+        let string = query_nodes
+            .iter()
+            .find_map(|n| n.captures.get("string"))
+            .unwrap();
+        assert_eq!(9, string.start.line);
+        assert_eq!(31, string.start.col);
+        assert_eq!(9, string.end.line);
+        assert_eq!(45, string.end.col);
+    }
+
+    // capture the method of call nodes in Ruby
+    #[test]
+    fn test_get_query_nodes_ruby() {
+        let q = r#"
+(call
+  receiver: (identifier) @receiver
+  method: (identifier) @method
+)
+        "#;
+
+        let c = r#"
+user = User.new
+user.update_attribute(:name, "foo")
+"#;
+
+        let tree = get_tree(c, &Language::Ruby).unwrap();
+        let query = get_query(q, &Language::Ruby).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "user.rb", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        let method = query_node.captures.get("method").unwrap();
+        assert_eq!("identifier", method.ast_type);
+        assert_eq!(3, method.start.line);
+        assert_eq!(6, method.start.col);
+        assert_eq!(3, method.end.line);
+        assert_eq!(22, method.end.col);
+    }
+
+    // capture function calls in a PHP file that mixes HTML and PHP
+    #[test]
+    fn test_get_query_nodes_php() {
+        let q = r#"
+(function_call_expression
+  function: (name) @name
+) @call
+        "#;
+
+        let c = r#"
+<html>
+<body>
+<?php
+echo strtolower("FOO");
+?>
+</body>
+</html>
+"#;
+
+        let tree = get_tree(c, &Language::Php).unwrap();
+        let query = get_query(q, &Language::Php).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "index.php", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        let call = query_node.captures.get("call").unwrap();
+        assert_eq!("function_call_expression", call.ast_type);
+        assert_eq!(5, call.start.line);
+        assert_eq!(6, call.start.col);
+        assert_eq!(5, call.end.line);
+        assert_eq!(23, call.end.col);
+        let name = query_node.captures.get("name").unwrap();
+        assert_eq!(5, name.start.line);
+        assert_eq!(16, name.end.col);
+    }
+
+    // capture annotated functions inside a Kotlin class
+    #[test]
+    fn test_get_query_nodes_kotlin() {
+        let q = r#"
+(class_declaration
+  (type_identifier) @classname
+  (class_body
+    (function_declaration
+      (modifiers (annotation) @annotation)
+      (simple_identifier) @functionname
+    )
+  )
+)
+        "#;
+
+        let c = r#"
+class Greeter {
+    @Deprecated("use hello")
+    fun greet(name: String): String {
+        return "Hello, $name"
+    }
+}
+"#;
+
+        let tree = get_tree(c, &Language::Kotlin).unwrap();
+        let query = get_query(q, &Language::Kotlin).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "Greeter.kt", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        assert_eq!(3, query_node.captures.len());
+        let classname = query_node.captures.get("classname").unwrap();
+        assert_eq!(2, classname.start.line);
+        assert_eq!(7, classname.start.col);
+        assert_eq!(2, classname.end.line);
+        assert_eq!(14, classname.end.col);
+        let annotation = query_node.captures.get("annotation").unwrap();
+        assert_eq!("annotation", annotation.ast_type);
+        assert_eq!(3, annotation.start.line);
+        assert_eq!(5, annotation.start.col);
+        let function_name = query_node.captures.get("functionname").unwrap();
+        assert_eq!("simple_identifier", function_name.ast_type);
+        assert_eq!(4, function_name.start.line);
+        assert_eq!(9, function_name.start.col);
+        assert_eq!(4, function_name.end.line);
+        assert_eq!(14, function_name.end.col);
+    }
+
+    // capture function names in Swift
+    #[test]
+    fn test_get_query_nodes_swift() {
+        let q = r#"
+(function_declaration
+  name: (simple_identifier) @name
+)
+        "#;
+
+        let c = r#"
+func greet(person: String) -> String {
+    return "Hello, " + person
+}
+
+func main() {
+    print(greet(person: "Anna"))
+}
+"#;
+
+        let tree = get_tree(c, &Language::Swift).unwrap();
+        let query = get_query(q, &Language::Swift).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "main.swift", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 2);
+        let first = query_nodes.get(0).unwrap().captures.get("name").unwrap();
+        assert_eq!("simple_identifier", first.ast_type);
+        assert_eq!(2, first.start.line);
+        assert_eq!(6, first.start.col);
+        assert_eq!(2, first.end.line);
+        assert_eq!(11, first.end.col);
+        let second = query_nodes.get(1).unwrap().captures.get("name").unwrap();
+        assert_eq!(6, second.start.line);
+        assert_eq!(6, second.start.col);
+        assert_eq!(10, second.end.col);
+    }
+
+    // capture call expressions in C and check the `function` field survives the mapping
+    #[test]
+    fn test_get_query_nodes_c() {
+        let q = r#"
+(call_expression
+  function: (identifier) @name
+) @call
+        "#;
+
+        let c = r#"
+#include <string.h>
+
+void copy(char *dst, const char *src) {
+    strcpy(dst, src);
+}
+"#;
+
+        let tree = get_tree(c, &Language::C).unwrap();
+        let query = get_query(q, &Language::C).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "copy.c", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        let name = query_node.captures.get("name").unwrap();
+        assert_eq!(5, name.start.line);
+        assert_eq!(5, name.start.col);
+        assert_eq!(5, name.end.line);
+        assert_eq!(11, name.end.col);
+        let call = query_node.captures.get("call").unwrap();
+        assert_eq!("call_expression", call.ast_type);
+        let function = call.children.get(0).unwrap();
+        assert_eq!(Some("function".to_string()), function.field_name);
+        assert_eq!("identifier", function.ast_type);
+        let arguments = call.children.get(1).unwrap();
+        assert_eq!(Some("arguments".to_string()), arguments.field_name);
+    }
+
+    // capture templated classes inside nested namespaces in C++
+    #[test]
+    fn test_get_query_nodes_cpp_template() {
+        let q = r#"
+(namespace_definition
+  name: (_) @namespace
+  body: (declaration_list
+    (template_declaration
+      (class_specifier name: (type_identifier) @classname)
+    )
+  )
+)
+        "#;
+
+        let c = r#"
+namespace outer {
+namespace inner {
+template <typename T>
+class Box {
+    T value;
+};
+}
+}
+"#;
+
+        let tree = get_tree(c, &Language::Cpp).unwrap();
+        let query = get_query(q, &Language::Cpp).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "box.hpp", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        let namespace = query_node.captures.get("namespace").unwrap();
+        assert_eq!(3, namespace.start.line);
+        assert_eq!(11, namespace.start.col);
+        assert_eq!(3, namespace.end.line);
+        assert_eq!(16, namespace.end.col);
+        let classname = query_node.captures.get("classname").unwrap();
+        assert_eq!("type_identifier", classname.ast_type);
+        assert_eq!(5, classname.start.line);
+        assert_eq!(7, classname.start.col);
+        assert_eq!(5, classname.end.line);
+        assert_eq!(10, classname.end.col);
+    }
+
+    // capture all the implicit parameters of a Scala function in `captures_list`
+    #[test]
+    fn test_get_query_nodes_scala_implicit_parameters() {
+        let q = r#"
+(function_definition
+  name: (identifier) @name
+  parameters: (parameters
+    "implicit"
+    (parameter name: (identifier) @implicit)+
+  )
+)
+        "#;
+
+        let c = r#"
+object Job {
+  def run(input: String)(implicit spark: SparkSession, conf: Config): Unit = {
+    println(input)
+  }
+}
+"#;
+
+        let tree = get_tree(c, &Language::Scala).unwrap();
+        let query = get_query(q, &Language::Scala).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "Job.scala", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        assert_eq!(2, query_node.captures_list.len());
+        let implicits = query_node.captures_list.get("implicit").unwrap();
+        assert_eq!(2, implicits.len());
+        assert_eq!(3, implicits.get(0).unwrap().start.line);
+        assert_eq!(35, implicits.get(0).unwrap().start.col);
+        assert_eq!(3, implicits.get(1).unwrap().start.line);
+        assert_eq!(56, implicits.get(1).unwrap().start.col);
+        let name = query_node.captures.get("name").unwrap();
+        assert_eq!(3, name.start.line);
+        assert_eq!(7, name.start.col);
+    }
+
+    // capture unquoted variable expansions passed to a command
+    #[test]
+    fn test_get_query_nodes_shell() {
+        let q = r#"
+(command
+  name: (command_name) @name
+  argument: (simple_expansion) @expansion
+)
+        "#;
+
+        let c = r#"
+DIR="/tmp/build"
+rm -rf $DIR
+echo "done"
+"#;
+
+        let tree = get_tree(c, &Language::Shell).unwrap();
+        let query = get_query(q, &Language::Shell).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "clean.sh", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        let name = query_node.captures.get("name").unwrap();
+        assert_eq!("command_name", name.ast_type);
+        assert_eq!(3, name.start.line);
+        assert_eq!(1, name.start.col);
+        let expansion = query_node.captures.get("expansion").unwrap();
+        assert_eq!("simple_expansion", expansion.ast_type);
+        assert_eq!(3, expansion.start.line);
+        assert_eq!(8, expansion.start.col);
+        assert_eq!(3, expansion.end.line);
+        assert_eq!(12, expansion.end.col);
+    }
+
+    // capture the image names of the FROM instructions
+    #[test]
+    fn test_get_query_nodes_dockerfile() {
+        let q = r#"
+(from_instruction
+  (image_spec name: (image_name) @image)
+)
+        "#;
+
+        let c = r#"
+FROM golang:1.21 AS build
+RUN go build -o /app
+FROM alpine
+COPY --from=build /app /app
+"#;
+
+        let tree = get_tree(c, &Language::Dockerfile).unwrap();
+        let query = get_query(q, &Language::Dockerfile).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "Dockerfile", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 2);
+        let golang = query_nodes.get(0).unwrap().captures.get("image").unwrap();
+        assert_eq!("image_name", golang.ast_type);
+        assert_eq!(2, golang.start.line);
+        assert_eq!(6, golang.start.col);
+        assert_eq!(2, golang.end.line);
+        assert_eq!(12, golang.end.col);
+        let alpine = query_nodes.get(1).unwrap().captures.get("image").unwrap();
+        assert_eq!(4, alpine.start.line);
+        assert_eq!(6, alpine.start.col);
+        assert_eq!(4, alpine.end.line);
+        assert_eq!(12, alpine.end.col);
+    }
+
+    // capture both labels of a resource block in `captures_list`
+    #[test]
+    fn test_get_query_nodes_terraform_block_labels() {
+        let q = r#"
+(block
+  (identifier) @type
+  (string_lit) @label
+  (string_lit) @label
+  (#eq? @type "resource")
+)
+        "#;
+
+        let c = r#"
+resource "aws_s3_bucket" "logs" {
+  bucket = "my-logs"
+}
+"#;
+
+        let tree = get_tree(c, &Language::Terraform).unwrap();
+        let query = get_query(q, &Language::Terraform).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "main.tf", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        let labels = query_node.captures_list.get("label").unwrap();
+        assert_eq!(2, labels.len());
+        assert_eq!(2, labels.get(0).unwrap().start.line);
+        assert_eq!(10, labels.get(0).unwrap().start.col);
+        assert_eq!(25, labels.get(0).unwrap().end.col);
+        assert_eq!(2, labels.get(1).unwrap().start.line);
+        assert_eq!(26, labels.get(1).unwrap().start.col);
+        assert_eq!(32, labels.get(1).unwrap().end.col);
+    }
+
+    // capture the value of a specific key path in a Kubernetes manifest
+    #[test]
+    fn test_get_query_nodes_yaml_key_path() {
+        let q = r#"
+(block_mapping_pair
+  key: (flow_node) @parent
+  value: (block_node
+    (block_mapping
+      (block_mapping_pair
+        key: (flow_node) @key
+        value: (flow_node) @value
+      )
+    )
+  )
+  (#eq? @parent "securityContext")
+  (#eq? @key "privileged")
+)
+        "#;
+
+        let c = r#"
+apiVersion: v1
+kind: Pod
+spec:
+  containers:
+    - name: app
+      securityContext:
+        privileged: true
+"#;
+
+        let tree = get_tree(c, &Language::Yaml).unwrap();
+        let query = get_query(q, &Language::Yaml).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "pod.yaml", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let value = query_nodes.get(0).unwrap().captures.get("value").unwrap();
+        assert_eq!(8, value.start.line);
+        assert_eq!(21, value.start.col);
+        assert_eq!(8, value.end.line);
+        assert_eq!(25, value.end.col);
+    }
+
+    // capture a pair nested four levels deep and check its position
+    #[test]
+    fn test_get_query_nodes_json_nested() {
+        let q = r#"
+(pair
+  key: (string) @key
+  value: (array) @value
+  (#eq? @key "\"plugins\"")
+)
+        "#;
+
+        let c = r#"
+{
+  "compilerOptions": {
+    "paths": {
+      "lint": {
+        "plugins": ["a", "b"]
+      }
+    }
+  }
+}"#;
+
+        let tree = get_tree(c, &Language::Json).unwrap();
+        let query = get_query(q, &Language::Json).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "tsconfig.json", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        let key = query_node.captures.get("key").unwrap();
+        assert_eq!("string", key.ast_type);
+        assert_eq!(6, key.start.line);
+        assert_eq!(9, key.start.col);
+        assert_eq!(6, key.end.line);
+        assert_eq!(18, key.end.col);
+        let value = query_node.captures.get("value").unwrap();
+        assert_eq!("array", value.ast_type);
+        assert_eq!(2, value.children.len());
+        assert_eq!(20, value.start.col);
+        assert_eq!(30, value.end.col);
+    }
+
+    // capture the pairs of the [dependencies] table
+    #[test]
+    fn test_get_query_nodes_toml_dependencies() {
+        let q = r#"
+(table
+  (bare_key) @table
+  (pair (bare_key) @name (_) @value)
+  (#eq? @table "dependencies")
+)
+        "#;
+
+        let c = r#"
+[package]
+name = "kernel"
+edition = "2021"
+
+[dependencies]
+serde = "1"
+tree-sitter = { version = "0.20" }
+"#;
+
+        let tree = get_tree(c, &Language::Toml).unwrap();
+        let query = get_query(q, &Language::Toml).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "Cargo.toml", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 2);
+        let serde = query_nodes.get(0).unwrap().captures.get("value").unwrap();
+        assert_eq!("string", serde.ast_type);
+        assert_eq!(7, serde.start.line);
+        assert_eq!(9, serde.start.col);
+        assert_eq!(7, serde.end.line);
+        assert_eq!(12, serde.end.col);
+        let tree_sitter = query_nodes.get(1).unwrap().captures.get("value").unwrap();
+        assert_eq!("inline_table", tree_sitter.ast_type);
+        assert_eq!(8, tree_sitter.start.line);
+        assert_eq!(15, tree_sitter.start.col);
+    }
+
+    // capture Postgres-style CREATE INDEX and ALTER TABLE statements
+    #[test]
+    fn test_get_query_nodes_sql() {
+        let q = r#"
+[
+  (create_index_statement) @statement
+  (alter_table_statement) @statement
+]
+        "#;
+
+        let c = r#"
+CREATE INDEX idx_users_email ON users (email);
+SELECT * FROM users;
+ALTER TABLE users DROP COLUMN age;
+"#;
+
+        let tree = get_tree(c, &Language::Sql).unwrap();
+        let query = get_query(q, &Language::Sql).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "migration.sql", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 2);
+        let create_index = query_nodes
+            .get(0)
+            .unwrap()
+            .captures
+            .get("statement")
+            .unwrap();
+        assert_eq!("create_index_statement", create_index.ast_type);
+        assert_eq!(2, create_index.start.line);
+        assert_eq!(1, create_index.start.col);
+        let alter_table = query_nodes
+            .get(1)
+            .unwrap()
+            .captures
+            .get("statement")
+            .unwrap();
+        assert_eq!("alter_table_statement", alter_table.ast_type);
+        assert_eq!(4, alter_table.start.line);
+        assert_eq!(1, alter_table.start.col);
+    }
+
+    // capture calls to a specific global function
+    #[test]
+    fn test_get_query_nodes_lua() {
+        let q = r#"
+(function_call
+  name: (identifier) @name
+  arguments: (arguments) @arguments
+  (#eq? @name "loadstring")
+)
+        "#;
+
+        let c = r#"
+local code = "return 1"
+local f = loadstring(code)
+print(f())
+"#;
+
+        let tree = get_tree(c, &Language::Lua).unwrap();
+        let query = get_query(q, &Language::Lua).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "main.lua", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        let name = query_node.captures.get("name").unwrap();
+        assert_eq!(3, name.start.line);
+        assert_eq!(11, name.start.col);
+        assert_eq!(3, name.end.line);
+        assert_eq!(21, name.end.col);
+        let arguments = query_node.captures.get("arguments").unwrap();
+        assert_eq!(21, arguments.start.col);
+        assert_eq!(27, arguments.end.col);
+    }
+
+    // capture widget classes extending StatelessWidget, like the Python superclasses test
+    #[test]
+    fn test_get_query_nodes_dart_superclass() {
+        let q = r#"
+(class_definition
+  name: (identifier) @classname
+  superclass: (superclass (type_identifier) @superclass)
+  (#eq? @superclass "StatelessWidget")
+)
+        "#;
+
+        let c = r#"
+class Greeting extends StatelessWidget {
+  Widget build(BuildContext context) => Text("hello");
+}
+
+class Counter extends StatefulWidget {
+}
+"#;
+
+        let tree = get_tree(c, &Language::Dart).unwrap();
+        let query = get_query(q, &Language::Dart).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "greeting.dart", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let query_node = query_nodes.get(0).unwrap();
+        assert_eq!(2, query_node.captures.len());
+        let classname = query_node.captures.get("classname").unwrap();
+        assert_eq!(2, classname.start.line);
+        assert_eq!(7, classname.start.col);
+        assert_eq!(2, classname.end.line);
+        assert_eq!(15, classname.end.col);
+        let superclass = query_node.captures.get("superclass").unwrap();
+        assert_eq!("type_identifier", superclass.ast_type);
+        assert_eq!(24, superclass.start.col);
+        assert_eq!(39, superclass.end.col);
+    }
+
+    // every clause of a pattern-matched function produces a distinct match
+    #[test]
+    fn test_get_query_nodes_elixir_function_clauses() {
+        let q = r#"
+(call
+  target: (identifier) @keyword
+  (arguments (call target: (identifier) @name))
+  (#eq? @keyword "def")
+)
+        "#;
+
+        let c = r#"
+defmodule Math do
+  def fact(0), do: 1
+  def fact(n), do: n * fact(n - 1)
+
+  def double(n) do
+    n * 2
+  end
+end
+"#;
+
+        let tree = get_tree(c, &Language::Elixir).unwrap();
+        let query = get_query(q, &Language::Elixir).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "math.ex", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 3);
+        let lines: Vec<u32> = query_nodes
+            .iter()
+            .map(|n| n.captures.get("name").unwrap().start.line)
+            .collect();
+        assert_eq!(vec![3, 4, 6], lines);
+        for query_node in &query_nodes {
+            assert_eq!(7, query_node.captures.get("name").unwrap().start.col);
+        }
+    }
+
+    // capture the argument of the sh steps of a Jenkinsfile, with or without parentheses
+    #[test]
+    fn test_get_query_nodes_groovy_sh_argument() {
+        let q = r#"
+(
+  [
+    (function_call
+      function: (identifier) @name
+      args: (argument_list (_) @argument))
+    (juxt_function_call
+      function: (identifier) @name
+      args: (argument_list (_) @argument))
+  ]
+  (#eq? @name "sh")
+)
+        "#;
+
+        let c = r#"
+node {
+    stage('Test') {
+        sh("make test")
+        sh "make ${TARGET}"
+        echo "done"
+    }
+}
+"#;
+
+        let tree = get_tree(c, &Language::Groovy).unwrap();
+        let query = get_query(q, &Language::Groovy).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "Jenkinsfile", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 2);
+        let first_argument = query_nodes
+            .get(0)
+            .unwrap()
+            .captures
+            .get("argument")
+            .unwrap();
+        assert_eq!(4, first_argument.start.line);
+        assert_eq!(12, first_argument.start.col);
+        assert_eq!(4, first_argument.end.line);
+        assert_eq!(23, first_argument.end.col);
+        let second_argument = query_nodes
+            .get(1)
+            .unwrap()
+            .captures
+            .get("argument")
+            .unwrap();
+        assert_eq!(5, second_argument.start.line);
+        assert_eq!(12, second_argument.start.col);
+        assert_eq!(5, second_argument.end.line);
+        assert_eq!(28, second_argument.end.col);
+    }
+
+    // the #eq? predicate filters the matches on the text of the captured node
+    #[test]
+    fn test_get_query_nodes_eq_predicate() {
+        let c = r#"
+eval(user_input)
+print(user_input)
+exec(user_input)
+"#;
+        let q_without_predicate = r#"
+(call
+    function: (identifier) @name
+)
+        "#;
+        let q_with_predicate = r#"
+(call
+    function: (identifier) @name
+    (#eq? @name "eval")
+)
+        "#;
+
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q_without_predicate, &Language::Python).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 3);
+
+        let query = get_query(q_with_predicate, &Language::Python).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 1);
+        let name = query_nodes.get(0).unwrap().captures.get("name").unwrap();
+        assert_eq!(2, name.start.line);
+        assert_eq!(1, name.start.col);
+    }
+
+    // the #match? predicate filters the matches with a regular expression
+    #[test]
+    fn test_get_query_nodes_match_predicate() {
+        let c = r#"
+def test_login():
+    pass
+
+def login():
+    pass
+
+def test_logout():
+    pass
+"#;
+        let q = r#"
+(function_definition
+    name: (identifier) @name
+    (#match? @name "^test_")
+)
+        "#;
+
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "test_auth.py", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 2);
+        let lines: Vec<u32> = query_nodes
+            .iter()
+            .map(|n| n.captures.get("name").unwrap().start.line)
+            .collect();
+        assert_eq!(vec![2, 8], lines);
+    }
+
+    // filter the calls using each operator on the function name
+    #[test]
+    fn test_get_query_nodes_negated_and_any_of_predicates() {
+        let c = r#"
+eval(user_input)
+print(user_input)
+exec(user_input)
+compile(user_input)
+"#;
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let get_names = |predicate: &str| -> Vec<String> {
+            let q = format!("(call function: (identifier) @name {})", predicate);
+            let query = get_query(&q, &Language::Python).expect("query defined");
+            get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new())
+                .iter()
+                .map(|n| {
+                    let name = n.captures.get("name").unwrap();
+                    let line = c.lines().nth(name.start.line as usize - 1).unwrap();
+                    line[..line.find('(').unwrap()].to_string()
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            vec!["eval", "exec", "compile"],
+            get_names(r#"(#any-of? @name "exec" "eval" "compile")"#)
+        );
+        assert_eq!(
+            vec!["print"],
+            get_names(r#"(#not-any-of? @name "exec" "eval" "compile")"#)
+        );
+        assert_eq!(
+            vec!["print", "exec", "compile"],
+            get_names(r#"(#not-eq? @name "eval")"#)
+        );
+        assert_eq!(
+            vec!["eval", "print"],
+            get_names(r#"(#not-match? @name "^(exec|compile)$")"#)
+        );
+    }
+
+    // a predicate we do not know is an error instead of being ignored
+    #[test]
+    fn test_get_query_unknown_predicate() {
+        let q = r#"
+(call
+    function: (identifier) @name
+    (#any-off? @name "eval" "exec")
+)
+        "#;
+        let query = get_query(q, &Language::Python);
+        assert!(query.is_err());
+        let error = query.unwrap_err();
+        assert_eq!(QueryErrorKind::Predicate, error.kind);
+        assert_eq!("unknown predicate #any-off?", error.message);
+        assert_eq!(4, error.line);
+        assert_eq!(6, error.col);
+
+        let q = r#"((identifier) @name
+    (#any-of? "eval" @name))"#;
+        let error = get_query(q, &Language::Python).unwrap_err();
+        assert_eq!(QueryErrorKind::Predicate, error.kind);
+        assert_eq!(
+            "predicate #any-of? expects a capture followed by strings",
+            error.message
+        );
+        assert_eq!(2, error.line);
+        assert_eq!(6, error.col);
+    }
+
+    // tree-sitter parses #is? and #is-not? but does not evaluate them when matching
+    #[test]
+    fn test_get_unknown_predicates() {
+        let q = r#"
+(call
+    function: (identifier) @name
+    (#any-of? @name "eval" "print")
+    (#is-not? local)
+    (#is? @name "builtin")
+)
+        "#;
+        let c = "eval(x)\nprint(x)\n";
+        let query = get_query(q, &Language::Python).unwrap();
+        assert_eq!(vec!["#is-not?", "#is?"], get_unknown_predicates(&query));
+        let tree = get_tree(c, &Language::Python).unwrap();
+        assert_eq!(
+            2,
+            get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new()).len()
+        );
+
+        let query = get_query(
+            r#"((identifier) @name (#any-of? @name "eval"))"#,
+            &Language::Python,
+        );
+        assert!(get_unknown_predicates(&query.unwrap()).is_empty());
+    }
+
+    // each pattern has its own settings
+    #[test]
+    fn test_get_query_nodes_set_directive() {
+        let q = r#"
+(call
+    function: (attribute
+        attribute: (identifier) @name
+        (#eq? @name "execute")
+    )
+    (#set! "kind" "sql-sink")
+)
+
+(call
+    function: (identifier) @name
+    (#eq? @name "eval")
+    (#set! "kind" "code-sink")
+    (#set! "dangerous")
+)
+        "#;
+
+        let c = r#"
+cursor.execute(query)
+eval(user_input)
+"#;
+
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert_eq!(query_nodes.len(), 2);
+        let execute = query_nodes.get(0).unwrap();
+        assert_eq!(1, execute.settings.len());
+        assert_eq!("sql-sink", execute.settings.get("kind").unwrap());
+        let eval = query_nodes.get(1).unwrap();
+        assert_eq!(2, eval.settings.len());
+        assert_eq!("code-sink", eval.settings.get("kind").unwrap());
+        assert_eq!("", eval.settings.get("dangerous").unwrap());
+
+        // no setting when the pattern does not use #set!
+        let query = get_query("(call) @call", &Language::Python).expect("query defined");
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert!(query_nodes.iter().all(|n| n.settings.is_empty()));
+    }
+
+    // syntax errors are visible in the nodes and in the context of the matches
+    #[test]
+    fn test_syntax_errors() {
+        fn has_error_node(node: &TreeSitterNode) -> bool {
+            node.is_error || node.is_missing || node.children.iter().any(has_error_node)
+        }
+
+        let q = "(identifier) @name";
+        let c = "def foo(arg1:\n    print(arg1\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        assert!(tree.root_node().has_error());
+        assert!(has_error_node(&map_node(tree.root_node()).unwrap()));
+        let query = get_query(q, &Language::Python).unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert!(!query_nodes.is_empty());
+        assert!(query_nodes.iter().all(|n| n.context.has_errors));
+
+        let c = "def foo(arg1):\n    print(arg1)\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        assert!(!has_error_node(&map_node(tree.root_node()).unwrap()));
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert!(query_nodes.iter().all(|n| !n.context.has_errors));
+    }
+
+    // incremental parsing gives the same tree as parsing the new code from scratch
+    #[test]
+    fn test_get_tree_incremental() {
+        let old_code = "def foo(arg1):\n    pass\n";
+        let new_code = "def foo(arg1, arg2):\n    print(arg2)\n";
+        let old_tree = get_tree(old_code, &Language::Python).unwrap();
+
+        let edit = get_input_edit(old_code, new_code).unwrap();
+        assert_eq!(12, edit.start_byte);
+        assert_eq!(23, edit.old_end_byte);
+        assert_eq!(36, edit.new_end_byte);
+        assert_eq!(
+            tree_sitter::Point { row: 0, column: 12 },
+            edit.start_position
+        );
+        assert_eq!(
+            tree_sitter::Point { row: 1, column: 8 },
+            edit.old_end_position
+        );
+        assert_eq!(
+            tree_sitter::Point { row: 1, column: 15 },
+            edit.new_end_position
+        );
+
+        let incremental_tree =
+            get_tree_incremental(&old_tree, &[edit], new_code, &Language::Python).unwrap();
+        let cold_tree = get_tree(new_code, &Language::Python).unwrap();
+        assert_eq!(
+            serde_json::to_string(&map_node(cold_tree.root_node())).unwrap(),
+            serde_json::to_string(&map_node(incremental_tree.root_node())).unwrap()
+        );
+        assert!(get_input_edit(new_code, new_code).is_none());
+    }
+
+    // the edit never splits a multi-byte character
+    #[test]
+    fn test_get_input_edit_multibyte() {
+        let edit = get_input_edit("x = 'é'", "x = 'è'").unwrap();
+        assert_eq!(5, edit.start_byte);
+        assert_eq!(7, edit.old_end_byte);
+        assert_eq!(7, edit.new_end_byte);
+
+        let edit = get_input_edit("abab", "ab").unwrap();
+        assert_eq!(2, edit.start_byte);
+        assert_eq!(4, edit.old_end_byte);
+        assert_eq!(2, edit.new_end_byte);
+    }
+
+    // the parser of each grammar is created once per thread and reused for all the files
+    #[test]
+    fn test_parser_reuse() {
+        fn get_parser_count() -> usize {
+            PARSERS.with(|parsers| parsers.borrow().len())
+        }
+
+        let parser_count = get_parser_count();
+        for i in 0..1000 {
+            let (language, code) = if i % 2 == 0 {
+                (Language::Python, format!("x = {i}\n"))
+            } else {
+                (Language::JavaScript, format!("const x = {i};\n"))
+            };
+            let tree = try_get_tree(&code, &language).unwrap();
+            assert!(!tree.root_node().has_error());
+        }
+        assert_eq!(parser_count + 2, get_parser_count());
+
+        // each thread has its own parsers
+        let other_parser_count = std::thread::spawn(|| {
+            assert!(try_get_tree("x = 1", &Language::Python).is_ok());
+            get_parser_count()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(1, other_parser_count);
+    }
+
+    // Compare the time to parse 1,000 small files of two languages with the parser of each
+    // language and with a single parser set up with the language of each file.
+    // Run with `cargo test --release -- --ignored --nocapture bench_parse_files`.
+    #[test]
+    #[ignore]
+    fn bench_parse_files() {
+        let python = get_tree_sitter_language(&Language::Python).unwrap();
+        let javascript = get_tree_sitter_language(&Language::JavaScript).unwrap();
+        let files: Vec<(tree_sitter::Language, String)> = (0..1_000)
+            .map(|i| {
+                if i % 2 == 0 {
+                    (python, format!("def f{i}(x):\n    return x + {i}\n"))
+                } else {
+                    (
+                        javascript,
+                        format!("function f{i}(x) {{ return x + {i}; }}\n"),
+                    )
+                }
+            })
+            .collect();
+
+        let start = Instant::now();
+        for (language, code) in &files {
+            assert!(get_tree_with_language(code, *language).is_ok());
+        }
+        let per_language = start.elapsed();
+
+        let start = Instant::now();
+        let mut parser = tree_sitter::Parser::new();
+        for (language, code) in &files {
+            parser.set_language(*language).unwrap();
+            assert!(parser.parse(code, None).is_some());
+        }
+        let single = start.elapsed();
+
+        println!("parser per language: {per_language:?}, single parser: {single:?}");
+    }
+
+    // byte offsets and columns are different when the code has multi-byte characters
+    #[test]
+    fn test_node_byte_offsets() {
+        let q = r#"(call function: (identifier) @name arguments: (argument_list (string) @arg))"#;
+        let c = "# héllo wörld\nprint(\"日本語\", name)\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert_eq!(1, query_nodes.len());
+
+        let name = query_nodes[0].captures.get("name").unwrap();
+        assert_eq!(16, name.start_byte);
+        assert_eq!(21, name.end_byte);
+        assert_eq!("print", &c[name.start_byte..name.end_byte]);
+
+        let arg = query_nodes[0].captures.get("arg").unwrap();
+        assert_eq!(22, arg.start_byte);
+        assert_eq!(33, arg.end_byte);
+        assert_eq!("\"日本語\"", &c[arg.start_byte..arg.end_byte]);
+        // columns count the characters
+        assert_eq!(7, arg.start.col);
+        assert_eq!(12, arg.end.col);
+    }
+
+    // the queries run in a batch return the same nodes as the queries run one by one
+    #[test]
+    fn test_get_query_nodes_batch() {
+        let c = "def foo(a, b):\n    return bar(a, \"é\") + b\n\nclass A(B):\n    x = foo(1, 2)\n";
+        let query_codes = [
+            "(function_definition name: (identifier) @name) @function",
+            "(call function: (identifier) @function arguments: (argument_list) @arguments)",
+            "((identifier) @id (#eq? @id \"foo\"))",
+            "(class_definition superclasses: (argument_list (identifier)+ @superclasses))",
+            "(lambda) @lambda",
+        ];
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let queries: Vec<Arc<tree_sitter::Query>> = query_codes
+            .iter()
+            .map(|q| get_query(q, &Language::Python).unwrap())
+            .collect();
+        let query_refs: Vec<&tree_sitter::Query> = queries.iter().map(|q| q.as_ref()).collect();
+
+        let batch = get_query_nodes_batch(&tree, &query_refs, "myfile.py", c, &HashMap::new());
+        assert_eq!(queries.len(), batch.len());
+        for (query, batch_nodes) in queries.iter().zip(batch) {
+            let nodes = get_query_nodes(&tree, query, "myfile.py", c, &HashMap::new());
+            assert_eq!(nodes.len(), batch_nodes.len());
+            for (node, batch_node) in nodes.iter().zip(batch_nodes.iter()) {
+                let mut capture_names: Vec<&String> = node.captures.keys().collect();
+                capture_names.sort();
+                let mut batch_capture_names: Vec<&String> = batch_node.captures.keys().collect();
+                batch_capture_names.sort();
+                assert_eq!(capture_names, batch_capture_names);
+                for name in capture_names {
+                    assert_eq!(
+                        serde_json::to_string(&node.captures[name]).unwrap(),
+                        serde_json::to_string(&batch_node.captures[name]).unwrap()
+                    );
+                    assert_eq!(
+                        serde_json::to_string(&node.captures_list[name]).unwrap(),
+                        serde_json::to_string(&batch_node.captures_list[name]).unwrap()
+                    );
+                }
+            }
+        }
+    }
+
+    // Compare the time to run 100 queries one by one and in a batch.
+    // Run with `cargo test --release -- --ignored --nocapture bench_get_query_nodes_batch`.
+    #[test]
+    #[ignore]
+    fn bench_get_query_nodes_batch() {
+        let c = "def foo(a, b):\n    return bar(a, b) + baz(b)\n".repeat(2_000);
+        let tree = get_tree(&c, &Language::Python).unwrap();
+        let queries: Vec<Arc<tree_sitter::Query>> = (0..100)
+            .map(|i| {
+                let q = format!("((identifier) @id (#eq? @id \"foo{i}\"))");
+                get_query(&q, &Language::Python).unwrap()
+            })
+            .collect();
+        let query_refs: Vec<&tree_sitter::Query> = queries.iter().map(|q| q.as_ref()).collect();
+
+        let start = Instant::now();
+        for query in &queries {
+            get_query_nodes(&tree, query, "myfile.py", &c, &HashMap::new());
+        }
+        let one_by_one = start.elapsed();
+
+        let start = Instant::now();
+        get_query_nodes_batch(&tree, &query_refs, "myfile.py", &c, &HashMap::new());
+        let batch = start.elapsed();
+
+        println!("one by one: {one_by_one:?}, batch: {batch:?}");
+    }
+
+    // the batch returns the same nodes as the functions of each mode with the same options
+    #[test]
+    fn test_query_nodes_batch_options() {
+        let c = "def foo(a, b):\n    return bar(a, b) + baz(b)\n\nfoo(1, 2)\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query =
+            get_query("(call function: (identifier) @function)", &Language::Python).unwrap();
+        let options = QueryNodesOptions {
+            match_limit: Some(2),
+            ..QueryNodesOptions::default()
+        };
+        let variables = HashMap::new();
+        let mut batch = QueryNodesBatch::new(&tree, "myfile.py", c, &variables);
+        let to_json = |nodes: &QueryNodes| {
+            nodes
+                .match_nodes
+                .iter()
+                .map(|n| serde_json::to_string(&n.captures_list).unwrap())
+                .collect::<Vec<String>>()
+        };
+
+        let nodes =
+            get_query_nodes_with_options(&tree, &query, "myfile.py", c, &variables, &options);
+        assert_eq!(
+            to_json(&nodes),
+            to_json(&batch.get_query_nodes(&query, &options))
+        );
+        let nodes = get_query_capture_nodes_with_options(
+            &tree,
+            &query,
+            "myfile.py",
+            c,
+            &variables,
+            &options,
+        );
+        assert_eq!(3, nodes.match_nodes.len());
+        assert_eq!(
+            to_json(&nodes),
+            to_json(&batch.get_query_capture_nodes(&query, &options))
+        );
+        // the options of a query are not kept for the next one
+        let nodes = batch.get_query_nodes(&query, &QueryNodesOptions::default());
+        assert_eq!(3, nodes.match_nodes.len());
+    }
+
+    // the quantifiers of the captures tell if a capture can have more than one node
+    #[test]
+    fn test_captures_meta() {
+        let q = r#"
+(class_definition
+  name: (identifier) @classname
+  superclasses: (argument_list (identifier)+ @superclasses)
+)
+"#;
+        let c = "class A(B, C):\n    x = 1\n    y = 2\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert_eq!(1, query_nodes.len());
+        let captures_meta = &query_nodes[0].captures_meta;
+        assert_eq!(2, captures_meta.len());
+        assert_eq!(CaptureQuantifier::One, captures_meta["classname"]);
+        assert_eq!(CaptureQuantifier::OneOrMore, captures_meta["superclasses"]);
+        assert_eq!(2, query_nodes[0].captures_list["superclasses"].len());
+
+        let value = serde_json::to_value(&query_nodes[0]).unwrap();
+        assert_eq!("ONE_OR_MORE", value["capturesMeta"]["superclasses"]);
+    }
+
+    // the captures starting with an underscore are used to match but are not in the matches
+    #[test]
+    fn test_private_captures() {
+        let q = r#"
+(call
+    function: (identifier) @_function
+    arguments: (argument_list (identifier) @argument)
+    (#eq? @_function "eval"))
+"#;
+        let c = "eval(a)
+print(b)
+eval(c)
+";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert_eq!(2, query_nodes.len());
+        for (query_node, argument) in query_nodes.iter().zip(["a", "c"]) {
+            assert_eq!(
+                vec!["argument"],
+                query_node.captures.keys().collect::<Vec<_>>()
+            );
+            assert_eq!(
+                vec!["argument"],
+                query_node.captures_list.keys().collect::<Vec<_>>()
+            );
+            let node = &query_node.captures["argument"];
+            assert_eq!(argument, &c[node.start_byte..node.end_byte]);
+        }
+
+        // a match with only private captures is not returned
+        let q = "((identifier) @_id (#any-of? @_id \"eval\" \"print\"))";
+        let query = get_query(q, &Language::Python).unwrap();
+        assert!(get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new()).is_empty());
+    }
+
+    // the children of a node are found with their field
+    #[test]
+    fn test_child_by_field() {
+        let q = "(function_definition) @function";
+        let c = "def foo(a, b):\n    return a + b\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        let function = &query_nodes[0].captures["function"];
+
+        let name = function.child_by_field("name").unwrap();
+        assert_eq!("identifier", name.ast_type);
+        assert_eq!("foo", &c[name.start_byte..name.end_byte]);
+        let parameters = function.child_by_field("parameters").unwrap();
+        assert_eq!("parameters", parameters.ast_type);
+        assert_eq!(2, parameters.children.len());
+        let body = function.child_by_field("body").unwrap();
+        assert_eq!("block", body.ast_type);
+        assert!(function.child_by_field("return_type").is_none());
+
+        assert_eq!(1, function.children_by_field("name").len());
+        assert!(function.children_by_field("alias").is_empty());
+    }
+
+    // anonymous nodes are only mapped when the rule asks for them
+    #[test]
+    fn test_map_node_include_unnamed() {
+        let q = "(comparison_operator) @comparison";
+        let c = "if a is None:\n    pass\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let get_child_types = |options: &MapNodeOptions| -> Vec<String> {
+            let options = QueryNodesOptions {
+                map_node_options: *options,
+                ..QueryNodesOptions::default()
+            };
+            let query_nodes = get_query_nodes_with_options(
+                &tree,
+                &query,
+                "myfile.py",
+                c,
+                &HashMap::new(),
+                &options,
+            );
+            query_nodes.match_nodes[0].captures["comparison"]
+                .children
+                .iter()
+                .map(|child| child.ast_type.clone())
+                .collect()
+        };
+
+        assert_eq!(
+            vec!["identifier", "none"],
+            get_child_types(&MapNodeOptions::default())
+        );
+        let options = MapNodeOptions {
+            include_unnamed: true,
+            max_depth: None,
+            max_nodes: None,
+            position_encoding: PositionEncoding::default(),
+        };
+        assert_eq!(vec!["identifier", "is", "none"], get_child_types(&options));
+
+        // the children below the maximum depth are not mapped
+        let options = MapNodeOptions {
+            include_unnamed: false,
+            max_depth: Some(0),
+            max_nodes: None,
+            position_encoding: PositionEncoding::default(),
+        };
+        assert!(get_child_types(&options).is_empty());
+    }
+
+    // deeply nested code does not overflow the stack and the deepest nodes are truncated
+    #[test]
+    fn test_map_node_deeply_nested() {
+        fn get_depth(node: &TreeSitterNode) -> (usize, bool) {
+            let mut depth = 0;
+            let mut current = node;
+            while let Some(child) = current.children.last() {
+                depth += 1;
+                current = child;
+            }
+            (depth, current.is_truncated)
+        }
+
+        let nesting = 50_000;
+        let c = format!("x = {}1{}\n", "(".repeat(nesting), ")".repeat(nesting));
+        let tree = get_tree(&c, &Language::Python).unwrap();
+
+        let node = map_node(tree.root_node()).unwrap();
+        assert_eq!((DEFAULT_MAP_NODE_MAX_DEPTH, true), get_depth(&node));
+
+        let options = MapNodeOptions {
+            include_unnamed: false,
+            max_depth: Some(10),
+            max_nodes: None,
+            position_encoding: PositionEncoding::default(),
+        };
+        let node = map_node_in_code(tree.root_node(), &c, &options).unwrap();
+        assert_eq!((10, true), get_depth(&node));
+
+        // the depth is limited even without limit in the options, the tree can be dropped
+        let options = MapNodeOptions {
+            include_unnamed: false,
+            max_depth: None,
+            max_nodes: None,
+            position_encoding: PositionEncoding::default(),
+        };
+        let node = map_node_in_code(tree.root_node(), &c, &options).unwrap();
+        assert_eq!((MAX_MAP_NODE_DEPTH, true), get_depth(&node));
+        let options = MapNodeOptions {
+            include_unnamed: false,
+            max_depth: Some(nesting * 2),
+            max_nodes: None,
+            position_encoding: PositionEncoding::default(),
+        };
+        let node = map_node_in_code(tree.root_node(), &c, &options).unwrap();
+        assert_eq!((MAX_MAP_NODE_DEPTH, true), get_depth(&node));
+
+        // the trees that are not as deep are mapped entirely
+        let c = format!("x = {}1{}\n", "(".repeat(100), ")".repeat(100));
+        let tree = get_tree(&c, &Language::Python).unwrap();
+        let options = MapNodeOptions {
+            include_unnamed: false,
+            max_depth: None,
+            max_nodes: None,
+            position_encoding: PositionEncoding::default(),
+        };
+        let node = map_node_in_code(tree.root_node(), &c, &options).unwrap();
+        let (depth, is_truncated) = get_depth(&node);
+        assert!(depth > 100);
+        assert!(!is_truncated);
+    }
+
+    // the number of nodes mapped for a capture can be limited
+    #[test]
+    fn test_map_node_max_nodes() {
+        fn count_nodes(node: &TreeSitterNode) -> usize {
+            1 + node.children.iter().map(count_nodes).sum::<usize>()
+        }
+
+        let q = "(class_definition body: (block) @body)";
+        let c = r#"
+class Foo:
+    def foo(self, arg1):
+        return arg1 + 1
+
+    def bar(self, arg2):
+        return arg2 * 2
+"#;
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let get_body = |max_nodes: Option<usize>| {
+            let options = QueryNodesOptions {
+                map_node_options: MapNodeOptions {
+                    max_nodes,
+                    ..MapNodeOptions::default()
+                },
+                ..QueryNodesOptions::default()
+            };
+            let query_nodes = get_query_nodes_with_options(
+                &tree,
+                &query,
+                "myfile.py",
+                c,
+                &HashMap::new(),
+                &options,
+            );
+            query_nodes.match_nodes[0].captures["body"].clone()
+        };
+
+        let body = get_body(None);
+        assert_eq!(21, count_nodes(&body));
+        assert!(!body.is_truncated);
+
+        // only the captured node
+        let body = get_body(Some(1));
+        assert_eq!(1, count_nodes(&body));
+        assert!(body.is_truncated);
+
+        // the first method is mapped up to the left operand of the addition
+        let body = get_body(Some(10));
+        assert_eq!(10, count_nodes(&body));
+        assert!(body.is_truncated);
+        assert_eq!(1, body.children.len());
+        let addition = &body.children[0].children[2].children[0].children[0];
+        assert_eq!("binary_operator", addition.ast_type);
+        assert_eq!(1, addition.children.len());
+        assert!(addition.is_truncated);
+    }
+
+    // the matches are not collected after the maximum number of matches
+    #[test]
+    fn test_get_query_nodes_max_matches() {
+        let q = "(identifier) @id";
+        let c = "a = b + c\nd = e + f\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let get_nodes = |max_matches: Option<usize>| {
+            let options = QueryNodesOptions {
+                max_matches,
+                ..QueryNodesOptions::default()
+            };
+            get_query_nodes_with_options(&tree, &query, "myfile.py", c, &HashMap::new(), &options)
+        };
+
+        let query_nodes = get_nodes(None);
+        assert_eq!(6, query_nodes.match_nodes.len());
+        assert!(!query_nodes.is_truncated);
+
+        let query_nodes = get_nodes(Some(6));
+        assert_eq!(6, query_nodes.match_nodes.len());
+        assert!(!query_nodes.is_truncated);
+
+        let query_nodes = get_nodes(Some(4));
+        assert_eq!(4, query_nodes.match_nodes.len());
+        assert!(query_nodes.is_truncated);
+        assert_eq!(
+            "c",
+            &c[query_nodes.match_nodes[3].captures["id"].start_byte
+                ..query_nodes.match_nodes[3].captures["id"].end_byte]
+        );
+    }
+
+    // the columns of the nodes after non-ASCII characters count the characters, not the bytes
+    #[test]
+    fn test_map_node_multibyte_columns() {
+        let q = "(call) @call";
+        let c = "s = \"héllo\"; t = \"日本\"; u = \"😀\"; eval(x)\neval(y)\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert_eq!(2, query_nodes.len());
+
+        let call = &query_nodes[0].captures["call"];
+        assert_eq!(40, call.start_byte);
+        assert_eq!(Position::new(1, 33), call.start);
+        assert_eq!(Position::new(1, 40), call.end);
+        // the characters of the previous line do not change the columns
+        let call = &query_nodes[1].captures["call"];
+        assert_eq!(Position::new(2, 1), call.start);
+        assert_eq!(Position::new(2, 8), call.end);
+    }
+
+    // the columns of each byte offset of a line
+    #[test]
+    fn test_get_line_columns() {
+        let line = "aé日😀b\nrest";
+        assert_eq!(
+            vec![0, 1, 1, 3, 3, 3, 6, 6, 6, 6, 10, 11],
+            get_line_columns(line, PositionEncoding::Utf8Bytes)
+        );
+        assert_eq!(
+            vec![0, 1, 1, 2, 2, 2, 3, 3, 3, 3, 5, 6],
+            get_line_columns(line, PositionEncoding::Utf16)
+        );
+        assert_eq!(
+            vec![0, 1, 1, 2, 2, 2, 3, 3, 3, 3, 4, 5],
+            get_line_columns(line, PositionEncoding::Chars)
+        );
+    }
+
+    // the columns are counted in bytes, UTF-16 code units or characters
+    #[test]
+    fn test_position_encoding() {
+        let q = "(call) @call";
+        let c = "s = \"é😀\"; foo(x)\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let get_columns = |position_encoding: PositionEncoding| {
+            let options = QueryNodesOptions {
+                map_node_options: MapNodeOptions {
+                    position_encoding,
+                    ..MapNodeOptions::default()
+                },
+                ..QueryNodesOptions::default()
+            };
+            let query_nodes = get_query_nodes_with_options(
+                &tree,
+                &query,
+                "myfile.py",
+                c,
+                &HashMap::new(),
+                &options,
+            );
+            let call = &query_nodes.match_nodes[0].captures["call"];
+            (call.start.col, call.end.col)
+        };
+
+        assert_eq!((15, 21), get_columns(PositionEncoding::Utf8Bytes));
+        assert_eq!((12, 18), get_columns(PositionEncoding::Utf16));
+        assert_eq!((11, 17), get_columns(PositionEncoding::Chars));
+
+        // the children use the same encoding
+        let root_node = tree.root_node();
+        let options = MapNodeOptions {
+            position_encoding: PositionEncoding::Utf16,
+            ..MapNodeOptions::default()
+        };
+        let module = map_node_in_code(root_node, c, &options).unwrap();
+        let call = &module.children[1].children[0];
+        assert_eq!("call", call.ast_type);
+        assert_eq!(12, call.start.col);
+        // without the code, the columns are in bytes
+        let module = map_node(root_node).unwrap();
+        assert_eq!(15, module.children[1].children[0].start.col);
+    }
+
+    // only the matches in the range are returned, with the same nodes as on the whole file
+    #[test]
+    fn test_get_query_nodes_in_range() {
+        let q = "(function_definition name: (identifier) @name body: (block) @body) @function";
+        let c = "def foo():\n    pass\n\ndef bar():\n    a = 1\n    return a\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let line_index = LineIndex::new(c);
+        // the byte range of the lines (starting at 1), with the line ending of the last line
+        let get_range = |start_line: usize, end_line: usize| {
+            let start = line_index.get_line_range(start_line - 1).unwrap().start;
+            let end = line_index.get_line_range(end_line - 1).unwrap().end;
+            start..(end + 1).min(c.len())
+        };
+        let get_nodes = |range: Range<usize>| {
+            get_query_nodes_in_range(&tree, &query, range, "myfile.py", c, &HashMap::new())
+        };
+        let all_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
+        assert_eq!(2, all_nodes.len());
+
+        // the range is inside the body of bar, the function is still returned whole
+        let range = get_range(5, 5);
+        assert_eq!("    a = 1\n", &c[range.clone()]);
+        let nodes = get_nodes(range);
+        assert_eq!(1, nodes.len());
+        for capture_name in ["name", "body", "function"] {
+            assert_eq!(
+                serde_json::to_string(&all_nodes[1].captures[capture_name]).unwrap(),
+                serde_json::to_string(&nodes[0].captures[capture_name]).unwrap()
+            );
+        }
+        assert_eq!(4, nodes[0].captures["function"].start.line);
+        assert_eq!(6, nodes[0].captures["function"].end.line);
+
+        // no function in the empty line between both functions
+        assert!(get_nodes(get_range(3, 3)).is_empty());
+
+        // the range covering the whole file returns all the matches
+        let range = get_range(1, 6);
+        assert_eq!(0..c.len(), range);
+        assert_eq!(2, get_nodes(range).len());
+    }
+
+    // the matches stop once the deadline is reached
+    #[test]
+    fn test_get_query_nodes_deadline() {
+        let q = "(identifier) @id";
+        let c = "a = b + c\nd = e + f\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let get_nodes = |deadline: Option<Instant>| {
+            let options = QueryNodesOptions {
+                deadline,
+                ..QueryNodesOptions::default()
+            };
+            get_query_nodes_with_options(&tree, &query, "myfile.py", c, &HashMap::new(), &options)
+        };
+
+        let query_nodes = get_nodes(Some(Instant::now() + Duration::from_secs(3600)));
+        assert_eq!(6, query_nodes.match_nodes.len());
+        assert!(!query_nodes.is_timed_out);
+
+        let query_nodes = get_nodes(Some(Instant::now()));
+        assert!(query_nodes.match_nodes.is_empty());
+        assert!(query_nodes.is_timed_out);
+    }
+
+    // the in-progress matches over the limit are dropped and the result is truncated
+    #[test]
+    fn test_get_query_nodes_match_limit() {
+        let q = "(module (expression_statement) @first (expression_statement) @second)";
+        let c = "a = 1\nb = 2\nc = 3\nd = 4\n";
+        let tree = get_tree(c, &Language::Python).unwrap();
+        let query = get_query(q, &Language::Python).unwrap();
+        let get_nodes = |match_limit: Option<u32>| {
+            let options = QueryNodesOptions {
+                match_limit,
+                ..QueryNodesOptions::default()
+            };
+            get_query_nodes_with_options(&tree, &query, "myfile.py", c, &HashMap::new(), &options)
+        };
+
+        let query_nodes = get_nodes(None);
+        assert_eq!(6, query_nodes.match_nodes.len());
+        assert!(!query_nodes.is_truncated);
+
+        let query_nodes = get_nodes(Some(1));
+        assert!(query_nodes.match_nodes.len() < 6);
+        assert!(query_nodes.is_truncated);
+
+        // the limit is not kept by the cursor for the next queries
+        let variables = HashMap::new();
+        let mut runner = QueryRunner::new(&tree, "myfile.py", c, &variables);
+        let limited = QueryNodesOptions {
+            match_limit: Some(1),
+            ..QueryNodesOptions::default()
+        };
+        assert!(runner.run(&query, &limited).is_truncated);
+        let query_nodes = runner.run(&query, &QueryNodesOptions::default());
+        assert_eq!(6, query_nodes.match_nodes.len());
+        assert!(!query_nodes.is_truncated);
+    }
+
+    // the parsing fails with a timeout when it takes longer than the timeout
+    #[test]
+    fn test_get_tree_with_timeout() {
+        let c = "def foo(arg1, arg2):\n    return arg1 + arg2\n".repeat(50_000);
+        let python = get_tree_sitter_language(&Language::Python).unwrap();
+        assert_eq!(
+            Err(ParseError::Timeout),
+            get_tree_with_timeout(&c, python, Some(Duration::from_micros(1))).map(|_| ())
+        );
+        assert!(get_tree_with_timeout(&c, python, Some(Duration::from_secs(3600))).is_ok());
+        // the parser is not left with the cancelled parsing
+        assert!(get_tree("a = 1", &Language::Python).is_some());
+    }
+
+    // a query is compiled once per grammar
+    #[test]
+    fn test_query_cache() {
+        let q = "(identifier) @name";
+        let query = get_query(q, &Language::Python).unwrap();
+        assert!(Arc::ptr_eq(
+            &query,
+            &get_query(q, &Language::Python).unwrap()
+        ));
+        // the same query for another grammar is another query
+        let javascript_query = get_query(q, &Language::JavaScript).unwrap();
+        assert!(!Arc::ptr_eq(&query, &javascript_query));
+        // invalid queries are not cached
+        assert!(get_query("(function_definition", &Language::Python).is_err());
+        assert!(get_query("(function_definition", &Language::Python).is_err());
+    }
+
+    // all the threads share the same compiled query
+    #[test]
+    fn test_query_cache_concurrency() {
+        let q = "(call function: (identifier) @function (#eq? @function \"eval\"))";
+        let handles: Vec<_> = (0..8)
+            .map(|_| std::thread::spawn(move || get_query(q, &Language::Python).unwrap()))
+            .collect();
+        let queries: Vec<Arc<tree_sitter::Query>> =
+            handles.into_iter().map(|h| h.join().unwrap()).collect();
+        let query = get_query(q, &Language::Python).unwrap();
+        assert!(queries.iter().all(|other| Arc::ptr_eq(&query, other)));
+    }
+
+    // all the grammars are supported by the parser and the errors explain why there is no tree
+    #[test]
+    fn test_try_get_tree() {
+        for language in ALL_LANGUAGES {
+            assert!(try_get_tree("", language).is_ok());
+        }
+        assert_eq!(
+            "parser setup failed: incompatible language version 9",
+            ParseError::ParserSetup("incompatible language version 9".to_string()).to_string()
+        );
+        assert_eq!(
+            "parse failed: the parser did not return a tree",
+            ParseError::ParseFailed.to_string()
+        );
+    }
+
+    // well-known kinds and fields of the grammars, renamed kinds break the rules
+    #[test]
+    fn test_get_language_node_kinds() {
+        let has_kind = |language: &Language, kind: &str, is_named: bool| {
+            get_language_node_kinds(language)
+                .iter()
+                .any(|k| k.kind == kind && k.is_named == is_named && k.is_visible)
+        };
+        for kind in [
+            "function_definition",
+            "class_definition",
+            "call",
+            "identifier",
+        ] {
+            assert!(has_kind(&Language::Python, kind, true), "{kind}");
+        }
+        assert!(has_kind(&Language::Python, "def", false));
+        assert!(!has_kind(&Language::Python, "function_declaration", true));
+        for kind in [
+            "function_item",
+            "struct_item",
+            "impl_item",
+            "call_expression",
+        ] {
+            assert!(has_kind(&Language::Rust, kind, true), "{kind}");
+        }
+        assert!(has_kind(&Language::Rust, "fn", false));
+
+        let python_fields = get_language_fields(&Language::Python);
+        for field in ["name", "parameters", "body", "function", "arguments"] {
+            assert!(python_fields.contains(&field.to_string()), "{field}");
+        }
+        assert!(get_language_fields(&Language::Rust).contains(&"trait".to_string()));
+
+        for language in ALL_LANGUAGES {
+            assert!(!get_language_node_kinds(language).is_empty());
+        }
+    }
+
+    // grammars generated for another version of tree-sitter are reported with their language
+    #[test]
+    fn test_check_grammar_version() {
+        for language in ALL_LANGUAGES {
+            assert!(get_tree_sitter_language(language).is_ok());
+        }
+        let version = tree_sitter::LANGUAGE_VERSION;
+        assert!(check_grammar_version(&Language::Python, version).is_ok());
+
+        let error = check_grammar_version(&Language::Python, version + 1).unwrap_err();
+        assert_eq!(Language::Python, error.language);
+        assert_eq!(version + 1, error.version);
+        assert_eq!(
+            format!(
+                "unsupported python grammar: version {} is not between {} and {}",
+                version + 1,
+                tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+                version
+            ),
+            ParseError::from(error.clone()).to_string()
+        );
+        assert!(check_grammar_version(&Language::Python, 0).is_err());
+    }
+
+    // the error reports where the query is invalid
+    #[test]
+    fn test_get_query_error_location() {
+        let q = r#"(function_definition
+    name: (identifier) @name
+    body: (not_a_node) @body
+)"#;
+        let error = get_query(q, &Language::Python).unwrap_err();
+        assert_eq!(QueryErrorKind::NodeType, error.kind);
+        assert_eq!("not_a_node", error.message);
+        assert_eq!(3, error.line);
+        assert_eq!(12, error.col);
+        assert_eq!(q.find("not_a_node").unwrap(), error.offset);
+        assert_eq!("body: (not_a_node) @body", error.snippet);
+        assert_eq!(
+            "invalid node type at line 3, col 12: not_a_node (body: (not_a_node) @body)",
+            error.to_string()
+        );
+
+        let error = get_query("(function_definition", &Language::Python).unwrap_err();
+        assert_eq!(QueryErrorKind::Syntax, error.kind);
+        assert_eq!(1, error.line);
+    }
+
+    // the ancestors of a capture go from its parent to the root of the tree
+    #[test]
+    fn test_capture_ancestors() {
+        let code = r#"
+def outer():
+    def inner():
+        eval(x)
+"#;
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let query = get_query("(call function: (identifier) @name)", &Language::Python).unwrap();
+        let options = QueryNodesOptions {
+            include_ancestors: true,
+            ..QueryNodesOptions::default()
+        };
+        let get_nodes = |query: &tree_sitter::Query, options: &QueryNodesOptions| {
+            get_query_nodes_with_options(&tree, query, "myfile.py", code, &HashMap::new(), options)
+                .match_nodes
+        };
+        let query_nodes = get_nodes(&query, &options);
+        assert_eq!(1, query_nodes.len());
+        let name = &query_nodes[0].captures["name"];
+        let kinds: Vec<&str> = name.ancestors.iter().map(|a| a.ast_type.as_str()).collect();
+        assert_eq!(
+            vec![
+                "call",
+                "expression_statement",
+                "block",
+                "function_definition",
+                "block",
+                "function_definition",
+                "module"
+            ],
+            kinds
+        );
+        let inner = &name.ancestors[3];
+        assert_eq!(Position::new(3, 5), inner.start);
+        assert_eq!(Position::new(4, 16), inner.end);
+        assert_eq!(
+            &code[inner.start_byte..inner.end_byte],
+            "def inner():\n        eval(x)"
+        );
+
+        // the same ancestors from the summary of the node
+        let ancestors = get_node_ancestors(
+            &tree,
+            &name.get_summary(),
+            code,
+            PositionEncoding::default(),
+        )
+        .unwrap();
+        let get_ids = |nodes: &[TreeSitterNodeSummary]| {
+            nodes
+                .iter()
+                .map(|n| (n.id, n.start.clone(), n.end.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(get_ids(&name.ancestors), get_ids(&ancestors));
+
+        // the children of the captures do not have the ancestors
+        let query = get_query("(call) @call", &Language::Python).unwrap();
+        let query_nodes = get_nodes(&query, &options);
+        let call = &query_nodes[0].captures["call"];
+        assert_eq!(6, call.ancestors.len());
+        assert!(call.children.iter().all(|c| c.ancestors.is_empty()));
+
+        // the ancestors are not computed by default
+        let query_nodes = get_nodes(&query, &QueryNodesOptions::default());
+        assert!(query_nodes[0].captures["call"].ancestors.is_empty());
     }
 
+    // the captures of the same node by different patterns have the same id
     #[test]
-    fn test_yaml_get_tree() {
-        let source_code = r#"
-rulesets:
-  - my-ruleset
+    fn test_capture_ids() {
+        let code = "eval(x)\nprint(y)\n";
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let query_code = r#"
+(call function: (identifier) @name)
+(call function: (identifier) @name arguments: (argument_list (identifier) @arg))
 "#;
-        let t = get_tree(source_code, &Language::Yaml);
-        assert!(t.is_some());
-        assert_eq!("stream", t.unwrap().root_node().kind());
+        let query = get_query(query_code, &Language::Python).unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", code, &HashMap::new());
+        assert_eq!(4, query_nodes.len());
+        let eval_matches: Vec<&MatchNode> = query_nodes
+            .iter()
+            .filter(|m| m.captures["name"].start.line == 1)
+            .collect();
+        assert_eq!(2, eval_matches.len());
+        assert_eq!(
+            eval_matches[0].captures["name"].id,
+            eval_matches[1].captures["name"].id
+        );
+        assert_ne!(
+            query_nodes[0].captures["name"].id,
+            query_nodes[3].captures["name"].id
+        );
     }
 
-    // test the number of node we should retrieve when executing a rule
+    // the siblings of the captures are only set when asked
     #[test]
-    fn test_get_query_nodes() {
-        let q = r#"
-(class_definition
-  name: (identifier) @classname
-  superclasses: (argument_list
-    (identifier)+ @superclasses
-  )
-)
-        "#;
+    fn test_capture_siblings() {
+        let code = r#"
+def foo():
+    log("start")
+    bar()
+    return 1
+"#;
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let query = get_query("(block (_) @statement)", &Language::Python).unwrap();
+        let options = QueryNodesOptions {
+            include_siblings: true,
+            ..QueryNodesOptions::default()
+        };
+        let query_nodes = get_query_nodes_with_options(
+            &tree,
+            &query,
+            "myfile.py",
+            code,
+            &HashMap::new(),
+            &options,
+        )
+        .match_nodes;
+        assert_eq!(3, query_nodes.len());
 
-        let c = r#"
- class myClass(Parent):
-    def __init__(self):
+        // at the start of the block
+        let first = &query_nodes[0].captures["statement"];
+        assert!(first.previous_sibling.is_none());
+        let next = first.next_sibling.as_ref().unwrap();
+        assert_eq!("expression_statement", next.ast_type);
+        assert_eq!(Position::new(4, 5), next.start);
+
+        // in the middle of the block
+        let middle = &query_nodes[1].captures["statement"];
+        let previous = middle.previous_sibling.as_ref().unwrap();
+        assert_eq!(first.id, previous.id);
+        assert_eq!(Position::new(3, 5), previous.start);
+        assert_eq!(Position::new(3, 17), previous.end);
+        let next = middle.next_sibling.as_ref().unwrap();
+        assert_eq!("return_statement", next.ast_type);
+        assert_eq!(&code[next.start_byte..next.end_byte], "return 1");
+
+        // at the end of the block
+        let last = &query_nodes[2].captures["statement"];
+        assert_eq!(middle.id, last.previous_sibling.as_ref().unwrap().id);
+        assert!(last.next_sibling.is_none());
+
+        // the siblings are not set by default
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", code, &HashMap::new());
+        assert!(query_nodes[1].captures["statement"]
+            .previous_sibling
+            .is_none());
+        assert!(query_nodes[1].captures["statement"].next_sibling.is_none());
+    }
+
+    // the language of the file is in the context, with its name
+    #[test]
+    fn test_context_language() {
+        let code = "def foo():\n    pass\n";
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let query = get_query("(function_definition) @func", &Language::Python).unwrap();
+        let options = QueryNodesOptions {
+            language: Some(Language::Python),
+            ..QueryNodesOptions::default()
+        };
+        let query_nodes = get_query_nodes_with_options(
+            &tree,
+            &query,
+            "myfile.py",
+            code,
+            &HashMap::new(),
+            &options,
+        );
+        assert_eq!(
+            Some(Language::Python),
+            query_nodes.match_nodes[0].context.language
+        );
+        // not set when the language is not known
+        let match_nodes = get_query_nodes(&tree, &query, "myfile.py", code, &HashMap::new());
+        assert!(match_nodes[0].context.language.is_none());
+
+        for language in ALL_LANGUAGES {
+            let context = MatchNodeContext {
+                language: Some(*language),
+                ..match_nodes[0].context.clone()
+            };
+            let value = serde_json::to_value(&context).unwrap();
+            // the name of the language (e.g. `PYTHON`)
+            assert!(value["language"].is_string());
+            assert_eq!(serde_json::to_value(language).unwrap(), value["language"]);
+            let deserialized: MatchNodeContext = serde_json::from_value(value).unwrap();
+            assert_eq!(Some(*language), deserialized.language);
+        }
+    }
+
+    // the matches outside of the node are not returned and the positions are in the code
+    #[test]
+    fn test_get_query_nodes_in_node() {
+        let code = r#"
+def helper():
+    pass
+
+class Foo:
+    def bar(self):
         pass
-        "#;
 
-        let tree = get_tree(c, &Language::Python).unwrap();
-        let query = get_query(q, &Language::Python).expect("query defined");
-        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", c, &HashMap::new());
-        assert_eq!(query_nodes.len(), 1);
-        let query_node = query_nodes.get(0).unwrap();
-        assert_eq!(2, query_node.captures_list.len());
-        assert_eq!(1, query_node.captures_list.get("classname").unwrap().len());
+    def baz(self):
+        pass
+"#;
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let class_query = get_query("(class_definition) @cls", &Language::Python).unwrap();
+        let function_query = get_query(
+            "(function_definition name: (identifier) @name)",
+            &Language::Python,
+        )
+        .unwrap();
+        let class_nodes = get_query_nodes(&tree, &class_query, "myfile.py", code, &HashMap::new());
+        let class_node = &class_nodes[0].captures["cls"];
+        let summary = TreeSitterNodeSummary {
+            id: class_node.id,
+            ast_type: class_node.ast_type.clone(),
+            start: class_node.start.clone(),
+            end: class_node.end.clone(),
+            start_byte: class_node.start_byte,
+            end_byte: class_node.end_byte,
+        };
+        let tree_node = get_node_in_tree(&tree, &summary).unwrap();
+        assert_eq!("class_definition", tree_node.kind());
+
+        let match_nodes = get_query_nodes_in_node(
+            tree_node,
+            &function_query,
+            "myfile.py",
+            code,
+            &HashMap::new(),
+            &QueryNodesOptions::default(),
+        )
+        .match_nodes;
+        // `helper` is outside of the class
+        assert_eq!(2, match_nodes.len());
+        let bar = &match_nodes[0].captures["name"];
+        assert_eq!("bar", get_code_for_node(code, bar));
+        assert_eq!(Position::new(6, 9), bar.start);
+        let baz = &match_nodes[1].captures["name"];
+        assert_eq!("baz", get_code_for_node(code, baz));
+        assert_eq!(Position::new(9, 9), baz.start);
+        assert_eq!("myfile.py", match_nodes[0].context.filename);
+
+        // the nodes that are not in the tree are not found
+        let summary = TreeSitterNodeSummary {
+            start_byte: class_node.start_byte + 1,
+            ..summary
+        };
+        assert!(get_node_in_tree(&tree, &summary).is_none());
+    }
+
+    // the nodes of one capture, in the order of the code and once even when several patterns
+    // capture them
+    #[test]
+    fn test_get_query_captures() {
+        let code = r#"
+def foo(a, b):
+    return a + b
+"#;
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let query = get_query(
+            r#"
+(identifier) @id
+(binary_operator left: (identifier) @id)
+(function_definition name: (identifier) @name)
+"#,
+            &Language::Python,
+        )
+        .unwrap();
+        let nodes = get_query_captures(&tree, &query, "id", code);
         assert_eq!(
-            1,
-            query_node.captures_list.get("superclasses").unwrap().len()
+            vec!["foo", "a", "b", "a", "b"],
+            nodes
+                .iter()
+                .map(|n| get_code_for_node(code, n))
+                .collect::<Vec<&str>>()
         );
-        assert_eq!(2, query_node.captures.len());
-        assert!(query_node.captures.contains_key("superclasses"));
-        let superclasses = query_node.captures.get("superclasses").unwrap();
-        assert_eq!(2, superclasses.start.line);
-        assert_eq!(16, superclasses.start.col);
-        assert_eq!(2, superclasses.end.line);
-        assert_eq!(22, superclasses.end.col);
-        assert_eq!("identifier", superclasses.ast_type);
-        assert_eq!(None, superclasses.field_name);
-        assert!(query_node.captures.contains_key("classname"));
+        assert_eq!(Position::new(3, 12), nodes[3].start);
+
+        assert_eq!(1, get_query_captures(&tree, &query, "name", code).len());
+        assert!(get_query_captures(&tree, &query, "unknown", code).is_empty());
+
+        // one match for each captured node
+        let query_nodes = get_query_capture_nodes_with_options(
+            &tree,
+            &query,
+            "myfile.py",
+            code,
+            &HashMap::new(),
+            &QueryNodesOptions::default(),
+        );
+        assert_eq!(6, query_nodes.match_nodes.len());
+        assert!(query_nodes
+            .match_nodes
+            .iter()
+            .all(|m| m.captures.len() == 1 && m.context.filename == "myfile.py"));
+        let name_match = query_nodes
+            .match_nodes
+            .iter()
+            .find(|m| m.captures.contains_key("name"))
+            .unwrap();
+        assert_eq!(2, name_match.pattern_index);
+        assert_eq!(1, name_match.captures_list["name"].len());
+    }
+
+    // the captured nodes are the nodes of the matches, without building a match for each of them
+    #[test]
+    fn test_get_query_captures_many_nodes() {
+        let code = "a\n".repeat(10_000);
+        let tree = get_tree(&code, &Language::Python).unwrap();
+        let query = get_query("(identifier) @id", &Language::Python).unwrap();
+
+        let match_nodes = get_query_nodes(&tree, &query, "myfile.py", &code, &HashMap::new());
+        let nodes = get_query_captures(&tree, &query, "id", &code);
+        assert_eq!(10_000, match_nodes.len());
+        assert_eq!(
+            match_nodes
+                .iter()
+                .map(|m| m.captures["id"].id)
+                .collect::<Vec<usize>>(),
+            nodes.iter().map(|n| n.id).collect::<Vec<usize>>()
+        );
+        // the matches share the code of the file
+        let shared_code = match_nodes[0].context.code.as_ref().unwrap();
+        assert!(match_nodes
+            .iter()
+            .all(|m| Arc::ptr_eq(shared_code, m.context.code.as_ref().unwrap())));
+    }
+
+    #[test]
+    fn test_capture_text() {
+        let code = r#"
+def foo():
+    s = bar(
+        "😀 hi",
+    )
+"#;
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let query = get_query("(call) @call (string) @str", &Language::Python).unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", code, &HashMap::new());
+        assert_eq!(2, query_nodes.len());
+
+        // on several lines
+        let call = &query_nodes[0].captures["call"];
+        let call_code = "bar(\n        \"😀 hi\",\n    )";
+        assert_eq!(call_code, get_code_for_node(code, call));
+        assert_eq!(Some(call_code), call.text.as_deref());
+        assert!(!call.text_truncated);
+        // only on the captured nodes
+        assert!(call.children.iter().all(|c| c.text.is_none()));
+
+        // with an emoji
+        let string = &query_nodes[1].captures["str"];
+        assert_eq!("\"😀 hi\"", get_code_for_node(code, string));
+        assert_eq!(Some("\"😀 hi\""), string.text.as_deref());
+
+        // the code is truncated without splitting the emoji
+        let options = QueryNodesOptions {
+            max_text_length: Some(3),
+            ..QueryNodesOptions::default()
+        };
+        let query_nodes = get_query_nodes_with_options(
+            &tree,
+            &query,
+            "myfile.py",
+            code,
+            &HashMap::new(),
+            &options,
+        )
+        .match_nodes;
+        let string = &query_nodes[1].captures["str"];
+        assert_eq!(Some("\""), string.text.as_deref());
+        assert!(string.text_truncated);
+
+        // the code is not added when there is no length
+        let options = QueryNodesOptions {
+            max_text_length: None,
+            ..QueryNodesOptions::default()
+        };
+        let query_nodes = get_query_nodes_with_options(
+            &tree,
+            &query,
+            "myfile.py",
+            code,
+            &HashMap::new(),
+            &options,
+        )
+        .match_nodes;
+        assert!(query_nodes[0].captures["call"].text.is_none());
+    }
+
+    #[test]
+    fn test_get_comments_python() {
+        let code = "# first\nx = 1  # trailing\ndef foo():\n    # in a block\n    pass\n";
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let comments = get_comments(&tree, code, &Language::Python);
+        let texts: Vec<&str> = comments.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(vec!["# first", "# trailing", "# in a block"], texts);
+        assert!(comments.iter().all(|c| c.kind == CommentKind::Line));
+        assert_eq!(Position::new(2, 8), comments[1].start);
+        assert_eq!(Position::new(2, 18), comments[1].end);
+        assert_eq!(Position::new(4, 5), comments[2].start);
+    }
+
+    #[test]
+    fn test_get_comments_rust() {
+        let code = r#"// line comment
+/* block
+   comment */
+fn main() {
+    let x = 1; // trailing
+}
+"#;
+        let tree = get_tree(code, &Language::Rust).unwrap();
+        let comments = get_comments(&tree, code, &Language::Rust);
+        assert_eq!(3, comments.len());
+        assert_eq!(CommentKind::Line, comments[0].kind);
+        assert_eq!("/* block\n   comment */", comments[1].text);
+        assert_eq!(CommentKind::Block, comments[1].kind);
+        assert_eq!(Position::new(2, 1), comments[1].start);
+        assert_eq!(Position::new(3, 14), comments[1].end);
+        assert_eq!("// trailing", comments[2].text);
+        assert_eq!(CommentKind::Line, comments[2].kind);
+        assert_eq!(
+            &code[comments[2].start_byte..comments[2].end_byte],
+            "// trailing"
+        );
+    }
+
+    #[test]
+    fn test_get_comments_javascript() {
+        let code = r#"/**
+ * Add two numbers.
+ * @param {number} a
+ */
+function add(a, b) {
+  return a + b; // sum
+}
+"#;
+        let tree = get_tree(code, &Language::JavaScript).unwrap();
+        let comments = get_comments(&tree, code, &Language::JavaScript);
+        assert_eq!(2, comments.len());
+        assert!(comments[0].text.starts_with("/**\n * Add two numbers."));
+        assert_eq!(CommentKind::Block, comments[0].kind);
+        assert_eq!(Position::new(1, 1), comments[0].start);
+        assert_eq!(Position::new(4, 4), comments[0].end);
+        assert_eq!(CommentKind::Line, comments[1].kind);
+        assert_eq!(6, comments[1].start.line);
+    }
+
+    // the matches tell which pattern of the query matched
+    #[test]
+    fn test_pattern_index() {
+        let code = "foo()\nx = bar\n";
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let query_code = r#"
+(call function: (identifier) @name)
+(assignment right: (identifier) @name)
+"#;
+        let query = get_query(query_code, &Language::Python).unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", code, &HashMap::new());
+        assert_eq!(2, query_nodes.len());
+        assert_eq!(0, query_nodes[0].pattern_index);
+        assert_eq!(1, query_nodes[0].captures["name"].start.line);
+        assert_eq!(1, query_nodes[1].pattern_index);
+        assert_eq!(2, query_nodes[1].captures["name"].start.line);
+        assert!(query_nodes.iter().all(|m| m.pattern_count == 2));
+
+        let value = serde_json::to_value(&query_nodes[1]).unwrap();
+        assert_eq!(1, value["patternIndex"]);
+        assert_eq!(2, value["patternCount"]);
+    }
+
+    // a capture with several nodes has its first node in captures and all its nodes, in the
+    // order of the code, in captures_list
+    #[test]
+    fn test_multiple_nodes_capture() {
+        let code = "class Foo(Base1, Base2, Base3):\n    pass\n";
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let query_code = "(class_definition superclasses: (argument_list (identifier)+ @bases))";
+        let query = get_query(query_code, &Language::Python).unwrap();
+        for _ in 0..10 {
+            let query_nodes = get_query_nodes(&tree, &query, "myfile.py", code, &HashMap::new());
+            assert_eq!(1, query_nodes.len());
+            let first = &query_nodes[0].captures["bases"];
+            assert_eq!(Position::new(1, 11), first.start);
+            let columns: Vec<u32> = query_nodes[0].captures_list["bases"]
+                .iter()
+                .map(|n| n.start.col)
+                .collect();
+            assert_eq!(vec![11, 18, 25], columns);
+        }
+    }
+
+    #[test]
+    fn test_tree_to_sexp_python() {
+        let code = "def foo(a):\n    return a\n";
+        let options = SexpOptions {
+            positions: true,
+            fields: true,
+        };
+        let expected = r#"(module [0, 0] - [2, 0]
+  (function_definition [0, 0] - [1, 12]
+    name: (identifier [0, 4] - [0, 7])
+    parameters: (parameters [0, 7] - [0, 10]
+      (identifier [0, 8] - [0, 9]))
+    body: (block [1, 4] - [1, 12]
+      (return_statement [1, 4] - [1, 12]
+        (identifier [1, 11] - [1, 12])))))"#;
+        assert_eq!(
+            expected,
+            tree_to_sexp(code, &Language::Python, &options).unwrap()
+        );
+
+        let expected = r#"(module
+  (function_definition
+    (identifier)
+    (parameters
+      (identifier))
+    (block
+      (return_statement
+        (identifier)))))"#;
+        let sexp = tree_to_sexp(code, &Language::Python, &SexpOptions::default()).unwrap();
+        assert_eq!(expected, sexp);
+    }
+
+    #[test]
+    fn test_tree_to_sexp_typescript() {
+        let code = "const x: number = y;\n";
+        let options = SexpOptions {
+            positions: false,
+            fields: true,
+        };
+        let query = get_query(
+            "(variable_declarator name: (identifier) @name value: (_) @value) (identifier) @id",
+            &Language::TypeScript,
+        )
+        .unwrap();
+        let expected = r#"(program
+  (lexical_declaration
+    (variable_declarator
+      name: (identifier) @id @name
+      type: (type_annotation
+        (predefined_type))
+      value: (identifier) @id @value)))"#;
+        assert_eq!(
+            expected,
+            tree_to_sexp_with_captures(code, &Language::TypeScript, &query, &options).unwrap()
+        );
+    }
+
+    // the fields with their default value, added back by the JavaScript code of the rules
+    fn add_default_fields(value: &mut serde_json::Value) {
+        let node = value.as_object_mut().unwrap();
+        for (field, default) in [
+            ("text", serde_json::Value::Null),
+            ("textTruncated", serde_json::Value::Bool(false)),
+            ("fieldName", serde_json::Value::Null),
+            ("children", serde_json::json!([])),
+            ("isError", serde_json::Value::Bool(false)),
+            ("isMissing", serde_json::Value::Bool(false)),
+            ("isTruncated", serde_json::Value::Bool(false)),
+            ("ancestors", serde_json::json!([])),
+            ("parentKind", serde_json::Value::Null),
+            ("parentFieldName", serde_json::Value::Null),
+            ("previousSibling", serde_json::Value::Null),
+            ("nextSibling", serde_json::Value::Null),
+        ] {
+            node.entry(field).or_insert(default);
+        }
+        for child in node["children"].as_array_mut().unwrap() {
+            add_default_fields(child);
+        }
+    }
+
+    // the nodes sent to the rules do not have the fields with their default value
+    #[test]
+    fn test_node_serialization_size() {
+        let methods: String = (0..50)
+            .map(|i| format!("    def method{i}(self, a, b):\n        return a + b * {i}\n\n"))
+            .collect();
+        let code = format!("class Foo:\n{methods}");
+        assert!(code.lines().count() >= 150);
+        let tree = get_tree(&code, &Language::Python).unwrap();
+        let query = get_query("(class_definition body: (block) @body)", &Language::Python).unwrap();
+        let match_nodes = get_query_nodes(&tree, &query, "myfile.py", &code, &HashMap::new());
+        let body = &match_nodes[0].captures["body"];
+
+        let compact = serde_json::to_value(body).unwrap();
+        assert!(compact.get("children").is_some());
+        assert!(compact["children"][0]["children"][0]
+            .get("fieldName")
+            .is_some());
+        assert!(compact["children"][0]["children"][0]
+            .get("isError")
+            .is_none());
+        let compact_size = compact.to_string().len();
+
+        let mut full = compact.clone();
+        add_default_fields(&mut full);
+        let full_size = full.to_string().len();
+        assert!(
+            compact_size * 3 < full_size * 2,
+            "compact size {compact_size}, full size {full_size}"
+        );
+
+        // the nodes are read back with their default values
+        let deserialized: TreeSitterNode = serde_json::from_value(compact).unwrap();
+        assert_eq!(body.children.len(), deserialized.children.len());
+        assert!(!deserialized.children[0].is_error);
+        assert!(deserialized.children[0].children[1].children[0]
+            .children
+            .is_empty());
+        let mut round_trip = serde_json::to_value(&deserialized).unwrap();
+        add_default_fields(&mut round_trip);
+        assert_eq!(full, round_trip);
+    }
+
+    // the range of the match is the union of the ranges of all its captures
+    #[test]
+    fn test_match_range() {
+        let code = r#"
+def foo(a):
+    x = bar(1,
+            2)
+    return x
+"#;
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let query_code = r#"
+(function_definition
+  name: (identifier) @name
+  body: (block (expression_statement (assignment right: (call) @call))))
+"#;
+        let query = get_query(query_code, &Language::Python).unwrap();
+        let match_nodes = get_query_nodes(&tree, &query, "myfile.py", code, &HashMap::new());
+        assert_eq!(1, match_nodes.len());
+        let match_node = &match_nodes[0];
+        let nodes: Vec<&TreeSitterNode> = match_node.captures_list.values().flatten().collect();
+        let start = nodes.iter().map(|n| &n.start).min().unwrap();
+        let end = nodes.iter().map(|n| &n.end).max().unwrap();
+        assert_eq!(start, &match_node.match_start);
+        assert_eq!(end, &match_node.match_end);
+        assert_eq!(Position::new(2, 5), *start);
+        assert_eq!(Position::new(4, 15), *end);
+
+        // the captures that are not sent to the rule are in the range
+        let query_code = r#"
+(assignment left: (identifier) @_target right: (call arguments: (argument_list) @args))
+"#;
+        let query = get_query(query_code, &Language::Python).unwrap();
+        let match_nodes = get_query_nodes(&tree, &query, "myfile.py", code, &HashMap::new());
+        let match_node = &match_nodes[0];
+        assert_eq!(1, match_node.captures.len());
+        let args = &match_node.captures["args"];
+        assert_eq!(Position::new(3, 12), args.start);
+        assert_eq!(Position::new(3, 5), match_node.match_start);
+        assert_eq!(Position::new(4, 15), match_node.match_end);
+
+        // a match for each captured node in capture mode
+        let query = get_query("(call) @call", &Language::Python).unwrap();
+        let query_nodes = get_query_capture_nodes_with_options(
+            &tree,
+            &query,
+            "myfile.py",
+            code,
+            &HashMap::new(),
+            &QueryNodesOptions::default(),
+        );
+        let match_node = &query_nodes.match_nodes[0];
+        assert_eq!(Position::new(3, 9), match_node.match_start);
+        assert_eq!(Position::new(4, 15), match_node.match_end);
+    }
+
+    // the kind of the parent of the captures and the field of the captures in their parent
+    #[test]
+    fn test_capture_parent() {
+        let code = r#"
+@foo()
+def f():
+    assert bar()
+    baz()
+    x = qux(bar())
+"#;
+        let tree = get_tree(code, &Language::Python).unwrap();
+        let query = get_query(
+            "(call function: (identifier) @name) @call",
+            &Language::Python,
+        )
+        .unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", code, &HashMap::new());
+        let mut parents: Vec<(String, Option<&str>, Option<&str>)> = query_nodes
+            .iter()
+            .map(|m| {
+                let call = &m.captures["call"];
+                (
+                    get_code_for_node(code, &m.captures["name"]).to_string(),
+                    call.parent_kind.as_deref(),
+                    call.parent_field_name.as_deref(),
+                )
+            })
+            .collect();
+        parents.sort();
+        assert_eq!(
+            vec![
+                ("bar".to_string(), Some("argument_list"), None),
+                ("bar".to_string(), Some("assert_statement"), None),
+                ("baz".to_string(), Some("expression_statement"), None),
+                ("foo".to_string(), Some("decorator"), None),
+                ("qux".to_string(), Some("assignment"), Some("right")),
+            ],
+            parents
+        );
+        // the name is the function of the call
+        let name = &query_nodes[0].captures["name"];
+        assert_eq!(Some("call"), name.parent_kind.as_deref());
+        assert_eq!(Some("function"), name.parent_field_name.as_deref());
+        // only on the captured nodes
+        let call = &query_nodes[0].captures["call"];
+        assert!(call.children.iter().all(|c| c.parent_kind.is_none()));
+
+        // the root of the tree has no parent
+        let query = get_query("(module) @module", &Language::Python).unwrap();
+        let query_nodes = get_query_nodes(&tree, &query, "myfile.py", code, &HashMap::new());
+        let module = &query_nodes[0].captures["module"];
+        assert!(module.parent_kind.is_none());
+        assert!(module.parent_field_name.is_none());
     }
 }
@@ -1,19 +1,365 @@
-use crate::model::common::Position;
+use crate::model::common::{Language, Position};
+use crate::model::rule::{RuleArgumentValue, RuleSeverity};
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Instant;
 
 pub const ERROR_RULE_TIMEOUT: &str = "rule-timeout";
 pub const ERROR_RULE_EXECUTION: &str = "error-execution";
 pub const ERROR_RULE_CODE_TOO_BIG: &str = "error-code-too-big";
 pub const ERROR_INVALID_QUERY: &str = "error-invalid-query";
+pub const ERROR_PARSING_FAILED: &str = "error-parsing-failed";
+pub const ERROR_RULE_MATCHES_TRUNCATED: &str = "rule-matches-truncated";
+pub const ERROR_NOT_ANALYZED_PARSE_ERRORS: &str = "not-analyzed-parse-errors";
+pub const ERROR_FILE_TIMEOUT: &str = "file-timeout";
+pub const ERROR_PARSING_TIMEOUT: &str = "error-parsing-timeout";
+pub const ERROR_FILE_DECODED_LOSSILY: &str = "file-decoded-lossily";
+pub const ERROR_INVALID_FIX: &str = "invalid-fix";
+pub const ERROR_RULE_OUT_OF_MEMORY: &str = "rule-out-of-memory";
+
+// Version of the shape of the nodes sent to the rules, sent in the context of each match. The
+// JavaScript code of the rules checks that it can read this version before running the rules.
+// Changing the shape of the nodes bumps the version, adds its note in NODE_SCHEMA_CHANGES and
+// updates the snapshot of the nodes in the tests and the versions supported in stella.js.
+pub const NODE_SCHEMA_VERSION: u32 = 4;
+
+// What changed in each version of the nodes, for the authors of the rules
+pub const NODE_SCHEMA_CHANGES: [(u32, &str); 4] = [
+    (1, "all the fields of the nodes are serialized"),
+    (
+        2,
+        "the fields with their default value (no children, no field name, false flags) are not \
+         serialized, the JavaScript code of the rules adds them back",
+    ),
+    (
+        3,
+        "the context has the version of the nodes (schemaVersion) and the name and the severity \
+         of the rule (ruleName, ruleSeverity)",
+    ),
+    (
+        4,
+        "the context tells if the file contains tests (isTestFile)",
+    ),
+];
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[derive(Copy, Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub enum QueryErrorKind {
+    #[serde(rename = "SYNTAX")]
+    Syntax,
+    #[serde(rename = "NODE_TYPE")]
+    NodeType,
+    #[serde(rename = "FIELD")]
+    Field,
+    #[serde(rename = "CAPTURE")]
+    Capture,
+    #[serde(rename = "PREDICATE")]
+    Predicate,
+    #[serde(rename = "STRUCTURE")]
+    Structure,
+    #[serde(rename = "LANGUAGE")]
+    Language,
+}
+
+impl fmt::Display for QueryErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Syntax => "invalid syntax",
+            Self::NodeType => "invalid node type",
+            Self::Field => "invalid field",
+            Self::Capture => "invalid capture",
+            Self::Predicate => "invalid predicate",
+            Self::Structure => "invalid structure",
+            Self::Language => "invalid language",
+        };
+        write!(f, "{s}")
+    }
+}
+
+// Error when building the tree-sitter query of a rule, with its location in the query.
+#[derive(Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub struct QueryError {
+    pub kind: QueryErrorKind,
+    pub message: String,
+    pub offset: usize, // in bytes, from the start of the query
+    pub line: u32,     // starts at 1
+    pub col: u32,      // starts at 1
+    pub snippet: String,
+}
+
+impl QueryError {
+    // Build the error from the offset of the error in the query.
+    pub fn new(kind: QueryErrorKind, message: &str, query_code: &str, offset: usize) -> Self {
+        let offset = offset.min(query_code.len());
+        let before = &query_code[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line_end = query_code[offset..]
+            .find('\n')
+            .map_or(query_code.len(), |i| offset + i);
+
+        QueryError {
+            kind,
+            message: message.to_string(),
+            offset,
+            line: u32::try_from(before.matches('\n').count() + 1).unwrap(),
+            col: u32::try_from(offset - line_start + 1).unwrap(),
+            snippet: query_code[line_start..line_end].trim().to_string(),
+        }
+    }
+
+    pub fn from_tree_sitter(error: tree_sitter::QueryError, query_code: &str) -> Self {
+        let kind = match error.kind {
+            tree_sitter::QueryErrorKind::Syntax => QueryErrorKind::Syntax,
+            tree_sitter::QueryErrorKind::NodeType => QueryErrorKind::NodeType,
+            tree_sitter::QueryErrorKind::Field => QueryErrorKind::Field,
+            tree_sitter::QueryErrorKind::Capture => QueryErrorKind::Capture,
+            tree_sitter::QueryErrorKind::Predicate => QueryErrorKind::Predicate,
+            tree_sitter::QueryErrorKind::Structure => QueryErrorKind::Structure,
+            tree_sitter::QueryErrorKind::Language => QueryErrorKind::Language,
+        };
+        QueryError::new(kind, &error.message, query_code, error.offset)
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, col {}: {} ({})",
+            self.kind, self.line, self.col, self.message, self.snippet
+        )
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+// Error when the grammar of a language was generated for a version of tree-sitter that the
+// library does not support (e.g. after updating the grammar).
+#[derive(Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub struct LanguageError {
+    pub language: Language,
+    pub version: usize,
+    pub min_version: usize,
+    pub max_version: usize,
+}
+
+impl LanguageError {
+    pub fn new(language: Language, version: usize) -> Self {
+        LanguageError {
+            language,
+            version,
+            min_version: tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+            max_version: tree_sitter::LANGUAGE_VERSION,
+        }
+    }
+}
+
+impl fmt::Display for LanguageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unsupported {} grammar: version {} is not between {} and {}",
+            self.language, self.version, self.min_version, self.max_version
+        )
+    }
+}
+
+impl std::error::Error for LanguageError {}
+
+// Error when getting the tree-sitter tree of a file.
+#[derive(Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub enum ParseError {
+    // the grammar of the language is not supported by the library
+    #[serde(rename = "LANGUAGE")]
+    Language(LanguageError),
+    // the parser could not be set up with the grammar (e.g. the parser does not support the
+    // version of tree-sitter the grammar was generated for)
+    #[serde(rename = "PARSER_SETUP")]
+    ParserSetup(String),
+    // the parser did not return any tree (the parsing was cancelled or timed out)
+    #[serde(rename = "PARSE_FAILED")]
+    ParseFailed,
+    // the parsing did not complete before the deadline
+    #[serde(rename = "TIMEOUT")]
+    Timeout,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Language(e) => write!(f, "{e}"),
+            Self::ParserSetup(message) => write!(f, "parser setup failed: {message}"),
+            Self::ParseFailed => write!(f, "parse failed: the parser did not return a tree"),
+            Self::Timeout => write!(f, "timeout: the parsing did not complete in time"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<LanguageError> for ParseError {
+    fn from(error: LanguageError) -> Self {
+        ParseError::Language(error)
+    }
+}
+
+// Maximum depth of the mapped nodes by default. Deeper nodes (e.g. in generated code) are
+// truncated since they are too deep to be serialized and sent to the rules.
+pub const DEFAULT_MAP_NODE_MAX_DEPTH: usize = 1_000;
+
+// Maximum depth of the mapped nodes, even when the options do not limit it. The mapped nodes
+// are cloned, serialized and dropped recursively, deeper trees would overflow the stack.
+pub const MAX_MAP_NODE_DEPTH: usize = 2_000;
+
+// How the columns of the positions are counted. Tree-sitter counts the bytes of the code in
+// UTF-8 while editors and the LSP count the UTF-16 code units. By default, the columns count
+// the characters so that the violations on lines with non-ASCII characters are not shifted.
+#[derive(Copy, Clone, Deserialize, Debug, Serialize, Eq, PartialEq, Default)]
+pub enum PositionEncoding {
+    #[serde(rename = "UTF8_BYTES")]
+    Utf8Bytes,
+    #[serde(rename = "UTF16")]
+    Utf16,
+    #[default]
+    #[serde(rename = "CHARS")]
+    Chars,
+}
+
+impl PositionEncoding {
+    // number of columns taken by the character
+    pub fn get_char_width(&self, c: char) -> usize {
+        match self {
+            Self::Utf8Bytes => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
+            Self::Chars => 1,
+        }
+    }
+}
+
+// How a tree is written as an S-expression
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SexpOptions {
+    // write the range of each node (e.g. `[0, 4] - [0, 7]`), rows and columns starting at 0
+    pub positions: bool,
+    // write the field of the nodes in their parent (e.g. `name: (identifier)`)
+    pub fields: bool,
+}
+
+// How the tree-sitter nodes are mapped
+#[derive(Copy, Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub struct MapNodeOptions {
+    // also map the anonymous nodes (keywords, operators, punctuation)
+    pub include_unnamed: bool,
+    // do not map the children of the nodes at this depth (the mapped node is at depth 0), the
+    // depth is always limited to MAX_MAP_NODE_DEPTH
+    pub max_depth: Option<usize>,
+    // stop mapping the children once this number of nodes (including the mapped node) is mapped
+    pub max_nodes: Option<usize>,
+    // how the columns are counted, the code of the node is needed for other encodings than
+    // UTF-8 bytes
+    pub position_encoding: PositionEncoding,
+}
+
+impl Default for MapNodeOptions {
+    fn default() -> Self {
+        MapNodeOptions {
+            include_unnamed: false,
+            max_depth: Some(DEFAULT_MAP_NODE_MAX_DEPTH),
+            max_nodes: None,
+            position_encoding: PositionEncoding::default(),
+        }
+    }
+}
+
+// Files with more than this ratio of the code covered by syntax errors are not analyzed by
+// default, the rules are likely to miss matches or to match inside the errors.
+pub const DEFAULT_MAX_PARSING_ERROR_RATE: f64 = 0.2;
+
+// Maximum number of matches of a query on a file by default.
+pub const DEFAULT_MAX_QUERY_MATCHES: usize = 10_000;
+pub const DEFAULT_MAX_NODE_TEXT_LENGTH: usize = 500;
+
+// How the nodes matching a query are collected
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct QueryNodesOptions {
+    pub map_node_options: MapNodeOptions,
+    // stop looking for matches once this number of matches is found
+    pub max_matches: Option<usize>,
+    // maximum number of in-progress matches, the other matches are dropped and the result is
+    // truncated. Some queries on large files keep a huge number of in-progress matches.
+    pub match_limit: Option<u32>,
+    // stop looking for matches once this instant is reached
+    pub deadline: Option<Instant>,
+    // only return the matches that intersect this byte range, the captured nodes are whole
+    pub byte_range: Option<Range<usize>>,
+    // add the previous and next named siblings to the captured nodes
+    pub include_siblings: bool,
+    // add the ancestors (from the parent to the root of the tree) to the captured nodes
+    pub include_ancestors: bool,
+    // add the code of the captured nodes, truncated at this number of bytes. The code is not
+    // added when not set.
+    pub max_text_length: Option<usize>,
+    // the language of the file, added to the context of the matches
+    pub language: Option<Language>,
+}
+
+impl Default for QueryNodesOptions {
+    fn default() -> Self {
+        QueryNodesOptions {
+            map_node_options: MapNodeOptions::default(),
+            max_matches: Some(DEFAULT_MAX_QUERY_MATCHES),
+            match_limit: None,
+            deadline: None,
+            byte_range: None,
+            include_siblings: false,
+            include_ancestors: false,
+            max_text_length: Some(DEFAULT_MAX_NODE_TEXT_LENGTH),
+            language: None,
+        }
+    }
+}
+
+// The nodes matching a query
+#[derive(Clone, Debug)]
+pub struct QueryNodes {
+    pub match_nodes: Vec<MatchNode>,
+    // the query had more matches than the maximum, the other matches were ignored
+    pub is_truncated: bool,
+    // the deadline was reached before all the matches were found
+    pub is_timed_out: bool,
+}
 
 // Used internally to pass options to the analysis
-#[derive(Clone, Deserialize, Debug, Serialize, Builder)]
+#[derive(Clone, Default, Deserialize, Debug, Serialize, Builder)]
 pub struct AnalysisOptions {
     pub log_output: bool,
     pub use_debug: bool,
+    // skip the files when more than this ratio of the code is covered by syntax errors,
+    // DEFAULT_MAX_PARSING_ERROR_RATE by default
+    pub max_parsing_error_rate: Option<f64>,
+    // time budget to parse and query a file, in milliseconds
+    pub file_timeout_ms: Option<u64>,
+    // how the columns of the nodes passed to the rules are counted
+    pub position_encoding: PositionEncoding,
+    // force the file to be a test file or production code (e.g. from the configuration), the
+    // kind of file is guessed from its path and its code when not set
+    pub is_test_file: Option<bool>,
+    // how long each rule can execute on a file, in milliseconds. The rules can set their own
+    // timeout.
+    pub rule_timeout_ms: Option<u64>,
+    // how much memory the JavaScript code of each rule can use on a file, in megabytes
+    pub rule_memory_limit_mb: Option<u64>,
+    // the languages of the extensions overridden by the user, to get the language of the files
+    // with the code of another language in their strings (e.g. SQL queries in Python)
+    pub language_overrides: HashMap<String, Language>,
 }
 
 // Used only internally
@@ -25,28 +371,525 @@ pub struct AnalysisContext {
 // This is what you see when you do a .context on a node.
 #[derive(Clone, Deserialize, Debug, Serialize, Builder)]
 pub struct MatchNodeContext {
-    pub code: Option<String>,
+    // the code of the file, shared by all the matches of the file
+    pub code: Option<Arc<str>>,
     pub filename: String,
     pub variables: HashMap<String, String>,
+    // the file has syntax errors (ERROR or MISSING nodes)
+    #[serde(rename = "hasErrors")]
+    pub has_errors: bool,
+    // the comments of the file, in the order of the code, shared by all the matches of the file
+    // and sent once to the rule
+    #[serde(default)]
+    #[builder(default)]
+    pub comments: Arc<Vec<CommentInfo>>,
+    // the language the file was parsed as, for the rules of several languages
+    #[serde(default)]
+    #[builder(default)]
+    pub language: Option<Language>,
+    // the typed arguments of the rule, the variables are free-form strings
+    #[serde(default)]
+    #[builder(default)]
+    pub arguments: HashMap<String, RuleArgumentValue>,
+    // the size and the path of the file, computed once per file
+    #[serde(flatten, default)]
+    #[builder(default)]
+    pub file: FileMetadata,
+    // the rule running on the node, read-only for the rules
+    #[serde(rename = "ruleName", default)]
+    #[builder(default)]
+    pub rule_name: Option<String>,
+    #[serde(rename = "ruleSeverity", default)]
+    #[builder(default)]
+    pub rule_severity: Option<RuleSeverity>,
+    // the version of the shape of the nodes (NODE_SCHEMA_VERSION)
+    #[serde(rename = "schemaVersion", default)]
+    #[builder(default)]
+    pub schema_version: u32,
+}
+
+// The size and the parts of the path of the file being analyzed, so that the rules do not
+// parse the filename. The paths use `/` as separator, also for the files on Windows.
+#[derive(Clone, Default, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub struct FileMetadata {
+    #[serde(rename = "fileSizeBytes")]
+    pub file_size_bytes: usize,
+    // the name of the file without its directory (e.g. `test_api.py`)
+    pub basename: String,
+    // the directory of the file relative to the repository, empty at the root
+    pub directory: String,
+    // the path relative to the repository (e.g. `tests/test_api.py`)
+    #[serde(rename = "relativePath")]
+    pub relative_path: String,
+    // the file contains tests (e.g. `tests/test_api.py`), the rules can skip it
+    #[serde(rename = "isTestFile", default)]
+    pub is_test_file: bool,
+}
+
+impl FileMetadata {
+    pub fn new(filename: &str, file_size_bytes: usize) -> Self {
+        // the `.` components are dropped and the `..` components go up one directory
+        let mut components: Vec<&str> = vec![];
+        for component in filename.split(['/', '\\']) {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    components.pop();
+                }
+                _ => components.push(component),
+            }
+        }
+        let basename = components.pop().unwrap_or_default().to_string();
+        let directory = components.join("/");
+        let relative_path = if directory.is_empty() {
+            basename.clone()
+        } else {
+            format!("{directory}/{basename}")
+        };
+        FileMetadata {
+            file_size_bytes,
+            basename,
+            directory,
+            relative_path,
+            is_test_file: false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub enum CommentKind {
+    // goes until the end of the line (e.g. `// comment` or `# comment`)
+    #[serde(rename = "LINE")]
+    Line,
+    // delimited at both ends (e.g. `/* comment */`), can span several lines
+    #[serde(rename = "BLOCK")]
+    Block,
+}
+
+// A comment of the code, with its delimiters
+#[derive(Clone, Deserialize, Debug, Serialize)]
+pub struct CommentInfo {
+    pub text: String,
+    pub kind: CommentKind,
+    pub start: Position,
+    pub end: Position,
+    #[serde(rename = "startByte")]
+    pub start_byte: usize,
+    #[serde(rename = "endByte")]
+    pub end_byte: usize,
+}
+
+// A kind of node of a grammar, to help writing the queries
+#[derive(Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub struct NodeKindInfo {
+    pub id: u16,
+    pub kind: String,
+    // anonymous nodes (keywords, operators, punctuation) are matched with their text in quotes
+    #[serde(rename = "isNamed")]
+    pub is_named: bool,
+    // hidden nodes are not in the trees, only supertypes can be used in the queries
+    #[serde(rename = "isVisible")]
+    pub is_visible: bool,
+}
+
+// A node without its children, to describe where a captured node is in the tree
+#[derive(Clone, Deserialize, Debug, Serialize)]
+pub struct TreeSitterNodeSummary {
+    pub id: usize,
+    #[serde(rename = "astType")]
+    pub ast_type: String,
+    pub start: Position,
+    pub end: Position,
+    #[serde(rename = "startByte")]
+    pub start_byte: usize,
+    #[serde(rename = "endByte")]
+    pub end_byte: usize,
 }
 
 // The node used to capture data in tree-sitter
 #[derive(Clone, Deserialize, Debug, Serialize, Builder)]
 pub struct TreeSitterNode {
+    // identifier of the node in the tree, two captures of the same node have the same id. The
+    // ids are only stable for one parsing of the code.
+    #[serde(default)]
+    #[builder(default)]
+    pub id: usize,
     #[serde(rename = "astType")]
     pub ast_type: String,
     pub start: Position,
     pub end: Position,
-    #[serde(rename = "fieldName")]
+    // offsets in bytes from the start of the code, the end is exclusive
+    #[serde(rename = "startByte", default)]
+    #[builder(default)]
+    pub start_byte: usize,
+    #[serde(rename = "endByte", default)]
+    #[builder(default)]
+    pub end_byte: usize,
+    // the code of a captured node, not set on their children. The code of the long nodes is
+    // truncated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub text: Option<String>,
+    #[serde(rename = "textTruncated", default, skip_serializing_if = "is_false")]
+    #[builder(default)]
+    pub text_truncated: bool,
+    #[serde(rename = "fieldName", default, skip_serializing_if = "Option::is_none")]
     pub field_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub children: Vec<TreeSitterNode>,
+    // ERROR node, the code could not be parsed
+    #[serde(rename = "isError", default, skip_serializing_if = "is_false")]
+    #[builder(default)]
+    pub is_error: bool,
+    // MISSING node, inserted by the parser to recover from a syntax error
+    #[serde(rename = "isMissing", default, skip_serializing_if = "is_false")]
+    #[builder(default)]
+    pub is_missing: bool,
+    // the children were not mapped because the node is too deep in the tree
+    #[serde(rename = "isTruncated", default, skip_serializing_if = "is_false")]
+    #[builder(default)]
+    pub is_truncated: bool,
+    // the ancestors of a captured node, from its parent to the root of the tree. Only set on
+    // the captured nodes, not on their children.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[builder(default)]
+    pub ancestors: Vec<TreeSitterNodeSummary>,
+    // the kind of the parent of a captured node and the field of the node in its parent (e.g.
+    // `function` for the function of a call), None for the root of the tree. Only set on the
+    // captured nodes, not on their children.
+    #[serde(
+        rename = "parentKind",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[builder(default)]
+    pub parent_kind: Option<String>,
+    #[serde(
+        rename = "parentFieldName",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[builder(default)]
+    pub parent_field_name: Option<String>,
+    // the named siblings of a captured node, when the rule asked for them. Only set on the
+    // captured nodes, not on their children.
+    #[serde(
+        rename = "previousSibling",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[builder(default)]
+    pub previous_sibling: Option<TreeSitterNodeSummary>,
+    #[serde(
+        rename = "nextSibling",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[builder(default)]
+    pub next_sibling: Option<TreeSitterNodeSummary>,
+}
+
+impl TreeSitterNode {
+    // get the kind and the range of the node, without its children
+    pub fn get_summary(&self) -> TreeSitterNodeSummary {
+        TreeSitterNodeSummary {
+            id: self.id,
+            ast_type: self.ast_type.clone(),
+            start: self.start.clone(),
+            end: self.end.clone(),
+            start_byte: self.start_byte,
+            end_byte: self.end_byte,
+        }
+    }
+
+    // get the first child in the field (e.g. the `name` of a function definition)
+    pub fn child_by_field(&self, field_name: &str) -> Option<&TreeSitterNode> {
+        self.children
+            .iter()
+            .find(|c| c.field_name.as_deref() == Some(field_name))
+    }
+
+    // get all the children in the field (e.g. the `argument` of a list of arguments)
+    pub fn children_by_field(&self, field_name: &str) -> Vec<&TreeSitterNode> {
+        self.children
+            .iter()
+            .filter(|c| c.field_name.as_deref() == Some(field_name))
+            .collect()
+    }
+}
+
+// How many nodes a capture of a pattern can capture.
+#[derive(Copy, Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub enum CaptureQuantifier {
+    #[serde(rename = "ONE")]
+    One,
+    // `?`
+    #[serde(rename = "ZERO_OR_ONE")]
+    ZeroOrOne,
+    // `*`
+    #[serde(rename = "ZERO_OR_MORE")]
+    ZeroOrMore,
+    // `+`
+    #[serde(rename = "ONE_OR_MORE")]
+    OneOrMore,
 }
 
 // The node that is then passed to the visit function.
 #[derive(Clone, Debug, Serialize, Builder)]
 pub struct MatchNode {
+    // the captures whose name starts with an underscore (e.g. `@_anchor`) are only used to
+    // match the pattern, they are not in the captures
+    // when a capture has several nodes (e.g. `(identifier)+ @name`), this is the first one in
+    // the code, all the nodes are in captures_list
     pub captures: HashMap<String, TreeSitterNode>,
+    // the nodes of each capture, in the order of the code
     #[serde(rename = "capturesList")]
     pub captures_list: HashMap<String, Vec<TreeSitterNode>>,
+    // values set with the #set! directive in the pattern that matched
+    pub settings: HashMap<String, String>,
+    // quantifier of each capture of the pattern that matched, to know if a capture of
+    // captures_list can have more than one node
+    #[serde(rename = "capturesMeta")]
+    #[builder(default)]
+    pub captures_meta: HashMap<String, CaptureQuantifier>,
+    // index of the pattern of the query that matched, the patterns are counted from 0 in the
+    // order of the query
+    #[serde(rename = "patternIndex")]
+    #[builder(default)]
+    pub pattern_index: usize,
+    // number of patterns in the query
+    #[serde(rename = "patternCount")]
+    #[builder(default)]
+    pub pattern_count: usize,
+    // the range of the whole match, from the first captured node to the end of the last one.
+    // The captures that are not sent to the rule (e.g. `@_anchor`) are in the range.
+    #[serde(rename = "matchStart")]
+    pub match_start: Position,
+    #[serde(rename = "matchEnd")]
+    pub match_end: Position,
     pub context: MatchNodeContext,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_metadata_nested() {
+        let file_metadata = FileMetadata::new("src/tests/test_api.py", 42);
+        assert_eq!(42, file_metadata.file_size_bytes);
+        assert_eq!("test_api.py", file_metadata.basename);
+        assert_eq!("src/tests", file_metadata.directory);
+        assert_eq!("src/tests/test_api.py", file_metadata.relative_path);
+
+        // the paths on Windows use `/` as well
+        let windows_file_metadata = FileMetadata::new("src\\tests\\test_api.py", 42);
+        assert_eq!(file_metadata, windows_file_metadata);
+        assert_eq!(
+            file_metadata,
+            FileMetadata::new("./src/tests/../tests//test_api.py", 42)
+        );
+    }
+
+    #[test]
+    fn test_file_metadata_root() {
+        let file_metadata = FileMetadata::new("setup.py", 0);
+        assert_eq!("setup.py", file_metadata.basename);
+        assert_eq!("", file_metadata.directory);
+        assert_eq!("setup.py", file_metadata.relative_path);
+        assert_eq!(file_metadata, FileMetadata::new(".\\setup.py", 0));
+
+        let value = serde_json::to_value(&file_metadata).unwrap();
+        assert_eq!(0, value["fileSizeBytes"]);
+        assert_eq!("setup.py", value["relativePath"]);
+    }
+
+    // the versions of the nodes all have a note
+    #[test]
+    fn test_node_schema_changes() {
+        let versions: Vec<u32> = NODE_SCHEMA_CHANGES.iter().map(|(v, _)| *v).collect();
+        assert_eq!((1..=NODE_SCHEMA_VERSION).collect::<Vec<u32>>(), versions);
+    }
+
+    fn position(line: u32, col: u32) -> Position {
+        Position {
+            line,
+            col,
+            cell: None,
+        }
+    }
+
+    // Snapshot of the nodes sent to the rules. When this test fails, the shape of the nodes
+    // changed: bump NODE_SCHEMA_VERSION, add a note in NODE_SCHEMA_CHANGES and update the
+    // snapshot and stella.js.
+    #[test]
+    fn test_match_node_snapshot() {
+        let name = TreeSitterNode {
+            id: 2,
+            ast_type: "identifier".to_string(),
+            start: position(1, 5),
+            end: position(1, 8),
+            start_byte: 4,
+            end_byte: 7,
+            text: Some("foo".to_string()),
+            text_truncated: false,
+            field_name: Some("name".to_string()),
+            children: vec![],
+            is_error: false,
+            is_missing: false,
+            is_truncated: false,
+            ancestors: vec![TreeSitterNodeSummary {
+                id: 1,
+                ast_type: "function_definition".to_string(),
+                start: position(1, 1),
+                end: position(2, 9),
+                start_byte: 0,
+                end_byte: 23,
+            }],
+            parent_kind: Some("function_definition".to_string()),
+            parent_field_name: Some("name".to_string()),
+            previous_sibling: None,
+            next_sibling: None,
+        };
+        let body = TreeSitterNode {
+            id: 3,
+            ast_type: "block".to_string(),
+            start: position(2, 5),
+            end: position(2, 9),
+            start_byte: 19,
+            end_byte: 23,
+            text: None,
+            text_truncated: false,
+            field_name: Some("body".to_string()),
+            children: vec![TreeSitterNode {
+                id: 4,
+                ast_type: "pass_statement".to_string(),
+                start: position(2, 5),
+                end: position(2, 9),
+                start_byte: 19,
+                end_byte: 23,
+                text: None,
+                text_truncated: false,
+                field_name: None,
+                children: vec![],
+                is_error: false,
+                is_missing: false,
+                is_truncated: true,
+                ancestors: vec![],
+                parent_kind: None,
+                parent_field_name: None,
+                previous_sibling: None,
+                next_sibling: None,
+            }],
+            is_error: false,
+            is_missing: false,
+            is_truncated: false,
+            ancestors: vec![],
+            parent_kind: None,
+            parent_field_name: None,
+            previous_sibling: None,
+            next_sibling: None,
+        };
+        let match_node = MatchNode {
+            captures: HashMap::from([("name".to_string(), name.clone())]),
+            captures_list: HashMap::from([("body".to_string(), vec![body])]),
+            settings: HashMap::from([("kind".to_string(), "function".to_string())]),
+            captures_meta: HashMap::from([("body".to_string(), CaptureQuantifier::OneOrMore)]),
+            pattern_index: 0,
+            pattern_count: 1,
+            match_start: position(1, 5),
+            match_end: position(2, 9),
+            context: MatchNodeContext {
+                code: Some(Arc::from("def foo():\n    pass")),
+                filename: "src/foo.py".to_string(),
+                variables: HashMap::from([("max".to_string(), "10".to_string())]),
+                has_errors: false,
+                comments: Arc::default(),
+                language: Some(Language::Python),
+                arguments: HashMap::from([(
+                    "strict".to_string(),
+                    RuleArgumentValue::Boolean(true),
+                )]),
+                file: FileMetadata::new("src/foo.py", 19),
+                rule_name: Some("python-best-practices/foo".to_string()),
+                rule_severity: Some(RuleSeverity::Warning),
+                schema_version: NODE_SCHEMA_VERSION,
+            },
+        };
+
+        let expected = serde_json::json!({
+            "captures": {
+                "name": {
+                    "id": 2,
+                    "astType": "identifier",
+                    "start": {"line": 1, "col": 5},
+                    "end": {"line": 1, "col": 8},
+                    "startByte": 4,
+                    "endByte": 7,
+                    "text": "foo",
+                    "fieldName": "name",
+                    "ancestors": [{
+                        "id": 1,
+                        "astType": "function_definition",
+                        "start": {"line": 1, "col": 1},
+                        "end": {"line": 2, "col": 9},
+                        "startByte": 0,
+                        "endByte": 23
+                    }],
+                    "parentKind": "function_definition",
+                    "parentFieldName": "name"
+                }
+            },
+            "capturesList": {
+                "body": [{
+                    "id": 3,
+                    "astType": "block",
+                    "start": {"line": 2, "col": 5},
+                    "end": {"line": 2, "col": 9},
+                    "startByte": 19,
+                    "endByte": 23,
+                    "fieldName": "body",
+                    "children": [{
+                        "id": 4,
+                        "astType": "pass_statement",
+                        "start": {"line": 2, "col": 5},
+                        "end": {"line": 2, "col": 9},
+                        "startByte": 19,
+                        "endByte": 23,
+                        "isTruncated": true
+                    }]
+                }]
+            },
+            "settings": {"kind": "function"},
+            "capturesMeta": {"body": "ONE_OR_MORE"},
+            "patternIndex": 0,
+            "patternCount": 1,
+            "matchStart": {"line": 1, "col": 5},
+            "matchEnd": {"line": 2, "col": 9},
+            "context": {
+                "code": "def foo():\n    pass",
+                "filename": "src/foo.py",
+                "variables": {"max": "10"},
+                "hasErrors": false,
+                "comments": [],
+                "language": "PYTHON",
+                "arguments": {"strict": true},
+                "fileSizeBytes": 19,
+                "basename": "foo.py",
+                "directory": "src",
+                "relativePath": "src/foo.py",
+                "isTestFile": false,
+                "ruleName": "python-best-practices/foo",
+                "ruleSeverity": "WARNING",
+                "schemaVersion": 4
+            }
+        });
+        assert_eq!(expected, serde_json::to_value(&match_node).unwrap());
+
+        // the nodes are read back with the default values of the fields that are not sent
+        let deserialized: TreeSitterNode =
+            serde_json::from_value(expected["captures"]["name"].clone()).unwrap();
+        assert_eq!(name.ancestors.len(), deserialized.ancestors.len());
+        assert!(deserialized.children.is_empty());
+    }
+}
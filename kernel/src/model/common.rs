@@ -1,6 +1,11 @@
+use anyhow::anyhow;
 use derive_builder::Builder;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
 
 #[derive(Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
 pub enum OutputFormat {
@@ -20,67 +25,81 @@ impl fmt::Display for OutputFormat {
     }
 }
 
-#[derive(Copy, Clone, Deserialize, Debug, Serialize, Eq, Hash, PartialEq)]
-pub enum Language {
-    #[serde(rename = "CSHARP")]
-    Csharp,
-    #[serde(rename = "DOCKERFILE")]
-    Dockerfile,
-    #[serde(rename = "GO")]
-    Go,
-    #[serde(rename = "JAVA")]
-    Java,
-    #[serde(rename = "JAVASCRIPT")]
-    JavaScript,
-    #[serde(rename = "JSON")]
-    Json,
-    #[serde(rename = "KOTLIN")]
-    Kotlin,
-    #[serde(rename = "PYTHON")]
-    Python,
-    #[serde(rename = "RUST")]
-    Rust,
-    #[serde(rename = "SWIFT")]
-    Swift,
-    #[serde(rename = "TERRAFORM")]
-    Terraform,
-    #[serde(rename = "TYPESCRIPT")]
-    TypeScript,
-    #[serde(rename = "YAML")]
-    Yaml,
-}
-
-#[allow(dead_code)]
-pub static ALL_LANGUAGES: &[Language] = &[
-    Language::Csharp,
-    Language::Dockerfile,
-    Language::Go,
-    Language::Java,
-    Language::JavaScript,
-    Language::Json,
-    Language::Kotlin,
-    Language::Python,
-    Language::Rust,
-    Language::Swift,
-    Language::TypeScript,
-    Language::Terraform,
-    Language::Yaml,
-];
+// Declare the languages and the list of all the languages from the same names so that a language
+// cannot be added to the enum and not to the list.
+macro_rules! languages {
+    ($($language:ident => $name:tt,)*) => {
+        // The languages are serialized with their uppercase name (e.g. `TYPESCRIPT`) and
+        // deserialized from any of their names or aliases, ignoring the case (e.g. `TypeScript`
+        // or `ts`).
+        #[derive(Copy, Clone, Debug, Serialize, Eq, Hash, PartialEq)]
+        pub enum Language {
+            $(
+                #[serde(rename = $name)]
+                $language,
+            )*
+        }
+
+        // all the languages, in the order of the enum
+        #[allow(dead_code)]
+        pub static ALL_LANGUAGES: &[Language] = &[$(Language::$language,)*];
+    };
+}
+
+languages! {
+    C => "C",
+    Cpp => "CPP",
+    Csharp => "CSHARP",
+    Dart => "DART",
+    Dockerfile => "DOCKERFILE",
+    Elixir => "ELIXIR",
+    Go => "GO",
+    Groovy => "GROOVY",
+    Java => "JAVA",
+    JavaScript => "JAVASCRIPT",
+    Json => "JSON",
+    Kotlin => "KOTLIN",
+    Lua => "LUA",
+    Php => "PHP",
+    Python => "PYTHON",
+    Ruby => "RUBY",
+    Rust => "RUST",
+    Scala => "SCALA",
+    Shell => "SHELL",
+    Sql => "SQL",
+    Swift => "SWIFT",
+    Terraform => "TERRAFORM",
+    Toml => "TOML",
+    TypeScript => "TYPESCRIPT",
+    Yaml => "YAML",
+}
 
 impl fmt::Display for Language {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match self {
+            Self::C => "c",
+            Self::Cpp => "c++",
             Self::Csharp => "c#",
+            Self::Dart => "dart",
             Self::Dockerfile => "dockerfile",
+            Self::Elixir => "elixir",
             Self::Go => "go",
+            Self::Groovy => "groovy",
             Self::Java => "java",
             Self::JavaScript => "javascript",
             Self::Json => "json",
             Self::Kotlin => "kotlin",
+            Self::Lua => "lua",
+            Self::Php => "php",
             Self::Python => "python",
+            Self::Ruby => "ruby",
             Self::Rust => "rust",
+            Self::Scala => "scala",
+            Self::Shell => "shell",
+            Self::Sql => "sql",
             Self::Swift => "swift",
             Self::Terraform => "terraform",
+            Self::Toml => "toml",
             Self::TypeScript => "typescript",
             Self::Yaml => "yaml",
         };
@@ -88,10 +107,181 @@ impl fmt::Display for Language {
     }
 }
 
-#[derive(Deserialize, Debug, Serialize, Clone, Builder)]
+// Extensions of the files for each language. Some extensions (e.g. `h`, `vue`) are used
+// by several languages.
+pub static FILE_EXTENSIONS_PER_LANGUAGE_LIST: &[(Language, &[&str])] = &[
+    (Language::C, &["c", "h"]),
+    (Language::Cpp, &["cpp", "cc", "cxx", "hpp", "hh", "h"]),
+    (Language::Csharp, &["cs"]),
+    (Language::Dart, &["dart"]),
+    (Language::Dockerfile, &["docker", "dockerfile"]),
+    (Language::Elixir, &["ex", "exs"]),
+    (Language::Go, &["go"]),
+    (Language::Groovy, &["gradle", "groovy"]),
+    (Language::Java, &["java"]),
+    (
+        Language::JavaScript,
+        &["js", "jsx", "htm", "html", "svelte", "vue"],
+    ),
+    (Language::Json, &["json", "jsonc"]),
+    (Language::Kotlin, &["kt", "kts"]),
+    (Language::Lua, &["lua"]),
+    (Language::Php, &["php"]),
+    (Language::Python, &["ipynb", "py", "py3"]),
+    (Language::Ruby, &["rb", "rake"]),
+    (Language::Rust, &["rs"]),
+    (Language::Scala, &["scala", "sc"]),
+    (Language::Shell, &["sh", "bash"]),
+    (Language::Sql, &["sql"]),
+    (Language::Swift, &["swift"]),
+    (Language::Terraform, &["tf", "hcl"]),
+    (Language::Toml, &["toml"]),
+    (
+        Language::TypeScript,
+        &["ts", "tsx", "mts", "cts", "svelte", "vue"],
+    ),
+    (Language::Yaml, &["yml", "yaml"]),
+];
+
+// Interpreters found in the shebang of scripts (e.g. `#!/bin/bash` or
+// `#!/usr/bin/env python3`). Versions are removed from the interpreter before matching.
+static SHEBANG_INTERPRETERS_PER_LANGUAGE_LIST: &[(Language, &[&str])] = &[
+    (Language::JavaScript, &["node", "nodejs"]),
+    (Language::Lua, &["lua", "luajit"]),
+    (Language::Php, &["php"]),
+    (Language::Python, &["python", "pypy"]),
+    (Language::Ruby, &["ruby"]),
+    (Language::Shell, &["bash", "dash", "ksh", "sh", "zsh"]),
+    (Language::TypeScript, &["ts-node"]),
+];
+
+// get the interpreter from the shebang line of a file. When the shebang uses `env`
+// (e.g. `#!/usr/bin/env bash`), the interpreter is the first argument passed to `env`.
+fn get_shebang_interpreter(line: &str) -> Option<String> {
+    let mut parts = line.strip_prefix("#!")?.split_whitespace();
+    let program = Path::new(parts.next()?).file_name()?.to_str()?;
+    if program == "env" {
+        parts
+            .find(|p| !p.starts_with('-') && !p.contains('='))
+            .map(ToString::to_string)
+    } else {
+        Some(program.to_string())
+    }
+}
+
+impl FromStr for Language {
+    type Err = anyhow::Error;
+
+    // parse the name or an alias of a language, ignoring the case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        Language::all()
+            .find(|l| l.aliases().contains(&name.as_str()))
+            .ok_or_else(|| anyhow!("unknown language {}", s))
+    }
+}
+
+impl<'de> Deserialize<'de> for Language {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Language {
+    // all the languages, in the order of their names
+    pub fn all() -> impl Iterator<Item = Language> {
+        ALL_LANGUAGES.iter().copied()
+    }
+
+    // The names of the language in lowercase: its name, the name used in the serialization and
+    // the common abbreviations (e.g. `ts`) and extensions used as names (e.g. in the code blocks
+    // of the Markdown documents).
+    pub fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Self::C => &["c"],
+            Self::Cpp => &["c++", "cpp", "cc"],
+            Self::Csharp => &["c#", "csharp", "cs"],
+            Self::Dart => &["dart"],
+            Self::Dockerfile => &["dockerfile", "docker"],
+            Self::Elixir => &["elixir", "ex", "exs"],
+            Self::Go => &["go", "golang"],
+            Self::Groovy => &["groovy", "gradle"],
+            Self::Java => &["java"],
+            Self::JavaScript => &["javascript", "js", "jsx", "mjs"],
+            Self::Json => &["json", "jsonc"],
+            Self::Kotlin => &["kotlin", "kt", "kts"],
+            Self::Lua => &["lua"],
+            Self::Php => &["php"],
+            Self::Python => &["python", "py", "py3", "python3"],
+            Self::Ruby => &["ruby", "rb"],
+            Self::Rust => &["rust", "rs"],
+            Self::Scala => &["scala"],
+            Self::Shell => &["shell", "sh", "bash", "zsh"],
+            Self::Sql => &["sql"],
+            Self::Swift => &["swift"],
+            Self::Terraform => &["terraform", "tf", "hcl"],
+            Self::Toml => &["toml"],
+            Self::TypeScript => &["typescript", "ts", "tsx"],
+            Self::Yaml => &["yaml", "yml"],
+        }
+    }
+
+    // Get a language from its name or one of its aliases (e.g. `python`, `py`), ignoring the
+    // case.
+    pub fn from_name(name: &str) -> Option<Language> {
+        name.parse().ok()
+    }
+
+    // Get the language of a file from its extension. The overrides (extension -> language)
+    // provided by the user are used before the default extensions.
+    pub fn from_path(path: &Path, overrides: &HashMap<String, Language>) -> Option<Language> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        if let Some(language) = overrides.get(&extension) {
+            return Some(*language);
+        }
+
+        FILE_EXTENSIONS_PER_LANGUAGE_LIST
+            .iter()
+            .find(|(_, extensions)| extensions.contains(&extension.as_str()))
+            .map(|(language, _)| *language)
+    }
+
+    // Get the language of a script from the shebang on its first line.
+    pub fn from_shebang(code: &str) -> Option<Language> {
+        let interpreter = get_shebang_interpreter(code.lines().next()?.trim_end())?;
+        // python3.11 -> python
+        let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+        SHEBANG_INTERPRETERS_PER_LANGUAGE_LIST
+            .iter()
+            .find(|(_, interpreters)| interpreters.contains(&interpreter))
+            .map(|(language, _)| *language)
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone, Builder, Eq, Hash, PartialEq)]
 pub struct Position {
     pub line: u32,
     pub col: u32,
+    // index of the cell (starting at 0) when the position is in a notebook
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub cell: Option<u32>,
+}
+
+impl Position {
+    // a position in a file that is not a notebook
+    pub fn new(line: u32, col: u32) -> Self {
+        Position {
+            line,
+            col,
+            cell: None,
+        }
+    }
 }
 
 impl fmt::Display for Position {
@@ -99,3 +289,342 @@ impl fmt::Display for Position {
         write!(f, "position (line: {}, col: {})", self.line, self.col)
     }
 }
+
+// The positions are ordered by cell, line and column: the lines of a notebook start at 1 in
+// each cell.
+impl Ord for Position {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.cell, self.line, self.col).cmp(&(other.cell, other.line, other.col))
+    }
+}
+
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// A range of the code from `start` (inclusive) to `end` (exclusive), like the ranges of the
+// violations and edits. A range with the same start and end is empty: it contains no position
+// but it is still at a place of the code (e.g. an edit that adds text).
+#[derive(Deserialize, Debug, Serialize, Clone, Eq, Hash, PartialEq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    pub fn new(start: Position, end: Position) -> Self {
+        Range { start, end }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    // Check if the position is in the range. The end of the range is not in the range: the
+    // ranges that end where another one starts do not share any position.
+    pub fn contains(&self, position: &Position) -> bool {
+        self.start <= *position && *position < self.end
+    }
+
+    // Check if the ranges share some code. The ranges that end where the other one starts do not
+    // overlap. An empty range overlaps the ranges that contain its position after their start:
+    // adding text inside the code replaced by an edit is a conflict, adding it before is not.
+    pub fn overlaps(&self, other: &Range) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    // Get the code shared by the ranges, if they overlap.
+    pub fn intersection(&self, other: &Range) -> Option<Range> {
+        if !self.overlaps(other) {
+            return None;
+        }
+        Some(Range {
+            start: self.start.clone().max(other.start.clone()),
+            end: self.end.clone().min(other.end.clone()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_shebang_interpreter() {
+        assert_eq!(
+            Some("bash".to_string()),
+            get_shebang_interpreter("#!/bin/bash")
+        );
+        assert_eq!(
+            Some("sh".to_string()),
+            get_shebang_interpreter("#!/usr/bin/env sh")
+        );
+        assert_eq!(
+            Some("bash".to_string()),
+            get_shebang_interpreter("#! /usr/bin/env -S LC_ALL=C bash -e")
+        );
+        assert_eq!(None, get_shebang_interpreter("# just a comment"));
+        assert_eq!(None, get_shebang_interpreter("#!/usr/bin/env"));
+    }
+
+    #[test]
+    fn test_language_from_name() {
+        assert_eq!(Some(Language::Python), Language::from_name("python"));
+        assert_eq!(
+            Some(Language::JavaScript),
+            Language::from_name("JavaScript")
+        );
+        assert_eq!(Some(Language::Cpp), Language::from_name("c++"));
+        assert_eq!(None, Language::from_name("starlark"));
+    }
+
+    #[test]
+    fn test_language_from_str() {
+        assert_eq!(Language::TypeScript, "Typescript".parse().unwrap());
+        assert_eq!(Language::TypeScript, "ts".parse().unwrap());
+        assert_eq!(Language::JavaScript, " JS ".parse().unwrap());
+        assert_eq!(Language::Python, "py".parse().unwrap());
+        assert_eq!(Language::Rust, "RS".parse().unwrap());
+        assert_eq!(Language::Csharp, "C#".parse().unwrap());
+        assert_eq!(Some(Language::Python), Language::from_name("PY"));
+        assert_eq!(
+            "unknown language starlark",
+            "starlark".parse::<Language>().unwrap_err().to_string()
+        );
+    }
+
+    // the languages are read from any of their names, they are always written in uppercase
+    #[test]
+    fn test_language_serde() {
+        for name in ["\"TYPESCRIPT\"", "\"Typescript\"", "\"ts\""] {
+            let language: Language = serde_json::from_str(name).unwrap();
+            assert_eq!(Language::TypeScript, language);
+        }
+        assert!(serde_json::from_str::<Language>("\"starlark\"").is_err());
+        assert_eq!(
+            "\"TYPESCRIPT\"",
+            serde_json::to_string(&Language::TypeScript).unwrap()
+        );
+        let languages: HashMap<Language, u32> = serde_json::from_str(r#"{"py": 1}"#).unwrap();
+        assert_eq!(Some(&1), languages.get(&Language::Python));
+    }
+
+    // all the languages have aliases with their names, adding a language requires adding its
+    // aliases
+    #[test]
+    fn test_language_aliases() {
+        let languages: Vec<Language> = Language::all().collect();
+        let mut all_aliases: Vec<&str> = vec![];
+        for language in &languages {
+            assert_eq!(1, languages.iter().filter(|l| *l == language).count());
+            // each language has the extensions of its files
+            assert!(FILE_EXTENSIONS_PER_LANGUAGE_LIST
+                .iter()
+                .any(|(l, _)| l == language));
+            let serialized_name = serde_json::to_value(language).unwrap();
+            let serialized_name = serialized_name.as_str().unwrap().to_lowercase();
+            assert!(language.aliases().contains(&serialized_name.as_str()));
+            assert!(language.aliases().contains(&language.to_string().as_str()));
+            for alias in language.aliases() {
+                assert_eq!(alias.to_lowercase(), *alias);
+                assert_eq!(*language, alias.parse().unwrap());
+                assert_eq!(*language, alias.to_uppercase().parse().unwrap());
+                all_aliases.push(alias);
+            }
+            assert_eq!(*language, language.to_string().parse().unwrap());
+        }
+        // the aliases are not shared by several languages
+        let count = all_aliases.len();
+        all_aliases.sort();
+        all_aliases.dedup();
+        assert_eq!(count, all_aliases.len());
+    }
+
+    #[test]
+    fn test_language_from_path() {
+        let no_overrides = HashMap::new();
+        assert_eq!(
+            Some(Language::Python),
+            Language::from_path(Path::new("src/main.py"), &no_overrides)
+        );
+        assert_eq!(
+            Some(Language::TypeScript),
+            Language::from_path(Path::new("src/index.TS"), &no_overrides)
+        );
+        assert_eq!(
+            None,
+            Language::from_path(Path::new("rules.bzl"), &no_overrides)
+        );
+        assert_eq!(
+            None,
+            Language::from_path(Path::new("Makefile"), &no_overrides)
+        );
+
+        let overrides = HashMap::from([
+            ("bzl".to_string(), Language::Python),
+            ("js".to_string(), Language::TypeScript),
+        ]);
+        assert_eq!(
+            Some(Language::Python),
+            Language::from_path(Path::new("rules.bzl"), &overrides)
+        );
+        assert_eq!(
+            Some(Language::TypeScript),
+            Language::from_path(Path::new("index.js"), &overrides)
+        );
+    }
+
+    #[test]
+    fn test_language_from_shebang() {
+        assert_eq!(
+            Some(Language::Python),
+            Language::from_shebang("#!/usr/bin/env python3\nimport os\n")
+        );
+        assert_eq!(
+            Some(Language::Python),
+            Language::from_shebang("#!/usr/local/bin/python3.11\n")
+        );
+        assert_eq!(
+            Some(Language::JavaScript),
+            Language::from_shebang("#!/usr/bin/env node\nconsole.log(1);\n")
+        );
+        assert_eq!(
+            Some(Language::Shell),
+            Language::from_shebang("#!/bin/bash\necho hello\n")
+        );
+        assert_eq!(
+            Some(Language::Ruby),
+            Language::from_shebang("#!/usr/bin/ruby -w\r\nputs 1\r\n")
+        );
+        assert_eq!(None, Language::from_shebang("#!/usr/bin/env perl\n"));
+        assert_eq!(None, Language::from_shebang("import os\n#!/bin/bash\n"));
+        assert_eq!(None, Language::from_shebang(""));
+    }
+
+    fn position(line: u32, col: u32) -> Position {
+        Position {
+            line,
+            col,
+            cell: None,
+        }
+    }
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range::new(position(start.0, start.1), position(end.0, end.1))
+    }
+
+    #[test]
+    fn test_position_ordering() {
+        assert!(position(1, 10) < position(2, 1));
+        assert!(position(2, 1) < position(2, 2));
+        assert_eq!(Ordering::Equal, position(3, 4).cmp(&position(3, 4)));
+        assert_eq!(
+            Some(position(1, 2)),
+            [position(2, 1), position(1, 2)].into_iter().min()
+        );
+
+        // the positions in notebooks are ordered by cell first
+        let in_cell = |cell, line, col| Position {
+            line,
+            col,
+            cell: Some(cell),
+        };
+        assert!(in_cell(0, 10, 1) < in_cell(1, 1, 1));
+        assert!(in_cell(1, 1, 1) < in_cell(1, 1, 2));
+    }
+
+    #[test]
+    fn test_range_contains() {
+        let r = range((1, 5), (3, 2));
+        assert!(r.contains(&position(1, 5)));
+        assert!(r.contains(&position(2, 100)));
+        assert!(r.contains(&position(3, 1)));
+        assert!(!r.contains(&position(3, 2)));
+        assert!(!r.contains(&position(1, 4)));
+        assert!(!r.contains(&position(4, 1)));
+
+        // an empty range contains nothing, even its own position
+        let empty = range((2, 3), (2, 3));
+        assert!(empty.is_empty());
+        assert!(!empty.contains(&position(2, 3)));
+    }
+
+    #[test]
+    fn test_range_overlaps() {
+        // ranges ending where the other one starts
+        assert!(!range((1, 1), (1, 5)).overlaps(&range((1, 5), (1, 8))));
+        assert!(!range((1, 1), (2, 1)).overlaps(&range((2, 1), (3, 1))));
+        assert!(range((1, 1), (1, 6)).overlaps(&range((1, 5), (1, 8))));
+
+        // multi-line ranges
+        assert!(range((1, 10), (4, 1)).overlaps(&range((2, 1), (2, 3))));
+        assert!(range((1, 10), (4, 1)).overlaps(&range((3, 50), (6, 1))));
+        assert!(!range((1, 10), (4, 1)).overlaps(&range((4, 1), (6, 1))));
+
+        // empty ranges overlap only the ranges that contain them after their start
+        assert!(range((1, 1), (1, 5)).overlaps(&range((1, 3), (1, 3))));
+        assert!(!range((1, 1), (1, 5)).overlaps(&range((1, 1), (1, 1))));
+        assert!(!range((1, 1), (1, 5)).overlaps(&range((1, 5), (1, 5))));
+        assert!(!range((1, 3), (1, 3)).overlaps(&range((1, 3), (1, 3))));
+    }
+
+    #[test]
+    fn test_range_intersection() {
+        assert_eq!(
+            Some(range((2, 1), (3, 4))),
+            range((1, 1), (3, 4)).intersection(&range((2, 1), (5, 1)))
+        );
+        assert_eq!(
+            Some(range((2, 1), (2, 3))),
+            range((1, 10), (4, 1)).intersection(&range((2, 1), (2, 3)))
+        );
+        assert_eq!(
+            None,
+            range((1, 1), (1, 5)).intersection(&range((1, 5), (1, 8)))
+        );
+        assert_eq!(
+            Some(range((1, 3), (1, 3))),
+            range((1, 1), (1, 5)).intersection(&range((1, 3), (1, 3)))
+        );
+    }
+
+    // check the properties of the ranges on all the ranges of a small code
+    #[test]
+    fn test_range_properties() {
+        let positions: Vec<Position> = (1..=3)
+            .flat_map(|line| (1..=3).map(move |col| position(line, col)))
+            .collect();
+        let ranges: Vec<Range> = positions
+            .iter()
+            .flat_map(|start| {
+                positions
+                    .iter()
+                    .filter(move |end| *end >= start)
+                    .map(move |end| Range::new(start.clone(), end.clone()))
+            })
+            .collect();
+
+        for a in &ranges {
+            assert_eq!(a.is_empty(), !positions.iter().any(|p| a.contains(p)));
+            for b in &ranges {
+                assert_eq!(a.overlaps(b), b.overlaps(a));
+                assert_eq!(a.intersection(b), b.intersection(a));
+                let shared = positions.iter().any(|p| a.contains(p) && b.contains(p));
+                if shared {
+                    assert!(a.overlaps(b));
+                }
+                match a.intersection(b) {
+                    Some(i) => {
+                        assert!(a.overlaps(b));
+                        for p in &positions {
+                            assert_eq!(i.contains(p), a.contains(p) && b.contains(p));
+                        }
+                    }
+                    None => assert!(!a.overlaps(b) && !shared),
+                }
+            }
+        }
+    }
+}
@@ -10,20 +10,23 @@ use serde::{Deserialize, Serialize};
 use sha2::Digest;
 use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
 
 /// In the RuleCategory, we keep unknown. Old rules keep putting
 /// whatever they want as category. As a matter of fact, old rules that
 /// use old values (e.g. DEPLOYMENT) will fail deserialization. We then match
 /// them on the `Unknown` value.
 ///
-/// The `Unknown` value is never exposed tho because we always rewrite the
-/// category of all violations by the categories of the rules they come from.
+/// The `Unknown` value is never exposed tho because the rules with an unknown
+/// category are rejected when they are loaded (see `Rule::check_metadata`).
 #[derive(Copy, Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
 pub enum RuleCategory {
     #[serde(rename = "BEST_PRACTICES")]
     BestPractices,
     #[serde(rename = "CODE_STYLE")]
     CodeStyle,
+    #[serde(rename = "DOCUMENTATION")]
+    Documentation,
     #[serde(rename = "ERROR_PRONE")]
     ErrorProne,
     #[serde(rename = "PERFORMANCE")]
@@ -35,11 +38,24 @@ pub enum RuleCategory {
     Unknown, // kept only for backward compatibility
 }
 
+impl RuleCategory {
+    // the categories a rule can have
+    pub const ALL: [RuleCategory; 6] = [
+        Self::BestPractices,
+        Self::CodeStyle,
+        Self::Documentation,
+        Self::ErrorProne,
+        Self::Performance,
+        Self::Security,
+    ];
+}
+
 impl fmt::Display for RuleCategory {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::BestPractices => write!(f, "best_practices"),
             Self::CodeStyle => write!(f, "code_style"),
+            Self::Documentation => write!(f, "documentation"),
             Self::ErrorProne => write!(f, "error_prone"),
             Self::Performance => write!(f, "performance"),
             Self::Security => write!(f, "security"),
@@ -48,6 +64,19 @@ impl fmt::Display for RuleCategory {
     }
 }
 
+impl FromStr for RuleCategory {
+    type Err = anyhow::Error;
+
+    // parse the name of a category (e.g. `BEST_PRACTICES`), ignoring the case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|c| c.to_string() == name)
+            .ok_or_else(|| anyhow!("unknown category {}", s))
+    }
+}
+
 #[derive(Copy, Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
 pub enum RuleSeverity {
     #[serde(rename = "ERROR")]
@@ -71,6 +100,30 @@ impl fmt::Display for RuleSeverity {
     }
 }
 
+impl RuleSeverity {
+    // the severities, from the most severe
+    pub const ALL: [RuleSeverity; 4] = [Self::Error, Self::Warning, Self::Notice, Self::None];
+
+    // check if the severity is the same or more severe than the other one
+    pub fn is_at_least(&self, other: RuleSeverity) -> bool {
+        let rank = |s: RuleSeverity| Self::ALL.iter().position(|a| *a == s);
+        rank(*self) <= rank(other)
+    }
+}
+
+impl FromStr for RuleSeverity {
+    type Err = anyhow::Error;
+
+    // parse the name of a severity (e.g. `WARNING`), ignoring the case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|v| v.to_string() == name)
+            .ok_or_else(|| anyhow!("unknown severity {}", s))
+    }
+}
+
 #[derive(Copy, Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
 pub enum RuleType {
     #[serde(rename = "AST_CHECK")]
@@ -115,6 +168,75 @@ pub enum EntityChecked {
     Unknown,
 }
 
+#[derive(Copy, Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub enum RuleArgumentType {
+    #[serde(rename = "STRING")]
+    String,
+    #[serde(rename = "INTEGER")]
+    Integer,
+    #[serde(rename = "BOOLEAN")]
+    Boolean,
+    #[serde(rename = "STRING_LIST")]
+    StringList,
+}
+
+impl RuleArgumentType {
+    // the name of the type with its article, for the error messages (e.g. "an integer")
+    pub fn with_article(&self) -> String {
+        match self {
+            Self::Integer => format!("an {self}"),
+            _ => format!("a {self}"),
+        }
+    }
+}
+
+impl fmt::Display for RuleArgumentType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Boolean => "boolean",
+            Self::StringList => "string list",
+        };
+        write!(f, "{s}")
+    }
+}
+
+// The value of an argument, serialized as a native value (e.g. `50`, `["pickle", "marshal"]`)
+// so that the rules get numbers, booleans and arrays.
+#[derive(Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum RuleArgumentValue {
+    Boolean(bool),
+    Integer(i64),
+    String(String),
+    StringList(Vec<String>),
+}
+
+impl RuleArgumentValue {
+    pub fn get_type(&self) -> RuleArgumentType {
+        match self {
+            Self::Boolean(_) => RuleArgumentType::Boolean,
+            Self::Integer(_) => RuleArgumentType::Integer,
+            Self::String(_) => RuleArgumentType::String,
+            Self::StringList(_) => RuleArgumentType::StringList,
+        }
+    }
+}
+
+// An argument of a rule (e.g. the maximum length of a function), with its default value. The
+// value can be changed in the configuration.
+#[derive(Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub struct RuleArgument {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub argument_type: RuleArgumentType,
+    pub default: RuleArgumentValue,
+    // what the argument changes in the rule, for the users who set it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
 // This is the rule as this is exposed to the datadog API or JSON files.
 #[derive(Clone, Deserialize, Debug, Serialize, Builder)]
 pub struct Rule {
@@ -137,6 +259,46 @@ pub struct Rule {
     pub tree_sitter_query_base64: Option<String>,
     pub variables: HashMap<String, String>,
     pub tests: Vec<RuleTest>,
+    // map the anonymous nodes (keywords, operators, punctuation) of the captures
+    #[serde(default)]
+    #[builder(default)]
+    pub include_unnamed_nodes: bool,
+    // maximum number of nodes mapped for each capture, the other nodes are not sent to the rule
+    #[serde(default)]
+    #[builder(default)]
+    pub max_capture_nodes: Option<usize>,
+    // send the previous and next named siblings of the captured nodes to the rule
+    #[serde(default)]
+    #[builder(default)]
+    pub include_siblings: bool,
+    // send the ancestors of the captured nodes to the rule, the rules can also get them with
+    // getAncestors()
+    #[serde(default)]
+    #[builder(default)]
+    pub include_ancestors: bool,
+    // visit each captured node alone instead of the matches of the query, lighter for the
+    // queries with many matches
+    #[serde(default)]
+    #[builder(default)]
+    pub capture_mode: bool,
+    // the typed arguments of the rule and the values set for them in the configuration, the
+    // other arguments have their default value
+    #[serde(default)]
+    #[builder(default)]
+    pub arguments: Vec<RuleArgument>,
+    #[serde(default)]
+    #[builder(default)]
+    pub argument_values: HashMap<String, RuleArgumentValue>,
+    // how long the rule can execute on a file, in milliseconds. Overrides the timeout of the
+    // analysis.
+    #[serde(default)]
+    #[builder(default)]
+    pub timeout_ms: Option<u64>,
+    // maximum number of in-progress matches of the query on a file, not limited when not set.
+    // The violations are reported with ERROR_RULE_MATCHES_TRUNCATED when it is reached.
+    #[serde(default)]
+    #[builder(default)]
+    pub query_match_limit: Option<u32>,
 }
 
 // This structure is used internally to handle rules.
@@ -153,6 +315,46 @@ pub struct RuleInternal {
     pub code: String,
     pub tree_sitter_query: Option<String>,
     pub variables: HashMap<String, String>,
+    pub include_unnamed_nodes: bool,
+    pub max_capture_nodes: Option<usize>,
+    pub include_siblings: bool,
+    pub include_ancestors: bool,
+    pub capture_mode: bool,
+    // the value of each argument of the rule
+    pub arguments: HashMap<String, RuleArgumentValue>,
+    // the CWE identifiers of the rule (e.g. "79")
+    pub cwe: Vec<String>,
+    // how long the rule can execute on a file, the timeout of the analysis when not set
+    pub timeout_ms: Option<u64>,
+    // maximum number of in-progress matches of the query on a file
+    pub query_match_limit: Option<u32>,
+}
+
+// the rules of the tests only set the fields they use
+#[cfg(test)]
+impl Default for RuleInternal {
+    fn default() -> Self {
+        RuleInternal {
+            name: String::new(),
+            short_description: None,
+            description: None,
+            category: RuleCategory::BestPractices,
+            severity: RuleSeverity::Notice,
+            language: Language::Python,
+            code: String::new(),
+            tree_sitter_query: None,
+            variables: HashMap::new(),
+            include_unnamed_nodes: false,
+            max_capture_nodes: None,
+            include_siblings: false,
+            include_ancestors: false,
+            capture_mode: false,
+            arguments: HashMap::new(),
+            cwe: vec![],
+            timeout_ms: None,
+            query_match_limit: None,
+        }
+    }
 }
 
 impl Rule {
@@ -179,6 +381,41 @@ impl Rule {
         }
     }
 
+    // Get the CWE identifiers of the rule. The API gives them as a string with the
+    // identifiers separated by commas, with or without the `CWE-` prefix (e.g. "CWE-79, 89").
+    pub fn get_cwe(&self) -> anyhow::Result<Vec<String>> {
+        let Some(cwe) = &self.cwe else {
+            return Ok(vec![]);
+        };
+        cwe.split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(|c| {
+                let id = c
+                    .strip_prefix("CWE-")
+                    .or_else(|| c.strip_prefix("CWE:"))
+                    .unwrap_or(c);
+                if id.is_empty() || !id.chars().all(|ch| ch.is_ascii_digit()) {
+                    return Err(anyhow!("invalid CWE {} for rule {}", c, self.name));
+                }
+                Ok(id.to_string())
+            })
+            .collect()
+    }
+
+    // Check the category and the CWE of the rule when it is loaded
+    pub fn check_metadata(&self) -> anyhow::Result<()> {
+        if self.category == RuleCategory::Unknown {
+            return Err(anyhow!(
+                "rule {} has an unknown category, the category must be one of {}",
+                self.name,
+                RuleCategory::ALL.map(|c| c.to_string()).join(", ")
+            ));
+        }
+        self.get_cwe()?;
+        Ok(())
+    }
+
     fn decode_description(&self) -> anyhow::Result<Option<String>> {
         self.description_base64
             .as_ref()
@@ -189,11 +426,59 @@ impl Rule {
             .transpose()
     }
 
+    // Set the values of the arguments of the rule (e.g. from the configuration). The arguments
+    // must be defined by the rule and the values must have their type.
+    pub fn set_argument_values(
+        &mut self,
+        values: &HashMap<String, RuleArgumentValue>,
+    ) -> anyhow::Result<()> {
+        for (name, value) in values {
+            let argument = self
+                .arguments
+                .iter()
+                .find(|a| &a.name == name)
+                .ok_or_else(|| anyhow!("rule {} has no argument {}", self.name, name))?;
+            if value.get_type() != argument.argument_type {
+                return Err(anyhow!(
+                    "argument {} of rule {} must be {}, got {}",
+                    name,
+                    self.name,
+                    argument.argument_type.with_article(),
+                    value.get_type().with_article()
+                ));
+            }
+        }
+        self.argument_values.extend(values.clone());
+        Ok(())
+    }
+
+    // get the value of each argument, the value set in the configuration or the default value
+    pub fn get_arguments(&self) -> anyhow::Result<HashMap<String, RuleArgumentValue>> {
+        let mut arguments = HashMap::new();
+        for argument in &self.arguments {
+            if argument.default.get_type() != argument.argument_type {
+                return Err(anyhow!(
+                    "default value of argument {} of rule {} must be {}",
+                    argument.name,
+                    self.name,
+                    argument.argument_type.with_article()
+                ));
+            }
+            let value = self
+                .argument_values
+                .get(&argument.name)
+                .unwrap_or(&argument.default);
+            arguments.insert(argument.name.clone(), value.clone());
+        }
+        Ok(arguments)
+    }
+
     // convert the rule to rule internal
     pub fn to_rule_internal(&self) -> anyhow::Result<RuleInternal> {
         if self.rule_type != RuleType::TreeSitterQuery {
             return Err(anyhow!("invalid rule type: {:?}", &self.rule_type));
         }
+        self.check_metadata()?;
         let description = self
             .decode_description()
             .unwrap_or_else(|_| Some("invalid description".to_string()));
@@ -222,6 +507,15 @@ impl Rule {
             code,
             tree_sitter_query: Some(tree_sitter_query_code),
             variables: self.variables.clone(),
+            include_unnamed_nodes: self.include_unnamed_nodes,
+            max_capture_nodes: self.max_capture_nodes,
+            include_siblings: self.include_siblings,
+            include_ancestors: self.include_ancestors,
+            capture_mode: self.capture_mode,
+            arguments: self.get_arguments()?,
+            cwe: self.get_cwe()?,
+            timeout_ms: self.timeout_ms,
+            query_match_limit: self.query_match_limit,
         })
     }
 
@@ -245,6 +539,16 @@ impl fmt::Display for Rule {
     }
 }
 
+// An exception thrown by the JavaScript code of a rule on a file. The frames of the stack
+// trace are in the code of the rule (e.g. `at visit (my-rule:3:15)`).
+#[derive(Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
+pub struct RuleExecutionError {
+    pub rule_name: String,
+    pub filename: String,
+    pub message: String,
+    pub stack: Vec<String>,
+}
+
 #[derive(Clone, Builder, Serialize, Debug)]
 pub struct RuleResult {
     pub rule_name: String,
@@ -253,7 +557,15 @@ pub struct RuleResult {
     pub errors: Vec<String>,
     pub execution_error: Option<String>,
     pub output: Option<String>,
+    // wall-clock time of the execution of the JavaScript code of the rule on the file
     pub execution_time_ms: u128,
+    // wall-clock time of the tree-sitter query of the rule on the file
+    #[builder(default)]
+    pub query_time_ms: u128,
+    // the exception thrown by the code of the rule, with its stack trace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub exception: Option<RuleExecutionError>,
 }
 
 #[cfg(test)]
@@ -280,6 +592,15 @@ mod tests {
             tree_sitter_query_base64: None,
             variables: HashMap::new(),
             tests: vec![],
+            include_unnamed_nodes: false,
+            max_capture_nodes: None,
+            include_siblings: false,
+            include_ancestors: false,
+            capture_mode: false,
+            arguments: vec![],
+            argument_values: HashMap::new(),
+            timeout_ms: None,
+            query_match_limit: None,
         };
         let rule_valid_checksum = Rule {
             name: "myrule".to_string(),
@@ -298,6 +619,15 @@ mod tests {
             tree_sitter_query_base64: None,
             variables: HashMap::new(),
             tests: vec![],
+            include_unnamed_nodes: false,
+            max_capture_nodes: None,
+            include_siblings: false,
+            include_ancestors: false,
+            capture_mode: false,
+            arguments: vec![],
+            argument_values: HashMap::new(),
+            timeout_ms: None,
+            query_match_limit: None,
         };
         assert!(!rule_invalid_checksum.verify_checksum());
         assert!(rule_valid_checksum.verify_checksum());
@@ -321,6 +651,15 @@ mod tests {
             tree_sitter_query_base64: None,
             variables: HashMap::new(),
             tests: vec![],
+            include_unnamed_nodes: false,
+            max_capture_nodes: None,
+            include_siblings: false,
+            include_ancestors: false,
+            capture_mode: false,
+            arguments: vec![],
+            argument_values: HashMap::new(),
+            timeout_ms: None,
+            query_match_limit: None,
         };
         let fixed_ruled = rule.fix_cwe();
         assert!(fixed_ruled.cwe.is_none());
@@ -344,6 +683,15 @@ mod tests {
             tree_sitter_query_base64: None,
             variables: HashMap::new(),
             tests: vec![],
+            include_unnamed_nodes: false,
+            max_capture_nodes: None,
+            include_siblings: false,
+            include_ancestors: false,
+            capture_mode: false,
+            arguments: vec![],
+            argument_values: HashMap::new(),
+            timeout_ms: None,
+            query_match_limit: None,
         };
         let fixed_ruled = rule.fix_cwe();
         assert!(fixed_ruled.cwe.is_none());
@@ -367,8 +715,300 @@ mod tests {
             tree_sitter_query_base64: None,
             variables: HashMap::new(),
             tests: vec![],
+            include_unnamed_nodes: false,
+            max_capture_nodes: None,
+            include_siblings: false,
+            include_ancestors: false,
+            capture_mode: false,
+            arguments: vec![],
+            argument_values: HashMap::new(),
+            timeout_ms: None,
+            query_match_limit: None,
         };
         let fixed_ruled = rule.fix_cwe();
         assert!(fixed_ruled.cwe.is_some());
     }
+
+    fn get_rule_with_arguments(arguments: Vec<RuleArgument>) -> Rule {
+        Rule {
+            name: "myrule".to_string(),
+            short_description_base64: None,
+            description_base64: None,
+            category: RuleCategory::BestPractices,
+            severity: RuleSeverity::Warning,
+            language: Language::Python,
+            rule_type: RuleType::TreeSitterQuery,
+            entity_checked: None,
+            code_base64: encode_base64_string("function visit() {}".to_string()),
+            checksum: "foobar".to_string(),
+            pattern: None,
+            cwe: None,
+            tree_sitter_query_base64: Some(encode_base64_string("(identifier) @id".to_string())),
+            variables: HashMap::new(),
+            tests: vec![],
+            include_unnamed_nodes: false,
+            max_capture_nodes: None,
+            include_siblings: false,
+            include_ancestors: false,
+            capture_mode: false,
+            arguments,
+            argument_values: HashMap::new(),
+            timeout_ms: None,
+            query_match_limit: None,
+        }
+    }
+
+    #[test]
+    fn test_rule_arguments_default() {
+        let rule = get_rule_with_arguments(vec![RuleArgument {
+            name: "max".to_string(),
+            argument_type: RuleArgumentType::Integer,
+            default: RuleArgumentValue::Integer(50),
+            description: None,
+        }]);
+        let rule_internal = rule.to_rule_internal().unwrap();
+        assert_eq!(
+            Some(&RuleArgumentValue::Integer(50)),
+            rule_internal.arguments.get("max")
+        );
+    }
+
+    #[test]
+    fn test_rule_arguments_override() {
+        let mut rule = get_rule_with_arguments(vec![
+            RuleArgument {
+                name: "max".to_string(),
+                argument_type: RuleArgumentType::Integer,
+                default: RuleArgumentValue::Integer(50),
+                description: None,
+            },
+            RuleArgument {
+                name: "modules".to_string(),
+                argument_type: RuleArgumentType::StringList,
+                default: RuleArgumentValue::StringList(vec!["pickle".to_string()]),
+                description: None,
+            },
+        ]);
+        let values = HashMap::from([("max".to_string(), RuleArgumentValue::Integer(10))]);
+        assert!(rule.set_argument_values(&values).is_ok());
+        let arguments = rule.to_rule_internal().unwrap().arguments;
+        assert_eq!(Some(&RuleArgumentValue::Integer(10)), arguments.get("max"));
+        assert_eq!(
+            Some(&RuleArgumentValue::StringList(vec!["pickle".to_string()])),
+            arguments.get("modules")
+        );
+    }
+
+    #[test]
+    fn test_rule_arguments_invalid() {
+        let mut rule = get_rule_with_arguments(vec![RuleArgument {
+            name: "max".to_string(),
+            argument_type: RuleArgumentType::Integer,
+            default: RuleArgumentValue::Integer(50),
+            description: None,
+        }]);
+
+        // a value with another type
+        let values = HashMap::from([(
+            "max".to_string(),
+            RuleArgumentValue::String("10".to_string()),
+        )]);
+        let error = rule.set_argument_values(&values).unwrap_err();
+        assert_eq!(
+            "argument max of rule myrule must be an integer, got a string",
+            error.to_string()
+        );
+
+        // an argument not defined by the rule
+        let values = HashMap::from([("min".to_string(), RuleArgumentValue::Integer(1))]);
+        assert!(rule.set_argument_values(&values).is_err());
+        assert!(rule.argument_values.is_empty());
+
+        // a default value with another type
+        let rule = get_rule_with_arguments(vec![RuleArgument {
+            name: "max".to_string(),
+            argument_type: RuleArgumentType::Integer,
+            default: RuleArgumentValue::Boolean(true),
+            description: None,
+        }]);
+        assert!(rule.to_rule_internal().is_err());
+    }
+
+    #[test]
+    fn test_rule_argument_value_deserialize() {
+        let values: HashMap<String, RuleArgumentValue> = serde_json::from_str(
+            r#"{"max": 50, "strict": false, "prefix": "test_", "modules": ["pickle", "marshal"]}"#,
+        )
+        .unwrap();
+        assert_eq!(Some(&RuleArgumentValue::Integer(50)), values.get("max"));
+        assert_eq!(
+            Some(&RuleArgumentValue::Boolean(false)),
+            values.get("strict")
+        );
+        assert_eq!(
+            Some(&RuleArgumentValue::String("test_".to_string())),
+            values.get("prefix")
+        );
+        assert_eq!(
+            Some(RuleArgumentType::StringList),
+            values.get("modules").map(|v| v.get_type())
+        );
+        assert_eq!(
+            "[50,true]",
+            serde_json::to_string(&[
+                RuleArgumentValue::Integer(50),
+                RuleArgumentValue::Boolean(true)
+            ])
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rule_category() {
+        // the category of a rule from the API
+        let rule: Rule = serde_json::from_value(serde_json::json!({
+            "name": "myrule",
+            "short_description": null,
+            "description": null,
+            "category": "DOCUMENTATION",
+            "severity": "WARNING",
+            "language": "PYTHON",
+            "rule_type": "TREE_SITTER_QUERY",
+            "entity_checked": null,
+            "code": encode_base64_string("function visit() {}".to_string()),
+            "cwe": null,
+            "checksum": "foobar",
+            "pattern": null,
+            "tree_sitter_query": encode_base64_string("(identifier) @id".to_string()),
+            "variables": {},
+            "tests": []
+        }))
+        .unwrap();
+        assert_eq!(RuleCategory::Documentation, rule.category);
+        assert_eq!("documentation", rule.category.to_string());
+        assert!(rule.check_metadata().is_ok());
+
+        // a category we do not know is rejected when the rule is loaded
+        let mut rule = get_rule_with_arguments(vec![]);
+        rule.category = serde_json::from_str("\"DEPLOYMENT\"").unwrap();
+        assert_eq!(RuleCategory::Unknown, rule.category);
+        let error = rule.to_rule_internal().unwrap_err().to_string();
+        assert!(error.contains("unknown category"));
+        assert!(error.contains("best_practices, code_style, documentation"));
+    }
+
+    #[test]
+    fn test_rule_cwe() {
+        let mut rule = get_rule_with_arguments(vec![]);
+        assert!(rule.get_cwe().unwrap().is_empty());
+        assert!(rule.to_rule_internal().unwrap().cwe.is_empty());
+
+        rule.cwe = Some("CWE-79, 89,CWE:95".to_string());
+        assert_eq!(vec!["79", "89", "95"], rule.get_cwe().unwrap());
+        assert_eq!(vec!["79", "89", "95"], rule.to_rule_internal().unwrap().cwe);
+
+        rule.cwe = Some("1234".to_string());
+        assert_eq!(vec!["1234"], rule.get_cwe().unwrap());
+
+        // only numeric identifiers are valid
+        rule.cwe = Some("79,injection".to_string());
+        assert!(rule.get_cwe().is_err());
+        assert!(rule.check_metadata().is_err());
+        assert!(rule.to_rule_internal().is_err());
+    }
+
+    #[test]
+    fn test_severity_and_category_from_str() {
+        assert_eq!(
+            RuleSeverity::Error,
+            "ERROR".parse::<RuleSeverity>().unwrap()
+        );
+        assert_eq!(
+            RuleSeverity::Notice,
+            "notice".parse::<RuleSeverity>().unwrap()
+        );
+        assert!("CRITICAL".parse::<RuleSeverity>().is_err());
+        assert_eq!(
+            RuleCategory::BestPractices,
+            "BEST_PRACTICES".parse::<RuleCategory>().unwrap()
+        );
+        assert_eq!(
+            RuleCategory::Security,
+            "security".parse::<RuleCategory>().unwrap()
+        );
+        // the unknown category is only for the old rules
+        assert!("unknown".parse::<RuleCategory>().is_err());
+        assert!("SAFETY".parse::<RuleCategory>().is_err());
+
+        assert!(RuleSeverity::Error.is_at_least(RuleSeverity::Warning));
+        assert!(RuleSeverity::Warning.is_at_least(RuleSeverity::Warning));
+        assert!(!RuleSeverity::Notice.is_at_least(RuleSeverity::Warning));
+    }
+
+    // the value of an argument in the configuration changes the verdict of the rule
+    #[test]
+    fn test_rule_arguments_threshold() {
+        use crate::analysis::analyze::analyze;
+        use crate::model::analysis::AnalysisOptions;
+
+        let rule_code = r#"
+function visit(node, filename, code) {
+    const name = node.captures["name"];
+    const max = node.context.arguments["max-length"];
+    if (typeof max === "number" && name.text.length > max) {
+        addError(buildError(name.start.line, name.start.col, name.end.line, name.end.col,
+                            `the name is longer than ${max} characters`));
+    }
+}
+"#;
+        let mut rule = get_rule_with_arguments(vec![RuleArgument {
+            name: "max-length".to_string(),
+            argument_type: RuleArgumentType::Integer,
+            default: RuleArgumentValue::Integer(10),
+            description: Some("maximum length of the name of a function".to_string()),
+        }]);
+        rule.code_base64 = encode_base64_string(rule_code.to_string());
+        rule.tree_sitter_query_base64 = Some(encode_base64_string(
+            "(function_definition name: (identifier) @name)".to_string(),
+        ));
+        let code = "def get_user_by_name():\n    pass\n";
+        let analysis_options = AnalysisOptions {
+            log_output: false,
+            use_debug: false,
+            ..AnalysisOptions::default()
+        };
+        let run = |rule: &Rule| {
+            let rule_internal = rule.to_rule_internal().unwrap();
+            analyze(
+                &Language::Python,
+                vec![rule_internal],
+                "myfile.py",
+                code,
+                &analysis_options,
+            )
+            .remove(0)
+            .violations
+        };
+
+        // the default value
+        let violations = run(&rule);
+        assert_eq!(1, violations.len());
+        assert_eq!(
+            "the name is longer than 10 characters",
+            violations[0].message
+        );
+
+        // the value of the configuration
+        let values = serde_json::from_str(r#"{"max-length": 30}"#).unwrap();
+        rule.set_argument_values(&values).unwrap();
+        assert!(run(&rule).is_empty());
+
+        // a string where an integer is expected
+        let values = serde_json::from_str(r#"{"max-length": "5"}"#).unwrap();
+        let error = rule.set_argument_values(&values).unwrap_err();
+        assert_eq!(
+            "argument max-length of rule myrule must be an integer, got a string",
+            error.to_string()
+        );
+    }
 }
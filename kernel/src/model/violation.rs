@@ -1,8 +1,10 @@
-use crate::model::common::Position;
+use crate::model::analysis::{PositionEncoding, TreeSitterNodeSummary};
+use crate::model::common::{Position, Range};
 use crate::model::rule::{RuleCategory, RuleSeverity};
 
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 #[derive(Copy, Clone, Deserialize, Debug, Serialize, Eq, PartialEq)]
 pub enum EditType {
@@ -23,12 +25,98 @@ pub struct Edit {
     pub content: Option<String>,
 }
 
+// separators of the elements of lists, removed with the elements
+const SEPARATORS: [char; 2] = [',', ';'];
+const SPACES: [char; 2] = [' ', '\t'];
+
+// number of columns of the text in the encoding
+fn get_width(text: &str, encoding: PositionEncoding) -> u32 {
+    let width: usize = text.chars().map(|c| encoding.get_char_width(c)).sum();
+    u32::try_from(width).unwrap()
+}
+
+// Edits built from the range of a node, so that the rules do not compute the columns
+impl Edit {
+    // insert the text just before the node
+    pub fn insert_before(node: &TreeSitterNodeSummary, text: &str) -> Edit {
+        Edit {
+            start: node.start.clone(),
+            end: None,
+            edit_type: EditType::Add,
+            content: Some(text.to_string()),
+        }
+    }
+
+    // insert the text just after the node, before the end of its line
+    pub fn insert_after(node: &TreeSitterNodeSummary, text: &str) -> Edit {
+        Edit {
+            start: node.end.clone(),
+            end: None,
+            edit_type: EditType::Add,
+            content: Some(text.to_string()),
+        }
+    }
+
+    pub fn replace_node(node: &TreeSitterNodeSummary, text: &str) -> Edit {
+        Edit {
+            start: node.start.clone(),
+            end: Some(node.end.clone()),
+            edit_type: EditType::Update,
+            content: Some(text.to_string()),
+        }
+    }
+
+    // Remove the node. With `consume_separator`, the separator of the element of a list (e.g.
+    // `,`) after the node and the spaces after it are removed too. For the last element, the
+    // separator before the node is removed. The separators on other lines are kept.
+    pub fn delete_node(
+        node: &TreeSitterNodeSummary,
+        code: &str,
+        encoding: PositionEncoding,
+        consume_separator: bool,
+    ) -> Edit {
+        let mut start = node.start.clone();
+        let mut end = node.end.clone();
+        if consume_separator {
+            let after = &code[node.end_byte..];
+            let separator = after.trim_start_matches(SPACES);
+            let before = &code[..node.start_byte];
+            let before_trimmed = before.trim_end_matches(SPACES);
+            if separator.starts_with(SEPARATORS) {
+                let next = separator[1..].trim_start_matches(SPACES);
+                end.col += get_width(&after[..after.len() - next.len()], encoding);
+            } else if before_trimmed.ends_with(SEPARATORS) {
+                let removed = &before[before_trimmed.len() - 1..];
+                start.col -= get_width(removed, encoding);
+            }
+        }
+        Edit {
+            start,
+            end: Some(end),
+            edit_type: EditType::Remove,
+            content: None,
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Serialize, Clone, Builder)]
 pub struct Fix {
     pub description: String,
     pub edits: Vec<Edit>,
 }
 
+// A location that explains a violation (e.g. where a shadowed variable is defined). The
+// location is in the file of the violation when there is no filename.
+#[derive(Deserialize, Debug, Serialize, Clone, Builder)]
+pub struct RelatedLocation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub filename: Option<String>,
+    pub start: Position,
+    pub end: Position,
+    pub label: String,
+}
+
 #[derive(Deserialize, Debug, Serialize, Clone, Builder)]
 pub struct Violation {
     pub start: Position,
@@ -37,4 +125,33 @@ pub struct Violation {
     pub severity: RuleSeverity,
     pub category: RuleCategory,
     pub fixes: Vec<Fix>,
+    #[serde(
+        rename = "relatedLocations",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    #[builder(default)]
+    pub related_locations: Vec<RelatedLocation>,
+    // the CWE identifiers of the rule of the violation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[builder(default)]
+    pub cwe: Vec<String>,
+}
+
+impl Violation {
+    // the range of the code of the violation
+    pub fn get_range(&self) -> Range {
+        Range::new(self.start.clone(), self.end.clone())
+    }
+}
+
+// Remove the violations of a rule with the same range and message as a previous violation, for
+// example when several patterns of the query capture the same node and the rule reports each
+// capture. The first violation is kept with its fixes.
+pub fn dedup_violations(violations: Vec<Violation>) -> Vec<Violation> {
+    let mut seen: HashSet<(Range, String)> = HashSet::new();
+    violations
+        .into_iter()
+        .filter(|violation| seen.insert((violation.get_range(), violation.message.clone())))
+        .collect()
 }
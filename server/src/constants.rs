@@ -7,6 +7,8 @@ pub const ERROR_CODE_LANGUAGE_MISMATCH: &str = "language-mismatch";
 // no root node when trying to get the AST
 pub const ERROR_CODE_NO_ROOT_NODE: &str = "no-root-node";
 pub const ERROR_CHECKSUM_MISMATCH: &str = "checksum-mismatch";
+// an option of the request has an invalid value
+pub const ERROR_INVALID_OPTION: &str = "invalid-option";
 
 pub const SERVER_HEADER_SHUTDOWN_ENABLED: &str = "X-static-analyzer-server-shutdown-enabled";
 pub const SERVER_HEADER_KEEPALIVE_ENABLED: &str = "X-static-analyzer-server-keepalive-enabled";
@@ -1,5 +1,6 @@
+use kernel::model::analysis::PositionEncoding;
 use kernel::model::common::Language;
-use kernel::model::rule::{EntityChecked, RuleCategory, RuleSeverity, RuleType};
+use kernel::model::rule::{EntityChecked, RuleArgument, RuleCategory, RuleSeverity, RuleType};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -27,12 +28,26 @@ pub struct ServerRule {
     #[serde(rename = "tree_sitter_query")]
     pub tree_sitter_query_base64: Option<String>,
     pub variables: Option<HashMap<String, String>>,
+    pub include_unnamed_nodes: Option<bool>,
+    pub max_capture_nodes: Option<usize>,
+    pub include_siblings: Option<bool>,
+    pub include_ancestors: Option<bool>,
+    pub capture_mode: Option<bool>,
+    pub arguments: Option<Vec<RuleArgument>>,
 }
 
 #[derive(Clone, Deserialize, Debug, Serialize)]
 pub struct AnalysisRequestOptions {
     pub use_tree_sitter: Option<bool>,
     pub log_output: Option<bool>,
+    // how the columns of the violations are counted, characters by default
+    pub position_encoding: Option<PositionEncoding>,
+    // time budget to parse and analyze the file, no limit by default
+    pub file_timeout_ms: Option<u64>,
+    // time budget of each rule on the file, the default timeout of the rules when not set
+    pub rule_timeout_ms: Option<u64>,
+    // memory of each rule on the file in megabytes, the default limit of the rules when not set
+    pub rule_memory_limit_mb: Option<u64>,
 }
 
 #[derive(Clone, Deserialize, Debug, Serialize)]
@@ -1,4 +1,5 @@
 use crate::model::violation::ServerViolation;
+use kernel::model::rule::RuleExecutionError;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Deserialize, Debug, Serialize)]
@@ -9,10 +10,18 @@ pub struct RuleResponse {
     pub execution_error: Option<String>,
     pub output: Option<String>,
     pub execution_time_ms: u128,
+    pub query_time_ms: u128,
+    // the exception thrown by the code of the rule, with its stack trace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exception: Option<RuleExecutionError>,
 }
 
 #[derive(Clone, Deserialize, Debug, Serialize)]
 pub struct AnalysisResponse {
     pub rule_responses: Vec<RuleResponse>,
     pub errors: Vec<String>,
+    // the problems of the rules that do not prevent the analysis (e.g. the rule reads captures
+    // that are not in its query)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
@@ -10,8 +10,13 @@ pub struct ServerTreeSitterNode {
     pub ast_type: String,
     pub start: Position,
     pub end: Position,
+    pub start_byte: usize,
+    pub end_byte: usize,
     pub field_name: Option<String>,
     pub children: Vec<ServerTreeSitterNode>,
+    pub is_error: bool,
+    pub is_missing: bool,
+    pub is_truncated: bool,
 }
 
 impl From<TreeSitterNode> for ServerTreeSitterNode {
@@ -20,12 +25,17 @@ impl From<TreeSitterNode> for ServerTreeSitterNode {
             ast_type: value.ast_type,
             start: value.start,
             end: value.end,
+            start_byte: value.start_byte,
+            end_byte: value.end_byte,
             field_name: value.field_name,
             children: value
                 .children
                 .into_iter()
                 .map(ServerTreeSitterNode::from)
                 .collect(),
+            is_error: value.is_error,
+            is_missing: value.is_missing,
+            is_truncated: value.is_truncated,
         }
     }
 }
@@ -1,3 +1,4 @@
+use kernel::model::analysis::PositionEncoding;
 use kernel::model::common::Language;
 use serde::{Deserialize, Serialize};
 
@@ -7,4 +8,8 @@ pub struct TreeSitterRequest {
     pub file_encoding: String,
     #[serde(rename = "code")]
     pub code_base64: String,
+    // how the columns of the nodes are counted, characters by default
+    pub position_encoding: Option<PositionEncoding>,
+    // the file of the code, the tree of the previous request for the same file is reused
+    pub filename: Option<String>,
 }
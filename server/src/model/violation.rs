@@ -2,7 +2,7 @@ use kernel::model::common::Position;
 use kernel::model::rule::{RuleCategory, RuleSeverity};
 
 use derive_builder::Builder;
-use kernel::model::violation::{Edit, EditType, Fix, Violation};
+use kernel::model::violation::{Edit, EditType, Fix, RelatedLocation, Violation};
 use serde::{Deserialize, Serialize};
 
 /// because of our naming conventions that mix camelCase in the JS code
@@ -34,6 +34,7 @@ pub struct ServerViolation {
     pub severity: RuleSeverity,
     pub category: RuleCategory,
     pub fixes: Vec<ServerFix>,
+    pub related_locations: Vec<RelatedLocation>,
 }
 
 /// Transform an edit from the kernel into an edit that is surfaced by the server.
@@ -63,5 +64,6 @@ pub fn violation_to_server(violation: &Violation) -> ServerViolation {
         severity: violation.severity,
         category: violation.category,
         fixes: violation.fixes.iter().map(fix_to_server).collect(),
+        related_locations: violation.related_locations.clone(),
     }
 }
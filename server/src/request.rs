@@ -1,16 +1,34 @@
 use crate::constants::{
     ERROR_CHECKSUM_MISMATCH, ERROR_CODE_LANGUAGE_MISMATCH, ERROR_CODE_NOT_BASE64,
-    ERROR_DECODING_BASE64,
+    ERROR_DECODING_BASE64, ERROR_INVALID_OPTION,
 };
 use crate::model::analysis_request::{AnalysisRequest, ServerRule};
 use crate::model::analysis_response::{AnalysisResponse, RuleResponse};
 use crate::model::violation::violation_to_server;
 use kernel::analysis::analyze::analyze;
-use kernel::model::analysis::AnalysisOptions;
+use kernel::analysis::rule_validation::{get_unknown_captures, get_unknown_query_predicates};
+use kernel::model::analysis::{AnalysisOptions, ERROR_PARSING_FAILED};
+use kernel::model::common::Language;
 use kernel::model::rule::{Rule, RuleCategory, RuleInternal, RuleSeverity};
 use kernel::utils::decode_base64_string;
+use std::collections::HashMap;
+use std::path::Path;
 
 pub fn process_analysis_request(request: AnalysisRequest) -> AnalysisResponse {
+    // a rule cannot run without memory, like for the CLI the limit must be positive
+    if request
+        .options
+        .as_ref()
+        .and_then(|o| o.rule_memory_limit_mb)
+        == Some(0)
+    {
+        return AnalysisResponse {
+            rule_responses: vec![],
+            errors: vec![ERROR_INVALID_OPTION.to_string()],
+            warnings: vec![],
+        };
+    }
+
     let rules_with_invalid_language: Vec<ServerRule> = request
         .rules
         .iter()
@@ -21,6 +39,7 @@ pub fn process_analysis_request(request: AnalysisRequest) -> AnalysisResponse {
         return AnalysisResponse {
             rule_responses: vec![],
             errors: vec![ERROR_CODE_LANGUAGE_MISMATCH.to_string()],
+            warnings: vec![],
         };
     }
 
@@ -43,6 +62,15 @@ pub fn process_analysis_request(request: AnalysisRequest) -> AnalysisResponse {
             tree_sitter_query_base64: r.tree_sitter_query_base64.clone(),
             variables: r.variables.clone().unwrap_or_default(),
             tests: vec![],
+            include_unnamed_nodes: r.include_unnamed_nodes.unwrap_or(false),
+            max_capture_nodes: r.max_capture_nodes,
+            include_siblings: r.include_siblings.unwrap_or(false),
+            include_ancestors: r.include_ancestors.unwrap_or(false),
+            capture_mode: r.capture_mode.unwrap_or(false),
+            arguments: r.arguments.clone().unwrap_or_default(),
+            argument_values: HashMap::new(),
+            timeout_ms: None,
+            query_match_limit: None,
         })
         .collect();
 
@@ -58,9 +86,28 @@ pub fn process_analysis_request(request: AnalysisRequest) -> AnalysisResponse {
         return AnalysisResponse {
             rule_responses: vec![],
             errors: vec![ERROR_CODE_NOT_BASE64.to_string()],
+            warnings: vec![],
         };
     }
 
+    // Files without extension (e.g. scripts in bin/) are identified by their shebang. If the
+    // shebang is for another language, the rules cannot apply to the code.
+    if Path::new(&request.filename).extension().is_none() {
+        if let Some(shebang_language) = code_decoded_attempt
+            .as_ref()
+            .ok()
+            .and_then(|code| Language::from_shebang(code))
+        {
+            if shebang_language != request.language {
+                return AnalysisResponse {
+                    rule_responses: vec![],
+                    errors: vec![ERROR_CODE_LANGUAGE_MISMATCH.to_string()],
+                    warnings: vec![],
+                };
+            }
+        }
+    }
+
     // We check each rule and if the checksum is correct or not. If one rule does not
     // have a valid checksum, we return an error.
     for rule in &server_rules_to_rules {
@@ -69,10 +116,35 @@ pub fn process_analysis_request(request: AnalysisRequest) -> AnalysisResponse {
             return AnalysisResponse {
                 rule_responses: vec![],
                 errors: vec![ERROR_CHECKSUM_MISMATCH.to_string()],
+                warnings: vec![],
             };
         }
     }
 
+    // the rules reading captures that are not in their query silently report no violation,
+    // the problems are returned to the client with the results
+    let mut warnings = vec![];
+    for rule in rules_converted.iter().flatten() {
+        if let Ok(unknown_captures) = get_unknown_captures(rule) {
+            if !unknown_captures.is_empty() {
+                warnings.push(format!(
+                    "Rule {} reads captures not defined in its query: {}",
+                    rule.name,
+                    unknown_captures.join(", ")
+                ));
+            }
+        }
+        if let Ok(unknown_predicates) = get_unknown_query_predicates(rule) {
+            if !unknown_predicates.is_empty() {
+                warnings.push(format!(
+                    "Rule {} uses predicates that are not evaluated: {}",
+                    rule.name,
+                    unknown_predicates.join(", ")
+                ));
+            }
+        }
+    }
+
     // execute the rule. If we fail to convert, return an error.
     match rules_converted {
         Ok(rules) => {
@@ -85,11 +157,37 @@ pub fn process_analysis_request(request: AnalysisRequest) -> AnalysisResponse {
                     use_debug: false,
                     log_output: request
                         .options
+                        .as_ref()
                         .map(|o| o.log_output.unwrap_or(false))
                         .unwrap_or(false),
+                    max_parsing_error_rate: None,
+                    file_timeout_ms: request.options.as_ref().and_then(|o| o.file_timeout_ms),
+                    position_encoding: request
+                        .options
+                        .as_ref()
+                        .and_then(|o| o.position_encoding)
+                        .unwrap_or_default(),
+                    is_test_file: None,
+                    rule_timeout_ms: request.options.as_ref().and_then(|o| o.rule_timeout_ms),
+                    rule_memory_limit_mb: request
+                        .options
+                        .as_ref()
+                        .and_then(|o| o.rule_memory_limit_mb),
+                    language_overrides: HashMap::new(),
                 },
             );
 
+            if let Some(parsing_error) = rule_results
+                .iter()
+                .find(|rr| rr.errors.contains(&ERROR_PARSING_FAILED.to_string()))
+                .and_then(|rr| rr.execution_error.as_ref())
+            {
+                eprintln!(
+                    "Error when parsing file {}: {}",
+                    request.filename, parsing_error
+                );
+            }
+
             let rule_responses = rule_results
                 .iter()
                 .map(|rr| RuleResponse {
@@ -99,24 +197,28 @@ pub fn process_analysis_request(request: AnalysisRequest) -> AnalysisResponse {
                     execution_error: rr.execution_error.clone(),
                     output: rr.output.clone(),
                     execution_time_ms: rr.execution_time_ms,
+                    query_time_ms: rr.query_time_ms,
+                    exception: rr.exception.clone(),
                 })
                 .collect();
 
             AnalysisResponse {
                 rule_responses,
                 errors: vec![],
+                warnings,
             }
         }
         Err(_) => AnalysisResponse {
             rule_responses: vec![],
             errors: vec![ERROR_DECODING_BASE64.to_string()],
+            warnings: vec![],
         },
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::model::analysis_request::ServerRule;
+    use crate::model::analysis_request::{AnalysisRequestOptions, ServerRule};
     use kernel::model::{
         common::Language,
         rule::{RuleCategory, RuleSeverity, RuleType},
@@ -147,15 +249,65 @@ mod tests {
                     pattern: None,
                     tree_sitter_query_base64: Some("KGZ1bmN0aW9uX2RlZmluaXRpb24KICAgIG5hbWU6IChpZGVudGlmaWVyKSBAbmFtZQogIHBhcmFtZXRlcnM6IChwYXJhbWV0ZXJzKSBAcGFyYW1zCik=".to_string()),
                     variables: None,
+                    include_unnamed_nodes: None,
+                    max_capture_nodes: None,
+                    include_siblings: None,
+                    include_ancestors: None,
+                    capture_mode: None,
+                    arguments: None,
                 }
             ]
         };
         let response = process_analysis_request(request);
         assert!(response.errors.is_empty());
+        assert!(response.warnings.is_empty());
         assert_eq!(1, response.rule_responses.len());
         assert_eq!(1, response.rule_responses.get(0).unwrap().violations.len());
     }
 
+    // the rule reads a capture that is not in its query, the client gets a warning
+    #[test]
+    fn test_request_unknown_captures_warning() {
+        let request = AnalysisRequest {
+            filename: "myfile.py".to_string(),
+            language: Language::Python,
+            file_encoding: "utf-8".to_string(),
+            code_base64: "ZGVmIGZvbyhhcmcxKToKICAgIHBhc3M=".to_string(),
+            options: None,
+            rules: vec![
+                ServerRule{
+                    name: "myrule".to_string(),
+                    short_description_base64: None,
+                    description_base64: None,
+                    category: Some(RuleCategory::BestPractices),
+                    severity: Some(RuleSeverity::Warning),
+                    language: Language::Python,
+                    rule_type: RuleType::TreeSitterQuery,
+                    entity_checked: None,
+                    code_base64: "ZnVuY3Rpb24gdmlzaXQobm9kZSwgZmlsZW5hbWUsIGNvZGUpIHsKICAgIGNvbnN0IGZ1bmN0aW9uTmFtZSA9IG5vZGUuY2FwdHVyZXNbImZ1bmN0aW9uTmFtZSJdOwogICAgaWYgKGZ1bmN0aW9uTmFtZSkgewogICAgICAgIGFkZEVycm9yKGJ1aWxkRXJyb3IoZnVuY3Rpb25OYW1lLnN0YXJ0LmxpbmUsIGZ1bmN0aW9uTmFtZS5zdGFydC5jb2wsIGZ1bmN0aW9uTmFtZS5lbmQubGluZSwgZnVuY3Rpb25OYW1lLmVuZC5jb2wsICJpbnZhbGlkIG5hbWUiLCAiQ1JJVElDQUwiLCAic2VjdXJpdHkiKSk7CiAgICB9Cn0K".to_string(),
+                    checksum: Some("338a4efc87fbff47842c6cf30ed2cdc9cd2ca52e200e41365e93ce50df787ecc".to_string()),
+                    pattern: None,
+                    tree_sitter_query_base64: Some("KGZ1bmN0aW9uX2RlZmluaXRpb24KICAgIG5hbWU6IChpZGVudGlmaWVyKSBAbmFtZQogIHBhcmFtZXRlcnM6IChwYXJhbWV0ZXJzKSBAcGFyYW1zCik=".to_string()),
+                    variables: None,
+                    include_unnamed_nodes: None,
+                    max_capture_nodes: None,
+                    include_siblings: None,
+                    include_ancestors: None,
+                    capture_mode: None,
+                    arguments: None,
+                }
+            ]
+        };
+        let response = process_analysis_request(request);
+        assert!(response.errors.is_empty());
+        assert_eq!(
+            vec!["Rule myrule reads captures not defined in its query: functionName".to_string()],
+            response.warnings
+        );
+        assert_eq!(1, response.rule_responses.len());
+        assert_eq!(0, response.rule_responses.get(0).unwrap().violations.len());
+    }
+
     #[test]
     fn test_invalid_checksum() {
         let request = AnalysisRequest {
@@ -179,6 +331,12 @@ mod tests {
                     pattern: None,
                     tree_sitter_query_base64: Some("KGZ1bmN0aW9uX2RlZmluaXRpb24KICAgIG5hbWU6IChpZGVudGlmaWVyKSBAbmFtZQogIHBhcmFtZXRlcnM6IChwYXJhbWV0ZXJzKSBAcGFyYW1zCik=".to_string()),
                     variables: None,
+                    include_unnamed_nodes: None,
+                    max_capture_nodes: None,
+                    include_siblings: None,
+                    include_ancestors: None,
+                    capture_mode: None,
+                    arguments: None,
                 }
             ]
         };
@@ -213,6 +371,12 @@ mod tests {
                     pattern: None,
                     tree_sitter_query_base64: Some("KGZ1bmN0aW9uX2RlZmluaXRpb24KICAgIG5hbWU6IChpZGVudGlmaWVyKSBAbmFtZQogIHBhcmFtZXRlcnM6IChwYXJhbWV0ZXJzKSBAcGFyYW1zCik=".to_string()),
                     variables: None,
+                    include_unnamed_nodes: None,
+                    max_capture_nodes: None,
+                    include_siblings: None,
+                    include_ancestors: None,
+                    capture_mode: None,
+                    arguments: None,
                 }
             ]
         };
@@ -247,6 +411,12 @@ mod tests {
                     pattern: None,
                     tree_sitter_query_base64: Some("KGZ1bmN0aW9uX2RlZmluaXRpb24KICAgIG5hbWU6IChpZGVudGlmaWVyKSBAbmFtZQogIHBhcmFtZXRlcnM6IChwYXJhbWV0ZXJzKSBAcGFyYW1zCik=".to_string()),
                     variables: None,
+                    include_unnamed_nodes: None,
+                    max_capture_nodes: None,
+                    include_siblings: None,
+                    include_ancestors: None,
+                    capture_mode: None,
+                    arguments: None,
                 }
             ]
         };
@@ -281,6 +451,12 @@ mod tests {
                     pattern: None,
                     tree_sitter_query_base64: Some("KGZ1bmN0aW9uX2RlZmluaXRpb24KICAgIG5hbWU6IChpZGVudGlmaWVyKSBAbmFtZQogIHBhcmFtZXRlcnM6IChwYXJhbWV0ZXJzKSBAcGFyYW1zCik=".to_string()),
                     variables: None,
+                    include_unnamed_nodes: None,
+                    max_capture_nodes: None,
+                    include_siblings: None,
+                    include_ancestors: None,
+                    capture_mode: None,
+                    arguments: None,
                 }
             ]
         };
@@ -291,4 +467,107 @@ mod tests {
             response.errors.get(0).unwrap()
         );
     }
+
+    // files without extension are analyzed when their shebang matches the language
+    #[test]
+    fn test_request_shebang_without_extension() {
+        let request = AnalysisRequest {
+            filename: "manage".to_string(),
+            language: Language::Python,
+            file_encoding: "utf-8".to_string(),
+            code_base64: "IyEvdXNyL2Jpbi9lbnYgcHl0aG9uMwpkZWYgZm9vKGFyZzEpOgogICAgcGFzcw==".to_string(),
+            options: None,
+            rules: vec![
+                ServerRule{
+                    name: "myrule".to_string(),
+                    short_description_base64: None,
+                    description_base64: None,
+                    category: Some(RuleCategory::BestPractices),
+                    severity: Some(RuleSeverity::Warning),
+                    language: Language::Python,
+                    rule_type: RuleType::TreeSitterQuery,
+                    entity_checked: None,
+                    code_base64: "ZnVuY3Rpb24gdmlzaXQobm9kZSwgZmlsZW5hbWUsIGNvZGUpIHsKICAgIGNvbnN0IGZ1bmN0aW9uTmFtZSA9IG5vZGUuY2FwdHVyZXNbIm5hbWUiXTsKICAgIGlmKGZ1bmN0aW9uTmFtZSkgewogICAgICAgIGNvbnN0IGVycm9yID0gYnVpbGRFcnJvcihmdW5jdGlvbk5hbWUuc3RhcnQubGluZSwgZnVuY3Rpb25OYW1lLnN0YXJ0LmNvbCwgZnVuY3Rpb25OYW1lLmVuZC5saW5lLCBmdW5jdGlvbk5hbWUuZW5kLmNvbCwKICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgImludmFsaWQgbmFtZSIsICJDUklUSUNBTCIsICJzZWN1cml0eSIpOwoKICAgICAgICBjb25zdCBlZGl0ID0gYnVpbGRFZGl0KGZ1bmN0aW9uTmFtZS5zdGFydC5saW5lLCBmdW5jdGlvbk5hbWUuc3RhcnQuY29sLCBmdW5jdGlvbk5hbWUuZW5kLmxpbmUsIGZ1bmN0aW9uTmFtZS5lbmQuY29sLCAidXBkYXRlIiwgImJhciIpOwogICAgICAgIGNvbnN0IGZpeCA9IGJ1aWxkRml4KCJ1c2UgYmFyIiwgW2VkaXRdKTsKICAgICAgICBhZGRFcnJvcihlcnJvci5hZGRGaXgoZml4KSk7CiAgICB9Cn0=".to_string(),
+                    checksum: Some("f546e49732dc071fd5da82e1a2d9bcf5cf9a824c3679d8b59237c4ba23340057".to_string()),
+                    pattern: None,
+                    tree_sitter_query_base64: Some("KGZ1bmN0aW9uX2RlZmluaXRpb24KICAgIG5hbWU6IChpZGVudGlmaWVyKSBAbmFtZQogIHBhcmFtZXRlcnM6IChwYXJhbWV0ZXJzKSBAcGFyYW1zCik=".to_string()),
+                    variables: None,
+                    include_unnamed_nodes: None,
+                    max_capture_nodes: None,
+                    include_siblings: None,
+                    include_ancestors: None,
+                    capture_mode: None,
+                    arguments: None,
+                }
+            ]
+        };
+        let response = process_analysis_request(request);
+        assert!(response.errors.is_empty());
+        assert_eq!(1, response.rule_responses.len());
+        assert_eq!(1, response.rule_responses.get(0).unwrap().violations.len());
+    }
+
+    // the shebang of a file without extension is for another language
+    #[test]
+    fn test_request_shebang_language_mismatch() {
+        let request = AnalysisRequest {
+            filename: "serve".to_string(),
+            language: Language::Python,
+            file_encoding: "utf-8".to_string(),
+            code_base64: "IyEvdXNyL2Jpbi9lbnYgbm9kZQpmdW5jdGlvbiBmb28oYXJnMSkge30=".to_string(),
+            options: None,
+            rules: vec![
+                ServerRule{
+                    name: "myrule".to_string(),
+                    short_description_base64: None,
+                    description_base64: None,
+                    category: Some(RuleCategory::BestPractices),
+                    severity: Some(RuleSeverity::Warning),
+                    language: Language::Python,
+                    rule_type: RuleType::TreeSitterQuery,
+                    entity_checked: None,
+                    code_base64: "ZnVuY3Rpb24gdmlzaXQobm9kZSwgZmlsZW5hbWUsIGNvZGUpIHsKICAgIGNvbnN0IGZ1bmN0aW9uTmFtZSA9IG5vZGUuY2FwdHVyZXNbIm5hbWUiXTsKICAgIGlmKGZ1bmN0aW9uTmFtZSkgewogICAgICAgIGNvbnN0IGVycm9yID0gYnVpbGRFcnJvcihmdW5jdGlvbk5hbWUuc3RhcnQubGluZSwgZnVuY3Rpb25OYW1lLnN0YXJ0LmNvbCwgZnVuY3Rpb25OYW1lLmVuZC5saW5lLCBmdW5jdGlvbk5hbWUuZW5kLmNvbCwKICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgImludmFsaWQgbmFtZSIsICJDUklUSUNBTCIsICJzZWN1cml0eSIpOwoKICAgICAgICBjb25zdCBlZGl0ID0gYnVpbGRFZGl0KGZ1bmN0aW9uTmFtZS5zdGFydC5saW5lLCBmdW5jdGlvbk5hbWUuc3RhcnQuY29sLCBmdW5jdGlvbk5hbWUuZW5kLmxpbmUsIGZ1bmN0aW9uTmFtZS5lbmQuY29sLCAidXBkYXRlIiwgImJhciIpOwogICAgICAgIGNvbnN0IGZpeCA9IGJ1aWxkRml4KCJ1c2UgYmFyIiwgW2VkaXRdKTsKICAgICAgICBhZGRFcnJvcihlcnJvci5hZGRGaXgoZml4KSk7CiAgICB9Cn0=".to_string(),
+                    checksum: Some("f546e49732dc071fd5da82e1a2d9bcf5cf9a824c3679d8b59237c4ba23340057".to_string()),
+                    pattern: None,
+                    tree_sitter_query_base64: Some("KGZ1bmN0aW9uX2RlZmluaXRpb24KICAgIG5hbWU6IChpZGVudGlmaWVyKSBAbmFtZQogIHBhcmFtZXRlcnM6IChwYXJhbWV0ZXJzKSBAcGFyYW1zCik=".to_string()),
+                    variables: None,
+                    include_unnamed_nodes: None,
+                    max_capture_nodes: None,
+                    include_siblings: None,
+                    include_ancestors: None,
+                    capture_mode: None,
+                    arguments: None,
+                }
+            ]
+        };
+        let response = process_analysis_request(request);
+        assert_eq!(0, response.rule_responses.len());
+        assert_eq!(
+            &ERROR_CODE_LANGUAGE_MISMATCH.to_string(),
+            response.errors.get(0).unwrap()
+        );
+    }
+
+    // like for the CLI, a rule memory limit of 0 MB is rejected
+    #[test]
+    fn test_request_invalid_rule_memory_limit() {
+        let request = AnalysisRequest {
+            filename: "myfile.py".to_string(),
+            language: Language::Python,
+            file_encoding: "utf-8".to_string(),
+            code_base64: "ZGVmIGZvbyhhcmcxKToKICAgIHBhc3M=".to_string(),
+            options: Some(AnalysisRequestOptions {
+                use_tree_sitter: None,
+                log_output: None,
+                position_encoding: None,
+                file_timeout_ms: None,
+                rule_timeout_ms: None,
+                rule_memory_limit_mb: Some(0),
+            }),
+            rules: vec![],
+        };
+        let response = process_analysis_request(request);
+        assert_eq!(0, response.rule_responses.len());
+        assert_eq!(vec![ERROR_INVALID_OPTION.to_string()], response.errors);
+    }
 }
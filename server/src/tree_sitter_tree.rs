@@ -1,8 +1,14 @@
 use crate::constants::{ERROR_CODE_NOT_BASE64, ERROR_CODE_NO_ROOT_NODE};
 use crate::model::tree_sitter_tree_request::TreeSitterRequest;
 use crate::model::tree_sitter_tree_response::TreeSitterResponse;
-use kernel::analysis::tree_sitter::{get_tree, map_node};
+use kernel::analysis::tree_cache::TreeCache;
+use kernel::analysis::tree_sitter::{map_node_in_code, try_get_tree};
+use kernel::model::analysis::MapNodeOptions;
 use kernel::utils::decode_base64_string;
+use std::sync::Mutex;
+
+// the trees of the last files sent with their filename, shared by all the requests
+static TREE_CACHE: Mutex<Option<TreeCache>> = Mutex::new(None);
 
 // Return the tree for the language and code sent as parameter.
 pub fn process_tree_sitter_tree_request(request: TreeSitterRequest) -> TreeSitterResponse {
@@ -20,12 +26,27 @@ pub fn process_tree_sitter_tree_request(request: TreeSitterRequest) -> TreeSitte
         };
     }
 
-    let tree = get_tree(&decoded.unwrap(), &request.language);
-
-    if tree.is_none() {
-        return no_root_node;
-    }
-    let root_node = map_node(tree.unwrap().root_node());
+    let code = decoded.unwrap();
+    let tree = match &request.filename {
+        Some(filename) => TREE_CACHE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(TreeCache::default)
+            .get_tree(filename, &code, &request.language),
+        None => try_get_tree(&code, &request.language),
+    };
+    let tree = match tree {
+        Ok(tree) => tree,
+        Err(e) => {
+            eprintln!("Error when parsing {} code: {}", request.language, e);
+            return no_root_node;
+        }
+    };
+    let map_node_options = MapNodeOptions {
+        position_encoding: request.position_encoding.unwrap_or_default(),
+        ..MapNodeOptions::default()
+    };
+    let root_node = map_node_in_code(tree.root_node(), &code, &map_node_options);
 
     if root_node.is_none() {
         return no_root_node;
@@ -40,6 +61,7 @@ pub fn process_tree_sitter_tree_request(request: TreeSitterRequest) -> TreeSitte
 #[cfg(test)]
 mod tests {
     use kernel::model::common::Language;
+    use kernel::utils::encode_base64_string;
 
     use super::*;
 
@@ -49,6 +71,8 @@ mod tests {
             code_base64: "ZnVuY3Rpb24gdmlzaXQobm9kZSwgZmlsZW5hbWUsIGNvZGUpIHsKICAgIGNvbnN0IGZ1bmN0aW9uTmFtZSA9IG5vZGUuY2FwdHVyZXNbIm5hbWUiXTsKICAgIGlmKGZ1bmN0aW9uTmFtZSkgewogICAgICAgIGNvbnN0IGVycm9yID0gYnVpbGRFcnJvcihmdW5jdGlvbk5hbWUuc3RhcnQubGluZSwgZnVuY3Rpb25OYW1lLnN0YXJ0LmNvbCwgZnVuY3Rpb25OYW1lLmVuZC5saW5lLCBmdW5jdGlvbk5hbWUuZW5kLmNvbCwKICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgImludmFsaWQgbmFtZSIsICJDUklUSUNBTCIsICJzZWN1cml0eSIpOwoKICAgICAgICBjb25zdCBlZGl0ID0gYnVpbGRFZGl0KGZ1bmN0aW9uTmFtZS5zdGFydC5saW5lLCBmdW5jdGlvbk5hbWUuc3RhcnQuY29sLCBmdW5jdGlvbk5hbWUuZW5kLmxpbmUsIGZ1bmN0aW9uTmFtZS5lbmQuY29sLCAidXBkYXRlIiwgImJhciIpOwogICAgICAgIGNvbnN0IGZpeCA9IGJ1aWxkRml4KCJ1c2UgYmFyIiwgW2VkaXRdKTsKICAgICAgICBhZGRFcnJvcihlcnJvci5hZGRGaXgoZml4KSk7CiAgICB9Cn0=".to_string(),
             file_encoding: "utf-8".to_string(),
             language: Language::Python,
+            position_encoding: None,
+            filename: None,
         };
         let response = process_tree_sitter_tree_request(request);
         assert!(response.errors.is_empty());
@@ -62,6 +86,8 @@ mod tests {
             code_base64: "we2323423423090909)()(&(*&!@!@=".to_string(),
             file_encoding: "utf-8".to_string(),
             language: Language::Python,
+            position_encoding: None,
+            filename: None,
         };
         let response = process_tree_sitter_tree_request(request);
         assert_eq!(
@@ -70,4 +96,25 @@ mod tests {
         );
         assert!(response.result.is_none());
     }
+
+    // the requests for the same file reuse its tree and return the same tree as a new parse
+    #[test]
+    fn test_process_tree_sitter_tree_request_with_filename() {
+        let get_request = |code: &str, filename: Option<&str>| TreeSitterRequest {
+            code_base64: encode_base64_string(code.to_string()),
+            file_encoding: "utf-8".to_string(),
+            language: Language::Python,
+            position_encoding: None,
+            filename: filename.map(str::to_string),
+        };
+        for code in ["def foo(a):\n    pass\n", "def foo(a, b):\n    return b\n"] {
+            let response = process_tree_sitter_tree_request(get_request(code, Some("myfile.py")));
+            let expected = process_tree_sitter_tree_request(get_request(code, None));
+            assert!(response.errors.is_empty());
+            assert_eq!(
+                serde_json::to_value(&expected.result).unwrap(),
+                serde_json::to_value(&response.result).unwrap()
+            );
+        }
+    }
 }